@@ -1,7 +1,7 @@
 use std::{fmt::Debug, io::Write, ops::{Bound, Range}, sync::Arc};
 
 use bytes::{Buf,  Bytes};
-use loro_common::LoroResult;
+use loro_common::{LoroError, LoroResult};
 use once_cell::sync::OnceCell;
 
 use crate::{compress::{compress, decompress, CompressionType}, iter::KvIterator, sstable::{get_common_prefix_len_and_strip,  SIZE_OF_U32, XXH_SEED}};
@@ -25,19 +25,20 @@ impl LargeValueBlock{
     /// ││ bytes │      u32        │
     /// │ ─ ─ ─ ─ ─ ─ ─ ─ ─ ─ ─ ─ ┘│
     /// └──────────────────────────┘
-    fn encode(&self, w: &mut Vec<u8>, mut compression_type: CompressionType) -> CompressionType {
+    fn encode(&self, w: &mut Vec<u8>, mut compression_type: CompressionType, dict: Option<&[u8]>) -> CompressionType {
         if let Some((bytes, encoded_compression_type)) = self.encoded_bytes.get() {
-            if encoded_compression_type == &compression_type {
+            // The dictionary of the new table may be different
+            if encoded_compression_type == &compression_type && !compression_type.uses_dictionary() {
                 w.extend_from_slice(bytes);
                 return compression_type;
             }
         }
 
         let origin_len = w.len();
-        compress(w, &self.value_bytes, compression_type);
-        if !compression_type.is_none() && w.len() - origin_len > self.value_bytes.len(){
+        let compressed = compress(w, &self.value_bytes, compression_type, dict).is_ok();
+        if !compression_type.is_none() && (!compressed || w.len() - origin_len > self.value_bytes.len()){
             w.truncate(origin_len);
-            compress(w, &self.value_bytes, CompressionType::None);
+            compress(w, &self.value_bytes, CompressionType::None, None).unwrap();
             ensure_cov::notify_cov("kv_store::block::LargeValueBlock::encode::compress_fallback");
             compression_type = CompressionType::None;
         }
@@ -46,9 +47,9 @@ impl LargeValueBlock{
         compression_type
     }
 
-    fn decode(bytes: Bytes, key: Bytes, compression_type: CompressionType)->LoroResult<Self>{
+    fn decode(bytes: Bytes, key: Bytes, compression_type: CompressionType, dict: Option<&[u8]>)->LoroResult<Self>{
         let mut value_bytes = vec![];
-        decompress(&mut value_bytes, bytes.slice(..bytes.len() - SIZE_OF_U32), compression_type)?;
+        decompress(&mut value_bytes, bytes.slice(..bytes.len() - SIZE_OF_U32), compression_type, dict)?;
         Ok(LargeValueBlock{
             value_bytes: Bytes::from(value_bytes),
             encoded_bytes: OnceCell::with_value((bytes, compression_type)),
@@ -75,9 +76,10 @@ impl NormalBlock {
     /// └────────────────────────────────────────────────────────────────────────────────────────┘
     /// 
     /// The block body may be compressed then we calculate its checksum (the checksum is not compressed).
-    fn encode(&self, w: &mut Vec<u8>, mut compression_type: CompressionType) -> CompressionType  {
+    fn encode(&self, w: &mut Vec<u8>, mut compression_type: CompressionType, dict: Option<&[u8]>) -> CompressionType  {
         if let Some((encoded_data, encoded_compression_type)) = self.encoded_data.get() {
-            if encoded_compression_type == &compression_type {
+            // The dictionary of the new table may be different
+            if encoded_compression_type == &compression_type && !compression_type.uses_dictionary() {
                 w.extend_from_slice(encoded_data);
                 return compression_type;
             }
@@ -89,10 +91,10 @@ impl NormalBlock {
             buf.extend_from_slice(&offset.to_le_bytes());
         }
         buf.extend_from_slice(&(self.offsets.len() as u16).to_le_bytes());
-        compress(w, &buf, compression_type);
-        if !compression_type.is_none() && w.len() - origin_len > buf.len(){
+        let compressed = compress(w, &buf, compression_type, dict).is_ok();
+        if !compression_type.is_none() && (!compressed || w.len() - origin_len > buf.len()){
             w.truncate(origin_len);
-            compress(w, &buf, CompressionType::None);
+            compress(w, &buf, CompressionType::None, None).unwrap();
             ensure_cov::notify_cov("kv_store::block::NormalBlock::encode::compress_fallback");
             compression_type = CompressionType::None;
         }
@@ -101,12 +103,17 @@ impl NormalBlock {
         compression_type
     }
 
-    fn decode(raw_block_and_check: Bytes, first_key: Bytes, compression_type: CompressionType)-> LoroResult<NormalBlock>{
+    fn decode(raw_block_and_check: Bytes, first_key: Bytes, compression_type: CompressionType, dict: Option<&[u8]>)-> LoroResult<NormalBlock>{
         let buf = raw_block_and_check.slice(..raw_block_and_check.len() - SIZE_OF_U32);
         let mut data = vec![];
-        decompress(&mut data, buf, compression_type)?;
+        decompress(&mut data, buf, compression_type, dict)?;
+        if data.len() < SIZE_OF_U16 {
+            return Err(LoroError::DecodeError("Invalid block bytes".into()));
+        }
         let offsets_len = (&data[data.len() - SIZE_OF_U16..]).get_u16_le() as usize;
-        let data_end = data.len() - SIZE_OF_U16 * (offsets_len + 1);
+        let Some(data_end) = data.len().checked_sub(SIZE_OF_U16 * (offsets_len + 1)) else {
+            return Err(LoroError::DecodeError("Invalid block bytes".into()));
+        };
         let offsets = &data[data_end..data.len() - SIZE_OF_U16];
         let offsets = offsets.chunks(SIZE_OF_U16).map(|mut chunk| chunk.get_u16_le()).collect();
         Ok(NormalBlock{
@@ -163,19 +170,24 @@ impl Block{
         }
     }
 
-    pub fn encode(&self,  w: &mut Vec<u8>, compression_type: CompressionType)->CompressionType{
+    /// Encode the block and return the compression type actually used.
+    ///
+    /// `dict` is the dictionary of the table the block is written into.
+    pub fn encode(&self,  w: &mut Vec<u8>, compression_type: CompressionType, dict: Option<&[u8]>)->CompressionType{
         match self{
-            Block::Normal(block) => block.encode(w,compression_type),
-            Block::Large(block) => block.encode(w,compression_type),
+            Block::Normal(block) => block.encode(w,compression_type, dict),
+            Block::Large(block) => block.encode(w,compression_type, dict),
         }
     }
 
-    pub fn decode(raw_block_and_check: Bytes, is_large: bool, key: Bytes, compression_type: CompressionType)->Self{
-        // we have checked the checksum, so the block should be valid when decompressing
+    pub fn decode(raw_block_and_check: Bytes, is_large: bool, key: Bytes, compression_type: CompressionType, dict: Option<&[u8]>)->LoroResult<Self>{
+        if raw_block_and_check.len() < SIZE_OF_U32 {
+            return Err(LoroError::DecodeError("Invalid block bytes".into()));
+        }
         if is_large{
-            return LargeValueBlock::decode(raw_block_and_check, key, compression_type).map(Block::Large).unwrap()
+            return LargeValueBlock::decode(raw_block_and_check, key, compression_type, dict).map(Block::Large)
         }
-        NormalBlock::decode(raw_block_and_check, key, compression_type).map(Block::Normal).unwrap()
+        NormalBlock::decode(raw_block_and_check, key, compression_type, dict).map(Block::Normal)
     }
 
     pub fn len(&self)->usize{
@@ -3,16 +3,46 @@ use std::io::{self, Write};
 use bytes::Bytes;
 use loro_common::LoroError;
 
+#[cfg(feature = "zstd")]
+pub const ZSTD_COMPRESSION_LEVEL: i32 = 3;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompressionType {
-    None,
-    LZ4,
+    None = 0,
+    LZ4 = 1,
+    #[cfg(feature = "zstd")]
+    Zstd = 2,
+    /// Zstd with the dictionary stored in the SSTable meta.
+    ///
+    /// It's used automatically instead of [CompressionType::Zstd] when the store is
+    /// configured with a dictionary.
+    #[cfg(feature = "zstd")]
+    ZstdWithDict = 3,
 }
 
 impl CompressionType {
     pub fn is_none(&self) -> bool {
         matches!(self, CompressionType::None)
     }
+
+    /// Whether the compressed bytes depend on the dictionary of the SSTable
+    pub fn uses_dictionary(&self) -> bool {
+        #[cfg(feature = "zstd")]
+        if matches!(self, CompressionType::ZstdWithDict) {
+            return true;
+        }
+
+        false
+    }
+
+    /// Whether it can only be read by the readers that support the schema version 1
+    pub(crate) fn requires_v1_schema(&self) -> bool {
+        match self {
+            CompressionType::None | CompressionType::LZ4 => false,
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd | CompressionType::ZstdWithDict => true,
+        }
+    }
 }
 
 impl TryFrom<u8> for CompressionType {
@@ -22,6 +52,14 @@ impl TryFrom<u8> for CompressionType {
         match value {
             0 => Ok(CompressionType::None),
             1 => Ok(CompressionType::LZ4),
+            #[cfg(feature = "zstd")]
+            2 => Ok(CompressionType::Zstd),
+            #[cfg(feature = "zstd")]
+            3 => Ok(CompressionType::ZstdWithDict),
+            #[cfg(not(feature = "zstd"))]
+            2 | 3 => Err(LoroError::DecodeError(
+                "Zstd compression is not supported. Enable the `zstd` feature to read it".into(),
+            )),
             _ => Err(LoroError::DecodeError(
                 format!("Invalid compression type: {}", value).into(),
             )),
@@ -34,27 +72,65 @@ impl From<CompressionType> for u8 {
         match value {
             CompressionType::None => 0,
             CompressionType::LZ4 => 1,
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd => 2,
+            #[cfg(feature = "zstd")]
+            CompressionType::ZstdWithDict => 3,
         }
     }
 }
 
-pub fn compress(w: &mut Vec<u8>, data: &[u8], compression_type: CompressionType) {
+/// Compress the data. `dict` is only used by [CompressionType::ZstdWithDict].
+///
+/// `w` may contain partially written bytes when it fails.
+pub fn compress(
+    w: &mut Vec<u8>,
+    data: &[u8],
+    compression_type: CompressionType,
+    #[allow(unused)] dict: Option<&[u8]>,
+) -> Result<(), LoroError> {
     match compression_type {
         CompressionType::None => {
             w.write_all(data).unwrap();
+            Ok(())
         }
         CompressionType::LZ4 => {
             let mut encoder = lz4_flex::frame::FrameEncoder::new(w);
-            encoder.write_all(data).unwrap();
-            let _w = encoder.finish().unwrap();
+            encoder.write_all(data).map_err(encode_error)?;
+            encoder.finish().map_err(encode_error)?;
+            Ok(())
+        }
+        #[cfg(feature = "zstd")]
+        CompressionType::Zstd => {
+            zstd::stream::copy_encode(data, w, ZSTD_COMPRESSION_LEVEL).map_err(encode_error)
+        }
+        #[cfg(feature = "zstd")]
+        CompressionType::ZstdWithDict => {
+            let Some(dict) = dict else {
+                return Err(LoroError::Unknown(
+                    "ZstdWithDict requires a dictionary".into(),
+                ));
+            };
+            let mut encoder =
+                zstd::stream::Encoder::with_dictionary(w, ZSTD_COMPRESSION_LEVEL, dict)
+                    .map_err(encode_error)?;
+            encoder.write_all(data).map_err(encode_error)?;
+            encoder.finish().map_err(encode_error)?;
+            Ok(())
         }
     }
 }
 
+fn encode_error(e: impl std::fmt::Display) -> LoroError {
+    LoroError::Unknown(format!("Failed to compress the block: {}", e).into())
+}
+
+/// Decompress the data. `dict` is only used by [CompressionType::ZstdWithDict].
 pub fn decompress(
     out: &mut Vec<u8>,
     data: Bytes,
     compression_type: CompressionType,
+    #[allow(unused)] dict: Option<&[u8]>,
 ) -> Result<(), LoroError> {
     match compression_type {
         CompressionType::None => {
@@ -67,5 +143,38 @@ pub fn decompress(
                 .map_err(|e| LoroError::DecodeError(e.to_string().into()))?;
             Ok(())
         }
+        #[cfg(feature = "zstd")]
+        CompressionType::Zstd => {
+            zstd::stream::copy_decode(data.as_ref(), out)
+                .map_err(|e| LoroError::DecodeError(e.to_string().into()))?;
+            Ok(())
+        }
+        #[cfg(feature = "zstd")]
+        CompressionType::ZstdWithDict => {
+            let Some(dict) = dict else {
+                return Err(LoroError::DecodeError(
+                    "Missing zstd dictionary in the sstable".into(),
+                ));
+            };
+            let mut decoder = zstd::stream::Decoder::with_dictionary(data.as_ref(), dict)
+                .map_err(|e| LoroError::DecodeError(e.to_string().into()))?;
+            io::copy(&mut decoder, out)
+                .map_err(|e| LoroError::DecodeError(e.to_string().into()))?;
+            Ok(())
+        }
     }
 }
+
+/// Train a zstd dictionary from the sample values.
+///
+/// The dictionary can be set by [crate::mem_store::MemKvConfig::zstd_dictionary].
+/// It's most effective when the values are small and share a lot of content, e.g. map keys.
+#[cfg(feature = "zstd")]
+pub fn train_zstd_dictionary<S: AsRef<[u8]>>(
+    samples: &[S],
+    max_size: usize,
+) -> Result<Bytes, LoroError> {
+    zstd::dict::from_samples(samples, max_size)
+        .map(Bytes::from)
+        .map_err(|e| LoroError::Unknown(e.to_string().into()))
+}
@@ -0,0 +1,382 @@
+//! A persistent key-value store backed by a directory on the local file system.
+//!
+//! # Layout
+//!
+//! - `data.sst`: the compacted data, encoded in the SSTable format described in the crate docs.
+//! - `wal.log`: the write-ahead log of the mutations since the last compaction.
+//!
+//! Each WAL record is encoded as follows:
+//!
+//! ┌───────────┬───────────┬─────────────┬───────┬───────┐
+//! │ len       │ checksum  │ key len     │ key   │ value │
+//! │ u32       │ u32       │ u32         │ bytes │ bytes │
+//! └───────────┴───────────┴─────────────┴───────┴───────┘
+//!
+//! `len` is the length of the payload (key len + key + value), and `checksum` is the
+//! xxhash_32 of the payload. An empty value means the key is removed. A record whose
+//! key len is `u32::MAX` has no key and value, it marks the end of a batch.
+//!
+//! Mutations are buffered in memory until [FileKvStore::flush] is called, which appends
+//! them to the WAL as one batch and syncs it to disk. When the WAL grows larger than the configured
+//! threshold, the data is compacted into a new SSTable, which is written to a temporary
+//! file and then renamed over `data.sst`, so a crash never leaves a half-written table behind.
+//!
+//! On open, only the block meta of `data.sst` is loaded, the blocks are read from the file
+//! on demand. The WAL is replayed on top of it. A torn or corrupted record at the tail
+//! of the WAL (e.g. caused by a crash in the middle of a write) is discarded together with
+//! everything after it, and so is the unfinished batch before it. So each flush is atomic.
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Bound,
+    path::{Path, PathBuf},
+};
+
+use bytes::{Buf, BufMut, Bytes};
+
+use crate::{
+    compress::CompressionType,
+    mem_store::{MemKvConfig, MemKvStore},
+    sstable::{SsTable, XXH_SEED},
+};
+
+const DATA_FILE: &str = "data.sst";
+const DATA_TMP_FILE: &str = "data.sst.tmp";
+const WAL_FILE: &str = "wal.log";
+const WAL_HEADER_SIZE: usize = 8;
+const WAL_COMMIT_KEY_LEN: u32 = u32::MAX;
+
+pub struct FileKvConfig {
+    mem: MemKvConfig,
+    compact_threshold: usize,
+}
+
+impl Default for FileKvConfig {
+    fn default() -> Self {
+        Self {
+            mem: MemKvConfig::default(),
+            compact_threshold: FileKvStore::DEFAULT_COMPACT_THRESHOLD,
+        }
+    }
+}
+
+impl FileKvConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.mem = self.mem.block_size(block_size);
+        self
+    }
+
+    pub fn compression_type(mut self, compression_type: CompressionType) -> Self {
+        self.mem = self.mem.compression_type(compression_type);
+        self
+    }
+
+    /// See [MemKvConfig::zstd_dictionary]
+    #[cfg(feature = "zstd")]
+    pub fn zstd_dictionary(mut self, dictionary: Bytes) -> Self {
+        self.mem = self.mem.zstd_dictionary(dictionary);
+        self
+    }
+
+    /// The WAL will be compacted into the SSTable when its size exceeds this threshold in bytes.
+    pub fn compact_threshold(mut self, compact_threshold: usize) -> Self {
+        self.compact_threshold = compact_threshold;
+        self
+    }
+
+    pub fn open(self, dir: impl AsRef<Path>) -> io::Result<FileKvStore> {
+        FileKvStore::open_with_config(dir, self)
+    }
+}
+
+#[derive(Debug)]
+pub struct FileKvStore {
+    dir: PathBuf,
+    mem: MemKvStore,
+    wal: File,
+    wal_size: usize,
+    /// The encoded WAL records that haven't been written to the file yet
+    pending: Vec<u8>,
+    /// The whole content is replaced by [FileKvStore::import_all], the WAL cannot describe it.
+    need_compact: bool,
+    compact_threshold: usize,
+}
+
+impl FileKvStore {
+    pub const DEFAULT_COMPACT_THRESHOLD: usize = 4 * 1024 * 1024;
+
+    /// Open the store in the given directory. The directory is created if it doesn't exist.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        Self::open_with_config(dir, FileKvConfig::default())
+    }
+
+    pub fn open_with_config(dir: impl AsRef<Path>, config: FileKvConfig) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        // A leftover of an interrupted compaction. The data in it is still in the WAL.
+        let _ = fs::remove_file(dir.join(DATA_TMP_FILE));
+        let mut mem = config.mem.build();
+        match File::open(dir.join(DATA_FILE)) {
+            Ok(file) => {
+                if file.metadata()?.len() > 0 {
+                    mem.reset_with_table(Some(open_table(file)?));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let mut wal = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(WAL_FILE))?;
+        let mut wal_bytes = Vec::new();
+        wal.read_to_end(&mut wal_bytes)?;
+        let valid_len = replay_wal(&mut mem, &wal_bytes);
+        if valid_len != wal_bytes.len() {
+            tracing::warn!(
+                "Discard {} bytes of the corrupted WAL tail",
+                wal_bytes.len() - valid_len
+            );
+            wal.set_len(valid_len as u64)?;
+            wal.sync_all()?;
+            wal.seek(SeekFrom::Start(valid_len as u64))?;
+        }
+
+        Ok(Self {
+            dir,
+            mem,
+            wal,
+            wal_size: valid_len,
+            pending: Vec::new(),
+            need_compact: false,
+            compact_threshold: config.compact_threshold,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<Bytes> {
+        self.mem.get(key)
+    }
+
+    pub fn set(&mut self, key: &[u8], value: Bytes) {
+        encode_wal_record(&mut self.pending, key, &value);
+        self.mem.set(key, value);
+    }
+
+    pub fn compare_and_swap(&mut self, key: &[u8], old: Option<Bytes>, new: Bytes) -> bool {
+        if self.mem.get(key) != old {
+            return false;
+        }
+
+        self.set(key, new);
+        true
+    }
+
+    pub fn remove(&mut self, key: &[u8]) {
+        self.set(key, Bytes::new());
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.mem.contains_key(key)
+    }
+
+    pub fn scan(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Box<dyn DoubleEndedIterator<Item = (Bytes, Bytes)> + '_> {
+        self.mem.scan(start, end)
+    }
+
+    pub fn len(&self) -> usize {
+        self.mem.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mem.is_empty()
+    }
+
+    pub fn size(&self) -> usize {
+        self.mem.size()
+    }
+
+    /// Export all the data in the SSTable format. It doesn't touch the files.
+    pub fn export_all(&mut self) -> Bytes {
+        self.mem.export_all()
+    }
+
+    /// Import the SSTable bytes on top of the current data.
+    ///
+    /// The change is persisted by a compaction on the next [FileKvStore::flush].
+    pub fn import_all(&mut self, bytes: Bytes) -> Result<(), String> {
+        self.mem.import_all(bytes)?;
+        self.need_compact = true;
+        Ok(())
+    }
+
+    /// Get an in-memory copy of the current data
+    pub fn to_mem_store(&self) -> MemKvStore {
+        self.mem.clone()
+    }
+
+    /// The size of the WAL file in bytes, excluding the unflushed mutations.
+    pub fn wal_size(&self) -> usize {
+        self.wal_size
+    }
+
+    /// Persist the buffered mutations.
+    ///
+    /// The WAL is compacted into the SSTable if it exceeds the compaction threshold.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.need_compact || self.wal_size + self.pending.len() > self.compact_threshold {
+            return self.compact();
+        }
+
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        encode_wal_commit(&mut self.pending);
+        self.wal.write_all(&self.pending)?;
+        self.wal.sync_data()?;
+        self.wal_size += self.pending.len();
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Write all the data into a new SSTable and clear the WAL.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let data = self.mem.export_all();
+        let tmp_path = self.dir.join(DATA_TMP_FILE);
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            tmp.write_all(&data)?;
+            tmp.sync_all()?;
+        }
+        // Release the old file before it's replaced
+        let table = if data.is_empty() {
+            None
+        } else {
+            Some(
+                SsTable::import_all(data)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?,
+            )
+        };
+        self.mem.reset_with_table(table);
+        let data_path = self.dir.join(DATA_FILE);
+        fs::rename(&tmp_path, &data_path)?;
+        sync_dir(&self.dir)?;
+        // Read the blocks from the new file instead of keeping the whole table in memory
+        let file = File::open(&data_path)?;
+        if file.metadata()?.len() > 0 {
+            self.mem.reset_with_table(Some(open_table(file)?));
+        }
+
+        // The WAL must be cleared after the new table is in place.
+        // Otherwise a crash in between would lose the data in the WAL.
+        self.wal.set_len(0)?;
+        self.wal.sync_all()?;
+        self.wal.seek(SeekFrom::Start(0))?;
+        self.wal_size = 0;
+        self.pending.clear();
+        self.need_compact = false;
+        Ok(())
+    }
+}
+
+impl Drop for FileKvStore {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            tracing::error!("Failed to flush FileKvStore: {}", e);
+        }
+    }
+}
+
+fn open_table(file: File) -> io::Result<SsTable> {
+    SsTable::open_file(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+fn encode_wal_commit(buf: &mut Vec<u8>) {
+    let payload = WAL_COMMIT_KEY_LEN.to_le_bytes();
+    buf.put_u32_le(payload.len() as u32);
+    buf.put_u32_le(xxhash_rust::xxh32::xxh32(&payload, XXH_SEED));
+    buf.extend_from_slice(&payload);
+}
+
+fn encode_wal_record(buf: &mut Vec<u8>, key: &[u8], value: &[u8]) {
+    let payload_len = 4 + key.len() + value.len();
+    buf.reserve(WAL_HEADER_SIZE + payload_len);
+    buf.put_u32_le(payload_len as u32);
+    let checksum_pos = buf.len();
+    buf.put_u32_le(0);
+    let payload_start = buf.len();
+    buf.put_u32_le(key.len() as u32);
+    buf.extend_from_slice(key);
+    buf.extend_from_slice(value);
+    let checksum = xxhash_rust::xxh32::xxh32(&buf[payload_start..], XXH_SEED);
+    buf[checksum_pos..payload_start].copy_from_slice(&checksum.to_le_bytes());
+}
+
+/// Apply the complete batches of valid WAL records to the store.
+/// Returns the length of the valid prefix.
+fn replay_wal(mem: &mut MemKvStore, wal: &[u8]) -> usize {
+    let mut offset = 0;
+    let mut committed = 0;
+    let mut batch = Vec::new();
+    while wal.len() - offset >= WAL_HEADER_SIZE {
+        let mut header = &wal[offset..offset + WAL_HEADER_SIZE];
+        let payload_len = header.get_u32_le() as usize;
+        let checksum = header.get_u32_le();
+        let payload_start = offset + WAL_HEADER_SIZE;
+        if payload_len < 4 || wal.len() - payload_start < payload_len {
+            break;
+        }
+
+        let payload = &wal[payload_start..payload_start + payload_len];
+        if xxhash_rust::xxh32::xxh32(payload, XXH_SEED) != checksum {
+            break;
+        }
+
+        let key_len = (&payload[..4]).get_u32_le();
+        if key_len == WAL_COMMIT_KEY_LEN {
+            for (key, value) in batch.drain(..) {
+                mem.set(&key, value);
+            }
+            offset = payload_start + payload_len;
+            committed = offset;
+            continue;
+        }
+
+        let key_len = key_len as usize;
+        if key_len > payload_len - 4 {
+            break;
+        }
+
+        let key = Bytes::copy_from_slice(&payload[4..4 + key_len]);
+        let value = Bytes::copy_from_slice(&payload[4 + key_len..]);
+        batch.push((key, value));
+        offset = payload_start + payload_len;
+    }
+
+    committed
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -18,6 +18,10 @@
 //!
 //! 1. Magic Number (4 bytes): A fixed value "LORO" to identify the file format.
 //! 2. Schema Version (1 byte): The version of the MemKVStore schema.
+//!     - `0`: the blocks are compressed by `None` or `LZ4`.
+//!     - `1`: the blocks may also be compressed by zstd, and the Block Meta section contains the zstd dictionary.
+//!       A table is only written in this version when it needs to be, so the tables without zstd can still be read
+//!       by the old readers. The old readers reject the version 1 tables with an "Invalid schema version" error.
 //! 3. Block Chunks: A series of data blocks containing key-value pairs.
 //! 4. Block Meta: Metadata for all blocks, including block offset, the first key of the block, `is_large` flag, and last key
 //!    if not large.
@@ -64,12 +68,13 @@
 //! 2. Write offsets for each key-value pair.
 //! 3. Write the number of key-value pairs.
 //! 4. By default, **Compress** the entire block using LZ4. If you set `compression_type` to `None`, it will not compress the block.
-//!     - For now, there are two compression type: `None` and `LZ4`.
+//!     - For now, there are four compression types: `None`, `LZ4`, `Zstd` and `ZstdWithDict`.
+//!       The zstd ones are only available with the `zstd` feature.
 //! 5. Calculate and append xxhash_32 checksum.
 //!
 //! Decoding:
 //! 1. Verify the xxhash_32 checksum.
-//! 2. By default, **Decompress** the block using LZ4. If you set `compression_type` to `None`, it will not decompress the block.
+//! 2. **Decompress** the block according to the compression type in the Block Meta. If you set `compression_type` to `None`, it will not decompress the block.
 //! 3. Read the number of key-value pairs.
 //! 4. Read offsets for each key-value pair.
 //! 5. Parse individual key-value chunks.
@@ -122,21 +127,26 @@
 //! 1. Write the number of blocks.
 //! 2. For each block, write its metadata (offset, first key, block type, and last key if not large).
 //!     - block type: the first bit for is_large, the next 7 bits for compression_type.
-//! 3. Calculate and append xxhash_32 checksum.
+//! 3. Since schema version 1, write the length of the zstd dictionary (u32) and the dictionary bytes.
+//!    The length is 0 if there is no dictionary.
+//! 4. Calculate and append xxhash_32 checksum.
 //!
 //! Decoding:
 //! 1. Read the number of blocks.
 //! 2. For each block, read its metadata.
-//! 3. Verify the xxhash_32 checksum.
+//! 3. Since schema version 1, read the zstd dictionary.
+//! 4. Verify the xxhash_32 checksum.
 //!
 //!
 //! Note: In this crate, the empty value is regarded as deleted. **only** [MemStoreIterator] will filter empty value.
 //! Other iterators will still return empty value.
 pub mod block;
 pub mod compress;
+pub mod file_store;
 pub mod iter;
 pub mod mem_store;
 pub mod sstable;
 mod utils;
+pub use file_store::FileKvStore;
 pub use iter::{KvIterator, MergeIterator};
 pub use mem_store::{MemKvStore, MemStoreIterator};
@@ -14,6 +14,7 @@ pub struct MemKvStore {
     ss_table: Vec<SsTable>,
     block_size: usize,
     compression_type: CompressionType,
+    dictionary: Option<Bytes>,
     /// It's only true when using it to fuzz.
     /// Otherwise, importing and exporting GC snapshot relies on this field being false to work.
     should_encode_none: bool,
@@ -22,6 +23,7 @@ pub struct MemKvStore {
 pub struct MemKvConfig {
     block_size: usize,
     compression_type: CompressionType,
+    dictionary: Option<Bytes>,
     should_encode_none: bool,
 }
 
@@ -30,6 +32,7 @@ impl Default for MemKvConfig {
         Self {
             block_size: MemKvStore::DEFAULT_BLOCK_SIZE,
             compression_type: CompressionType::LZ4,
+            dictionary: None,
             should_encode_none: false,
         }
     }
@@ -50,6 +53,16 @@ impl MemKvConfig {
         self
     }
 
+    /// Use the dictionary to compress the blocks when the compression type is zstd.
+    ///
+    /// The dictionary can be trained by [crate::compress::train_zstd_dictionary].
+    /// It's stored in the exported bytes, so the reader doesn't need to know it in advance.
+    #[cfg(feature = "zstd")]
+    pub fn zstd_dictionary(mut self, dictionary: Bytes) -> Self {
+        self.dictionary = Some(dictionary);
+        self
+    }
+
     pub fn should_encode_none(mut self, should_encode_none: bool) -> Self {
         self.should_encode_none = should_encode_none;
         self
@@ -68,6 +81,7 @@ impl MemKvStore {
             ss_table: Vec::new(),
             block_size: config.block_size,
             compression_type: config.compression_type,
+            dictionary: config.dictionary,
             should_encode_none: config.should_encode_none,
         }
     }
@@ -208,7 +222,8 @@ impl MemKvStore {
             self.block_size,
             self.compression_type,
             self.should_encode_none,
-        );
+        )
+        .with_dictionary(self.dictionary.clone());
         // we could use scan() here, we should keep the empty value
         let iter = MemStoreIterator::new(
             self.mem_table
@@ -248,6 +263,12 @@ impl MemKvStore {
         Ok(())
     }
 
+    /// Replace all the data with the given table, e.g. a table opened by [SsTable::open_file].
+    pub(crate) fn reset_with_table(&mut self, table: Option<SsTable>) {
+        self.mem_table.clear();
+        self.ss_table = table.into_iter().collect();
+    }
+
     #[tracing::instrument(level = "debug", skip(self))]
     fn export_with_encoded_block(&mut self) -> Bytes {
         ensure_cov::notify_cov("kv-store::mem_store::export_with_encoded_block");
@@ -257,7 +278,8 @@ impl MemKvStore {
             self.block_size,
             self.compression_type,
             self.should_encode_none,
-        );
+        )
+        .with_dictionary(self.dictionary.clone());
         'outer: while let Some(next_mem_pair) = mem_iter.peek() {
             let block = loop {
                 let Some(block) = sstable_iter.peek_next_block() else {
@@ -364,7 +386,9 @@ where
     T: DoubleEndedIterator<Item = (Bytes, Bytes)>,
     S: DoubleEndedIterator<Item = (Bytes, Bytes)>,
 {
-    fn new(mut mem: T, sst: S, filter_empty: bool) -> Self {
+    /// Merge the two sorted iterators. The entries of `mem` override the entries of `sst`
+    /// with the same key, and the empty values are skipped if `filter_empty` is true.
+    pub fn new(mut mem: T, sst: S, filter_empty: bool) -> Self {
         let current_mem = mem.next();
         let back_mem = mem.next_back();
         Self {
@@ -8,16 +8,29 @@ use crate::{
 use bytes::{Buf, BufMut, Bytes};
 use ensure_cov::*;
 use loro_common::{LoroError, LoroResult};
-use std::{fmt::Debug, ops::Bound, sync::Arc};
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    ops::{Bound, Range},
+    sync::{Arc, Mutex},
+};
 
 pub(crate) const XXH_SEED: u32 = u32::from_le_bytes(*b"LORO");
 const MAGIC_BYTES: [u8; 4] = *b"LORO";
-const CURRENT_SCHEMA_VERSION: u8 = 0;
+/// The tables that don't need the features of the newer versions are still written in this
+/// version, so that they can be read by the old readers.
+const LEGACY_SCHEMA_VERSION: u8 = 0;
+/// Since version 1, the blocks may be compressed by zstd and the meta contains the zstd dictionary.
+const CURRENT_SCHEMA_VERSION: u8 = 1;
 pub const SIZE_OF_U8: usize = std::mem::size_of::<u8>();
 pub const SIZE_OF_U16: usize = std::mem::size_of::<u16>();
 pub const SIZE_OF_U32: usize = std::mem::size_of::<u32>();
 // TODO: cache size
 const DEFAULT_CACHE_SIZE: usize = 1 << 20;
+/// The tables opened from files are not expected to fit in memory,
+/// so only a limited number of their blocks are cached.
+const FILE_CACHE_SIZE: usize = 1 << 10;
 const MAX_BLOCK_NUM: u32 = 10_000_000;
 
 /// ```log
@@ -46,7 +59,22 @@ impl BlockMeta {
     /// ││     u32      │   bytes    │      │   bytes    │   u32     │
     /// │ ─ ─ ─ ─ ─ ─ ─ ┘─ ─ ─ ─ ─ ─ ┘─ ─ ─ ┘─ ─ ─ ─ ─ ─ ┘─ ─ ─ ─ ─ ┘│
     /// └────────────────────────────────────────────────────────────┘
-    fn encode_meta(meta: &[BlockMeta], buf: &mut Vec<u8>) {
+    ///
+    /// Since schema version 1, the dictionary is stored between the last Block Meta and the checksum:
+    ///
+    /// ┌───────────────────────────────┐
+    /// │ Dictionary                    │
+    /// │┌ ─ ─ ─ ─ ─ ─ ─ ─┌ ─ ─ ─ ─ ─ ─ │
+    /// │  dict length   │  dict      ││
+    /// ││     u32        │  bytes      │
+    /// │ ─ ─ ─ ─ ─ ─ ─ ─ ┘─ ─ ─ ─ ─ ─ ┘│
+    /// └───────────────────────────────┘
+    fn encode_meta(
+        meta: &[BlockMeta],
+        schema_version: u8,
+        dictionary: Option<&[u8]>,
+        buf: &mut Vec<u8>,
+    ) {
         // the number of blocks
         let mut estimated_size = SIZE_OF_U32;
         for m in meta {
@@ -66,6 +94,9 @@ impl BlockMeta {
             // last key
             estimated_size += m.last_key.as_ref().unwrap().len();
         }
+        if schema_version >= CURRENT_SCHEMA_VERSION {
+            estimated_size += SIZE_OF_U32 + dictionary.map_or(0, |d| d.len());
+        }
         // checksum
         estimated_size += SIZE_OF_U32;
 
@@ -76,7 +107,7 @@ impl BlockMeta {
             buf.put_u32_le(m.offset as u32);
             buf.put_u16_le(m.first_key.len() as u16);
             buf.put_slice(&m.first_key);
-            let large_and_compress = (m.is_large as u8) << 7 | m.compression_type as u8;
+            let large_and_compress = (m.is_large as u8) << 7 | u8::from(m.compression_type);
             buf.put_u8(large_and_compress);
             if m.is_large {
                 continue;
@@ -84,11 +115,17 @@ impl BlockMeta {
             buf.put_u16_le(m.last_key.as_ref().unwrap().len() as u16);
             buf.put_slice(m.last_key.as_ref().unwrap());
         }
+        if schema_version >= CURRENT_SCHEMA_VERSION {
+            let dictionary = dictionary.unwrap_or_default();
+            buf.put_u32_le(dictionary.len() as u32);
+            buf.put_slice(dictionary);
+        }
         let checksum = xxhash_rust::xxh32::xxh32(&buf[ori_length + 4..], XXH_SEED);
         buf.put_u32_le(checksum);
     }
 
-    fn decode_meta(data: &[u8]) -> LoroResult<Vec<BlockMeta>> {
+    /// Returns the block metas and the dictionary
+    fn decode_meta(data: &[u8], schema_version: u8) -> LoroResult<(Vec<BlockMeta>, Option<Bytes>)> {
         let (num, mut data) = get_u32_le(data)?;
         if num > MAX_BLOCK_NUM {
             return Err(LoroError::DecodeError("Invalid bytes".into()));
@@ -133,11 +170,22 @@ impl BlockMeta {
             });
             data = buf;
         }
+        let mut dictionary = None;
+        if schema_version >= CURRENT_SCHEMA_VERSION {
+            let (dict_len, mut buf) = get_u32_le(data)?;
+            if buf.len() < dict_len as usize {
+                return Err(LoroError::DecodeError("Invalid bytes".into()));
+            }
+            if dict_len > 0 {
+                dictionary = Some(buf.copy_to_bytes(dict_len as usize));
+            }
+            data = buf;
+        }
         let (checksum_read, _) = get_u32_le(data)?;
         if checksum != checksum_read {
             return Err(LoroError::DecodeChecksumMismatchError);
         }
-        Ok(ans)
+        Ok((ans, dictionary))
     }
 }
 
@@ -149,6 +197,7 @@ pub(crate) struct SsTableBuilder {
     meta: Vec<BlockMeta>,
     block_size: usize,
     compression_type: CompressionType,
+    dictionary: Option<Bytes>,
     include_none: bool, // TODO: bloom filter
 }
 
@@ -156,7 +205,8 @@ impl SsTableBuilder {
     pub fn new(block_size: usize, compression_type: CompressionType, include_none: bool) -> Self {
         let mut data = Vec::with_capacity(5);
         data.put_u32_le(u32::from_le_bytes(MAGIC_BYTES));
-        data.put_u8(CURRENT_SCHEMA_VERSION);
+        // It will be updated in `build` if the newer schema is required
+        data.put_u8(LEGACY_SCHEMA_VERSION);
         Self {
             block_builder: BlockBuilder::new(block_size),
             first_key: Bytes::new(),
@@ -165,10 +215,29 @@ impl SsTableBuilder {
             meta: Vec::new(),
             block_size,
             compression_type,
+            dictionary: None,
             include_none,
         }
     }
 
+    /// Set the zstd dictionary of the table.
+    ///
+    /// It only takes effect when the compression type is zstd.
+    pub fn with_dictionary(mut self, dictionary: Option<Bytes>) -> Self {
+        #[cfg(feature = "zstd")]
+        {
+            self.compression_type = match (self.compression_type, &dictionary) {
+                (CompressionType::Zstd, Some(_)) => CompressionType::ZstdWithDict,
+                (CompressionType::ZstdWithDict, None) => CompressionType::Zstd,
+                (t, _) => t,
+            };
+        }
+        if self.compression_type.uses_dictionary() {
+            self.dictionary = dictionary;
+        }
+        self
+    }
+
     pub fn add(&mut self, key: Bytes, value: Bytes) {
         if !self.include_none && value.is_empty() {
             return;
@@ -235,7 +304,11 @@ impl SsTableBuilder {
     fn add_new_block_inner(&mut self, block: &Block) {
         assert!(self.block_builder.is_empty());
         let offset = self.data.len();
-        let real_compression_type = block.encode(&mut self.data, self.compression_type);
+        let real_compression_type = block.encode(
+            &mut self.data,
+            self.compression_type,
+            self.dictionary.as_deref(),
+        );
         let is_large = block.is_large();
         let meta = BlockMeta {
             offset,
@@ -263,8 +336,24 @@ impl SsTableBuilder {
     pub fn build(mut self) -> SsTable {
         self.finish_current_block();
         let mut buf = self.data;
+        let schema_version = if self.dictionary.is_some()
+            || self
+                .meta
+                .iter()
+                .any(|m| m.compression_type.requires_v1_schema())
+        {
+            CURRENT_SCHEMA_VERSION
+        } else {
+            LEGACY_SCHEMA_VERSION
+        };
+        buf[SIZE_OF_U32] = schema_version;
         let meta_offset = buf.len() as u32;
-        BlockMeta::encode_meta(&self.meta, &mut buf);
+        BlockMeta::encode_meta(
+            &self.meta,
+            schema_version,
+            self.dictionary.as_deref(),
+            &mut buf,
+        );
         buf.put_u32_le(meta_offset);
         let first_key = self
             .meta
@@ -284,11 +373,12 @@ impl SsTableBuilder {
             })
             .unwrap_or_default();
         SsTable {
-            data: Bytes::from(buf),
+            data: TableData::Mem(Bytes::from(buf)),
             first_key,
             last_key,
             meta: self.meta,
             meta_offset: meta_offset as usize,
+            dictionary: self.dictionary,
             block_cache: BlockCache::new(DEFAULT_CACHE_SIZE),
         }
     }
@@ -296,14 +386,62 @@ impl SsTableBuilder {
 
 type BlockCache = quick_cache::sync::Cache<usize, Arc<Block>>;
 
+/// The encoded bytes of a [SsTable]
+#[derive(Debug, Clone)]
+enum TableData {
+    Mem(Bytes),
+    /// The bytes are read from the file on demand, so the table doesn't need to fit in memory
+    File(Arc<TableFile>),
+}
+
+#[derive(Debug)]
+struct TableFile {
+    file: Mutex<File>,
+    len: usize,
+}
+
+impl TableData {
+    fn new_block_cache(&self) -> BlockCache {
+        match self {
+            TableData::Mem(_) => BlockCache::new(DEFAULT_CACHE_SIZE),
+            TableData::File(_) => BlockCache::new(FILE_CACHE_SIZE),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            TableData::Mem(bytes) => bytes.len(),
+            TableData::File(file) => file.len,
+        }
+    }
+
+    fn read(&self, range: Range<usize>) -> LoroResult<Bytes> {
+        match self {
+            TableData::Mem(bytes) => Ok(bytes.slice(range)),
+            TableData::File(file) => {
+                let mut buf = vec![0; range.len()];
+                let mut f = file.file.lock().unwrap();
+                f.seek(SeekFrom::Start(range.start as u64))
+                    .and_then(|_| f.read_exact(&mut buf))
+                    .map_err(io_error)?;
+                Ok(Bytes::from(buf))
+            }
+        }
+    }
+}
+
+fn io_error(e: io::Error) -> LoroError {
+    LoroError::DecodeError(format!("Failed to read the sstable: {}", e).into_boxed_str())
+}
+
 #[derive(Debug)]
 pub struct SsTable {
-    // TODO: mmap?
-    data: Bytes,
+    data: TableData,
     pub(crate) first_key: Bytes,
     pub(crate) last_key: Bytes,
     meta: Vec<BlockMeta>,
     meta_offset: usize,
+    dictionary: Option<Bytes>,
     block_cache: BlockCache,
 }
 
@@ -315,14 +453,22 @@ impl Clone for SsTable {
             last_key: self.last_key.clone(),
             meta: self.meta.clone(),
             meta_offset: self.meta_offset,
-            block_cache: BlockCache::new(DEFAULT_CACHE_SIZE),
+            dictionary: self.dictionary.clone(),
+            block_cache: self.data.new_block_cache(),
         }
     }
 }
 
 impl SsTable {
+    /// Get the encoded bytes of the table. A table opened from a file is read entirely.
     pub fn export_all(&self) -> Bytes {
-        self.data.clone()
+        match &self.data {
+            TableData::Mem(bytes) => bytes.clone(),
+            TableData::File(_) => self
+                .data
+                .read(0..self.data.len())
+                .expect("Failed to read the sstable file"),
+        }
     }
 
     pub fn iter(&self) -> SsTableIter {
@@ -337,17 +483,39 @@ impl SsTable {
     ///    - "Invalid magic number"
     ///    - "Invalid schema version"
     pub fn import_all(bytes: Bytes) -> LoroResult<Self> {
+        Self::open(TableData::Mem(bytes))
+    }
+
+    /// Open the table stored in the file.
+    ///
+    /// Only the block meta is kept in memory, the blocks are read from the file when
+    /// they are accessed. The checksums of the blocks are verified one block at a time.
+    ///
+    /// # Errors
+    ///
+    /// The same as [SsTable::import_all], and [LoroError::DecodeError] if the file cannot be read.
+    pub fn open_file(file: File) -> LoroResult<Self> {
+        let len = file.metadata().map_err(io_error)?.len() as usize;
+        Self::open(TableData::File(Arc::new(TableFile {
+            file: Mutex::new(file),
+            len,
+        })))
+    }
+
+    fn open(data: TableData) -> LoroResult<Self> {
+        let data_len = data.len();
         // magic number + schema version + meta offset
-        if bytes.len() < SIZE_OF_U32 + SIZE_OF_U8 + SIZE_OF_U32 {
+        if data_len < SIZE_OF_U32 + SIZE_OF_U8 + SIZE_OF_U32 {
             return Err(LoroError::DecodeError("Invalid sstable bytes".into()));
         }
-        let magic_number = u32::from_le_bytes((&bytes[..SIZE_OF_U32]).try_into().unwrap());
+        let header = data.read(0..SIZE_OF_U32 + SIZE_OF_U8)?;
+        let magic_number = u32::from_le_bytes((&header[..SIZE_OF_U32]).try_into().unwrap());
         if magic_number != u32::from_le_bytes(MAGIC_BYTES) {
             return Err(LoroError::DecodeError("Invalid magic number".into()));
         }
-        let schema_version = bytes[SIZE_OF_U32];
+        let schema_version = header[SIZE_OF_U32];
         match schema_version {
-            CURRENT_SCHEMA_VERSION => {}
+            LEGACY_SCHEMA_VERSION | CURRENT_SCHEMA_VERSION => {}
             _ => {
                 return Err(LoroError::DecodeError(
                     format!(
@@ -359,14 +527,18 @@ impl SsTable {
                 ))
             }
         }
-        let data_len = bytes.len();
-        let meta_offset = (&bytes[data_len - SIZE_OF_U32..]).get_u32_le() as usize;
+        let meta_offset = data.read(data_len - SIZE_OF_U32..data_len)?.get_u32_le() as usize;
         if meta_offset >= data_len - SIZE_OF_U32 {
             return Err(LoroError::DecodeError("Invalid bytes".into()));
         }
-        let raw_meta = &bytes[meta_offset..data_len - SIZE_OF_U32];
-        let meta = BlockMeta::decode_meta(raw_meta)?;
-        Self::check_block_checksum(&meta, &bytes, meta_offset)?;
+        let raw_meta = data.read(meta_offset..data_len - SIZE_OF_U32)?;
+        let (meta, dictionary) = BlockMeta::decode_meta(&raw_meta, schema_version)?;
+        if dictionary.is_none() && meta.iter().any(|m| m.compression_type.uses_dictionary()) {
+            return Err(LoroError::DecodeError(
+                "Missing zstd dictionary in the sstable".into(),
+            ));
+        }
+        Self::check_block_checksum(&meta, &data, meta_offset)?;
         let first_key = meta
             .first()
             .map(|m| m.first_key.clone())
@@ -380,28 +552,29 @@ impl SsTable {
             })
             .unwrap_or_default();
         let ans = Self {
-            data: bytes,
+            block_cache: data.new_block_cache(),
+            data,
             first_key,
             last_key,
             meta,
             meta_offset,
-            block_cache: BlockCache::new(DEFAULT_CACHE_SIZE),
+            dictionary,
         };
         Ok(ans)
     }
 
     fn check_block_checksum(
         meta: &[BlockMeta],
-        bytes: &Bytes,
+        data: &TableData,
         meta_offset: usize,
     ) -> LoroResult<()> {
         for i in 0..meta.len() {
             let offset = meta[i].offset;
             let offset_end = meta.get(i + 1).map_or(meta_offset, |m| m.offset);
-            if offset_end > bytes.len() {
+            if offset_end > data.len() || offset + SIZE_OF_U32 > offset_end {
                 return Err(LoroError::DecodeError("Invalid bytes".into()));
             }
-            let raw_block_and_check = bytes.slice(offset..offset_end);
+            let raw_block_and_check = data.read(offset..offset_end)?;
             let checksum = raw_block_and_check
                 .slice(raw_block_and_check.len() - SIZE_OF_U32..)
                 .get_u32_le();
@@ -429,26 +602,27 @@ impl SsTable {
             .min(self.meta.len() - 1)
     }
 
-    fn read_block(&self, block_idx: usize) -> Arc<Block> {
+    fn read_block(&self, block_idx: usize) -> LoroResult<Arc<Block>> {
         let offset = self.meta[block_idx].offset;
         let offset_end = self
             .meta
             .get(block_idx + 1)
             .map_or(self.meta_offset, |m| m.offset);
-        let raw_block_and_check = self.data.slice(offset..offset_end);
-        Arc::new(Block::decode(
+        let raw_block_and_check = self.data.read(offset..offset_end)?;
+        Ok(Arc::new(Block::decode(
             raw_block_and_check,
             self.meta[block_idx].is_large,
             self.meta[block_idx].first_key.clone(),
             self.meta[block_idx].compression_type,
-        ))
+            self.dictionary.as_deref(),
+        )?))
     }
 
     pub(crate) fn read_block_cached(&self, block_idx: usize) -> Arc<Block> {
+        // The checksums and the dictionary are verified when the table is opened,
+        // so it only fails when the file cannot be read anymore.
         self.block_cache
-            .get_or_insert_with(&block_idx, || {
-                Ok::<_, LoroError>(self.read_block(block_idx))
-            })
+            .get_or_insert_with(&block_idx, || self.read_block(block_idx))
             .unwrap()
     }
 
@@ -1129,4 +1303,60 @@ mod test {
         buffer[11] = 123;
         assert!(SsTable::import_all(buffer.into()).is_err());
     }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_dict_block_without_dictionary() {
+        let samples: Vec<Bytes> = (0..1000)
+            .map(|i| Bytes::from(format!("value of the key {}", i * 13)))
+            .collect();
+        let dict = crate::compress::train_zstd_dictionary(&samples, 1024).unwrap();
+        let mut builder =
+            SsTableBuilder::new(4096, CompressionType::Zstd, true).with_dictionary(Some(dict));
+        for i in 0..100 {
+            builder.add(
+                Bytes::from(format!("key{:03}", i)),
+                Bytes::from(format!("value of the key {}", i)),
+            );
+        }
+        let table = builder.build();
+        assert!(table
+            .meta
+            .iter()
+            .any(|m| m.compression_type.uses_dictionary()));
+
+        // Rewrite the meta with an empty dictionary
+        let mut buffer = table.export_all()[..table.meta_offset].to_vec();
+        BlockMeta::encode_meta(&table.meta, CURRENT_SCHEMA_VERSION, None, &mut buffer);
+        buffer.put_u32_le(table.meta_offset as u32);
+        assert!(matches!(
+            SsTable::import_all(buffer.into()),
+            Err(LoroError::DecodeError(_))
+        ));
+
+        // The block itself can't be decoded without the dictionary either
+        let meta = &table.meta[0];
+        let end = table.meta.get(1).map_or(table.meta_offset, |m| m.offset);
+        let raw = table.data.read(meta.offset..end).unwrap();
+        assert!(matches!(
+            Block::decode(
+                raw,
+                meta.is_large,
+                meta.first_key.clone(),
+                meta.compression_type,
+                None
+            ),
+            Err(LoroError::DecodeError(_))
+        ));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_dict_compression_requires_dictionary() {
+        let mut w = Vec::new();
+        assert!(
+            crate::compress::compress(&mut w, b"data", CompressionType::ZstdWithDict, None)
+                .is_err()
+        );
+    }
 }
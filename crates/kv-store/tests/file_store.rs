@@ -0,0 +1,191 @@
+use std::{fs::OpenOptions, io::Write, ops::Bound, path::PathBuf};
+
+use bytes::Bytes;
+use loro_kv_store::{file_store::FileKvConfig, mem_store::MemKvConfig, FileKvStore, MemKvStore};
+
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("loro-kv-store-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn reopen_after_flush() {
+    let dir = test_dir("reopen_after_flush");
+    {
+        let mut store = FileKvStore::open(&dir).unwrap();
+        store.set(b"a", Bytes::from_static(b"1"));
+        store.set(b"b", Bytes::from_static(b"2"));
+        store.set(b"c", Bytes::from_static(b"3"));
+        store.remove(b"b");
+        store.flush().unwrap();
+    }
+
+    let store = FileKvStore::open(&dir).unwrap();
+    assert_eq!(store.get(b"a"), Some(Bytes::from_static(b"1")));
+    assert_eq!(store.get(b"b"), None);
+    assert_eq!(store.len(), 2);
+    let keys: Vec<_> = store
+        .scan(Bound::Unbounded, Bound::Unbounded)
+        .map(|(k, _)| k)
+        .collect();
+    assert_eq!(
+        keys,
+        vec![Bytes::from_static(b"a"), Bytes::from_static(b"c")]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unflushed_mutations_are_lost_on_crash() {
+    let dir = test_dir("unflushed_mutations");
+    let mut store = FileKvStore::open(&dir).unwrap();
+    store.set(b"a", Bytes::from_static(b"1"));
+    store.flush().unwrap();
+    store.set(b"b", Bytes::from_static(b"2"));
+    // Simulate a crash: the store is not dropped normally
+    std::mem::forget(store);
+
+    let store = FileKvStore::open(&dir).unwrap();
+    assert_eq!(store.get(b"a"), Some(Bytes::from_static(b"1")));
+    assert_eq!(store.get(b"b"), None);
+    drop(store);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn torn_wal_tail_is_discarded() {
+    let dir = test_dir("torn_wal_tail");
+    {
+        let mut store = FileKvStore::open(&dir).unwrap();
+        store.set(b"a", Bytes::from_static(b"1"));
+        store.flush().unwrap();
+        store.set(b"b", Bytes::from_static(b"2"));
+        store.flush().unwrap();
+    }
+
+    let wal_path = dir.join("wal.log");
+    let wal_len = std::fs::metadata(&wal_path).unwrap().len();
+    {
+        // A partially written record
+        let mut wal = OpenOptions::new().append(true).open(&wal_path).unwrap();
+        wal.write_all(&[20, 0, 0, 0, 1, 2, 3]).unwrap();
+    }
+
+    let mut store = FileKvStore::open(&dir).unwrap();
+    assert_eq!(store.get(b"a"), Some(Bytes::from_static(b"1")));
+    assert_eq!(store.get(b"b"), Some(Bytes::from_static(b"2")));
+    assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), wal_len);
+    // The store is still writable after the recovery
+    store.set(b"c", Bytes::from_static(b"3"));
+    store.flush().unwrap();
+    drop(store);
+    let store = FileKvStore::open(&dir).unwrap();
+    assert_eq!(store.get(b"c"), Some(Bytes::from_static(b"3")));
+    drop(store);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn corrupted_wal_record_is_discarded() {
+    let dir = test_dir("corrupted_wal_record");
+    {
+        let mut store = FileKvStore::open(&dir).unwrap();
+        store.set(b"a", Bytes::from_static(b"1"));
+        store.flush().unwrap();
+        store.set(b"b", Bytes::from_static(b"2"));
+        store.flush().unwrap();
+    }
+
+    let wal_path = dir.join("wal.log");
+    let mut wal = std::fs::read(&wal_path).unwrap();
+    let last = wal.len() - 1;
+    wal[last] ^= 0xff;
+    std::fs::write(&wal_path, wal).unwrap();
+
+    let store = FileKvStore::open(&dir).unwrap();
+    assert_eq!(store.get(b"a"), Some(Bytes::from_static(b"1")));
+    assert_eq!(store.get(b"b"), None);
+    drop(store);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unfinished_wal_batch_is_discarded() {
+    let dir = test_dir("unfinished_wal_batch");
+    {
+        let mut store = FileKvStore::open(&dir).unwrap();
+        store.set(b"a", Bytes::from_static(b"1"));
+        store.flush().unwrap();
+        store.set(b"b", Bytes::from_static(b"2"));
+        store.set(b"c", Bytes::from_static(b"3"));
+        store.flush().unwrap();
+    }
+
+    // Drop the record that marks the end of the last batch
+    let wal_path = dir.join("wal.log");
+    let wal_len = std::fs::metadata(&wal_path).unwrap().len();
+    OpenOptions::new()
+        .write(true)
+        .open(&wal_path)
+        .unwrap()
+        .set_len(wal_len - 12)
+        .unwrap();
+
+    let store = FileKvStore::open(&dir).unwrap();
+    assert_eq!(store.get(b"a"), Some(Bytes::from_static(b"1")));
+    assert_eq!(store.get(b"b"), None);
+    assert_eq!(store.get(b"c"), None);
+    drop(store);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compaction_clears_wal() {
+    let dir = test_dir("compaction_clears_wal");
+    {
+        let mut store = FileKvConfig::new()
+            .compact_threshold(256)
+            .open(&dir)
+            .unwrap();
+        for i in 0..100u32 {
+            store.set(&i.to_be_bytes(), Bytes::from(vec![i as u8; 8]));
+            store.flush().unwrap();
+            assert!(store.wal_size() <= 256);
+        }
+        for i in 0..50u32 {
+            store.remove(&i.to_be_bytes());
+        }
+        store.compact().unwrap();
+        assert_eq!(store.wal_size(), 0);
+    }
+
+    assert!(dir.join("data.sst").exists());
+    let store = FileKvStore::open(&dir).unwrap();
+    assert_eq!(store.len(), 50);
+    assert_eq!(store.get(&10u32.to_be_bytes()), None);
+    assert_eq!(
+        store.get(&60u32.to_be_bytes()),
+        Some(Bytes::from(vec![60u8; 8]))
+    );
+    drop(store);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn import_all_is_persisted() {
+    let dir = test_dir("import_all_is_persisted");
+    let mut other = MemKvStore::new(MemKvConfig::default());
+    other.set(b"x", Bytes::from_static(b"1"));
+    let bytes = other.export_all();
+    {
+        let mut store = FileKvStore::open(&dir).unwrap();
+        store.import_all(bytes).unwrap();
+        store.flush().unwrap();
+    }
+
+    let store = FileKvStore::open(&dir).unwrap();
+    assert_eq!(store.get(b"x"), Some(Bytes::from_static(b"1")));
+    drop(store);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -130,3 +130,101 @@ fn export_import_edit_export() {
     assert_eq!(new_new_store.get(b"b99"), Some(Bytes::from_static(b"2")));
     assert_eq!(new_new_store.get(b"a"), Some(Bytes::from_static(b"2")));
 }
+
+#[test]
+fn lz4_table_keeps_legacy_schema_version() {
+    let mut store = MemKvStore::new(MemKvConfig::default());
+    store.set(b"a", Bytes::from_static(b"1"));
+    let bytes = store.export_all();
+    assert_eq!(&bytes[..4], b"LORO");
+    assert_eq!(bytes[4], 0);
+}
+
+#[cfg(feature = "zstd")]
+mod zstd {
+    use bytes::Bytes;
+    use loro_kv_store::{
+        compress::{train_zstd_dictionary, CompressionType},
+        mem_store::MemKvConfig,
+        MemKvStore,
+    };
+
+    fn sample_value(i: usize) -> Bytes {
+        Bytes::from(format!(
+            r#"{{"id":{},"type":"paragraph","text":"The quick brown fox {}","marks":["bold","italic"]}}"#,
+            i,
+            i * 7
+        ))
+    }
+
+    fn check_round_trip(config: MemKvConfig, new_config: impl Fn() -> MemKvConfig) -> Bytes {
+        let mut store = config.build();
+        for i in 0..2000 {
+            store.set(format!("key{:05}", i).as_bytes(), sample_value(i));
+        }
+        store.remove(b"key00010");
+        let bytes = store.export_all();
+        assert_eq!(bytes[4], 1);
+
+        // The reader doesn't need to be configured with the same compression type
+        let mut imported = MemKvStore::new(MemKvConfig::default());
+        imported.import_all(bytes.clone()).unwrap();
+        assert_eq!(imported.get(b"key00001"), Some(sample_value(1)));
+        assert_eq!(imported.get(b"key01999"), Some(sample_value(1999)));
+        assert_eq!(imported.get(b"key00010"), None);
+        assert_eq!(imported.len(), 1999);
+
+        // Edit and export again, reusing the encoded blocks
+        let mut store = new_config().build();
+        store.import_all(bytes.clone()).unwrap();
+        store.set(b"key00500", Bytes::from_static(b"new"));
+        let new_bytes = store.export_all();
+        let mut imported = MemKvStore::new(MemKvConfig::default());
+        imported.import_all(new_bytes).unwrap();
+        assert_eq!(imported.get(b"key00500"), Some(Bytes::from_static(b"new")));
+        assert_eq!(imported.get(b"key00501"), Some(sample_value(501)));
+        bytes
+    }
+
+    #[test]
+    fn zstd_round_trip() {
+        let config = || MemKvConfig::new().compression_type(CompressionType::Zstd);
+        check_round_trip(config(), config);
+    }
+
+    #[test]
+    fn zstd_dictionary_round_trip() {
+        let samples: Vec<Bytes> = (0..1000).map(|i| sample_value(i * 13)).collect();
+        let dict = train_zstd_dictionary(&samples, 4 * 1024).unwrap();
+        let config = || {
+            MemKvConfig::new()
+                .compression_type(CompressionType::Zstd)
+                .zstd_dictionary(dict.clone())
+        };
+        let with_dict = check_round_trip(config(), config);
+        let without_dict = check_round_trip(
+            MemKvConfig::new().compression_type(CompressionType::Zstd),
+            || MemKvConfig::new().compression_type(CompressionType::LZ4),
+        );
+        // The dictionary is stored in the table, but the blocks are smaller
+        assert!(with_dict.len() < without_dict.len() + dict.len());
+    }
+
+    #[test]
+    fn corrupted_dictionary_is_rejected() {
+        let samples: Vec<Bytes> = (0..1000).map(|i| sample_value(i * 13)).collect();
+        let dict = train_zstd_dictionary(&samples, 4 * 1024).unwrap();
+        let mut store = MemKvConfig::new()
+            .compression_type(CompressionType::Zstd)
+            .zstd_dictionary(dict)
+            .build();
+        store.set(b"a", sample_value(1));
+        let bytes = store.export_all();
+        let mut corrupted = bytes.to_vec();
+        // The dictionary is right before the meta checksum and the meta offset
+        let len = corrupted.len();
+        corrupted[len - 9] ^= 0xff;
+        let mut imported = MemKvStore::new(MemKvConfig::default());
+        assert!(imported.import_all(Bytes::from(corrupted)).is_err());
+    }
+}
@@ -94,6 +94,20 @@ pub enum LoroError {
     ContainerDeleted { container: Box<ContainerID> },
     #[error("You cannot set the `PeerID` with `PeerID::MAX`, which is an internal specific value")]
     InvalidPeerID,
+    #[error("Sync error: The doc is behind the start version of the remote peer's shallow history. It should be recreated from the remote snapshot.")]
+    SyncBehindRemoteShallowRoot,
+    #[error("The incremental snapshot cannot be applied on the base. The base should include its start version and be included by its end version.")]
+    IncrementalSnapshotMismatch,
+    #[error("The doc is not opened on a kv store. It should be opened by `LoroDoc::from_kv_store`.")]
+    NotOpenedOnKvStore,
+    #[error("Cannot compact the history when the doc is detached. Checkout to the latest version first.")]
+    CompactHistoryWhenDetached,
+    #[error("Schema violation: {0}")]
+    SchemaViolation(Box<str>),
+    #[error("Cannot deserialize the value: {0}")]
+    DeserializeError(Box<str>),
+    #[error("Encode error: {0}")]
+    EncodeError(#[from] LoroEncodeError),
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -112,6 +126,8 @@ pub enum LoroTreeError {
     FractionalIndexNotEnabled,
     #[error("TreeID {0:?} is deleted or does not exist")]
     TreeNodeDeletedOrNotExist(TreeID),
+    #[error("Tree constraint violation: {0}")]
+    ConstraintViolation(Box<str>),
 }
 
 #[non_exhaustive]
@@ -123,6 +139,14 @@ pub enum LoroEncodeError {
     ShallowSnapshotIncompatibleWithOldFormat,
     #[error("Cannot export shallow snapshot with unknown container type. Please upgrade the Loro version.")]
     UnknownContainer,
+    #[error("The start version of the incremental snapshot is not included in this doc: {0}")]
+    IncrementalSnapshotStartNotFound(String),
+    #[error("Incremental snapshot is not supported on the doc with shallow history.")]
+    IncrementalSnapshotOnShallowDoc,
+    #[error("Cannot export the incremental snapshot when the doc has pending events or an uncommitted transaction.")]
+    IncrementalSnapshotWithPendingChanges,
+    #[error("Failed to check out the doc for exporting: {0}")]
+    CheckoutFailed(String),
 }
 
 #[cfg(feature = "wasm")]
@@ -167,3 +191,9 @@ impl From<ColumnarError> for LoroError {
         }
     }
 }
+
+impl serde::de::Error for LoroError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        LoroError::DeserializeError(msg.to_string().into_boxed_str())
+    }
+}
@@ -0,0 +1,220 @@
+//! Derive macros of `loro::mapping::LoroSerialize` and `loro::mapping::LoroDeserialize`.
+//!
+//! Use them through the `derive` feature of the `loro` crate.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Generics, Ident, LitStr,
+    Result,
+};
+
+/// Derive `LoroSerialize` for a struct with named fields.
+///
+/// The struct is stored in a `LoroMap`. Supported field attributes:
+///
+/// - `#[loro(rename = "key")]`: use another key in the map.
+/// - `#[loro(text)]`: store the `String` in a `LoroText`.
+/// - `#[loro(movable)]`: store the `Vec<T>` in a `LoroMovableList`.
+/// - `#[loro(skip)]`: don't write the field.
+#[proc_macro_derive(LoroSerialize, attributes(loro))]
+pub fn derive_loro_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_serialize(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Derive `LoroDeserialize` for a struct with named fields.
+///
+/// Supported field attributes:
+///
+/// - `#[loro(rename = "key")]`: read another key in the map.
+/// - `#[loro(default)]`: use `Default::default()` when the key is missing.
+/// - `#[loro(skip)]`: don't read the field, use `Default::default()` instead.
+///
+/// `#[loro(text)]` and `#[loro(movable)]` are accepted and ignored, because
+/// the deep values of texts and movable lists are strings and lists.
+#[proc_macro_derive(LoroDeserialize, attributes(loro))]
+pub fn derive_loro_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_deserialize(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    text: bool,
+    movable: bool,
+    skip: bool,
+    default: bool,
+}
+
+struct Field {
+    ident: Ident,
+    key: String,
+    attrs: FieldAttrs,
+}
+
+fn parse_fields(input: &DeriveInput) -> Result<Vec<Field>> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "Loro mapping can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "Loro mapping can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut ans = Vec::with_capacity(fields.named.len());
+    for field in fields.named.iter() {
+        let ident = field.ident.clone().unwrap();
+        let mut attrs = FieldAttrs::default();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("loro")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let name: LitStr = meta.value()?.parse()?;
+                    attrs.rename = Some(name.value());
+                } else if meta.path.is_ident("text") {
+                    attrs.text = true;
+                } else if meta.path.is_ident("movable") {
+                    attrs.movable = true;
+                } else if meta.path.is_ident("skip") {
+                    attrs.skip = true;
+                } else if meta.path.is_ident("default") {
+                    attrs.default = true;
+                } else {
+                    return Err(meta.error("unknown loro attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        if attrs.text && attrs.movable {
+            return Err(Error::new_spanned(
+                &ident,
+                "`text` and `movable` cannot be used on the same field",
+            ));
+        }
+
+        let key = attrs
+            .rename
+            .clone()
+            .unwrap_or_else(|| ident.to_string().trim_start_matches("r#").to_string());
+        ans.push(Field { ident, key, attrs });
+    }
+
+    Ok(ans)
+}
+
+fn add_bound(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+fn expand_serialize(input: DeriveInput) -> Result<TokenStream2> {
+    let fields = parse_fields(&input)?;
+    let name = &input.ident;
+    let generics = add_bound(&input.generics, quote!(::loro::mapping::LoroSerialize));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let fields: Vec<_> = fields.iter().filter(|f| !f.attrs.skip).collect();
+    let to_values = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let key = &f.key;
+        quote! {
+            map.insert(
+                ::std::string::String::from(#key),
+                ::loro::mapping::LoroSerialize::to_loro_value(&self.#ident),
+            );
+        }
+    });
+    let writes = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let key = &f.key;
+        if f.attrs.text {
+            quote! {
+                ::loro::mapping::write_text_entry(
+                    map,
+                    #key,
+                    ::core::convert::AsRef::<str>::as_ref(&self.#ident),
+                )?;
+            }
+        } else if f.attrs.movable {
+            quote! {
+                ::loro::mapping::write_movable_list_entry(map, #key, &self.#ident)?;
+            }
+        } else {
+            quote! {
+                ::loro::mapping::LoroSerialize::write_to_map(&self.#ident, map, #key)?;
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::loro::mapping::LoroSerialize for #name #ty_generics #where_clause {
+            const CONTAINER_TYPE: ::core::option::Option<::loro::ContainerType> =
+                ::core::option::Option::Some(::loro::ContainerType::Map);
+
+            fn to_loro_value(&self) -> ::loro::LoroValue {
+                #[allow(unused_mut)]
+                let mut map = ::std::collections::HashMap::<
+                    ::std::string::String,
+                    ::loro::LoroValue,
+                >::new();
+                #(#to_values)*
+                ::loro::LoroValue::from(map)
+            }
+
+            fn update_container(&self, container: &::loro::Container) -> ::loro::LoroResult<()> {
+                #[allow(unused_variables)]
+                let map = ::loro::mapping::expect_map(container)?;
+                #(#writes)*
+                ::core::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+fn expand_deserialize(input: DeriveInput) -> Result<TokenStream2> {
+    let fields = parse_fields(&input)?;
+    let name = &input.ident;
+    let generics = add_bound(&input.generics, quote!(::loro::mapping::LoroDeserialize));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let reads = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let key = &f.key;
+        if f.attrs.skip {
+            quote! { #ident: ::core::default::Default::default(), }
+        } else if f.attrs.default {
+            quote! { #ident: ::loro::mapping::read_entry_or_default(value, #key)?, }
+        } else {
+            quote! { #ident: ::loro::mapping::read_entry(value, #key)?, }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::loro::mapping::LoroDeserialize for #name #ty_generics #where_clause {
+            fn from_loro_value(value: &::loro::LoroValue) -> ::loro::LoroResult<Self> {
+                if !::core::matches!(value, ::loro::LoroValue::Map(_)) {
+                    return ::core::result::Result::Err(::loro::LoroError::DeserializeError(
+                        ::std::format!("expected a map, found {:?}", value).into_boxed_str(),
+                    ));
+                }
+
+                ::core::result::Result::Ok(Self {
+                    #(#reads)*
+                })
+            }
+        }
+    })
+}
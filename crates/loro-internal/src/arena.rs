@@ -309,6 +309,28 @@ impl SharedArena {
         }
     }
 
+    /// Get the container and all its descendants that have been registered with parents
+    pub(crate) fn get_descendants_with_self(&self, container: ContainerIdx) -> Vec<ContainerIdx> {
+        let parents = self.inner.parents.try_lock().unwrap();
+        let mut children: FxHashMap<ContainerIdx, Vec<ContainerIdx>> = FxHashMap::default();
+        for (child, parent) in parents.iter() {
+            if let Some(parent) = parent {
+                children.entry(*parent).or_default().push(*child);
+            }
+        }
+
+        let mut ans = vec![container];
+        let mut i = 0;
+        while i < ans.len() {
+            if let Some(c) = children.get(&ans[i]) {
+                ans.extend_from_slice(c);
+            }
+            i += 1;
+        }
+
+        ans
+    }
+
     #[inline]
     pub fn slice_by_unicode(&self, range: impl RangeBounds<usize>) -> BytesSlice {
         self.inner.str.try_lock().unwrap().slice_by_unicode(range)
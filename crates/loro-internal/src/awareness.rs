@@ -1,8 +1,15 @@
-use fxhash::FxHashMap;
-use loro_common::{LoroValue, PeerID};
+use std::sync::{Arc, Mutex, Weak};
+
+use fxhash::{FxHashMap, FxHashSet};
+use loro_common::{ContainerID, InternalString, LoroError, LoroResult, LoroValue, PeerID};
 use serde::{Deserialize, Serialize};
 
-use crate::change::get_sys_timestamp;
+use crate::{
+    change::get_sys_timestamp,
+    cursor::{AbsolutePosition, Cursor},
+    utils::subscription::{SubscriberSetWithQueue, Subscription},
+    LoroDoc,
+};
 
 /// `Awareness` is a structure that tracks the ephemeral state of peers.
 ///
@@ -10,11 +17,67 @@ use crate::change::get_sys_timestamp;
 ///
 /// The state of a specific peer is expected to be removed after a specified timeout. Use
 /// `remove_outdated` to eliminate outdated states.
-#[derive(Debug, Clone)]
+///
+/// The changes of the states can be observed by [Awareness::subscribe].
 pub struct Awareness {
     peer: PeerID,
     peers: FxHashMap<PeerID, PeerInfo>,
     timeout: i64,
+    clock: AwarenessClock,
+    subscribers: SubscriberSetWithQueue<(), AwarenessCallback, AwarenessEvent>,
+}
+
+/// The source of the current time in milliseconds used by [Awareness] and [EphemeralStore].
+///
+/// It defaults to the system time. A custom clock makes the timeout behavior deterministic.
+pub type AwarenessClock = Arc<dyn Fn() -> i64 + Send + Sync>;
+
+/// The callback of the awareness changes. Return false to unsubscribe.
+pub type AwarenessCallback = Box<dyn Fn(&AwarenessEvent) -> bool + Send + Sync + 'static>;
+
+/// What triggered an [AwarenessEvent].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwarenessEventBy {
+    /// The local state is set
+    Local,
+    /// The states from the other peers are applied
+    Import,
+    /// The outdated states are removed
+    Timeout,
+}
+
+/// The peers whose states are changed in an [Awareness].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwarenessEvent {
+    pub by: AwarenessEventBy,
+    /// The origin passed to [Awareness::apply_with_origin]. It's empty for other events.
+    pub origin: InternalString,
+    pub added: Vec<PeerID>,
+    pub updated: Vec<PeerID>,
+    pub removed: Vec<PeerID>,
+}
+
+impl std::fmt::Debug for Awareness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Awareness")
+            .field("peer", &self.peer)
+            .field("peers", &self.peers)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl Clone for Awareness {
+    /// The subscriptions are not shared with the cloned instance.
+    fn clone(&self) -> Self {
+        Self {
+            peer: self.peer,
+            peers: self.peers.clone(),
+            timeout: self.timeout,
+            clock: self.clock.clone(),
+            subscribers: SubscriberSetWithQueue::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -38,12 +101,37 @@ impl Awareness {
             peer,
             timeout,
             peers: FxHashMap::default(),
+            clock: Arc::new(get_sys_timestamp),
+            subscribers: SubscriberSetWithQueue::new(),
         }
     }
 
+    /// Use the given clock instead of the system time to timestamp the states and
+    /// to decide whether they are outdated.
+    pub fn with_clock(mut self, clock: AwarenessClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Subscribe to the changes of the states, including the local changes, the applied remote
+    /// changes and the removal of the outdated states.
+    pub fn subscribe(&self, callback: AwarenessCallback) -> Subscription {
+        let (sub, activate) = self.subscribers.inner().insert((), callback);
+        activate();
+        sub
+    }
+
+    fn emit(&self, event: AwarenessEvent) {
+        if event.added.is_empty() && event.updated.is_empty() && event.removed.is_empty() {
+            return;
+        }
+
+        self.subscribers.emit(&(), event);
+    }
+
     pub fn encode(&self, peers: &[PeerID]) -> Vec<u8> {
         let mut peers_info = Vec::new();
-        let now = get_sys_timestamp();
+        let now = (self.clock)();
         for peer in peers {
             if let Some(peer_info) = self.peers.get(peer) {
                 if now - peer_info.timestamp > self.timeout {
@@ -64,7 +152,7 @@ impl Awareness {
 
     pub fn encode_all(&self) -> Vec<u8> {
         let mut peers_info = Vec::new();
-        let now = get_sys_timestamp();
+        let now = (self.clock)();
         for (peer, peer_info) in self.peers.iter() {
             if now - peer_info.timestamp > self.timeout {
                 continue;
@@ -83,10 +171,21 @@ impl Awareness {
 
     /// Returns (updated, added)
     pub fn apply(&mut self, encoded_peers_info: &[u8]) -> (Vec<PeerID>, Vec<PeerID>) {
+        self.apply_with_origin(encoded_peers_info, "")
+    }
+
+    /// Same as [Awareness::apply], but the emitted event is tagged with the given origin.
+    ///
+    /// Returns (updated, added)
+    pub fn apply_with_origin(
+        &mut self,
+        encoded_peers_info: &[u8],
+        origin: &str,
+    ) -> (Vec<PeerID>, Vec<PeerID>) {
         let peers_info: Vec<EncodedPeerInfo> = postcard::from_bytes(encoded_peers_info).unwrap();
         let mut changed_peers = Vec::new();
         let mut added_peers = Vec::new();
-        let now = get_sys_timestamp();
+        let now = (self.clock)();
         for peer_info in peers_info {
             match self.peers.get(&peer_info.peer) {
                 Some(x) if x.counter >= peer_info.counter || peer_info.peer == self.peer => {
@@ -110,6 +209,13 @@ impl Awareness {
             }
         }
 
+        self.emit(AwarenessEvent {
+            by: AwarenessEventBy::Import,
+            origin: origin.into(),
+            added: added_peers.clone(),
+            updated: changed_peers.clone(),
+            removed: Vec::new(),
+        });
         (changed_peers, added_peers)
     }
 
@@ -118,6 +224,8 @@ impl Awareness {
     }
 
     fn _set_local_state(&mut self, value: LoroValue) {
+        let now = (self.clock)();
+        let is_new = !self.peers.contains_key(&self.peer);
         let peer = self.peers.entry(self.peer).or_insert_with(|| PeerInfo {
             state: Default::default(),
             counter: 0,
@@ -126,7 +234,19 @@ impl Awareness {
 
         peer.state = value;
         peer.counter += 1;
-        peer.timestamp = get_sys_timestamp();
+        peer.timestamp = now;
+        let (added, updated) = if is_new {
+            (vec![self.peer], Vec::new())
+        } else {
+            (Vec::new(), vec![self.peer])
+        };
+        self.emit(AwarenessEvent {
+            by: AwarenessEventBy::Local,
+            origin: Default::default(),
+            added,
+            updated,
+            removed: Vec::new(),
+        });
     }
 
     pub fn get_local_state(&self) -> Option<LoroValue> {
@@ -134,7 +254,7 @@ impl Awareness {
     }
 
     pub fn remove_outdated(&mut self) -> Vec<PeerID> {
-        let now = get_sys_timestamp();
+        let now = (self.clock)();
         let mut removed = Vec::new();
         self.peers.retain(|id, v| {
             if now - v.timestamp > self.timeout {
@@ -145,6 +265,13 @@ impl Awareness {
             }
         });
 
+        self.emit(AwarenessEvent {
+            by: AwarenessEventBy::Timeout,
+            origin: Default::default(),
+            added: Vec::new(),
+            updated: Vec::new(),
+            removed: removed.clone(),
+        });
         removed
     }
 
@@ -156,3 +283,555 @@ impl Awareness {
         self.peer
     }
 }
+
+/// `EphemeralStore` tracks the ephemeral state of peers at the granularity of keys.
+///
+/// Unlike [Awareness], each peer can set or delete individual keys, e.g. `"cursor"`, `"selection"`
+/// and `"user"`, and [EphemeralStore::encode_delta] only encodes the keys changed since the last call.
+/// Deleted keys are kept as tombstones until they are outdated, so the deletions can be propagated.
+///
+/// Each key has its own timestamp. The key is expected to be removed after the specified timeout
+/// since it's updated. Use [EphemeralStore::remove_outdated] to eliminate outdated keys.
+///
+/// The changes of the keys can be observed by [EphemeralStore::subscribe].
+pub struct EphemeralStore {
+    peer: PeerID,
+    states: FxHashMap<PeerID, FxHashMap<InternalString, KeyState>>,
+    timeout: i64,
+    clock: AwarenessClock,
+    /// The local keys changed since the last `encode_delta`
+    pending: FxHashSet<InternalString>,
+    subscribers: SubscriberSetWithQueue<(), EphemeralCallback, EphemeralEvent>,
+}
+
+/// The callback of the changes in an [EphemeralStore]. Return false to unsubscribe.
+pub type EphemeralCallback = Box<dyn Fn(&EphemeralEvent) -> bool + Send + Sync + 'static>;
+
+/// The keys changed in an [EphemeralStore].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EphemeralEvent {
+    pub by: AwarenessEventBy,
+    /// The changed keys grouped by peer, including the deleted ones
+    pub changed: ChangedKeys,
+}
+
+impl std::fmt::Debug for EphemeralStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EphemeralStore")
+            .field("peer", &self.peer)
+            .field("states", &self.states)
+            .field("timeout", &self.timeout)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+impl Clone for EphemeralStore {
+    /// The subscriptions are not shared with the cloned instance.
+    fn clone(&self) -> Self {
+        Self {
+            peer: self.peer,
+            states: self.states.clone(),
+            timeout: self.timeout,
+            clock: self.clock.clone(),
+            pending: self.pending.clone(),
+            subscribers: SubscriberSetWithQueue::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyState {
+    /// `None` if the key is deleted
+    pub value: Option<LoroValue>,
+    pub counter: i32,
+    // This field is generated locally
+    pub timestamp: i64,
+}
+
+/// The keys changed in an [EphemeralStore], grouped by peer.
+pub type ChangedKeys = FxHashMap<PeerID, Vec<InternalString>>;
+
+#[derive(Serialize, Deserialize)]
+struct EncodedKeyState {
+    peer: PeerID,
+    key: InternalString,
+    counter: i32,
+    value: Option<LoroValue>,
+}
+
+impl EphemeralStore {
+    pub fn new(peer: PeerID, timeout: i64) -> EphemeralStore {
+        EphemeralStore {
+            peer,
+            timeout,
+            states: FxHashMap::default(),
+            clock: Arc::new(get_sys_timestamp),
+            pending: FxHashSet::default(),
+            subscribers: SubscriberSetWithQueue::new(),
+        }
+    }
+
+    /// Use the given clock instead of the system time to timestamp the keys and
+    /// to decide whether they are outdated.
+    pub fn with_clock(mut self, clock: AwarenessClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn peer(&self) -> PeerID {
+        self.peer
+    }
+
+    /// Subscribe to the changes of the keys, including the local changes, the applied remote
+    /// changes and the removal of the outdated keys.
+    pub fn subscribe(&self, callback: EphemeralCallback) -> Subscription {
+        let (sub, activate) = self.subscribers.inner().insert((), callback);
+        activate();
+        sub
+    }
+
+    fn emit(&self, by: AwarenessEventBy, changed: &ChangedKeys) {
+        if changed.is_empty() {
+            return;
+        }
+
+        self.subscribers.emit(
+            &(),
+            EphemeralEvent {
+                by,
+                changed: changed.clone(),
+            },
+        );
+    }
+
+    /// Move the local keys to the new peer id.
+    ///
+    /// The keys of the old peer are removed locally, and they are outdated on the other peers.
+    pub(crate) fn set_peer(&mut self, peer: PeerID) {
+        if peer == self.peer {
+            return;
+        }
+
+        let old_peer = self.peer;
+        let old = self.states.remove(&self.peer);
+        self.peer = peer;
+        self.pending.clear();
+        let mut changed: ChangedKeys = FxHashMap::default();
+        for (key, state) in old.into_iter().flatten() {
+            if state.value.is_some() {
+                changed.entry(old_peer).or_default().push(key.clone());
+                changed.entry(peer).or_default().push(key.clone());
+                self._set(key, state.value);
+            }
+        }
+
+        self.emit(AwarenessEventBy::Local, &changed);
+    }
+
+    /// Set the value of the key in the local state.
+    pub fn set(&mut self, key: impl Into<InternalString>, value: impl Into<LoroValue>) {
+        let key = key.into();
+        self._set(key.clone(), Some(value.into()));
+        self.emit_local(key);
+    }
+
+    /// Delete the key in the local state.
+    ///
+    /// A tombstone is kept until it's outdated, so the deletion can be encoded.
+    pub fn delete(&mut self, key: &str) {
+        let key: InternalString = key.into();
+        let exists = self
+            .states
+            .get(&self.peer)
+            .and_then(|x| x.get(&key))
+            .is_some_and(|x| x.value.is_some());
+        if exists {
+            self._set(key.clone(), None);
+            self.emit_local(key);
+        }
+    }
+
+    fn emit_local(&self, key: InternalString) {
+        let mut changed: ChangedKeys = FxHashMap::default();
+        changed.insert(self.peer, vec![key]);
+        self.emit(AwarenessEventBy::Local, &changed);
+    }
+
+    fn _set(&mut self, key: InternalString, value: Option<LoroValue>) {
+        let state = self
+            .states
+            .entry(self.peer)
+            .or_default()
+            .entry(key.clone())
+            .or_insert_with(|| KeyState {
+                value: None,
+                counter: 0,
+                timestamp: 0,
+            });
+        state.value = value;
+        state.counter += 1;
+        state.timestamp = (self.clock)();
+        self.pending.insert(key);
+    }
+
+    /// Get the value of the key in the local state.
+    pub fn get_local(&self, key: &str) -> Option<LoroValue> {
+        self.get(self.peer, key)
+    }
+
+    /// Get the value of the key in the state of the given peer.
+    pub fn get(&self, peer: PeerID, key: &str) -> Option<LoroValue> {
+        self.states
+            .get(&peer)?
+            .get(&InternalString::from(key))?
+            .value
+            .clone()
+    }
+
+    /// Get all the keys and values in the state of the given peer.
+    pub fn get_peer_states(&self, peer: PeerID) -> FxHashMap<InternalString, LoroValue> {
+        self.states
+            .get(&peer)
+            .map(|states| {
+                states
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.clone(), v.value.clone()?)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the states of all the peers, including the tombstones.
+    pub fn get_all_states(&self) -> &FxHashMap<PeerID, FxHashMap<InternalString, KeyState>> {
+        &self.states
+    }
+
+    /// Encode the local keys changed since the last call, including the deleted ones.
+    pub fn encode_delta(&mut self) -> Vec<u8> {
+        let mut ans = Vec::new();
+        if let Some(states) = self.states.get(&self.peer) {
+            for key in self.pending.drain() {
+                if let Some(state) = states.get(&key) {
+                    ans.push(EncodedKeyState {
+                        peer: self.peer,
+                        key,
+                        counter: state.counter,
+                        value: state.value.clone(),
+                    });
+                }
+            }
+        }
+
+        self.pending.clear();
+        postcard::to_allocvec(&ans).unwrap()
+    }
+
+    /// Encode all the keys of the given peers that are not outdated.
+    pub fn encode(&self, peers: &[PeerID]) -> Vec<u8> {
+        let mut ans = Vec::new();
+        for peer in peers {
+            self.encode_peer(*peer, &mut ans);
+        }
+
+        postcard::to_allocvec(&ans).unwrap()
+    }
+
+    /// Encode all the keys that are not outdated.
+    pub fn encode_all(&self) -> Vec<u8> {
+        let mut ans = Vec::new();
+        for peer in self.states.keys() {
+            self.encode_peer(*peer, &mut ans);
+        }
+
+        postcard::to_allocvec(&ans).unwrap()
+    }
+
+    fn encode_peer(&self, peer: PeerID, ans: &mut Vec<EncodedKeyState>) {
+        let Some(states) = self.states.get(&peer) else {
+            return;
+        };
+
+        let now = (self.clock)();
+        for (key, state) in states.iter() {
+            if now - state.timestamp > self.timeout {
+                continue;
+            }
+
+            ans.push(EncodedKeyState {
+                peer,
+                key: key.clone(),
+                counter: state.counter,
+                value: state.value.clone(),
+            });
+        }
+    }
+
+    /// Apply the encoded states from the other peers.
+    ///
+    /// Returns the keys whose values are changed, including the deleted ones.
+    pub fn apply(&mut self, encoded: &[u8]) -> LoroResult<ChangedKeys> {
+        let encoded: Vec<EncodedKeyState> = postcard::from_bytes(encoded).map_err(|e| {
+            LoroError::DecodeError(format!("Invalid ephemeral state: {}", e).into())
+        })?;
+        let mut changed: ChangedKeys = FxHashMap::default();
+        let now = (self.clock)();
+        for item in encoded {
+            if item.peer == self.peer {
+                continue;
+            }
+
+            let states = self.states.entry(item.peer).or_default();
+            match states.get_mut(&item.key) {
+                Some(state) if state.counter >= item.counter => {}
+                Some(state) => {
+                    if state.value != item.value {
+                        changed.entry(item.peer).or_default().push(item.key);
+                    }
+                    state.value = item.value;
+                    state.counter = item.counter;
+                    state.timestamp = now;
+                }
+                None => {
+                    if item.value.is_some() {
+                        changed.entry(item.peer).or_default().push(item.key.clone());
+                    }
+                    states.insert(
+                        item.key,
+                        KeyState {
+                            value: item.value,
+                            counter: item.counter,
+                            timestamp: now,
+                        },
+                    );
+                }
+            }
+        }
+
+        self.emit(AwarenessEventBy::Import, &changed);
+        Ok(changed)
+    }
+
+    /// Remove the keys that are not updated within the timeout.
+    ///
+    /// Returns the removed keys that have values, i.e. the tombstones are not included.
+    pub fn remove_outdated(&mut self) -> ChangedKeys {
+        let now = (self.clock)();
+        let mut removed: ChangedKeys = FxHashMap::default();
+        self.states.retain(|peer, states| {
+            states.retain(|key, state| {
+                if now - state.timestamp > self.timeout {
+                    if state.value.is_some() {
+                        removed.entry(*peer).or_default().push(key.clone());
+                    }
+                    false
+                } else {
+                    true
+                }
+            });
+            !states.is_empty()
+        });
+
+        self.emit(AwarenessEventBy::Timeout, &removed);
+        removed
+    }
+}
+
+/// The resolved selection of a peer in a container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionRange {
+    /// Where the selection starts from
+    pub anchor: AbsolutePosition,
+    /// Where the selection ends, i.e. the position of the caret
+    pub focus: AbsolutePosition,
+}
+
+impl SelectionRange {
+    pub fn start(&self) -> usize {
+        self.anchor.pos.min(self.focus.pos)
+    }
+
+    pub fn end(&self) -> usize {
+        self.anchor.pos.max(self.focus.pos)
+    }
+
+    pub fn is_collapsed(&self) -> bool {
+        self.anchor.pos == self.focus.pos
+    }
+}
+
+/// `RemoteSelections` shares the selections of the peers through an [EphemeralStore] and
+/// resolves them to the absolute positions in the current state of the doc.
+///
+/// A selection is stored as a pair of anchor and focus [Cursor]s under the key of its container,
+/// so it sticks to the same content when the doc is edited. The resolved positions are cached
+/// until the doc or the selections change.
+///
+/// It only holds a weak reference to the doc, and it follows the peer id changes of the doc.
+pub struct RemoteSelections {
+    doc: Weak<LoroDoc>,
+    store: Arc<Mutex<EphemeralStore>>,
+    cache: Arc<Mutex<FxHashMap<ContainerID, FxHashMap<PeerID, SelectionRange>>>>,
+    _doc_sub: Subscription,
+    _peer_id_change_sub: Subscription,
+}
+
+impl std::fmt::Debug for RemoteSelections {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteSelections")
+            .field("store", &self.store)
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+impl RemoteSelections {
+    pub fn new(doc: &Arc<LoroDoc>, timeout: i64) -> Self {
+        let cache: Arc<Mutex<FxHashMap<ContainerID, FxHashMap<PeerID, SelectionRange>>>> =
+            Default::default();
+        let store = Arc::new(Mutex::new(EphemeralStore::new(doc.peer_id(), timeout)));
+        let cache_clone = cache.clone();
+        let doc_sub = doc.subscribe_root(Arc::new(move |_| {
+            cache_clone.lock().unwrap().clear();
+        }));
+        let store_clone = store.clone();
+        let cache_clone = cache.clone();
+        let peer_id_change_sub = doc.subscribe_peer_id_change(Box::new(move |id| {
+            store_clone.lock().unwrap().set_peer(id.peer);
+            cache_clone.lock().unwrap().clear();
+            true
+        }));
+        Self {
+            doc: Arc::downgrade(doc),
+            store,
+            cache,
+            _doc_sub: doc_sub,
+            _peer_id_change_sub: peer_id_change_sub,
+        }
+    }
+
+    /// Set the selection of the local peer.
+    ///
+    /// The anchor and the focus must be in the same container.
+    pub fn set_local_selection(&mut self, anchor: &Cursor, focus: &Cursor) -> LoroResult<()> {
+        if anchor.container != focus.container {
+            return Err(LoroError::ArgErr(
+                "The anchor and the focus of a selection must be in the same container".into(),
+            ));
+        }
+
+        self.store.lock().unwrap().set(
+            anchor.container.to_string(),
+            LoroValue::from(vec![
+                LoroValue::Binary(anchor.encode().into()),
+                LoroValue::Binary(focus.encode().into()),
+            ]),
+        );
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Remove the selection of the local peer in the given container.
+    pub fn remove_local_selection(&mut self, container: &ContainerID) {
+        self.store.lock().unwrap().delete(&container.to_string());
+        self.invalidate();
+    }
+
+    /// Encode the local selections changed since the last call.
+    pub fn encode_delta(&mut self) -> Vec<u8> {
+        self.store.lock().unwrap().encode_delta()
+    }
+
+    /// Encode all the selections that are not outdated.
+    pub fn encode_all(&self) -> Vec<u8> {
+        self.store.lock().unwrap().encode_all()
+    }
+
+    /// Apply the encoded selections from the other peers.
+    ///
+    /// Returns the keys of the changed selections, i.e. the container ids in string.
+    pub fn apply(&mut self, encoded: &[u8]) -> LoroResult<ChangedKeys> {
+        let changed = self.store.lock().unwrap().apply(encoded)?;
+        if !changed.is_empty() {
+            self.invalidate();
+        }
+
+        Ok(changed)
+    }
+
+    /// Remove the selections that are not updated within the timeout.
+    pub fn remove_outdated(&mut self) -> ChangedKeys {
+        let removed = self.store.lock().unwrap().remove_outdated();
+        if !removed.is_empty() {
+            self.invalidate();
+        }
+
+        removed
+    }
+
+    /// Get the up-to-date selections of all the peers, including the local peer,
+    /// in the given text or list container.
+    ///
+    /// The selections whose cursors cannot be resolved, e.g. the container is deleted,
+    /// are skipped. It's empty if the doc is dropped.
+    pub fn get_selections(&self, container: &ContainerID) -> FxHashMap<PeerID, SelectionRange> {
+        let Some(doc) = self.doc.upgrade() else {
+            return FxHashMap::default();
+        };
+        // The ops in the pending transaction are applied to the state without emitting events,
+        // so the cache cannot track them until they are committed
+        let use_cache = doc.get_pending_txn_len() == 0;
+        if use_cache {
+            if let Some(ans) = self.cache.lock().unwrap().get(container) {
+                return ans.clone();
+            }
+        }
+
+        let key = container.to_string();
+        let mut cursors = Vec::new();
+        {
+            let store = self.store.lock().unwrap();
+            for peer in store.get_all_states().keys() {
+                let Some(LoroValue::List(value)) = store.get(*peer, &key) else {
+                    continue;
+                };
+                let [LoroValue::Binary(anchor), LoroValue::Binary(focus)] = value.as_slice() else {
+                    continue;
+                };
+                let (Ok(anchor), Ok(focus)) = (Cursor::decode(anchor), Cursor::decode(focus))
+                else {
+                    continue;
+                };
+                cursors.push((*peer, anchor, focus));
+            }
+        }
+
+        // The locks are released here, because querying the positions may commit
+        // the pending transaction and emit the events
+        let mut ans = FxHashMap::default();
+        for (peer, anchor, focus) in cursors {
+            let (Ok(anchor), Ok(focus)) = (doc.query_pos(&anchor), doc.query_pos(&focus)) else {
+                continue;
+            };
+            ans.insert(
+                peer,
+                SelectionRange {
+                    anchor: anchor.current,
+                    focus: focus.current,
+                },
+            );
+        }
+
+        if use_cache {
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(container.clone(), ans.clone());
+        }
+        ans
+    }
+
+    fn invalidate(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
@@ -1,5 +1,9 @@
 pub use crate::container::richtext::config::{StyleConfig, StyleConfigMap};
+use crate::schema::DocSchema;
+use crate::tree_constraint::TreeConstraints;
 use crate::LoroDoc;
+use fxhash::FxHashMap;
+use loro_common::ContainerID;
 
 #[derive(Clone, Debug)]
 pub struct Configure {
@@ -7,6 +11,8 @@ pub struct Configure {
     record_timestamp: Arc<AtomicBool>,
     pub(crate) merge_interval: Arc<AtomicI64>,
     pub(crate) editable_detached_mode: Arc<AtomicBool>,
+    schema: Arc<RwLock<Option<Arc<DocSchema>>>>,
+    tree_constraints: Arc<RwLock<FxHashMap<ContainerID, Arc<TreeConstraints>>>>,
 }
 
 impl LoroDoc {
@@ -15,6 +21,9 @@ impl LoroDoc {
         self.set_record_timestamp(config.record_timestamp());
         self.set_change_merge_interval(config.merge_interval());
         self.set_detached_editing(config.detached_editing());
+        self.config.set_schema(config.schema());
+        *self.config.tree_constraints.write().unwrap() =
+            config.tree_constraints.read().unwrap().clone();
     }
 }
 
@@ -25,6 +34,8 @@ impl Default for Configure {
             record_timestamp: Arc::new(AtomicBool::new(false)),
             editable_detached_mode: Arc::new(AtomicBool::new(false)),
             merge_interval: Arc::new(AtomicI64::new(1000 * 1000)),
+            schema: Arc::new(RwLock::new(None)),
+            tree_constraints: Arc::new(RwLock::new(FxHashMap::default())),
         }
     }
 }
@@ -47,6 +58,8 @@ impl Configure {
                 self.editable_detached_mode
                     .load(std::sync::atomic::Ordering::Relaxed),
             )),
+            schema: Arc::new(RwLock::new(self.schema())),
+            tree_constraints: Arc::new(RwLock::new(self.tree_constraints.read().unwrap().clone())),
         }
     }
 
@@ -83,6 +96,44 @@ impl Configure {
         self.merge_interval
             .store(interval, std::sync::atomic::Ordering::Relaxed);
     }
+
+    pub fn schema(&self) -> Option<Arc<DocSchema>> {
+        self.schema.read().unwrap().clone()
+    }
+
+    pub fn set_schema(&self, schema: Option<Arc<DocSchema>>) {
+        *self.schema.write().unwrap() = schema;
+    }
+
+    pub fn tree_constraints(&self, tree: &ContainerID) -> Option<Arc<TreeConstraints>> {
+        self.tree_constraints.read().unwrap().get(tree).cloned()
+    }
+
+    pub fn set_tree_constraints(
+        &self,
+        tree: &ContainerID,
+        constraints: Option<Arc<TreeConstraints>>,
+    ) {
+        let mut map = self.tree_constraints.write().unwrap();
+        match constraints {
+            Some(c) => map.insert(tree.clone(), c),
+            None => map.remove(tree),
+        };
+    }
+
+    /// The ids of the trees that have constraints
+    pub fn tree_constraint_trees(&self) -> Vec<ContainerID> {
+        self.tree_constraints
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    pub fn has_tree_constraints(&self) -> bool {
+        !self.tree_constraints.read().unwrap().is_empty()
+    }
 }
 
 #[derive(Debug)]
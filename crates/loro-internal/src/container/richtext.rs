@@ -25,7 +25,7 @@ use std::fmt::Debug;
 
 pub(crate) use fugue_span::{RichtextChunk, RichtextChunkValue};
 pub(crate) use richtext_state::RichtextState;
-pub(crate) use style_range_map::Styles;
+pub(crate) use style_range_map::{merge_style_map, Styles};
 pub(crate) use tracker::{CrdtRopeDelta, Tracker as RichtextTracker};
 
 /// This is the data structure that represents a span of rich text.
@@ -132,7 +132,7 @@ impl Ord for StyleOp {
 /// - 0              (1st bit)
 /// - Expand Before  (2nd bit): when inserting new text before this style, whether the new text should inherit this style.
 /// - Expand After   (3rd bit): when inserting new text after  this style, whether the new text should inherit this style.
-/// - 0              (4th bit):
+/// - Merge Map      (4th bit): whether the map values of this style are merged per field instead of being overridden.
 /// - 0              (5th bit):
 /// - 0              (6th bit)
 /// - 0              (7th bit)
@@ -149,12 +149,14 @@ impl Debug for TextStyleInfoFlag {
             .field("data", &format!("{:#010b}", self.data))
             .field("expand_before", &self.expand_before())
             .field("expand_after", &self.expand_after())
+            .field("merge_map", &self.merge_map())
             .finish()
     }
 }
 
 const EXPAND_BEFORE_MASK: u8 = 0b0000_0010;
 const EXPAND_AFTER_MASK: u8 = 0b0000_0100;
+const MERGE_MAP_MASK: u8 = 0b0000_1000;
 const ALIVE_MASK: u8 = 0b1000_0000;
 
 /// Whether to expand the style when inserting new text around it.
@@ -236,6 +238,25 @@ impl TextStyleInfoFlag {
         self.data & EXPAND_AFTER_MASK != 0
     }
 
+    /// When the values of this style are maps, compose them per field with the values of the
+    /// concurrent or earlier styles of the same key.
+    #[inline(always)]
+    pub const fn merge_map(self) -> bool {
+        self.data & MERGE_MAP_MASK != 0
+    }
+
+    pub const fn with_merge_map(self, merge_map: bool) -> Self {
+        if merge_map {
+            Self {
+                data: self.data | MERGE_MAP_MASK,
+            }
+        } else {
+            Self {
+                data: self.data & !MERGE_MAP_MASK,
+            }
+        }
+    }
+
     pub const fn expand_type(self) -> ExpandType {
         match (self.expand_before(), self.expand_after()) {
             (true, true) => ExpandType::Both,
@@ -274,7 +295,7 @@ impl TextStyleInfoFlag {
 
     #[inline(always)]
     pub const fn to_delete(self) -> Self {
-        TextStyleInfoFlag::new(self.expand_type().reverse())
+        TextStyleInfoFlag::new(self.expand_type().reverse()).with_merge_map(self.merge_map())
     }
 
     pub const BOLD: TextStyleInfoFlag = TextStyleInfoFlag::new(ExpandType::After);
@@ -38,6 +38,7 @@ impl StyleConfigMap {
     fn _get_style_flag(&self, key: &InternalString, is_del: bool) -> Option<TextStyleInfoFlag> {
         let f = |x: &StyleConfig| {
             TextStyleInfoFlag::new(if is_del { x.expand.reverse() } else { x.expand })
+                .with_merge_map(x.merge_map)
         };
         if let Some(index) = key.find(':') {
             let key = key[..index].into();
@@ -56,6 +57,7 @@ impl StyleConfigMap {
             "bold".into(),
             StyleConfig {
                 expand: ExpandType::After,
+                merge_map: false,
             },
         );
 
@@ -63,6 +65,7 @@ impl StyleConfigMap {
             "italic".into(),
             StyleConfig {
                 expand: ExpandType::After,
+                merge_map: false,
             },
         );
 
@@ -70,6 +73,7 @@ impl StyleConfigMap {
             "underline".into(),
             StyleConfig {
                 expand: ExpandType::After,
+                merge_map: false,
             },
         );
 
@@ -77,6 +81,7 @@ impl StyleConfigMap {
             "link".into(),
             StyleConfig {
                 expand: ExpandType::None,
+                merge_map: false,
             },
         );
 
@@ -84,6 +89,7 @@ impl StyleConfigMap {
             "highlight".into(),
             StyleConfig {
                 expand: ExpandType::None,
+                merge_map: false,
             },
         );
 
@@ -91,6 +97,7 @@ impl StyleConfigMap {
             "comment".into(),
             StyleConfig {
                 expand: ExpandType::None,
+                merge_map: false,
             },
         );
 
@@ -98,6 +105,7 @@ impl StyleConfigMap {
             "code".into(),
             StyleConfig {
                 expand: ExpandType::None,
+                merge_map: false,
             },
         );
 
@@ -108,12 +116,22 @@ impl StyleConfigMap {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct StyleConfig {
     pub expand: ExpandType,
+    /// Whether the map values of the style are merged per field.
+    ///
+    /// If enabled, marking a range with a map value only overrides the fields in the map,
+    /// and concurrent marks that edit different fields are merged. A field can be removed
+    /// by setting it to null.
+    ///
+    /// Struct literals that list the fields of [`StyleConfig`] need to set it as well,
+    /// e.g. `StyleConfig { expand, ..Default::default() }` leaves it disabled.
+    pub merge_map: bool,
 }
 
 impl StyleConfig {
     pub fn new() -> Self {
         Self {
             expand: ExpandType::None,
+            merge_map: false,
         }
     }
 
@@ -121,6 +139,11 @@ impl StyleConfig {
         self.expand = expand;
         self
     }
+
+    pub fn merge_map(mut self, merge_map: bool) -> Self {
+        self.merge_map = merge_map;
+        self
+    }
 }
 
 impl Default for StyleConfig {
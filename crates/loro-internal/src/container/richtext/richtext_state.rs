@@ -33,7 +33,7 @@ use self::query::{
 
 use super::{
     style_range_map::{IterAnchorItem, StyleRangeMap, Styles},
-    AnchorType, RichtextSpan, StyleOp,
+    AnchorType, RichtextSpan, StyleKey, StyleOp,
 };
 
 pub(crate) use query::PosType;
@@ -2103,6 +2103,58 @@ impl RichtextState {
         self.cursor_to_event_index(cursor.cursor)
     }
 
+    /// Get the styles of the char at the event index `pos` that match `filter`,
+    /// with their values and event ranges.
+    pub(crate) fn get_style_ranges_at_event_index(
+        &self,
+        pos: usize,
+        filter: impl FnMut(&StyleKey) -> bool,
+    ) -> Vec<(StyleKey, Range<usize>, LoroValue)> {
+        if !self.has_styles() {
+            return Vec::new();
+        }
+
+        let Some(entity_index) = self
+            .get_text_entity_ranges(pos, 1, PosType::Event)
+            .ok()
+            .and_then(|ranges| ranges.first().map(|x| x.entity_start))
+        else {
+            return Vec::new();
+        };
+
+        self.style_ranges
+            .as_ref()
+            .unwrap()
+            .get_style_ranges_at(entity_index, filter)
+            .into_iter()
+            .filter_map(|(key, range, value)| {
+                Some((
+                    key.clone(),
+                    self.entity_index_to_event_index(range.start)
+                        ..self.entity_index_to_event_index(range.end),
+                    value.to_value()?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Get the event range and the value of the first span that has the style `key`.
+    pub(crate) fn get_style_event_range(
+        &self,
+        key: &StyleKey,
+    ) -> Option<(Range<usize>, LoroValue)> {
+        if !self.has_styles() {
+            return None;
+        }
+
+        let (range, value) = self.style_ranges.as_ref().unwrap().get_style_range(key)?;
+        Some((
+            self.entity_index_to_event_index(range.start)
+                ..self.entity_index_to_event_index(range.end),
+            value.to_value()?,
+        ))
+    }
+
     pub fn index_to_event_index(&self, index: usize, pos_type: PosType) -> usize {
         if self.tree.is_empty() {
             return 0;
@@ -2524,7 +2576,7 @@ impl RichtextState {
         result
     }
 
-    fn len(&self, pos_type: PosType) -> usize {
+    pub(crate) fn len(&self, pos_type: PosType) -> usize {
         self.check_cache();
         let result = {
             match pos_type {
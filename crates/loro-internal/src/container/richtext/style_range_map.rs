@@ -13,6 +13,7 @@ use generic_btree::{
     BTree, BTreeTrait, ElemSlice, LengthFinder, UseLengthFinder,
 };
 
+use loro_common::LoroValue;
 use once_cell::sync::Lazy;
 
 use crate::delta::StyleMeta;
@@ -39,8 +40,8 @@ pub(crate) struct Styles {
 impl Styles {
     pub(crate) fn has_key_value(&self, key: &str, value: &loro_common::LoroValue) -> bool {
         match self.get(&StyleKey::Key(key.into())) {
-            Some(v) => match v.get() {
-                Some(v) => &v.value == value,
+            Some(v) => match v.to_value() {
+                Some(v) => &v == value,
                 _ => false,
             },
             _ => false,
@@ -110,6 +111,56 @@ impl StyleValue {
     pub fn get(&self) -> Option<&Arc<StyleOp>> {
         self.set.last()
     }
+
+    /// Get the resolved value of the style.
+    ///
+    /// It's the value of the latest style op. But if the latest op merges map values, the
+    /// map values of the preceding ops that also merge map values are composed field by
+    /// field, with the later ones taking precedence.
+    pub fn to_value(&self) -> Option<LoroValue> {
+        let mut iter = self.set.iter().rev();
+        let last = iter.next()?;
+        if !last.info.merge_map() || !last.value.is_map() {
+            return Some(last.value.clone());
+        }
+
+        let mut patches = vec![&last.value];
+        for op in iter {
+            if !op.info.merge_map() || !op.value.is_map() {
+                break;
+            }
+
+            patches.push(&op.value);
+        }
+
+        let mut ans: Option<LoroValue> = None;
+        for patch in patches.into_iter().rev() {
+            ans = Some(merge_style_map(ans.as_ref(), patch));
+        }
+
+        ans
+    }
+}
+
+/// Compose the map `patch` onto `base` field by field. The fields set to null in `patch`
+/// are removed. If `base` is not a map, `patch` is composed onto an empty map.
+pub(crate) fn merge_style_map(base: Option<&LoroValue>, patch: &LoroValue) -> LoroValue {
+    let mut map: FxHashMap<String, LoroValue> = match base {
+        Some(LoroValue::Map(m)) => (**m).clone(),
+        _ => FxHashMap::default(),
+    };
+
+    if let LoroValue::Map(patch) = patch {
+        for (k, v) in patch.iter() {
+            if v.is_null() {
+                map.remove(k);
+            } else {
+                map.insert(k.clone(), v.clone());
+            }
+        }
+    }
+
+    LoroValue::Map(Arc::new(map))
 }
 
 impl Default for StyleRangeMap {
@@ -296,6 +347,77 @@ impl StyleRangeMap {
         })
     }
 
+    /// Get the styles at the entity `index` that match `filter`, with the range of each of them.
+    ///
+    /// The range of a style is extended from `index` in both directions until
+    /// an element without the style.
+    pub(crate) fn get_style_ranges_at(
+        &self,
+        index: usize,
+        mut filter: impl FnMut(&StyleKey) -> bool,
+    ) -> Vec<(&StyleKey, Range<usize>, &StyleValue)> {
+        if !self.has_style || index >= *self.tree.root_cache() as usize {
+            return Vec::new();
+        }
+
+        let cursor = self.tree.query::<LengthFinder>(&index).unwrap().cursor;
+        let elem = self.tree.get_elem(cursor.leaf).unwrap();
+        let elem_start = index - cursor.offset;
+        let mut ans = Vec::new();
+        for (key, value) in elem.styles.iter() {
+            if !filter(key) {
+                continue;
+            }
+
+            let mut start = elem_start;
+            let mut prev = self.tree.prev_elem(cursor);
+            while let Some(c) = prev {
+                let prev_elem = self.tree.get_elem(c.leaf).unwrap();
+                if !prev_elem.styles.contains_key(key) {
+                    break;
+                }
+
+                start -= prev_elem.len;
+                prev = self.tree.prev_elem(c);
+            }
+
+            let mut end = elem_start + elem.len;
+            let mut next = self.tree.next_elem(cursor);
+            while let Some(c) = next {
+                let next_elem = self.tree.get_elem(c.leaf).unwrap();
+                if !next_elem.styles.contains_key(key) {
+                    break;
+                }
+
+                end += next_elem.len;
+                next = self.tree.next_elem(c);
+            }
+
+            ans.push((key, start..end, value));
+        }
+
+        ans
+    }
+
+    /// Get the range of the first span that has the style `key`.
+    pub(crate) fn get_style_range(&self, key: &StyleKey) -> Option<(Range<usize>, &StyleValue)> {
+        if !self.has_style {
+            return None;
+        }
+
+        let mut iter = self.iter();
+        let (mut ans, value) = iter.find_map(|(range, styles)| Some((range, styles.get(key)?)))?;
+        for (range, styles) in iter {
+            if range.start != ans.end || !styles.contains_key(key) {
+                break;
+            }
+
+            ans.end = range.end;
+        }
+
+        Some((ans, value))
+    }
+
     /// Update the styles from `pos` to the start of the document.
     fn update_styles_scanning_backward(
         &mut self,
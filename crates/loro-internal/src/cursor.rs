@@ -80,3 +80,63 @@ impl Cursor {
         postcard::from_bytes(data)
     }
 }
+
+/// A range anchored by a pair of [Cursor]s, e.g. the range of a comment.
+///
+/// The range follows the content between the cursors when the container is edited.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CursorRange {
+    pub start: Cursor,
+    pub end: Cursor,
+}
+
+/// The result of resolving a [CursorRange].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeQueryResult {
+    pub start: PosQueryResult,
+    pub end: PosQueryResult,
+}
+
+impl CursorRange {
+    pub fn new(start: Cursor, end: Cursor) -> Self {
+        Self { start, end }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        postcard::to_allocvec(self).unwrap()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, postcard::Error> {
+        postcard::from_bytes(data)
+    }
+}
+
+impl RangeQueryResult {
+    /// Whether the range is empty now, e.g. all the content inside is deleted.
+    pub fn is_collapsed(&self) -> bool {
+        self.start.current.pos >= self.end.current.pos
+    }
+
+    /// Whether the element anchored by the start or the end cursor is deleted.
+    ///
+    /// If so, [RangeQueryResult::updated_range] returns the new range that should replace the stale one.
+    pub fn is_anchor_deleted(&self) -> bool {
+        self.start.update.is_some() || self.end.update.is_some()
+    }
+
+    /// The range with the stale cursors replaced, if any of them is stale.
+    pub fn updated_range(&self, old: &CursorRange) -> Option<CursorRange> {
+        if !self.is_anchor_deleted() {
+            return None;
+        }
+
+        Some(CursorRange {
+            start: self
+                .start
+                .update
+                .clone()
+                .unwrap_or_else(|| old.start.clone()),
+            end: self.end.update.clone().unwrap_or_else(|| old.end.clone()),
+        })
+    }
+}
@@ -38,13 +38,13 @@ impl From<&Styles> for StyleMeta {
     fn from(styles: &Styles) -> Self {
         let mut map = FxHashMap::with_capacity_and_hasher(styles.len(), Default::default());
         for (key, value) in styles.iter() {
-            if let Some(value) = value.get() {
+            if let (Some(op), Some(value)) = (value.get(), value.to_value()) {
                 map.insert(
                     key.key().clone(),
                     StyleMetaItem {
-                        value: value.to_value(),
-                        lamport: value.lamport,
-                        peer: value.peer,
+                        value,
+                        lamport: op.lamport,
+                        peer: op.peer,
                     },
                 );
             }
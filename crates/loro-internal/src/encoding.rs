@@ -12,6 +12,8 @@ use outdated_encode_reordered::{import_changes_to_oplog, ImportChangesResult};
 pub(crate) use value::OwnedValue;
 
 use crate::op::OpWithId;
+use crate::schema::SchemaViolation;
+use crate::tree_constraint::TreeViolation;
 use crate::version::{Frontiers, VersionRange};
 use crate::LoroDoc;
 use crate::{oplog::OpLog, LoroError, VersionVector};
@@ -153,6 +155,25 @@ impl<'a> ExportMode<'a> {
     }
 }
 
+/// The policy of [LoroDoc::compact_history], i.e. which part of the history is kept.
+///
+/// The history is trimmed at a single version, so the kept history is always a superset
+/// of the one described by the policy. The changes concurrent to the trimming point are kept.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HistoryRetention {
+    /// Keep the changes made in the last N days.
+    ///
+    /// It relies on the timestamps of the changes, so the doc should record the timestamps
+    /// via `set_record_timestamp` or commit with explicit timestamps.
+    /// It fails if none of the changes has a timestamp.
+    LastDays(u32),
+    /// Keep the last N changes, ordered by their lamport timestamps.
+    LastChanges(usize),
+    /// Keep the history after the given version.
+    After(Frontiers),
+}
+
 const MAGIC_BYTES: [u8; 4] = *b"loro";
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -163,6 +184,7 @@ pub(crate) enum EncodeMode {
     OutdatedSnapshot = 2,
     FastSnapshot = 3,
     FastUpdates = 4,
+    FastIncrementalSnapshot = 5,
 }
 
 impl num_traits::FromPrimitive for EncodeMode {
@@ -175,6 +197,9 @@ impl num_traits::FromPrimitive for EncodeMode {
             n if n == EncodeMode::OutdatedSnapshot as i64 => Some(EncodeMode::OutdatedSnapshot),
             n if n == EncodeMode::FastSnapshot as i64 => Some(EncodeMode::FastSnapshot),
             n if n == EncodeMode::FastUpdates as i64 => Some(EncodeMode::FastUpdates),
+            n if n == EncodeMode::FastIncrementalSnapshot as i64 => {
+                Some(EncodeMode::FastIncrementalSnapshot)
+            }
             _ => None,
         }
     }
@@ -194,6 +219,7 @@ impl num_traits::ToPrimitive for EncodeMode {
             EncodeMode::OutdatedSnapshot => EncodeMode::OutdatedSnapshot as i64,
             EncodeMode::FastSnapshot => EncodeMode::FastSnapshot as i64,
             EncodeMode::FastUpdates => EncodeMode::FastUpdates as i64,
+            EncodeMode::FastIncrementalSnapshot => EncodeMode::FastIncrementalSnapshot as i64,
         })
     }
     #[inline]
@@ -225,10 +251,20 @@ impl TryFrom<[u8; 2]> for EncodeMode {
     }
 }
 
+/// The result of an import.
+///
+/// More fields may be added in the future, so it cannot be constructed outside of this crate.
+#[non_exhaustive]
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct ImportStatus {
     pub success: VersionRange,
     pub pending: Option<VersionRange>,
+    /// The places that violate the schema attached to the doc after the import.
+    /// Only the containers changed by the import are checked.
+    pub schema_violations: Vec<SchemaViolation>,
+    /// The nodes that violate the tree constraints after the import and the resolutions.
+    /// Only the trees changed by the import are checked.
+    pub tree_violations: Vec<TreeViolation>,
 }
 
 /// The encoder used to encode the container states.
@@ -316,6 +352,7 @@ pub(crate) fn decode_oplog(
         }
         EncodeMode::FastSnapshot => fast_snapshot::decode_oplog(oplog, body),
         EncodeMode::FastUpdates => fast_snapshot::decode_updates(oplog, body.to_vec().into()),
+        EncodeMode::FastIncrementalSnapshot => Err(incremental_snapshot_import_error()),
         EncodeMode::Auto => unreachable!(),
     }?;
     let ImportChangesResult {
@@ -338,6 +375,8 @@ pub(crate) fn decode_oplog(
     Ok(ImportStatus {
         success: imported,
         pending: (!pending.is_empty()).then_some(pending),
+        schema_violations: Vec::new(),
+        tree_violations: Vec::new(),
     })
 }
 
@@ -358,7 +397,9 @@ impl ParsedHeaderAndBody<'_> {
                     return Err(LoroError::DecodeChecksumMismatchError);
                 }
             }
-            EncodeMode::FastSnapshot | EncodeMode::FastUpdates => {
+            EncodeMode::FastSnapshot
+            | EncodeMode::FastUpdates
+            | EncodeMode::FastIncrementalSnapshot => {
                 let expected = u32::from_le_bytes(self.checksum[12..16].try_into().unwrap());
                 if xxhash_rust::xxh32::xxh32(self.checksum_body, XXH_SEED) != expected {
                     return Err(LoroError::DecodeChecksumMismatchError);
@@ -431,6 +472,21 @@ pub(crate) fn export_fast_snapshot(doc: &LoroDoc) -> Vec<u8> {
     .unwrap()
 }
 
+pub(crate) fn export_incremental_snapshot(
+    doc: &LoroDoc,
+    since: &VersionVector,
+) -> Result<Vec<u8>, LoroEncodeError> {
+    encode_with(EncodeMode::FastIncrementalSnapshot, &mut |ans| {
+        fast_snapshot::encode_incremental_snapshot(doc, since, ans)
+    })
+}
+
+pub(crate) fn incremental_snapshot_import_error() -> LoroError {
+    LoroError::DecodeError(
+        "Incremental snapshot should be imported by `LoroDoc::from_incremental_snapshots`".into(),
+    )
+}
+
 pub(crate) fn export_snapshot_at(
     doc: &LoroDoc,
     frontiers: &Frontiers,
@@ -468,6 +524,14 @@ pub(crate) fn export_shallow_snapshot(
     })
 }
 
+pub(crate) fn compact_history(
+    doc: &LoroDoc,
+    start_from: &Frontiers,
+) -> Result<Frontiers, LoroEncodeError> {
+    check_target_version_reachable(doc, start_from)?;
+    shallow_snapshot::compact_history(doc, start_from)
+}
+
 fn check_target_version_reachable(doc: &LoroDoc, f: &Frontiers) -> Result<(), LoroEncodeError> {
     let oplog = doc.oplog.try_lock().unwrap();
     if !oplog.dag.can_export_shallow_snapshot_on(f) {
@@ -522,6 +586,8 @@ pub(crate) fn decode_snapshot(
     Ok(ImportStatus {
         success: VersionRange::from_vv(&doc.oplog_vv()),
         pending: None,
+        schema_violations: Vec::new(),
+        tree_violations: Vec::new(),
     })
 }
 
@@ -11,15 +11,52 @@
 //!
 //! All of `oplog bytes`, `state bytes` and `gc bytes` are encoded KV store bytes.
 //!
+//! # Incremental snapshot
 //!
+//! An incremental snapshot only contains the entries that may have changed since a base version.
+//! Because the entries are keyed the same way as in the full snapshot, it can be layered onto
+//! the base by importing the KV store bytes in order, where the latter overrides the former.
 //!
-use std::io::{Read, Write};
+//! - u32 in little endian for len of bytes for the start version vector
+//! - start version vector bytes
+//! - u32 in little endian for len of bytes for the end version vector
+//! - end version vector bytes
+//! - u32 in little endian for len of bytes for oplog
+//! - oplog bytes: the blocks that contain the changes after the start version and the meta entries
+//! - u32 in little endian for len of bytes for state
+//! - state bytes: the states of the containers modified after the start version
+//!
+//! # Persisting to a [KvStore]
+//!
+//! A doc can be opened on a [KvStore], which stores the entries of the three parts
+//! under the prefixes `o`, `s` and `g`. The change store and the container store of the doc
+//! read their entries from the store lazily, and saving the doc only writes the blocks
+//! and the container states modified since the last save.
+//!
+//! - `f`: the frontiers of the saved state
+//! - `r`: the shallow root frontiers of the entries under `g`, it only exists for shallow docs
+//!
+//!
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    ops::Bound,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
-    change::Change, encoding::shallow_snapshot, oplog::ChangeStore, LoroDoc, OpLog, VersionVector,
+    change::Change,
+    encoding::shallow_snapshot,
+    kv_store::{KvStore, PrefixedKvStore},
+    oplog::{ChangeStore, CHANGE_STORE_VV_KEY},
+    utils::kv_wrapper::KvWrapper,
+    version::Frontiers,
+    LoroDoc, OpLog, VersionVector,
 };
 use bytes::{Buf, Bytes};
-use loro_common::{IdSpan, LoroError, LoroResult};
+use fxhash::FxHashSet;
+use loro_common::{IdSpan, LoroEncodeError, LoroError, LoroResult};
+use loro_kv_store::{mem_store::MemKvConfig, MemKvStore};
 use tracing::trace;
 pub(crate) const EMPTY_MARK: &[u8] = b"E";
 pub(crate) struct Snapshot {
@@ -213,6 +250,325 @@ pub(crate) fn encode_snapshot_inner(doc: &LoroDoc) -> Snapshot {
     snapshot
 }
 
+pub(crate) fn encode_incremental_snapshot<W: Write>(
+    doc: &LoroDoc,
+    since: &VersionVector,
+    w: &mut W,
+) -> Result<(), LoroEncodeError> {
+    {
+        let state = doc.app_state().try_lock().unwrap();
+        // The pending events would be dropped together with the events of the checkouts below
+        if state.is_in_txn() || state.has_pending_events() {
+            return Err(LoroEncodeError::IncrementalSnapshotWithPendingChanges);
+        }
+    }
+
+    let oplog = doc.oplog().try_lock().unwrap();
+    if oplog.is_shallow() {
+        return Err(LoroEncodeError::IncrementalSnapshotOnShallowDoc);
+    }
+
+    if !oplog.vv().includes_vv(since) {
+        return Err(LoroEncodeError::IncrementalSnapshotStartNotFound(format!(
+            "{:?}",
+            since
+        )));
+    }
+
+    let vv = oplog.vv().clone();
+    let latest = oplog.frontiers().clone();
+    let oplog_bytes = oplog.encode_change_store_since(since);
+    let mut modified_containers = FxHashSet::default();
+    for span in vv.sub_iter(since) {
+        for change in oplog.change_store().iter_changes(span) {
+            for op in change.ops().iter() {
+                modified_containers.insert(op.container);
+            }
+        }
+    }
+    drop(oplog);
+
+    let old_state_frontiers = doc.state_frontiers();
+    let was_detached = doc.is_detached();
+    if was_detached {
+        doc.checkout_without_emitting(&latest, false)
+            .map_err(|e| LoroEncodeError::CheckoutFailed(e.to_string()))?;
+    }
+
+    let mut state_kv = MemKvStore::new(MemKvConfig::default());
+    {
+        let mut state = doc.app_state().try_lock().unwrap();
+        state.store.flush();
+        let kv = state.store.get_kv();
+        for idx in modified_containers {
+            let id = doc.arena().get_container_id(idx).unwrap();
+            let key = id.to_bytes();
+            if let Some(value) = kv.get(&key) {
+                state_kv.set(&key, value);
+            }
+        }
+    }
+
+    if was_detached {
+        let result = doc.checkout_without_emitting(&old_state_frontiers, false);
+        doc.drop_pending_events();
+        result.map_err(|e| LoroEncodeError::CheckoutFailed(e.to_string()))?;
+    }
+
+    let state_bytes = state_kv.export_all();
+    let since_bytes = since.encode();
+    let vv_bytes = vv.encode();
+    for part in [
+        &since_bytes[..],
+        &vv_bytes[..],
+        &oplog_bytes[..],
+        &state_bytes[..],
+    ] {
+        w.write_all(&(part.len() as u32).to_le_bytes()).unwrap();
+        w.write_all(part).unwrap();
+    }
+
+    Ok(())
+}
+
+/// Layer the incremental snapshots onto the base snapshot in order, and decode the result into the doc.
+pub(crate) fn decode_incremental_snapshots(
+    doc: &LoroDoc,
+    base: Bytes,
+    layers: Vec<Bytes>,
+) -> LoroResult<()> {
+    let Snapshot {
+        oplog_bytes,
+        state_bytes,
+        shallow_root_state_bytes,
+    } = _decode_snapshot_bytes(base)?;
+    if !shallow_root_state_bytes.is_empty() {
+        return Err(LoroError::DecodeError(
+            "Incremental snapshots cannot be applied on a shallow snapshot".into(),
+        ));
+    }
+
+    let mut oplog_kv = MemKvStore::new(MemKvConfig::default());
+    oplog_kv
+        .import_all(oplog_bytes)
+        .map_err(|e| LoroError::DecodeError(e.into_boxed_str()))?;
+    let mut state_kv = match state_bytes {
+        Some(bytes) => {
+            let mut kv = MemKvStore::new(MemKvConfig::default());
+            kv.import_all(bytes)
+                .map_err(|e| LoroError::DecodeError(e.into_boxed_str()))?;
+            Some(kv)
+        }
+        // The state will be calculated from the history
+        None => None,
+    };
+    let mut vv = match oplog_kv.get(CHANGE_STORE_VV_KEY) {
+        Some(bytes) => VersionVector::decode(&bytes)?,
+        None => VersionVector::new(),
+    };
+    for layer in layers {
+        let mut r = layer;
+        let since = VersionVector::decode(&read_incremental_part(&mut r)?)?;
+        let layer_vv = VersionVector::decode(&read_incremental_part(&mut r)?)?;
+        if !vv.includes_vv(&since) || !layer_vv.includes_vv(&vv) {
+            return Err(LoroError::IncrementalSnapshotMismatch);
+        }
+
+        oplog_kv
+            .import_all(read_incremental_part(&mut r)?)
+            .map_err(|e| LoroError::DecodeError(e.into_boxed_str()))?;
+        let state_bytes = read_incremental_part(&mut r)?;
+        if let Some(state_kv) = state_kv.as_mut() {
+            state_kv
+                .import_all(state_bytes)
+                .map_err(|e| LoroError::DecodeError(e.into_boxed_str()))?;
+        }
+        vv = layer_vv;
+    }
+
+    if vv.is_empty() {
+        return Ok(());
+    }
+
+    decode_snapshot_inner(
+        Snapshot {
+            oplog_bytes: oplog_kv.export_all(),
+            state_bytes: state_kv.map(|mut kv| kv.export_all()),
+            shallow_root_state_bytes: Bytes::new(),
+        },
+        doc,
+    )
+}
+
+fn read_incremental_part(r: &mut Bytes) -> LoroResult<Bytes> {
+    if r.remaining() < 4 {
+        return Err(LoroError::DecodeError(
+            "Invalid incremental snapshot".into(),
+        ));
+    }
+
+    let len = r.get_u32_le() as usize;
+    if r.remaining() < len {
+        return Err(LoroError::DecodeError(
+            "Invalid incremental snapshot".into(),
+        ));
+    }
+
+    Ok(r.split_to(len))
+}
+
+const KV_OPLOG_PREFIX: u8 = b'o';
+const KV_STATE_PREFIX: u8 = b's';
+const KV_SHALLOW_ROOT_STATE_PREFIX: u8 = b'g';
+const KV_STATE_FRONTIERS_KEY: &[u8] = b"f";
+const KV_SHALLOW_ROOT_FRONTIERS_KEY: &[u8] = b"r";
+
+/// The kv store a doc is opened on by [LoroDoc::from_kv_store]
+pub(crate) struct DocKvStore {
+    store: Arc<Mutex<dyn KvStore>>,
+    state: Arc<Mutex<PrefixedKvStore>>,
+}
+
+/// Open the empty doc on the kv store.
+///
+/// The change store and the container store of the doc are backed by the kv store,
+/// so the blocks of the history and the states of the containers are loaded when they are accessed.
+pub(crate) fn open_kv_store(
+    doc: &LoroDoc,
+    store: Arc<Mutex<dyn KvStore>>,
+) -> LoroResult<DocKvStore> {
+    let oplog_kv = Arc::new(Mutex::new(PrefixedKvStore::new(
+        store.clone(),
+        KV_OPLOG_PREFIX,
+    )));
+    let state_kv = Arc::new(Mutex::new(PrefixedKvStore::new(
+        store.clone(),
+        KV_STATE_PREFIX,
+    )));
+    let (state_frontiers, shallow_root_state_bytes) = {
+        let kv = store.lock().unwrap();
+        let shallow_root_state_bytes = kv
+            .contains_key(KV_SHALLOW_ROOT_FRONTIERS_KEY)
+            .then(|| read_kv_part(&*kv, KV_SHALLOW_ROOT_STATE_PREFIX));
+        (kv.get(KV_STATE_FRONTIERS_KEY), shallow_root_state_bytes)
+    };
+
+    let mut state = doc.app_state().try_lock().unwrap();
+    let mut oplog = doc.oplog().try_lock().unwrap();
+    oplog.open_kv_store(oplog_kv)?;
+    state.store.open_kv(KvWrapper::new(state_kv.clone()));
+    let kv_store = DocKvStore {
+        store,
+        state: state_kv,
+    };
+    let Some(state_frontiers) = state_frontiers else {
+        // Nothing has been saved in the store
+        return Ok(kv_store);
+    };
+
+    let state_frontiers = Frontiers::decode(&state_frontiers)?;
+    if let Some(bytes) = shallow_root_state_bytes {
+        state
+            .store
+            .decode_gc(bytes, oplog.dag().shallow_since_frontiers().clone())?;
+        let shallow_root_store = state.shallow_root_store().cloned();
+        oplog.with_history_cache(|h| {
+            h.set_shallow_root_store(shallow_root_store);
+        });
+    }
+
+    // The doc was saved when it was detached
+    let need_checkout = &state_frontiers != oplog.frontiers();
+    state.init_with_states_and_version(state_frontiers, &oplog, vec![], false);
+    drop(oplog);
+    drop(state);
+    if need_checkout {
+        doc.detach();
+        doc.checkout_to_latest();
+    }
+
+    Ok(kv_store)
+}
+
+impl DocKvStore {
+    /// Write the changes and the container states modified since the last save into the store.
+    pub(crate) fn save(&self, doc: &LoroDoc) -> LoroResult<()> {
+        let mut state = doc.app_state().try_lock().unwrap();
+        let mut oplog = doc.oplog().try_lock().unwrap();
+        assert!(!state.is_in_txn());
+        oplog.persist_kv_store();
+        state.ensure_children_of_unflushed_containers();
+        state.store.flush();
+        self.state.try_lock().unwrap().persist();
+
+        let mut kv = self.store.lock().unwrap();
+        let frontiers = state.frontiers.encode();
+        if kv.get(KV_STATE_FRONTIERS_KEY).as_deref() != Some(&frontiers[..]) {
+            kv.set(KV_STATE_FRONTIERS_KEY, frontiers.into());
+        }
+
+        if let Some(shallow_root_store) = state.store.shallow_root_store() {
+            // The shallow root state is only rewritten when the history is trimmed
+            let frontiers = shallow_root_store.shallow_root_frontiers.encode();
+            if kv.get(KV_SHALLOW_ROOT_FRONTIERS_KEY).as_deref() != Some(&frontiers[..]) {
+                let bytes = shallow_root_store.store.try_lock().unwrap().encode();
+                write_kv_part(&mut *kv, KV_SHALLOW_ROOT_STATE_PREFIX, bytes)?;
+                kv.set(KV_SHALLOW_ROOT_FRONTIERS_KEY, frontiers.into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Replace the entries under the prefix by the entries in the encoded kv store bytes.
+fn write_kv_part(kv: &mut dyn KvStore, prefix: u8, bytes: Bytes) -> LoroResult<()> {
+    let mut part = MemKvStore::new(MemKvConfig::default());
+    part.import_all(bytes)
+        .map_err(|e| LoroError::DecodeError(e.into_boxed_str()))?;
+    let mut new_entries: BTreeMap<Bytes, Bytes> = part
+        .scan(Bound::Unbounded, Bound::Unbounded)
+        .map(|(k, v)| {
+            let mut key = Vec::with_capacity(k.len() + 1);
+            key.push(prefix);
+            key.extend_from_slice(&k);
+            (Bytes::from(key), v)
+        })
+        .collect();
+    let (start, end) = ([prefix], [prefix + 1]);
+    let old_entries: Vec<(Bytes, Bytes)> = kv
+        .scan(Bound::Included(&start[..]), Bound::Excluded(&end[..]))
+        .collect();
+    for (k, v) in old_entries {
+        match new_entries.get(&k) {
+            Some(new_v) if new_v == &v => {
+                new_entries.remove(&k);
+            }
+            Some(_) => {}
+            None => {
+                kv.remove(&k);
+            }
+        }
+    }
+
+    for (k, v) in new_entries {
+        kv.set(&k, v);
+    }
+
+    Ok(())
+}
+
+/// Encode the entries under the prefix as kv store bytes
+fn read_kv_part(kv: &dyn KvStore, prefix: u8) -> Bytes {
+    let mut part = MemKvStore::new(MemKvConfig::default());
+    let (start, end) = ([prefix], [prefix + 1]);
+    for (k, v) in kv.scan(Bound::Included(&start[..]), Bound::Excluded(&end[..])) {
+        part.set(&k[1..], v);
+    }
+
+    part.export_all()
+}
+
 pub(crate) fn decode_oplog(oplog: &mut OpLog, bytes: &[u8]) -> Result<Vec<Change>, LoroError> {
     let oplog_len = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
     let oplog_bytes = &bytes[4..4 + oplog_len as usize];
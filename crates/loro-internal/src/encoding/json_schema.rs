@@ -88,6 +88,8 @@ pub(crate) fn import_json(oplog: &mut OpLog, json: JsonSchema) -> LoroResult<Imp
         } else {
             Some(pending)
         },
+        schema_violations: Vec::new(),
+        tree_violations: Vec::new(),
     })
 }
 
@@ -734,6 +734,8 @@ pub(crate) fn decode_snapshot(doc: &LoroDoc, bytes: &[u8]) -> LoroResult<()> {
                 Ok(ImportStatus {
                     success: Default::default(),
                     pending: None,
+                    schema_violations: Vec::new(),
+                    tree_violations: Vec::new(),
                 })
             },
             "".into(),
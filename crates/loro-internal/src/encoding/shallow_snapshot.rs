@@ -133,6 +133,33 @@ pub(crate) fn export_shallow_snapshot_inner(
     Ok((snapshot, start_from))
 }
 
+/// Trim the history before `start_from` in place.
+///
+/// The change store and the shallow root state are replaced by the ones in the shallow
+/// snapshot at `start_from`. The arena and the current state are kept, so the container
+/// indexes, the handlers and the subscriptions of the doc remain valid.
+pub(crate) fn compact_history(
+    doc: &LoroDoc,
+    start_from: &Frontiers,
+) -> Result<Frontiers, LoroEncodeError> {
+    let (snapshot, start_from) = export_shallow_snapshot_inner(doc, start_from)?;
+    let mut state = doc.app_state().try_lock().unwrap();
+    let mut oplog = doc.oplog().try_lock().unwrap();
+    oplog.replace_change_store(snapshot.oplog_bytes).unwrap();
+    state
+        .store
+        .replace_shallow_root(
+            snapshot.shallow_root_state_bytes,
+            oplog.shallow_since_frontiers().clone(),
+        )
+        .unwrap();
+    let shallow_root_store = state.shallow_root_store().cloned();
+    oplog.with_history_cache(|h| {
+        h.set_shallow_root_store(shallow_root_store);
+    });
+    Ok(start_from)
+}
+
 fn has_unknown_container<'a>(mut cids: impl Iterator<Item = &'a ContainerID>) -> bool {
     cids.any(|cid| matches!(cid.container_type(), ContainerType::Unknown(_)))
 }
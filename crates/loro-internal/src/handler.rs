@@ -1,10 +1,14 @@
 use super::{state::DocState, txn::Transaction};
 use crate::{
     arena::SharedArena,
+    change::Lamport,
     container::{
         idx::ContainerIdx,
         list::list_op::{DeleteSpan, DeleteSpanWithId, ListOp},
-        richtext::{richtext_state::PosType, RichtextState, StyleOp, TextStyleInfoFlag},
+        richtext::{
+            config::StyleConfigMap, merge_style_map, richtext_state::PosType, RichtextState,
+            StyleOp, TextStyleInfoFlag,
+        },
     },
     cursor::{Cursor, Side},
     delta::{DeltaItem, Meta, StyleMeta, TreeExternalDiff},
@@ -20,8 +24,8 @@ use enum_as_inner::EnumAsInner;
 use fxhash::FxHashMap;
 use generic_btree::rle::HasLength;
 use loro_common::{
-    ContainerID, ContainerType, IdFull, InternalString, LoroError, LoroResult, LoroValue, TreeID,
-    ID,
+    ContainerID, ContainerType, Counter, IdFull, InternalString, LoroError, LoroResult, LoroValue,
+    TreeID, ID,
 };
 use serde::{Deserialize, Serialize};
 use std::{
@@ -29,12 +33,12 @@ use std::{
     cmp::Reverse,
     collections::BinaryHeap,
     fmt::Debug,
-    ops::Deref,
+    ops::{Deref, Range},
     sync::{Arc, Mutex, Weak},
 };
 use tracing::{error, info, instrument, trace};
 
-pub use tree::TreeHandler;
+pub use tree::{TreeHandler, TreeSubtree};
 mod movable_list_apply_delta;
 mod tree;
 
@@ -112,6 +116,9 @@ struct DetachedInner<T> {
     value: T,
     /// If the handler attached later, this field will be filled.
     attached: Option<BasicHandler>,
+    /// It's increased on every edit of a detached text. It serves as the version id of the
+    /// detached text and orders the styles marked on it.
+    version: usize,
 }
 
 impl<T> DetachedInner<T> {
@@ -119,6 +126,7 @@ impl<T> DetachedInner<T> {
         Self {
             value: v,
             attached: None,
+            version: 0,
         }
     }
 }
@@ -365,6 +373,16 @@ impl std::fmt::Debug for TextHandler {
     }
 }
 
+/// An annotation attached to a range of text by [TextHandler::annotate].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextAnnotation {
+    pub id: ID,
+    pub key: InternalString,
+    /// The range in [Event Index]s
+    pub range: Range<usize>,
+    pub value: LoroValue,
+}
+
 #[derive(Debug, Clone, EnumAsInner, Deserialize, Serialize, PartialEq)]
 #[serde(untagged)]
 pub enum TextDelta {
@@ -1024,6 +1042,71 @@ impl Handler {
         }
     }
 
+    /// Create a detached deep copy of the container, including its child containers.
+    ///
+    /// The text styles are kept in the copy. The nodes of a tree get new ids in the copy.
+    pub fn to_detached(&self) -> LoroResult<Self> {
+        match self {
+            Self::Text(x) => {
+                let ans = TextHandler::new_detached();
+                ans.apply_delta(&x.get_delta())?;
+                Ok(Self::Text(ans))
+            }
+            Self::Map(x) => {
+                let mut entries = Vec::new();
+                x.for_each(|k, v| entries.push((k.to_string(), v)));
+                let ans = MapHandler::new_detached();
+                for (k, v) in entries {
+                    match v {
+                        ValueOrHandler::Value(v) => ans.insert(&k, v)?,
+                        ValueOrHandler::Handler(h) => {
+                            ans.insert_container(&k, h.to_detached()?)?;
+                        }
+                    }
+                }
+                Ok(Self::Map(ans))
+            }
+            Self::List(x) => {
+                let mut values = Vec::new();
+                x.for_each(|(_, v)| values.push(v));
+                let ans = ListHandler::new_detached();
+                for (i, v) in values.into_iter().enumerate() {
+                    match v {
+                        ValueOrHandler::Value(v) => ans.insert(i, v)?,
+                        ValueOrHandler::Handler(h) => {
+                            ans.insert_container(i, h.to_detached()?)?;
+                        }
+                    }
+                }
+                Ok(Self::List(ans))
+            }
+            Self::MovableList(x) => {
+                let mut values = Vec::new();
+                x.for_each(|v| values.push(v));
+                let ans = MovableListHandler::new_detached();
+                for (i, v) in values.into_iter().enumerate() {
+                    match v {
+                        ValueOrHandler::Value(v) => ans.insert(i, v)?,
+                        ValueOrHandler::Handler(h) => {
+                            ans.insert_container(i, h.to_detached()?)?;
+                        }
+                    }
+                }
+                Ok(Self::MovableList(ans))
+            }
+            Self::Tree(x) => Ok(Self::Tree(x.to_detached()?)),
+            #[cfg(feature = "counter")]
+            Self::Counter(x) => {
+                let ans = counter::CounterHandler::new_detached();
+                ans.increment(*x.get_value().as_double().unwrap())?;
+                Ok(Self::Counter(ans))
+            }
+            Self::Unknown(_) => Err(LoroError::NotImplemented(
+                "Copying a container of unknown type",
+            )),
+        }
+    }
+
     pub fn id(&self) -> ContainerID {
         match self {
             Self::Map(x) => x.id(),
@@ -1289,9 +1372,7 @@ impl TextHandler {
     /// This can be used to detect whether the richtext is changed
     pub fn version_id(&self) -> usize {
         match &self.inner {
-            MaybeDetached::Detached(_) => {
-                unimplemented!("Detached text container does not have version id")
-            }
+            MaybeDetached::Detached(t) => t.try_lock().unwrap().version,
             MaybeDetached::Attached(a) => {
                 a.with_state(|state| state.as_richtext_state_mut().unwrap().get_version_id())
             }
@@ -1486,15 +1567,7 @@ impl TextHandler {
         match &self.inner {
             MaybeDetached::Detached(t) => {
                 let mut t = t.try_lock().unwrap();
-                let (index, _) = t
-                    .value
-                    .get_entity_index_for_text_insert(pos, PosType::Event)
-                    .unwrap();
-                t.value.insert_at_entity_index(
-                    index,
-                    BytesSlice::from_bytes(s.as_bytes()),
-                    IdFull::NONE_ID,
-                );
+                Self::insert_for_detached(&mut t, pos, s, PosType::Event)?;
                 Ok(())
             }
             MaybeDetached::Attached(a) => a.with_txn(|txn| self.insert_with_txn(txn, pos, s)),
@@ -1505,15 +1578,7 @@ impl TextHandler {
         match &self.inner {
             MaybeDetached::Detached(t) => {
                 let mut t = t.try_lock().unwrap();
-                let (index, _) = t
-                    .value
-                    .get_entity_index_for_text_insert(pos, PosType::Bytes)
-                    .unwrap();
-                t.value.insert_at_entity_index(
-                    index,
-                    BytesSlice::from_bytes(s.as_bytes()),
-                    IdFull::NONE_ID,
-                );
+                Self::insert_for_detached(&mut t, pos, s, PosType::Bytes)?;
                 Ok(())
             }
             MaybeDetached::Attached(a) => a.with_txn(|txn| self.insert_with_txn_utf8(txn, pos, s)),
@@ -1524,15 +1589,7 @@ impl TextHandler {
         match &self.inner {
             MaybeDetached::Detached(t) => {
                 let mut t = t.try_lock().unwrap();
-                let (index, _) = t
-                    .value
-                    .get_entity_index_for_text_insert(pos, PosType::Unicode)
-                    .unwrap();
-                t.value.insert_at_entity_index(
-                    index,
-                    BytesSlice::from_bytes(s.as_bytes()),
-                    IdFull::NONE_ID,
-                );
+                Self::insert_for_detached(&mut t, pos, s, PosType::Unicode)?;
                 Ok(())
             }
             MaybeDetached::Attached(a) => a.with_txn(|txn| {
@@ -1571,15 +1628,7 @@ impl TextHandler {
         match &self.inner {
             MaybeDetached::Detached(t) => {
                 let mut t = t.try_lock().unwrap();
-                let ranges = t
-                    .value
-                    .get_text_entity_ranges(pos, len, PosType::Event)
-                    .unwrap();
-                for range in ranges.iter().rev() {
-                    t.value
-                        .drain_by_entity_index(range.entity_start, range.entity_len(), None);
-                }
-                Ok(())
+                Self::delete_for_detached(&mut t, pos, len, PosType::Event)
             }
             MaybeDetached::Attached(a) => a.with_txn(|txn| self.delete_with_txn(txn, pos, len)),
         }
@@ -1589,15 +1638,7 @@ impl TextHandler {
         match &self.inner {
             MaybeDetached::Detached(t) => {
                 let mut t = t.try_lock().unwrap();
-                let ranges = match t.value.get_text_entity_ranges(pos, len, PosType::Bytes) {
-                    Err(x) => return Err(x),
-                    Ok(x) => x,
-                };
-                for range in ranges.iter().rev() {
-                    t.value
-                        .drain_by_entity_index(range.entity_start, range.entity_len(), None);
-                }
-                Ok(())
+                Self::delete_for_detached(&mut t, pos, len, PosType::Bytes)
             }
             MaybeDetached::Attached(a) => {
                 a.with_txn(|txn| self.delete_with_txn_inline(txn, pos, len, PosType::Bytes))
@@ -1609,15 +1650,7 @@ impl TextHandler {
         match &self.inner {
             MaybeDetached::Detached(t) => {
                 let mut t = t.try_lock().unwrap();
-                let ranges = match t.value.get_text_entity_ranges(pos, len, PosType::Unicode) {
-                    Err(x) => return Err(x),
-                    Ok(x) => x,
-                };
-                for range in ranges.iter().rev() {
-                    t.value
-                        .drain_by_entity_index(range.entity_start, range.entity_len(), None);
-                }
-                Ok(())
+                Self::delete_for_detached(&mut t, pos, len, PosType::Unicode)
             }
             MaybeDetached::Attached(a) => {
                 a.with_txn(|txn| self.delete_with_txn_inline(txn, pos, len, PosType::Unicode))
@@ -1625,6 +1658,71 @@ impl TextHandler {
         }
     }
 
+    /// Insert `s` at `pos` of a detached text and return the styles at the insert position.
+    fn insert_for_detached(
+        t: &mut DetachedInner<RichtextState>,
+        pos: usize,
+        s: &str,
+        pos_type: PosType,
+    ) -> LoroResult<StyleMeta> {
+        if s.is_empty() {
+            return Ok(StyleMeta::empty());
+        }
+
+        let len = t.value.len(pos_type);
+        if pos > len {
+            return Err(LoroError::OutOfBound {
+                pos,
+                len,
+                info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+            });
+        }
+
+        let (index, _) = t
+            .value
+            .get_entity_index_for_text_insert(pos, pos_type)
+            .map_err(|_| match pos_type {
+                PosType::Bytes => LoroError::UTF8InUnicodeCodePoint { pos },
+                _ => LoroError::UTF16InUnicodeCodePoint { pos },
+            })?;
+        let styles = t.value.get_styles_at_entity_index_for_insert(index);
+        t.value.insert_at_entity_index(
+            index,
+            BytesSlice::from_bytes(s.as_bytes()),
+            IdFull::NONE_ID,
+        );
+        t.version += 1;
+        Ok(styles)
+    }
+
+    fn delete_for_detached(
+        t: &mut DetachedInner<RichtextState>,
+        pos: usize,
+        len: usize,
+        pos_type: PosType,
+    ) -> LoroResult<()> {
+        if len == 0 {
+            return Ok(());
+        }
+
+        let total = t.value.len(pos_type);
+        if pos + len > total {
+            return Err(LoroError::OutOfBound {
+                pos: pos + len,
+                len: total,
+                info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+            });
+        }
+
+        let ranges = t.value.get_text_entity_ranges(pos, len, pos_type)?;
+        for range in ranges.iter().rev() {
+            t.value
+                .drain_by_entity_index(range.entity_start, range.entity_len(), None);
+        }
+        t.version += 1;
+        Ok(())
+    }
+
     /// If attr is specified, it will be used as the attribute of the inserted text.
     /// It will override the existing attribute of the text.
     fn insert_with_txn_and_attr(
@@ -1730,28 +1828,9 @@ impl TextHandler {
             _ => entity_index.unwrap(),
         };
 
-        let mut override_styles = Vec::new();
-        if let Some(attr) = attr {
-            // current styles
-            let map: FxHashMap<_, _> = styles.iter().map(|x| (x.0.clone(), x.1.data)).collect();
-            for (key, style) in map.iter() {
-                match attr.get(key.deref()) {
-                    Some(v) if v == style => {}
-                    new_style_value => {
-                        // need to override
-                        let new_style_value = new_style_value.cloned().unwrap_or(LoroValue::Null);
-                        override_styles.push((key.clone(), new_style_value));
-                    }
-                }
-            }
-
-            for (key, style) in attr.iter() {
-                let key = key.as_str().into();
-                if !map.contains_key(&key) {
-                    override_styles.push((key, style.clone()));
-                }
-            }
-        }
+        let override_styles = attr
+            .map(|attr| get_override_styles(&styles, attr))
+            .unwrap_or_default();
 
         let unicode_len = s.chars().count();
         let event_len = if cfg!(feature = "wasm") {
@@ -1880,14 +1959,9 @@ impl TextHandler {
         value: LoroValue,
     ) -> LoroResult<()> {
         match &self.inner {
-            MaybeDetached::Detached(t) => self.mark_for_detached(
-                &mut t.try_lock().unwrap().value,
-                key,
-                &value,
-                start,
-                end,
-                false,
-            ),
+            MaybeDetached::Detached(t) => {
+                self.mark_for_detached(&mut t.try_lock().unwrap(), key, &value, start, end, false)
+            }
             MaybeDetached::Attached(a) => {
                 a.with_txn(|txn| self.mark_with_txn(txn, start, end, key, value, false))
             }
@@ -1896,7 +1970,7 @@ impl TextHandler {
 
     fn mark_for_detached(
         &self,
-        state: &mut RichtextState,
+        t: &mut DetachedInner<RichtextState>,
         key: impl Into<InternalString>,
         value: &LoroValue,
         start: usize,
@@ -1904,7 +1978,7 @@ impl TextHandler {
         is_delete: bool,
     ) -> Result<(), LoroError> {
         let key: InternalString = key.into();
-        let len = self.len_event();
+        let len = t.value.len_event();
         if start >= end {
             return Err(loro_common::LoroError::ArgErr(
                 "Start must be less than end".to_string().into_boxed_str(),
@@ -1917,8 +1991,9 @@ impl TextHandler {
                 info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
             });
         }
-        let (entity_range, styles) =
-            state.get_entity_range_and_text_styles_at_range(start..end, PosType::Event);
+        let (entity_range, styles) = t
+            .value
+            .get_entity_range_and_text_styles_at_range(start..end, PosType::Event);
         if let Some(styles) = styles {
             if styles.has_key_value(&key, value) {
                 // already has the same style, skip
@@ -1926,20 +2001,29 @@ impl TextHandler {
             }
         }
 
+        // A detached text has no doc config, so the default rich text config is used.
+        // The styles that are not in the default config behave like `bold`.
+        let config = StyleConfigMap::default_rich_text_config();
+        let info = if is_delete {
+            config
+                .get_style_flag_for_unmark(&key)
+                .unwrap_or(TextStyleInfoFlag::BOLD.to_delete())
+        } else {
+            config
+                .get_style_flag(&key)
+                .unwrap_or(TextStyleInfoFlag::BOLD)
+        };
+        // The version works as the lamport so that later marks override the earlier ones
+        t.version += 1;
         let style_op = Arc::new(StyleOp {
-            lamport: 0,
+            lamport: t.version as Lamport,
             peer: 0,
-            cnt: 0,
+            cnt: t.version as Counter,
             key,
             value: value.clone(),
-            // TODO: describe this behavior in the document
-            info: if is_delete {
-                TextStyleInfoFlag::BOLD.to_delete()
-            } else {
-                TextStyleInfoFlag::BOLD
-            },
+            info,
         });
-        state.mark_with_entity_index(entity_range, style_op);
+        t.value.mark_with_entity_index(entity_range, style_op);
         Ok(())
     }
 
@@ -1957,7 +2041,7 @@ impl TextHandler {
     ) -> LoroResult<()> {
         match &self.inner {
             MaybeDetached::Detached(t) => self.mark_for_detached(
-                &mut t.try_lock().unwrap().value,
+                &mut t.try_lock().unwrap(),
                 key,
                 &LoroValue::Null,
                 start,
@@ -2037,6 +2121,20 @@ impl TextHandler {
         };
 
         drop(style_config);
+        // The event of a style that merges map values should carry the merged values
+        let merged = if !is_delete && flag.merge_map() && value.is_map() {
+            Some(doc_state.with_state_mut(inner.container_idx, |state| {
+                state
+                    .as_richtext_state_mut()
+                    .unwrap()
+                    .get_style_values_at_event_range(start..end, &key)
+                    .into_iter()
+                    .map(|(len, old)| (len as u32, merge_style_map(old.as_ref(), &value)))
+                    .collect()
+            }))
+        } else {
+            None
+        };
         drop(doc_state);
         txn.apply_local_op(
             inner.container_idx,
@@ -2051,6 +2149,7 @@ impl TextHandler {
                 start: start as u32,
                 end: end as u32,
                 style: crate::container::richtext::Style { key, data: value },
+                merged,
             },
             &inner.state,
         )?;
@@ -2065,6 +2164,173 @@ impl TextHandler {
         Ok(())
     }
 
+    /// Attach an annotation to the range `start..end` and return its id.
+    ///
+    /// An annotation is stored as a style whose key is `{key}:{id}`, so annotations with the
+    /// same key never merge with each other even when they overlap. Its range is tracked by the
+    /// style anchors and survives concurrent edits. The expand behavior is taken from the style
+    /// config of `key`.
+    ///
+    /// `start` and `end` are [Event Index]s.
+    pub fn annotate(
+        &self,
+        start: usize,
+        end: usize,
+        key: &str,
+        value: LoroValue,
+    ) -> LoroResult<ID> {
+        if key.contains(':') {
+            return Err(LoroError::ArgErr(
+                "Annotation key should not contain ':'"
+                    .to_string()
+                    .into_boxed_str(),
+            ));
+        }
+
+        if value.is_null() {
+            return Err(LoroError::ArgErr(
+                "Annotation value should not be null"
+                    .to_string()
+                    .into_boxed_str(),
+            ));
+        }
+
+        match &self.inner {
+            MaybeDetached::Detached(_) => {
+                Err(LoroError::MisuseDetachedContainer { method: "annotate" })
+            }
+            MaybeDetached::Attached(a) => a.with_txn(|txn| {
+                let id = txn.next_id();
+                self.mark_with_txn(txn, start, end, annotation_key(key, id), value, false)?;
+                Ok(id)
+            }),
+        }
+    }
+
+    /// Remove the annotation with the given key and id.
+    ///
+    /// It's a no-op if the annotation is not visible anymore, e.g. all its text is deleted.
+    pub fn remove_annotation(&self, key: &str, id: ID) -> LoroResult<()> {
+        let style_key = annotation_key(key, id);
+        let range = match &self.inner {
+            MaybeDetached::Detached(_) => self
+                .get_annotations()
+                .into_iter()
+                .find(|a| a.id == id && a.key.as_str() == key)
+                .map(|a| a.range),
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                state
+                    .as_richtext_state_mut()
+                    .unwrap()
+                    .get_style_event_range(&style_key)
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(range, _)| range)
+            }),
+        };
+
+        let Some(range) = range else {
+            return Ok(());
+        };
+
+        self.unmark(range.start, range.end, style_key)
+    }
+
+    /// Get all the visible annotations in this text, sorted by their start positions.
+    pub fn get_annotations(&self) -> Vec<TextAnnotation> {
+        let spans: Vec<(usize, Option<FxHashMap<String, LoroValue>>)> = match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                t.value
+                    .iter()
+                    .map(|span| {
+                        (
+                            event_len(span.text.as_str()),
+                            span.attributes.to_option_map(),
+                        )
+                    })
+                    .collect()
+            }
+            MaybeDetached::Attached(_) => self
+                .get_delta()
+                .into_iter()
+                .filter_map(|d| match d {
+                    TextDelta::Insert { insert, attributes } => {
+                        Some((event_len(insert.as_str()), attributes))
+                    }
+                    _ => None,
+                })
+                .collect(),
+        };
+
+        let mut map: FxHashMap<String, TextAnnotation> = FxHashMap::default();
+        let mut index = 0;
+        for (len, attributes) in spans {
+            for (style_key, value) in attributes.into_iter().flatten() {
+                if value.is_null() {
+                    continue;
+                }
+
+                let Some((key, id)) = parse_annotation_key(&style_key) else {
+                    continue;
+                };
+
+                map.entry(style_key)
+                    .and_modify(|a| a.range.end = index + len)
+                    .or_insert_with(|| TextAnnotation {
+                        id,
+                        key: key.into(),
+                        range: index..index + len,
+                        value,
+                    });
+            }
+
+            index += len;
+        }
+
+        let mut ans: Vec<TextAnnotation> = map.into_values().collect();
+        ans.sort_by(|a, b| (a.range.start, a.id).cmp(&(b.range.start, b.id)));
+        ans
+    }
+
+    /// Get the annotations that cover the given [Event Index].
+    pub fn get_annotations_at(&self, pos: usize) -> Vec<TextAnnotation> {
+        match &self.inner {
+            MaybeDetached::Detached(_) => {
+                let mut ans = self.get_annotations();
+                ans.retain(|a| a.range.contains(&pos));
+                ans
+            }
+            MaybeDetached::Attached(a) => {
+                let styles = a.with_state(|state| {
+                    state
+                        .as_richtext_state_mut()
+                        .unwrap()
+                        .get_style_ranges_at_event_index(pos, |key| {
+                            parse_annotation_key(key.key()).is_some()
+                        })
+                });
+                let mut ans: Vec<TextAnnotation> = styles
+                    .into_iter()
+                    .filter_map(|(style_key, range, value)| {
+                        if value.is_null() {
+                            return None;
+                        }
+
+                        let (key, id) = parse_annotation_key(style_key.key())?;
+                        Some(TextAnnotation {
+                            id,
+                            key: key.into(),
+                            range,
+                            value,
+                        })
+                    })
+                    .collect();
+                ans.sort_by(|a, b| (a.range.start, a.id).cmp(&(b.range.start, b.id)));
+                ans
+            }
+        }
+    }
+
     pub fn check(&self) {
         match &self.inner {
             MaybeDetached::Detached(t) => {
@@ -2083,16 +2349,71 @@ impl TextHandler {
     pub fn apply_delta(&self, delta: &[TextDelta]) -> LoroResult<()> {
         match &self.inner {
             MaybeDetached::Detached(t) => {
-                let _t = t.try_lock().unwrap();
-                // TODO: implement
-                Err(LoroError::NotImplemented(
-                    "`apply_delta` on a detached text container",
-                ))
+                let mut t = t.try_lock().unwrap();
+                self.apply_delta_for_detached(&mut t, delta)
             }
             MaybeDetached::Attached(a) => a.with_txn(|txn| self.apply_delta_with_txn(txn, delta)),
         }
     }
 
+    fn apply_delta_for_detached(
+        &self,
+        t: &mut DetachedInner<RichtextState>,
+        delta: &[TextDelta],
+    ) -> LoroResult<()> {
+        let mut index = 0;
+        let mut marks = Vec::new();
+        for d in delta {
+            match d {
+                TextDelta::Insert { insert, attributes } => {
+                    if insert.is_empty() {
+                        continue;
+                    }
+
+                    let end = index + event_len(insert.as_str());
+                    let styles =
+                        Self::insert_for_detached(t, index, insert.as_str(), PosType::Event)?;
+                    let override_styles = match attributes {
+                        Some(attr) => get_override_styles(&styles, attr),
+                        None => get_override_styles(&styles, &Default::default()),
+                    };
+                    for (key, value) in override_styles {
+                        marks.push((index, end, key, value));
+                    }
+
+                    index = end;
+                }
+                TextDelta::Delete { delete } => {
+                    Self::delete_for_detached(t, index, *delete, PosType::Event)?;
+                }
+                TextDelta::Retain { attributes, retain } => {
+                    let end = index + *retain;
+                    match attributes {
+                        Some(attr) if !attr.is_empty() => {
+                            for (key, value) in attr {
+                                marks.push((index, end, key.deref().into(), value.clone()));
+                            }
+                        }
+                        _ => {}
+                    }
+                    index = end;
+                }
+            }
+        }
+
+        let mut len = t.value.len_event();
+        for (start, end, key, value) in marks {
+            if start >= len {
+                Self::insert_for_detached(t, len, &"\n".repeat(start - len + 1), PosType::Event)?;
+                len = start;
+            }
+
+            self.mark_for_detached(t, key, &value, start, end, false)?;
+        }
+
+        Ok(())
+    }
+
     pub fn apply_delta_with_txn(
         &self,
         txn: &mut Transaction,
@@ -2143,12 +2464,65 @@ impl TextHandler {
                 len = start;
             }
 
+            let value = self.style_value_to_patch(start, end, &key, value);
             self.mark_with_txn(txn, start, end, key.deref(), value, false)?;
         }
 
         Ok(())
     }
 
+    /// The attributes in a delta are the full values of the styles. But for a style that merges
+    /// map values, marking with a map only overrides the given fields. So the fields that are
+    /// missing in `value` need to be set to null explicitly.
+    fn style_value_to_patch(
+        &self,
+        start: usize,
+        end: usize,
+        key: &InternalString,
+        value: LoroValue,
+    ) -> LoroValue {
+        if !value.is_map() || start >= end || end > self.len_event() {
+            return value;
+        }
+
+        let Ok(inner) = self.inner.try_attached_state() else {
+            return value;
+        };
+        let mutex = &inner.state.upgrade().unwrap();
+        let mut doc_state = mutex.try_lock().unwrap();
+        let merge_map = doc_state
+            .config
+            .text_style_config
+            .try_read()
+            .unwrap()
+            .get_style_flag(key)
+            .map(|f| f.merge_map())
+            .unwrap_or(false);
+        if !merge_map {
+            return value;
+        }
+
+        let old_values = doc_state.with_state_mut(inner.container_idx, |state| {
+            state
+                .as_richtext_state_mut()
+                .unwrap()
+                .get_style_values_at_event_range(start..end, key)
+        });
+        let map = value.as_map().unwrap();
+        let mut patch = (**map).clone();
+        for (_, old) in old_values {
+            if let Some(LoroValue::Map(old)) = old {
+                for k in old.keys() {
+                    if !map.contains_key(k) {
+                        patch.insert(k.clone(), LoroValue::Null);
+                    }
+                }
+            }
+        }
+
+        LoroValue::Map(Arc::new(patch))
+    }
+
     #[instrument(level = "trace", skip(self))]
     pub fn update(&self, text: &str) {
         let old_str = self.to_string();
@@ -2278,6 +2652,94 @@ impl TextHandler {
     }
 }
 
+fn annotation_key(key: &str, id: ID) -> InternalString {
+    format!("{}:{}", key, id).into()
+}
+
+fn parse_annotation_key(style_key: &str) -> Option<(&str, ID)> {
+    let (key, id) = style_key.split_once(':')?;
+    let id = ID::try_from(id).ok()?;
+    Some((key, id))
+}
+
+/// Get the styles that need to be marked on a text inserted with the given `styles`, so that its
+/// attributes become exactly `attr`.
+fn get_override_styles(
+    styles: &StyleMeta,
+    attr: &FxHashMap<String, LoroValue>,
+) -> Vec<(InternalString, LoroValue)> {
+    let mut override_styles = Vec::new();
+    // current styles
+    let map: FxHashMap<_, _> = styles.iter().map(|x| (x.0.clone(), x.1.data)).collect();
+    for (key, style) in map.iter() {
+        match attr.get(key.deref()) {
+            Some(v) if v == style => {}
+            new_style_value => {
+                // need to override
+                let new_style_value = new_style_value.cloned().unwrap_or(LoroValue::Null);
+                override_styles.push((key.clone(), new_style_value));
+            }
+        }
+    }
+
+    for (key, style) in attr.iter() {
+        let key = key.as_str().into();
+        if !map.contains_key(&key) {
+            override_styles.push((key, style.clone()));
+        }
+    }
+
+    override_styles
+}
+
+/// Apply a list delta to the value of a detached list or movable list.
+///
+/// The child containers in the delta are inserted as they are, so their content will be copied
+/// when the list gets attached.
+fn apply_list_delta_for_detached(
+    list: &mut Vec<ValueOrHandler>,
+    delta: &loro_delta::DeltaRope<
+        loro_delta::array_vec::ArrayVec<ValueOrHandler, 8>,
+        crate::event::ListDeltaMeta,
+    >,
+) -> LoroResult<()> {
+    // Test whether the delta is valid before changing the list
+    let mut index = 0;
+    for item in delta.iter() {
+        match item {
+            loro_delta::DeltaItem::Retain { len, .. } => {
+                index += *len;
+            }
+            loro_delta::DeltaItem::Replace { delete, .. } => {
+                index += *delete;
+            }
+        }
+
+        if index > list.len() {
+            return Err(LoroError::OutOfBound {
+                pos: index,
+                len: list.len(),
+                info: "apply_delta".into(),
+            });
+        }
+    }
+
+    let mut index = 0;
+    for item in delta.iter() {
+        match item {
+            loro_delta::DeltaItem::Retain { len, .. } => {
+                index += *len;
+            }
+            loro_delta::DeltaItem::Replace { value, delete, .. } => {
+                list.splice(index..index + *delete, value.iter().cloned());
+                index += value.len();
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn event_len(s: &str) -> usize {
     if cfg!(feature = "wasm") {
         count_utf16_len(s.as_bytes())
@@ -2300,6 +2762,13 @@ impl ListHandler {
         match &self.inner {
             MaybeDetached::Detached(l) => {
                 let mut list = l.try_lock().unwrap();
+                if pos > list.value.len() {
+                    return Err(LoroError::OutOfBound {
+                        pos,
+                        info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+                        len: list.value.len(),
+                    });
+                }
                 list.value.insert(pos, ValueOrHandler::Value(v.into()));
                 Ok(())
             }
@@ -2384,6 +2853,13 @@ impl ListHandler {
         match &self.inner {
             MaybeDetached::Detached(l) => {
                 let mut list = l.try_lock().unwrap();
+                if pos > list.value.len() {
+                    return Err(LoroError::OutOfBound {
+                        pos,
+                        info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+                        len: list.value.len(),
+                    });
+                }
                 list.value
                     .insert(pos, ValueOrHandler::Handler(child.to_handler()));
                 Ok(child)
@@ -2429,6 +2905,13 @@ impl ListHandler {
         match &self.inner {
             MaybeDetached::Detached(l) => {
                 let mut list = l.try_lock().unwrap();
+                if pos + len > list.value.len() {
+                    return Err(LoroError::OutOfBound {
+                        pos: pos + len,
+                        info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+                        len: list.value.len(),
+                    });
+                }
                 list.value.drain(pos..pos + len);
                 Ok(())
             }
@@ -2650,7 +3133,10 @@ impl ListHandler {
         on_container_remap: &mut dyn FnMut(ContainerID, ContainerID),
     ) -> LoroResult<()> {
         match &self.inner {
-            MaybeDetached::Detached(_) => unimplemented!(),
+            MaybeDetached::Detached(l) => {
+                let mut l = l.try_lock().unwrap();
+                apply_list_delta_for_detached(&mut l.value, &delta)?;
+            }
             MaybeDetached::Attached(_) => {
                 let mut index = 0;
                 for item in delta.iter() {
@@ -3024,6 +3510,13 @@ impl MovableListHandler {
         match &self.inner {
             MaybeDetached::Detached(d) => {
                 let mut d = d.try_lock().unwrap();
+                if pos >= d.value.len() {
+                    return Err(LoroError::OutOfBound {
+                        pos,
+                        info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+                        len: d.value.len(),
+                    });
+                }
                 d.value[pos] = ValueOrHandler::Handler(child.to_handler());
                 Ok(child)
             }
@@ -3070,6 +3563,13 @@ impl MovableListHandler {
         match &self.inner {
             MaybeDetached::Detached(d) => {
                 let mut d = d.try_lock().unwrap();
+                if pos + len > d.value.len() {
+                    return Err(LoroError::OutOfBound {
+                        pos: pos + len,
+                        info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+                        len: d.value.len(),
+                    });
+                }
                 d.value.drain(pos..pos + len);
                 Ok(())
             }
@@ -3913,12 +4413,15 @@ pub mod counter {
 #[cfg(test)]
 mod test {
 
-    use super::{HandlerTrait, TextDelta};
+    use super::{
+        HandlerTrait, ListHandler, MovableListHandler, TextDelta, TextHandler, ValueOrHandler,
+    };
     use crate::loro::LoroDoc;
     use crate::state::TreeParentId;
     use crate::version::Frontiers;
     use crate::{fx_map, ToJson};
     use loro_common::ID;
+    use loro_delta::{array_vec::ArrayVec, DeltaRope};
     use serde_json::json;
 
     #[test]
@@ -4162,4 +4665,49 @@ mod test {
             ])
         )
     }
+
+    #[test]
+    fn detached_text_version_id() {
+        let text = TextHandler::new_detached();
+        let v0 = text.version_id();
+        text.insert(0, "Hello").unwrap();
+        let v1 = text.version_id();
+        assert_ne!(v0, v1);
+        text.mark(0, 5, "bold", true.into()).unwrap();
+        assert_ne!(v1, text.version_id());
+    }
+
+    #[test]
+    fn detached_list_apply_delta() {
+        let list = ListHandler::new_detached();
+        let movable_list = MovableListHandler::new_detached();
+        for i in 0..3 {
+            list.push(i).unwrap();
+            movable_list.push(i.into()).unwrap();
+        }
+
+        let mut delta = DeltaRope::new();
+        delta
+            .push_retain(1, Default::default())
+            .push_insert(
+                ArrayVec::from([ValueOrHandler::Value("a".into())]),
+                Default::default(),
+            )
+            .push_delete(1);
+        list.apply_delta(delta.clone(), &mut |_, _| {}).unwrap();
+        movable_list
+            .apply_delta(delta, &mut Default::default())
+            .unwrap();
+        assert_eq!(list.get_value().to_json_value(), json!([0, "a", 2]));
+        assert_eq!(movable_list.get_value().to_json_value(), json!([0, "a", 2]));
+
+        let mut delta = DeltaRope::new();
+        delta.push_retain(2, Default::default()).push_delete(2);
+        assert!(list.apply_delta(delta.clone(), &mut |_, _| {}).is_err());
+        assert!(movable_list
+            .apply_delta(delta, &mut Default::default())
+            .is_err());
+        assert_eq!(list.get_value().to_json_value(), json!([0, "a", 2]));
+        assert_eq!(movable_list.get_value().to_json_value(), json!([0, "a", 2]));
+    }
 }
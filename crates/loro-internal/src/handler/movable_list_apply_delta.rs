@@ -60,8 +60,9 @@ impl MovableListHandler {
         }
 
         match &self.inner {
-            MaybeDetached::Detached(_) => {
-                unimplemented!();
+            MaybeDetached::Detached(d) => {
+                let mut d = d.try_lock().unwrap();
+                apply_list_delta_for_detached(&mut d.value, &delta)
             }
             MaybeDetached::Attached(_) => {
                 // use tracing::debug;
@@ -3,8 +3,8 @@ use std::{collections::VecDeque, sync::Arc};
 use fractional_index::FractionalIndex;
 use fxhash::FxHashMap;
 use loro_common::{
-    ContainerID, ContainerType, Counter, IdLp, LoroError, LoroResult, LoroTreeError, LoroValue,
-    PeerID, TreeID,
+    ContainerID, ContainerType, Counter, IdFull, IdLp, LoroError, LoroResult, LoroTreeError,
+    LoroValue, PeerID, TreeID,
 };
 use smallvec::smallvec;
 
@@ -16,19 +16,35 @@ use crate::{
     BasicHandler, HandlerTrait, MapHandler,
 };
 
-use super::{create_handler, Handler, MaybeDetached};
+use super::{create_handler, Handler, MaybeDetached, ValueOrHandler};
 
 #[derive(Clone)]
 pub struct TreeHandler {
     pub(super) inner: MaybeDetached<TreeInner>,
 }
 
+/// A self-contained copy of a tree node and its descendants.
+///
+/// It's created by [TreeHandler::export_subtree] and can be imported into any tree, including
+/// the trees of other documents, by [TreeHandler::import_subtree].
+#[derive(Debug, Clone)]
+pub struct TreeSubtree {
+    /// The id of the node in the tree it's exported from
+    pub id: TreeID,
+    /// A detached copy of the meta of the node, including its child containers
+    pub meta: MapHandler,
+    /// The subtrees of the children of the node, in order
+    pub children: Vec<TreeSubtree>,
+}
+
 #[derive(Clone)]
 pub(super) struct TreeInner {
     next_counter: Counter,
     map: FxHashMap<TreeID, MapHandler>,
     parent_links: FxHashMap<TreeID, Option<TreeID>>,
     children_links: FxHashMap<Option<TreeID>, Vec<TreeID>>,
+    /// The jitter of the fractional index if it's enabled. It's applied to the tree when attached.
+    fractional_index_jitter: Option<u8>,
 }
 
 impl TreeInner {
@@ -38,25 +54,54 @@ impl TreeInner {
             map: FxHashMap::default(),
             parent_links: FxHashMap::default(),
             children_links: FxHashMap::default(),
+            fractional_index_jitter: None,
+        }
+    }
+
+    fn check_parent(&self, parent: Option<TreeID>) -> LoroResult<()> {
+        match parent {
+            Some(p) if !self.map.contains_key(&p) => {
+                Err(LoroTreeError::TreeNodeParentNotFound(p).into())
+            }
+            _ => Ok(()),
         }
     }
 
-    fn create(&mut self, parent: Option<TreeID>, index: usize) -> TreeID {
+    fn create(&mut self, parent: Option<TreeID>, index: usize) -> LoroResult<TreeID> {
+        self.check_parent(parent)?;
+        let len = self.children_num(parent).unwrap_or(0);
+        if index > len {
+            return Err(LoroTreeError::IndexOutOfBound { len, index }.into());
+        }
+
         let id = TreeID::new(PeerID::MAX, self.next_counter);
         self.next_counter += 1;
         self.map.insert(id, MapHandler::new_detached());
         self.parent_links.insert(id, parent);
         let children = self.children_links.entry(parent).or_default();
         children.insert(index, id);
-        id
+        Ok(id)
     }
 
     fn mov(&mut self, target: TreeID, new_parent: Option<TreeID>, index: usize) -> LoroResult<()> {
-        let old_parent = self
+        let old_parent = *self
             .parent_links
-            .get_mut(&target)
+            .get(&target)
             .ok_or(LoroTreeError::TreeNodeNotExist(target))?;
-        let children = self.children_links.get_mut(old_parent).unwrap();
+        self.check_parent(new_parent)?;
+        if self.is_ancestor_of(&target, new_parent) {
+            return Err(LoroTreeError::CyclicMoveError.into());
+        }
+
+        let mut len = self.children_num(new_parent).unwrap_or(0);
+        if old_parent == new_parent {
+            len -= 1;
+        }
+        if index > len {
+            return Err(LoroTreeError::IndexOutOfBound { len, index }.into());
+        }
+
+        let children = self.children_links.get_mut(&old_parent).unwrap();
         children.retain(|x| x != &target);
         self.parent_links.insert(target, new_parent);
         let children = self.children_links.entry(new_parent).or_default();
@@ -64,15 +109,36 @@ impl TreeInner {
         Ok(())
     }
 
+    /// Whether `target` is `node` or one of its ancestors
+    fn is_ancestor_of(&self, target: &TreeID, mut node: Option<TreeID>) -> bool {
+        while let Some(n) = node {
+            if &n == target {
+                return true;
+            }
+            node = self.parent_links.get(&n).copied().flatten();
+        }
+        false
+    }
+
     fn delete(&mut self, id: TreeID) -> LoroResult<()> {
-        self.map.remove(&id);
         let parent = self
             .parent_links
             .remove(&id)
             .ok_or(LoroTreeError::TreeNodeNotExist(id))?;
+        self.map.remove(&id);
         let children = self.children_links.get_mut(&parent).unwrap();
         children.retain(|x| x != &id);
-        self.children_links.remove(&Some(id));
+        // The descendants are deleted with the node
+        let mut q = vec![id];
+        while let Some(node) = q.pop() {
+            if let Some(children) = self.children_links.remove(&Some(node)) {
+                for child in children {
+                    self.map.remove(&child);
+                    self.parent_links.remove(&child);
+                    q.push(child);
+                }
+            }
+        }
         Ok(())
     }
 
@@ -105,42 +171,170 @@ impl TreeInner {
             .and_then(|children| children.iter().position(|x| x == target))
     }
 
-    fn get_value(&self, deep: bool) -> LoroValue {
+    fn ancestors(&self, target: &TreeID) -> Option<Vec<TreeID>> {
+        let mut ans = vec![];
+        let mut parent = *self.parent_links.get(target)?;
+        while let Some(p) = parent {
+            ans.push(p);
+            parent = self.parent_links.get(&p).copied().flatten();
+        }
+        Some(ans)
+    }
+
+    fn lowest_common_ancestor(&self, a: &TreeID, b: &TreeID) -> Option<TreeParentId> {
+        let a_path = self
+            .ancestors(a)?
+            .into_iter()
+            .rev()
+            .chain(std::iter::once(*a));
+        let b_path = self
+            .ancestors(b)?
+            .into_iter()
+            .rev()
+            .chain(std::iter::once(*b));
+        Some(
+            a_path
+                .zip(b_path)
+                .take_while(|(x, y)| x == y)
+                .last()
+                .map_or(TreeParentId::Root, |(x, _)| TreeParentId::Node(x)),
+        )
+    }
+
+    fn preorder_nodes_under(&self, parent: Option<TreeID>) -> Vec<TreeID> {
         let mut ans = vec![];
+        let mut stack = vec![];
+        if let Some(children) = self.children_links.get(&parent) {
+            stack.extend(children.iter().rev());
+        }
+        while let Some(node) = stack.pop() {
+            ans.push(node);
+            if let Some(children) = self.children_links.get(&Some(node)) {
+                stack.extend(children.iter().rev());
+            }
+        }
+        ans
+    }
 
-        let mut q = VecDeque::from_iter(
-            self.children_links
-                .get(&None)
-                .unwrap()
-                .iter()
-                .enumerate()
-                .zip(std::iter::repeat(None::<TreeID>)),
-        );
-        while let Some(((idx, target), parent)) = q.pop_front() {
-            let map = self.map.get(target).unwrap();
-            let mut loro_map_value = FxHashMap::default();
-            loro_map_value.insert("id".to_string(), target.to_string().into());
-            let parent = parent
-                .map(|p| p.to_string().into())
-                .unwrap_or(LoroValue::Null);
-            loro_map_value.insert("parent".to_string(), parent);
-            loro_map_value.insert(
-                "meta".to_string(),
-                if deep {
-                    map.get_deep_value()
-                } else {
-                    String::from("UnResolved").into()
-                },
-            );
-            loro_map_value.insert("index".to_string(), (idx as i64).into());
-            ans.push(loro_map_value);
-            if let Some(children) = self.children_links.get(&Some(*target)) {
-                for (idx, child) in children.iter().enumerate() {
-                    q.push_back(((idx, child), Some(*target)));
-                }
+    fn postorder_nodes_under(&self, parent: Option<TreeID>) -> Vec<TreeID> {
+        let mut ans = vec![];
+        let mut stack = vec![];
+        if let Some(children) = self.children_links.get(&parent) {
+            stack.extend(children.iter().rev().map(|&x| (x, false)));
+        }
+        while let Some((node, visited)) = stack.pop() {
+            if visited {
+                ans.push(node);
+                continue;
+            }
+            stack.push((node, true));
+            if let Some(children) = self.children_links.get(&Some(node)) {
+                stack.extend(children.iter().rev().map(|&x| (x, false)));
+            }
+        }
+        ans
+    }
+
+    fn get_node_by_index_path(&self, path: &[usize]) -> Option<TreeID> {
+        let mut ans = None;
+        for &index in path {
+            ans = Some(self.get_id_by_index(&ans, index)?);
+        }
+        ans
+    }
+
+    fn get_index_path(&self, target: &TreeID) -> Option<Vec<usize>> {
+        let ancestors = self.ancestors(target)?;
+        ancestors
+            .iter()
+            .rev()
+            .chain(std::iter::once(target))
+            .map(|node| self.get_index_by_tree_id(node))
+            .collect()
+    }
+
+    fn get_all_tree_nodes_under(&self, root: Option<TreeID>) -> Vec<TreeNode> {
+        let mut ans = vec![];
+        let mut q = self
+            .children_links
+            .get(&root)
+            .map(|c| VecDeque::from_iter(c.iter().enumerate().zip(std::iter::repeat(root))))
+            .unwrap_or_default();
+        while let Some(((index, &target), parent)) = q.pop_front() {
+            ans.push(TreeNode {
+                id: target,
+                parent: TreeParentId::from(parent),
+                fractional_index: FractionalIndex::default(),
+                index,
+                last_move_op: IdFull::NONE_ID,
+            });
+            if let Some(children) = self.children_links.get(&Some(target)) {
+                q.extend(
+                    children
+                        .iter()
+                        .enumerate()
+                        .zip(std::iter::repeat(Some(target))),
+                );
             }
         }
-        ans.into()
+        ans
+    }
+
+    fn get_all_hierarchy_nodes_under(&self, root: Option<TreeID>) -> Vec<TreeNodeWithChildren> {
+        let Some(children) = self.children_links.get(&root) else {
+            return vec![];
+        };
+        children
+            .iter()
+            .enumerate()
+            .map(|(index, &target)| TreeNodeWithChildren {
+                id: target,
+                parent: TreeParentId::from(root),
+                fractional_index: FractionalIndex::default(),
+                index,
+                children: self.get_all_hierarchy_nodes_under(Some(target)),
+            })
+            .collect()
+    }
+
+    /// The value has the same layout as the value of an attached tree,
+    /// except that `fractional_index` is null because the nodes don't have one until attached.
+    fn get_value(&self, deep: bool) -> LoroValue {
+        self.get_all_hierarchy_nodes_under(None)
+            .into_iter()
+            .map(|node| self.node_to_value(node, deep))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    fn node_to_value(&self, node: TreeNodeWithChildren, deep: bool) -> LoroValue {
+        let mut t = FxHashMap::default();
+        t.insert("id".to_string(), node.id.to_string().into());
+        let p = node
+            .parent
+            .tree_id()
+            .map(|p| p.to_string().into())
+            .unwrap_or(LoroValue::Null);
+        t.insert("parent".to_string(), p);
+        t.insert(
+            "meta".to_string(),
+            if deep {
+                self.map.get(&node.id).unwrap().get_deep_value()
+            } else {
+                String::from("UnResolved").into()
+            },
+        );
+        t.insert("index".to_string(), (node.index as i64).into());
+        t.insert("fractional_index".to_string(), LoroValue::Null);
+        t.insert(
+            "children".to_string(),
+            node.children
+                .into_iter()
+                .map(|x| self.node_to_value(x, deep))
+                .collect::<Vec<_>>()
+                .into(),
+        );
+        t.into()
     }
 }
 
@@ -160,6 +354,9 @@ impl HandlerTrait for TreeHandler {
                 let t = t.try_lock().unwrap();
                 let inner = create_handler(parent, self_id);
                 let tree = inner.into_tree().unwrap();
+                if let Some(jitter) = t.value.fractional_index_jitter {
+                    tree.enable_fractional_index(jitter);
+                }
 
                 let children = t.value.children_links.get(&None);
                 let mut q = children
@@ -323,7 +520,7 @@ impl TreeHandler {
         match &self.inner {
             MaybeDetached::Detached(t) => {
                 let t = &mut t.try_lock().unwrap().value;
-                Ok(t.create(parent.tree_id(), index))
+                t.create(parent.tree_id(), index)
             }
             MaybeDetached::Attached(a) => {
                 a.with_txn(|txn| self.create_with_txn(txn, parent, index))
@@ -719,10 +916,11 @@ impl TreeHandler {
         match &self.inner {
             MaybeDetached::Detached(t) => {
                 let t = t.try_lock().unwrap();
+                // The deleted nodes are removed from a detached tree
                 t.value
                     .map
                     .get(target)
-                    .and(Some(true))
+                    .and(Some(false))
                     .ok_or(LoroTreeError::TreeNodeNotExist(*target).into())
             }
             MaybeDetached::Attached(a) => a.with_state(|state| {
@@ -830,8 +1028,14 @@ impl TreeHandler {
 
     pub fn get_nodes_under(&self, parent: TreeParentId) -> Vec<TreeNode> {
         match &self.inner {
-            MaybeDetached::Detached(_t) => {
-                unreachable!()
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                match parent {
+                    TreeParentId::Root | TreeParentId::Node(_) => {
+                        t.value.get_all_tree_nodes_under(parent.tree_id())
+                    }
+                    TreeParentId::Deleted | TreeParentId::Unexist => vec![],
+                }
             }
             MaybeDetached::Attached(a) => a.with_state(|state| {
                 let a = state.as_tree_state().unwrap();
@@ -843,11 +1047,116 @@ impl TreeHandler {
         self.children(&TreeParentId::Root).unwrap_or_default()
     }
 
+    /// Export the node and its descendants, with their order and meta, as a [TreeSubtree].
+    pub fn export_subtree(&self, node: TreeID) -> LoroResult<TreeSubtree> {
+        if self.is_node_deleted(&node)? {
+            return Err(LoroTreeError::TreeNodeDeletedOrNotExist(node).into());
+        }
+
+        let meta = Handler::Map(self.get_meta(node)?)
+            .to_detached()?
+            .into_map()
+            .unwrap();
+        let children = self
+            .children(&TreeParentId::Node(node))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| self.export_subtree(child))
+            .collect::<LoroResult<Vec<_>>>()?;
+        Ok(TreeSubtree {
+            id: node,
+            meta,
+            children,
+        })
+    }
+
+    /// Recreate the exported subtree as the `index`-th child of `parent`.
+    ///
+    /// The nodes get new [TreeID]s. It returns the mapping from the ids in `data` to the new ids.
+    ///
+    /// It's atomic: if it fails, the tree is left without any node of the subtree.
+    pub fn import_subtree(
+        &self,
+        parent: TreeParentId,
+        index: usize,
+        data: &TreeSubtree,
+    ) -> LoroResult<FxHashMap<TreeID, TreeID>> {
+        // Check the parent and the index before anything is created
+        match parent {
+            TreeParentId::Deleted | TreeParentId::Unexist => {
+                return Err(LoroTreeError::InvalidParent.into());
+            }
+            TreeParentId::Node(p) => {
+                if self.is_node_deleted(&p)? {
+                    return Err(LoroTreeError::TreeNodeDeletedOrNotExist(p).into());
+                }
+            }
+            TreeParentId::Root => {}
+        }
+        let len = self.children_num(&parent).unwrap_or(0);
+        if index > len {
+            return Err(LoroTreeError::IndexOutOfBound { len, index }.into());
+        }
+
+        let root = self.create_at(parent, index)?;
+        let mut mapping = FxHashMap::default();
+        mapping.insert(data.id, root);
+        if let Err(e) = self.import_subtree_inner(root, data, &mut mapping) {
+            // Remove the partially imported subtree
+            self.delete(root)?;
+            return Err(e);
+        }
+
+        Ok(mapping)
+    }
+
+    /// Import the meta and the children of `data` into the created node `id`
+    fn import_subtree_inner(
+        &self,
+        id: TreeID,
+        data: &TreeSubtree,
+        mapping: &mut FxHashMap<TreeID, TreeID>,
+    ) -> LoroResult<()> {
+        let mut entries = Vec::new();
+        data.meta.for_each(|k, v| entries.push((k.to_string(), v)));
+        let meta = self.get_meta(id)?;
+        for (k, v) in entries {
+            match v {
+                ValueOrHandler::Value(v) => meta.insert(&k, v)?,
+                ValueOrHandler::Handler(h) => {
+                    // Insert a copy so that `data` can be imported again
+                    meta.insert_container(&k, h.to_detached()?)?;
+                }
+            }
+        }
+
+        for (i, child) in data.children.iter().enumerate() {
+            let child_id = self.create_at(TreeParentId::Node(id), i)?;
+            mapping.insert(child.id, child_id);
+            self.import_subtree_inner(child_id, child, mapping)?;
+        }
+        Ok(())
+    }
+
+    /// Create a detached deep copy of the tree. The nodes get new ids in the copy.
+    pub(super) fn to_detached(&self) -> LoroResult<Self> {
+        let ans = Self::new_detached();
+        for (i, root) in self.roots().into_iter().enumerate() {
+            ans.import_subtree(TreeParentId::Root, i, &self.export_subtree(root)?)?;
+        }
+        Ok(ans)
+    }
+
     pub fn get_all_hierarchy_nodes_under(&self, parent: TreeParentId) -> Vec<TreeNodeWithChildren> {
         match &self.inner {
-            MaybeDetached::Detached(_t) => {
-                // TODO: implement
-                unimplemented!()
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                match parent {
+                    TreeParentId::Root | TreeParentId::Node(_) => {
+                        t.value.get_all_hierarchy_nodes_under(parent.tree_id())
+                    }
+                    TreeParentId::Deleted | TreeParentId::Unexist => vec![],
+                }
             }
             MaybeDetached::Attached(a) => a.with_state(|state| {
                 let a = state.as_tree_state().unwrap();
@@ -856,6 +1165,125 @@ impl TreeHandler {
         }
     }
 
+    /// Get the ancestors of the node, from its parent to the root-level node.
+    ///
+    /// Return None if the node does not exist or is deleted.
+    pub fn ancestors(&self, target: &TreeID) -> Option<Vec<TreeID>> {
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                t.value.ancestors(target)
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                let a = state.as_tree_state().unwrap();
+                a.ancestors(target)
+            }),
+        }
+    }
+
+    /// Get the depth of the node. The root-level nodes have depth 0.
+    ///
+    /// Return None if the node does not exist or is deleted.
+    pub fn depth(&self, target: &TreeID) -> Option<usize> {
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                t.value.ancestors(target).map(|x| x.len())
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                let a = state.as_tree_state().unwrap();
+                a.depth(target)
+            }),
+        }
+    }
+
+    /// Get the lowest common ancestor of the two nodes. A node is regarded as an ancestor of itself.
+    ///
+    /// Return [TreeParentId::Root] if the nodes are under different root-level nodes,
+    /// or None if any of them does not exist or is deleted.
+    pub fn lowest_common_ancestor(&self, a: &TreeID, b: &TreeID) -> Option<TreeParentId> {
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                t.value.lowest_common_ancestor(a, b)
+            }
+            MaybeDetached::Attached(h) => h.with_state(|state| {
+                let s = state.as_tree_state().unwrap();
+                s.lowest_common_ancestor(a, b)
+            }),
+        }
+    }
+
+    /// Get all the descendants of `parent` in pre-order. `parent` itself is not included.
+    pub fn preorder_nodes_under(&self, parent: TreeParentId) -> Vec<TreeID> {
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                match parent {
+                    TreeParentId::Root | TreeParentId::Node(_) => {
+                        t.value.preorder_nodes_under(parent.tree_id())
+                    }
+                    TreeParentId::Deleted | TreeParentId::Unexist => vec![],
+                }
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                let a = state.as_tree_state().unwrap();
+                a.preorder_nodes_under(parent)
+            }),
+        }
+    }
+
+    /// Get all the descendants of `parent` in post-order. `parent` itself is not included.
+    pub fn postorder_nodes_under(&self, parent: TreeParentId) -> Vec<TreeID> {
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                match parent {
+                    TreeParentId::Root | TreeParentId::Node(_) => {
+                        t.value.postorder_nodes_under(parent.tree_id())
+                    }
+                    TreeParentId::Deleted | TreeParentId::Unexist => vec![],
+                }
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                let a = state.as_tree_state().unwrap();
+                a.postorder_nodes_under(parent)
+            }),
+        }
+    }
+
+    /// Get the node by its index path, e.g. `[2, 0]` is the first child of the third root-level node.
+    ///
+    /// Return None if the path is empty or out of bound.
+    pub fn get_node_by_index_path(&self, path: &[usize]) -> Option<TreeID> {
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                t.value.get_node_by_index_path(path)
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                let a = state.as_tree_state().unwrap();
+                a.get_node_by_index_path(path)
+            }),
+        }
+    }
+
+    /// Get the index path of the node. It's the inverse of [Self::get_node_by_index_path].
+    ///
+    /// Return None if the node does not exist or is deleted.
+    pub fn get_index_path(&self, target: &TreeID) -> Option<Vec<usize>> {
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                t.value.get_index_path(target)
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                let a = state.as_tree_state().unwrap();
+                a.get_index_path(target)
+            }),
+        }
+    }
+
     #[allow(non_snake_case)]
     pub fn __internal__next_tree_id(&self) -> TreeID {
         match &self.inner {
@@ -900,9 +1328,11 @@ impl TreeHandler {
         }
     }
 
+    /// The nodes of a detached tree don't have fractional indexes until they are attached,
+    /// so it returns `None` for them.
     pub fn get_position_by_tree_id(&self, target: &TreeID) -> Option<FractionalIndex> {
         match &self.inner {
-            MaybeDetached::Detached(_) => unreachable!(),
+            MaybeDetached::Detached(_) => None,
             MaybeDetached::Attached(a) => a.with_state(|state| {
                 let a = state.as_tree_state().unwrap();
                 a.get_position(target)
@@ -948,8 +1378,9 @@ impl TreeHandler {
 
     pub fn is_fractional_index_enabled(&self) -> bool {
         match &self.inner {
-            MaybeDetached::Detached(_) => {
-                unreachable!()
+            MaybeDetached::Detached(t) => {
+                let t = t.try_lock().unwrap();
+                t.value.fractional_index_jitter.is_some()
             }
             MaybeDetached::Attached(a) => a.with_state(|state| {
                 let a = state.as_tree_state().unwrap();
@@ -967,8 +1398,9 @@ impl TreeHandler {
     /// [Read more about it](https://www.loro.dev/blog/movable-tree#implementation-and-encoding-size)
     pub fn enable_fractional_index(&self, jitter: u8) {
         match &self.inner {
-            MaybeDetached::Detached(_) => {
-                unreachable!()
+            MaybeDetached::Detached(t) => {
+                let mut t = t.try_lock().unwrap();
+                t.value.fractional_index_jitter = Some(jitter);
             }
             MaybeDetached::Attached(a) => a.with_state(|state| {
                 let a = state.as_tree_state_mut().unwrap();
@@ -983,8 +1415,9 @@ impl TreeHandler {
     /// The LoroDoc is set to disable fractional index by default.
     pub fn disable_fractional_index(&self) {
         match &self.inner {
-            MaybeDetached::Detached(_) => {
-                unreachable!()
+            MaybeDetached::Detached(t) => {
+                let mut t = t.try_lock().unwrap();
+                t.value.fractional_index_jitter = None;
             }
             MaybeDetached::Attached(a) => a.with_state(|state| {
                 let a = state.as_tree_state_mut().unwrap();
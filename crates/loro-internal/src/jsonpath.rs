@@ -1,4 +1,5 @@
-use loro_common::{ContainerID, LoroValue};
+use loro_common::{ContainerID, LoroValue, TreeID};
+use regex::Regex;
 use thiserror::Error;
 use tracing::trace;
 
@@ -6,6 +7,8 @@ use crate::handler::{
     Handler, ListHandler, MapHandler, MovableListHandler, TextHandler, TreeHandler, ValueOrHandler,
 };
 use crate::loro::LoroDoc;
+use crate::state::TreeParentId;
+use std::cmp::Ordering;
 use std::ops::ControlFlow;
 
 #[derive(Error, Debug)]
@@ -33,7 +36,7 @@ enum JSONPathToken {
     UnionIndex(Vec<isize>),
     UnionKey(Vec<String>),
     Slice(Option<isize>, Option<isize>, Option<isize>),
-    Filter(Box<dyn for<'a> Fn(&'a ValueOrHandler) -> bool>),
+    Filter(Box<FilterExpr>),
 }
 
 use std::fmt;
@@ -51,7 +54,7 @@ impl fmt::Debug for JSONPathToken {
             }
             JSONPathToken::UnionIndex(indices) => write!(f, "UnionIndex({:?})", indices),
             JSONPathToken::UnionKey(keys) => write!(f, "UnionKey({:?})", keys),
-            JSONPathToken::Filter(_) => write!(f, "Filter(<function>)"),
+            JSONPathToken::Filter(expr) => write!(f, "Filter({:?})", expr),
         }
     }
 }
@@ -68,7 +71,7 @@ impl PartialEq for JSONPathToken {
                 a1 == b1 && a2 == b2 && a3 == b3
             }
             (JSONPathToken::Filter(_), JSONPathToken::Filter(_)) => {
-                // We can't compare functions for equality, so we'll consider all filters unequal
+                // The compiled regexes can't be compared, so we'll consider all filters unequal
                 false
             }
             _ => false,
@@ -107,14 +110,24 @@ fn parse_jsonpath(path: &str) -> Result<Vec<JSONPathToken>, JsonPathError> {
             }
             '[' => {
                 // Handle array index, slice, filter, or wildcard
+                // Filters may contain nested brackets and quoted strings, e.g. [?(@.tag in ['a', 'b'])]
                 let mut content = String::new();
-                let mut in_quotes = false;
+                let mut quote: Option<char> = None;
+                let mut escaped = false;
+                let mut depth = 0;
                 for &c in iter.by_ref() {
-                    if c == ']' && !in_quotes {
-                        break;
-                    }
-                    if c == '\'' {
-                        in_quotes = !in_quotes;
+                    match quote {
+                        Some(_) if escaped => escaped = false,
+                        Some(_) if c == '\\' => escaped = true,
+                        Some(q) if c == q => quote = None,
+                        Some(_) => {}
+                        None => match c {
+                            ']' if depth == 0 => break,
+                            '[' => depth += 1,
+                            ']' => depth -= 1,
+                            '\'' | '"' => quote = Some(c),
+                            _ => {}
+                        },
                     }
                     content.push(c);
                 }
@@ -123,19 +136,14 @@ fn parse_jsonpath(path: &str) -> Result<Vec<JSONPathToken>, JsonPathError> {
                     tokens.push(JSONPathToken::Wildcard);
                 } else if let Ok(index) = content.parse::<isize>() {
                     tokens.push(JSONPathToken::Index(index));
+                } else if let Some(filter) = content.strip_prefix('?') {
+                    tokens.push(JSONPathToken::Filter(Box::new(parse_filter(filter)?)));
                 } else if content.contains(':') {
                     let slice: Vec<&str> = content.split(':').collect();
                     let start = slice.first().and_then(|s| s.parse().ok());
                     let end = slice.get(1).and_then(|s| s.parse().ok());
                     let step = slice.get(2).and_then(|s| s.parse().ok()).unwrap_or(1);
                     tokens.push(JSONPathToken::Slice(start, end, Some(step as isize)));
-                } else if content.starts_with('?') {
-                    // let predicate = content[1..].to_string();
-                    tokens.push(JSONPathToken::Filter(Box::new(|_v| {
-                        // let result = evaluate_predicate(predicate, v);
-                        // result
-                        unimplemented!("JSONPath filter not implemented")
-                    })));
                 } else if content.starts_with('\'') && content.ends_with('\'') {
                     // Handle quoted keys
                     tokens.push(JSONPathToken::Child(
@@ -210,6 +218,498 @@ fn try_parse_union_index(content: &str) -> Option<Vec<isize>> {
     indices
 }
 
+/// A filter expression, e.g. the `(@.price < 10 && @.category == 'fiction')` in
+/// `$.store.book[?(@.price < 10 && @.category == 'fiction')]`.
+///
+/// It's evaluated against every child of the current value, with `@` referring to the child.
+/// The children that are containers are queried through their handlers, so
+/// the filter works on the live state without exporting the deep value of the whole doc.
+#[derive(Debug)]
+enum FilterExpr {
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+    /// `@.isbn` is true if the path exists. A literal is true if it's not `null` or `false`.
+    Exists(FilterOperand),
+    Compare(FilterOperand, CompareOp, FilterOperand),
+    /// `@.name =~ /^a.*/i`
+    Match(FilterOperand, Regex),
+}
+
+#[derive(Debug)]
+enum FilterOperand {
+    /// A path relative to the current value `@`. Only child keys and indexes are supported.
+    Current(Vec<JSONPathToken>),
+    /// A path relative to the root `$`. Only child keys and indexes are supported.
+    Root(Vec<JSONPathToken>),
+    Literal(LoroValue),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// The left value is an element of the right list, a key of the right map,
+    /// or a substring of the right string
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FilterToken {
+    Current,
+    Root,
+    Dot,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    Comma,
+    Not,
+    And,
+    Or,
+    Compare(CompareOp),
+    RegexMatch,
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Double(f64),
+    /// The pattern and the flags of `/pattern/flags`
+    Regex(String, String),
+}
+
+fn parse_filter(filter: &str) -> Result<FilterExpr, JsonPathError> {
+    let tokens = tokenize_filter(filter)?;
+    let mut parser = FilterParser {
+        source: filter,
+        tokens,
+        pos: 0,
+    };
+    let expr = parser.parse_or()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(parser.error("unexpected trailing tokens"));
+    }
+    Ok(expr)
+}
+
+fn tokenize_filter(filter: &str) -> Result<Vec<FilterToken>, JsonPathError> {
+    let invalid = |msg: &str| {
+        JsonPathError::InvalidJsonPath(format!("Invalid filter [?{}]: {}", filter, msg))
+    };
+    let chars = filter.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        i += 1;
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '@' => FilterToken::Current,
+            '$' => FilterToken::Root,
+            '.' => FilterToken::Dot,
+            '[' => FilterToken::LBracket,
+            ']' => FilterToken::RBracket,
+            '(' => FilterToken::LParen,
+            ')' => FilterToken::RParen,
+            ',' => FilterToken::Comma,
+            '&' if next == Some('&') => {
+                i += 1;
+                FilterToken::And
+            }
+            '|' if next == Some('|') => {
+                i += 1;
+                FilterToken::Or
+            }
+            '=' if next == Some('=') => {
+                i += 1;
+                FilterToken::Compare(CompareOp::Eq)
+            }
+            '=' if next == Some('~') => {
+                i += 1;
+                FilterToken::RegexMatch
+            }
+            '!' if next == Some('=') => {
+                i += 1;
+                FilterToken::Compare(CompareOp::Ne)
+            }
+            '!' => FilterToken::Not,
+            '<' if next == Some('=') => {
+                i += 1;
+                FilterToken::Compare(CompareOp::Le)
+            }
+            '<' => FilterToken::Compare(CompareOp::Lt),
+            '>' if next == Some('=') => {
+                i += 1;
+                FilterToken::Compare(CompareOp::Ge)
+            }
+            '>' => FilterToken::Compare(CompareOp::Gt),
+            '\'' | '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.get(i) {
+                        None => return Err(invalid("unterminated string")),
+                        Some('\\') => {
+                            let Some(&escaped) = chars.get(i + 1) else {
+                                return Err(invalid("unterminated string"));
+                            };
+                            s.push(escaped);
+                            i += 2;
+                        }
+                        Some(&q) if q == c => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&x) => {
+                            s.push(x);
+                            i += 1;
+                        }
+                    }
+                }
+                FilterToken::Str(s)
+            }
+            '/' if tokens.last() == Some(&FilterToken::RegexMatch) => {
+                let mut pattern = String::new();
+                loop {
+                    match chars.get(i) {
+                        None => return Err(invalid("unterminated regex")),
+                        // Keep the escape, it's handled by the regex engine
+                        Some('\\') if chars.get(i + 1) == Some(&'/') => {
+                            pattern.push('/');
+                            i += 2;
+                        }
+                        Some('/') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&x) => {
+                            pattern.push(x);
+                            i += 1;
+                        }
+                    }
+                }
+                let mut flags = String::new();
+                while let Some(&f) = chars.get(i) {
+                    if !f.is_ascii_alphabetic() {
+                        break;
+                    }
+                    flags.push(f);
+                    i += 1;
+                }
+                FilterToken::Regex(pattern, flags)
+            }
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let start = i - 1;
+                while i < chars.len()
+                    && (chars[i].is_ascii_digit()
+                        || matches!(chars[i], '.' | 'e' | 'E')
+                        || (matches!(chars[i], '+' | '-') && matches!(chars[i - 1], 'e' | 'E')))
+                {
+                    i += 1;
+                }
+                let s: String = chars[start..i].iter().collect();
+                if let Ok(n) = s.parse::<i64>() {
+                    FilterToken::Int(n)
+                } else if let Ok(n) = s.parse::<f64>() {
+                    FilterToken::Double(n)
+                } else {
+                    return Err(invalid(&format!("invalid number {}", s)));
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i - 1;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let ident: String = chars[start..i].iter().collect();
+                if ident == "in" {
+                    FilterToken::Compare(CompareOp::In)
+                } else {
+                    FilterToken::Ident(ident)
+                }
+            }
+            c => return Err(invalid(&format!("unexpected character '{}'", c))),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+struct FilterParser<'a> {
+    source: &'a str,
+    tokens: Vec<FilterToken>,
+    pos: usize,
+}
+
+impl FilterParser<'_> {
+    fn error(&self, msg: &str) -> JsonPathError {
+        JsonPathError::InvalidJsonPath(format!("Invalid filter [?{}]: {}", self.source, msg))
+    }
+
+    fn peek(&self) -> Option<&FilterToken> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<FilterToken> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, token: FilterToken) -> Result<(), JsonPathError> {
+        if self.next() != Some(token.clone()) {
+            return Err(self.error(&format!("expected {:?}", token)));
+        }
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, JsonPathError> {
+        let mut lhs = self.parse_and()?;
+        while self.peek() == Some(&FilterToken::Or) {
+            self.pos += 1;
+            let rhs = self.parse_and()?;
+            lhs = FilterExpr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, JsonPathError> {
+        let mut lhs = self.parse_unary()?;
+        while self.peek() == Some(&FilterToken::And) {
+            self.pos += 1;
+            let rhs = self.parse_unary()?;
+            lhs = FilterExpr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr, JsonPathError> {
+        match self.peek() {
+            Some(FilterToken::Not) => {
+                self.pos += 1;
+                Ok(FilterExpr::Not(Box::new(self.parse_unary()?)))
+            }
+            Some(FilterToken::LParen) => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                self.expect(FilterToken::RParen)?;
+                Ok(expr)
+            }
+            _ => self.parse_comparison(),
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<FilterExpr, JsonPathError> {
+        let lhs = self.parse_operand()?;
+        match self.peek() {
+            Some(&FilterToken::Compare(op)) => {
+                self.pos += 1;
+                let rhs = self.parse_operand()?;
+                Ok(FilterExpr::Compare(lhs, op, rhs))
+            }
+            Some(FilterToken::RegexMatch) => {
+                self.pos += 1;
+                let Some(FilterToken::Regex(pattern, flags)) = self.next() else {
+                    return Err(self.error("expected a regex like /pattern/flags after =~"));
+                };
+                let mut builder = regex::RegexBuilder::new(&pattern);
+                for flag in flags.chars() {
+                    match flag {
+                        'i' => builder.case_insensitive(true),
+                        'm' => builder.multi_line(true),
+                        's' => builder.dot_matches_new_line(true),
+                        'x' => builder.ignore_whitespace(true),
+                        _ => return Err(self.error(&format!("unknown regex flag '{}'", flag))),
+                    };
+                }
+                let regex = builder
+                    .build()
+                    .map_err(|e| self.error(&format!("invalid regex: {}", e)))?;
+                Ok(FilterExpr::Match(lhs, regex))
+            }
+            _ => Ok(FilterExpr::Exists(lhs)),
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<FilterOperand, JsonPathError> {
+        match self.peek() {
+            Some(FilterToken::Current) => {
+                self.pos += 1;
+                Ok(FilterOperand::Current(self.parse_path()?))
+            }
+            Some(FilterToken::Root) => {
+                self.pos += 1;
+                Ok(FilterOperand::Root(self.parse_path()?))
+            }
+            _ => Ok(FilterOperand::Literal(self.parse_literal()?)),
+        }
+    }
+
+    fn parse_path(&mut self) -> Result<Vec<JSONPathToken>, JsonPathError> {
+        let mut path = Vec::new();
+        loop {
+            match self.peek() {
+                Some(FilterToken::Dot) => {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(FilterToken::Ident(key)) => path.push(JSONPathToken::Child(key)),
+                        // e.g. `@.in` or `@.2024`
+                        Some(FilterToken::Compare(CompareOp::In)) => {
+                            path.push(JSONPathToken::Child("in".to_string()))
+                        }
+                        Some(FilterToken::Int(i)) => path.push(JSONPathToken::Child(i.to_string())),
+                        _ => return Err(self.error("expected a key after '.'")),
+                    }
+                }
+                Some(FilterToken::LBracket) => {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(FilterToken::Str(key)) => path.push(JSONPathToken::Child(key)),
+                        Some(FilterToken::Int(i)) => path.push(JSONPathToken::Index(i as isize)),
+                        _ => return Err(self.error("expected a quoted key or an index in []")),
+                    }
+                    self.expect(FilterToken::RBracket)?;
+                }
+                _ => return Ok(path),
+            }
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<LoroValue, JsonPathError> {
+        match self.next() {
+            Some(FilterToken::Str(s)) => Ok(LoroValue::from(s)),
+            Some(FilterToken::Int(i)) => Ok(LoroValue::I64(i)),
+            Some(FilterToken::Double(d)) => Ok(LoroValue::Double(d)),
+            Some(FilterToken::Ident(ident)) => match ident.as_str() {
+                "true" => Ok(LoroValue::Bool(true)),
+                "false" => Ok(LoroValue::Bool(false)),
+                "null" => Ok(LoroValue::Null),
+                _ => Err(self.error(&format!("unknown identifier {}", ident))),
+            },
+            Some(FilterToken::LBracket) => {
+                let mut list = Vec::new();
+                if self.peek() == Some(&FilterToken::RBracket) {
+                    self.pos += 1;
+                    return Ok(LoroValue::from(list));
+                }
+                loop {
+                    list.push(self.parse_literal()?);
+                    match self.next() {
+                        Some(FilterToken::Comma) => {}
+                        Some(FilterToken::RBracket) => return Ok(LoroValue::from(list)),
+                        _ => return Err(self.error("expected ',' or ']' in the list")),
+                    }
+                }
+            }
+            _ => Err(self.error("expected an operand")),
+        }
+    }
+}
+
+impl FilterExpr {
+    fn eval(&self, current: &ValueOrHandler, root: &dyn PathValue) -> bool {
+        match self {
+            FilterExpr::Or(a, b) => a.eval(current, root) || b.eval(current, root),
+            FilterExpr::And(a, b) => a.eval(current, root) && b.eval(current, root),
+            FilterExpr::Not(a) => !a.eval(current, root),
+            FilterExpr::Exists(operand) => match operand {
+                FilterOperand::Literal(v) => !matches!(v, LoroValue::Null | LoroValue::Bool(false)),
+                _ => operand.resolve(current, root).is_some(),
+            },
+            FilterExpr::Compare(lhs, op, rhs) => {
+                match (lhs.value(current, root), rhs.value(current, root)) {
+                    (Some(lhs), Some(rhs)) => compare_values(&lhs, *op, &rhs),
+                    // A missing value is only unequal to everything
+                    _ => *op == CompareOp::Ne,
+                }
+            }
+            FilterExpr::Match(lhs, regex) => match lhs.value(current, root) {
+                Some(LoroValue::String(s)) => regex.is_match(&s),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl FilterOperand {
+    fn resolve(&self, current: &ValueOrHandler, root: &dyn PathValue) -> Option<ValueOrHandler> {
+        match self {
+            FilterOperand::Current(path) => resolve_simple_path(current, path),
+            FilterOperand::Root(path) => resolve_simple_path(root, path),
+            FilterOperand::Literal(v) => Some(ValueOrHandler::Value(v.clone())),
+        }
+    }
+
+    fn value(&self, current: &ValueOrHandler, root: &dyn PathValue) -> Option<LoroValue> {
+        match self {
+            FilterOperand::Literal(v) => Some(v.clone()),
+            _ => self.resolve(current, root).map(|v| v.to_deep_value()),
+        }
+    }
+}
+
+fn resolve_simple_path(value: &dyn PathValue, path: &[JSONPathToken]) -> Option<ValueOrHandler> {
+    let child = match path.first() {
+        None => return value.clone_this().ok(),
+        Some(JSONPathToken::Child(key)) => value.get_by_key(key)?,
+        Some(JSONPathToken::Index(index)) => value.get_by_index(*index)?,
+        Some(_) => unreachable!("filter paths only contain keys and indexes"),
+    };
+    if path.len() == 1 {
+        Some(child)
+    } else {
+        resolve_simple_path(&child, &path[1..])
+    }
+}
+
+fn compare_values(lhs: &LoroValue, op: CompareOp, rhs: &LoroValue) -> bool {
+    match op {
+        CompareOp::Eq => loose_eq(lhs, rhs),
+        CompareOp::Ne => !loose_eq(lhs, rhs),
+        CompareOp::Lt => loose_cmp(lhs, rhs) == Some(Ordering::Less),
+        CompareOp::Le => matches!(loose_cmp(lhs, rhs), Some(Ordering::Less | Ordering::Equal)),
+        CompareOp::Gt => loose_cmp(lhs, rhs) == Some(Ordering::Greater),
+        CompareOp::Ge => matches!(
+            loose_cmp(lhs, rhs),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        CompareOp::In => match (lhs, rhs) {
+            (_, LoroValue::List(list)) => list.iter().any(|v| loose_eq(lhs, v)),
+            (LoroValue::String(key), LoroValue::Map(map)) => map.contains_key(key.as_str()),
+            (LoroValue::String(sub), LoroValue::String(s)) => s.contains(sub.as_str()),
+            _ => false,
+        },
+    }
+}
+
+fn as_f64(v: &LoroValue) -> Option<f64> {
+    match v {
+        LoroValue::I64(i) => Some(*i as f64),
+        LoroValue::Double(d) => Some(*d),
+        _ => None,
+    }
+}
+
+/// Like `==`, but integers and doubles are compared by their numeric values
+fn loose_eq(lhs: &LoroValue, rhs: &LoroValue) -> bool {
+    match (as_f64(lhs), as_f64(rhs)) {
+        (Some(a), Some(b)) => a == b,
+        _ => lhs == rhs,
+    }
+}
+
+/// Only numbers and strings are ordered
+fn loose_cmp(lhs: &LoroValue, rhs: &LoroValue) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (LoroValue::String(a), LoroValue::String(b)) => Some(a.as_str().cmp(b.as_str())),
+        _ => as_f64(lhs)?.partial_cmp(&as_f64(rhs)?),
+    }
+}
+
 // Evaluate JSONPath against a LoroDoc
 pub fn evaluate_jsonpath(
     doc: &dyn PathValue,
@@ -221,7 +721,7 @@ pub fn evaluate_jsonpath(
 
     // Start with the root
     if let Some(JSONPathToken::Root) = tokens.first() {
-        evaluate_tokens(doc, &tokens[1..], &mut results);
+        evaluate_tokens(doc, &tokens[1..], doc, &mut results);
     } else {
         return Err(JsonPathError::InvalidJsonPath(
             "JSONPath must start with $".to_string(),
@@ -234,6 +734,7 @@ pub fn evaluate_jsonpath(
 fn evaluate_tokens(
     value: &dyn PathValue,
     tokens: &[JSONPathToken],
+    root: &dyn PathValue,
     results: &mut Vec<ValueOrHandler>,
 ) {
     if tokens.is_empty() {
@@ -244,39 +745,39 @@ fn evaluate_tokens(
     match &tokens[0] {
         JSONPathToken::Child(key) => {
             if let Some(child) = value.get_by_key(key) {
-                evaluate_tokens(&child, &tokens[1..], results);
+                evaluate_tokens(&child, &tokens[1..], root, results);
             }
         }
         JSONPathToken::RecursiveDescend => {
             // Implement recursive descent
             value.for_each_for_path(&mut |child| {
-                evaluate_tokens(&child, tokens, results);
+                evaluate_tokens(&child, tokens, root, results);
                 ControlFlow::Continue(())
             });
-            evaluate_tokens(value, &tokens[1..], results);
+            evaluate_tokens(value, &tokens[1..], root, results);
         }
         JSONPathToken::Wildcard => {
             value.for_each_for_path(&mut |child| {
-                evaluate_tokens(&child, &tokens[1..], results);
+                evaluate_tokens(&child, &tokens[1..], root, results);
                 ControlFlow::Continue(())
             });
         }
         JSONPathToken::Index(index) => {
             if let Some(child) = value.get_by_index(*index) {
-                evaluate_tokens(&child, &tokens[1..], results);
+                evaluate_tokens(&child, &tokens[1..], root, results);
             }
         }
         JSONPathToken::UnionIndex(indices) => {
             for index in indices {
                 if let Some(child) = value.get_by_index(*index) {
-                    evaluate_tokens(&child, &tokens[1..], results);
+                    evaluate_tokens(&child, &tokens[1..], root, results);
                 }
             }
         }
         JSONPathToken::UnionKey(keys) => {
             for key in keys {
                 if let Some(child) = value.get_by_key(key) {
-                    evaluate_tokens(&child, &tokens[1..], results);
+                    evaluate_tokens(&child, &tokens[1..], root, results);
                 }
             }
         }
@@ -300,22 +801,21 @@ fn evaluate_tokens(
             if step > 0 {
                 for i in (start..end).step_by(step as usize) {
                     if let Some(child) = value.get_by_index(i) {
-                        evaluate_tokens(&child, &tokens[1..], results);
+                        evaluate_tokens(&child, &tokens[1..], root, results);
                     }
                 }
             } else {
                 for i in (start..end).rev().step_by((-step) as usize) {
                     if let Some(child) = value.get_by_index(i) {
-                        evaluate_tokens(&child, &tokens[1..], results);
+                        evaluate_tokens(&child, &tokens[1..], root, results);
                     }
                 }
             }
         }
-        JSONPathToken::Filter(predicate) => {
-            // Implement filter logic
+        JSONPathToken::Filter(filter) => {
             value.for_each_for_path(&mut |child| {
-                if predicate(&child) {
-                    evaluate_tokens(&child, &tokens[1..], results);
+                if filter.eval(&child, root) {
+                    evaluate_tokens(&child, &tokens[1..], root, results);
                 }
                 ControlFlow::Continue(())
             });
@@ -612,25 +1112,48 @@ impl PathValue for TextHandler {
     }
 }
 
+/// The children of a tree are the meta maps of its alive nodes, keyed by the node ids.
+/// So `$.tasks[?(@.status == 'done')]` returns the meta maps of the done tasks.
 impl PathValue for TreeHandler {
-    fn get_by_key(&self, _key: &str) -> Option<ValueOrHandler> {
-        None
+    fn get_by_key(&self, key: &str) -> Option<ValueOrHandler> {
+        let target = TreeID::try_from(key).ok()?;
+        alive_node_meta(self, target).map(|meta| ValueOrHandler::Handler(Handler::Map(meta)))
     }
 
     fn get_by_index(&self, _index: isize) -> Option<ValueOrHandler> {
         None
     }
 
-    fn for_each_for_path(&self, _f: &mut dyn FnMut(ValueOrHandler) -> ControlFlow<()>) {
-        unimplemented!()
+    fn for_each_for_path(&self, f: &mut dyn FnMut(ValueOrHandler) -> ControlFlow<()>) {
+        // Depth-first, in the order of the siblings
+        let mut stack = self.roots();
+        stack.reverse();
+        while let Some(node) = stack.pop() {
+            if let Ok(meta) = self.get_meta(node) {
+                if let ControlFlow::Break(_) = f(ValueOrHandler::Handler(Handler::Map(meta))) {
+                    break;
+                }
+            }
+            if let Some(children) = self.children(&TreeParentId::Node(node)) {
+                stack.extend(children.into_iter().rev());
+            }
+        }
     }
 
     fn length_for_path(&self) -> usize {
-        unimplemented!()
+        let mut len = 0;
+        self.for_each_for_path(&mut |_| {
+            len += 1;
+            ControlFlow::Continue(())
+        });
+        len
     }
 
-    fn get_child_by_id(&self, _id: ContainerID) -> Option<Handler> {
-        unimplemented!()
+    fn get_child_by_id(&self, id: ContainerID) -> Option<Handler> {
+        let ContainerID::Normal { peer, counter, .. } = id else {
+            return None;
+        };
+        alive_node_meta(self, TreeID::new(peer, counter)).map(Handler::Map)
     }
 
     fn clone_this(&self) -> Result<ValueOrHandler, JsonPathError> {
@@ -638,6 +1161,13 @@ impl PathValue for TreeHandler {
     }
 }
 
+fn alive_node_meta(tree: &TreeHandler, target: TreeID) -> Option<MapHandler> {
+    if !tree.contains(target) || tree.is_node_deleted(&target).ok()? {
+        return None;
+    }
+    tree.get_meta(target).ok()
+}
+
 impl PathValue for LoroValue {
     fn get_by_key(&self, key: &str) -> Option<ValueOrHandler> {
         match self {
@@ -724,6 +1254,52 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_filter() -> Result<(), JsonPathError> {
+        let tokens = parse_jsonpath("$.books[?(@.tags[0] == 'a' || @['price'] >= -1.5e1)]")?;
+        assert_eq!(tokens.len(), 3);
+        let JSONPathToken::Filter(filter) = &tokens[2] else {
+            panic!("expected a filter");
+        };
+        let FilterExpr::Or(lhs, rhs) = &**filter else {
+            panic!("expected ||");
+        };
+        assert!(matches!(
+            &**lhs,
+            FilterExpr::Compare(FilterOperand::Current(path), CompareOp::Eq, FilterOperand::Literal(_))
+                if path == &[JSONPathToken::Child("tags".into()), JSONPathToken::Index(0)]
+        ));
+        assert!(matches!(
+            &**rhs,
+            FilterExpr::Compare(_, CompareOp::Ge, FilterOperand::Literal(LoroValue::Double(d)))
+                if *d == -15.
+        ));
+        assert!(parse_jsonpath("$[?(@.a === 1)]").is_err());
+        assert!(parse_jsonpath("$[?(@.a =~ /a/q)]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_on_plain_value() -> Result<(), JsonPathError> {
+        let value = LoroValue::from(vec![
+            LoroValue::from(fxhash::FxHashMap::from_iter([
+                ("n".to_string(), LoroValue::I64(1)),
+                ("s".to_string(), LoroValue::from("a:b")),
+            ])),
+            LoroValue::from(fxhash::FxHashMap::from_iter([(
+                "n".to_string(),
+                LoroValue::Double(2.0),
+            )])),
+        ]);
+        let result = evaluate_jsonpath(&value, "$[?(@.n == 2)].n")?;
+        assert_eq!(result.len(), 1);
+        let result = evaluate_jsonpath(&value, "$[?(@.s == 'a:b' || @.n > $[0].n)]")?;
+        assert_eq!(result.len(), 2);
+        let result = evaluate_jsonpath(&value, "$[?('b' in @.s)]")?;
+        assert_eq!(result.len(), 1);
+        Ok(())
+    }
+
     #[test]
     fn test_evaluate_jsonpath() -> Result<(), JsonPathError> {
         let doc = LoroDoc::new();
@@ -1,6 +1,7 @@
 use bytes::Bytes;
 pub use loro_kv_store::compress::CompressionType;
-pub use loro_kv_store::MemKvStore;
+use loro_kv_store::mem_store::{MemKvConfig, MemStoreIterator};
+pub use loro_kv_store::{FileKvStore, MemKvStore};
 use std::{
     collections::BTreeMap,
     ops::Bound,
@@ -98,6 +99,318 @@ impl KvStore for MemKvStore {
     }
 }
 
+impl KvStore for FileKvStore {
+    fn get(&self, key: &[u8]) -> Option<Bytes> {
+        self.get(key)
+    }
+
+    fn set(&mut self, key: &[u8], value: Bytes) {
+        self.set(key, value)
+    }
+
+    fn compare_and_swap(&mut self, key: &[u8], old: Option<Bytes>, new: Bytes) -> bool {
+        self.compare_and_swap(key, old, new)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<Bytes> {
+        let ans = self.get(key);
+        self.remove(key);
+        ans
+    }
+
+    fn contains_key(&self, key: &[u8]) -> bool {
+        self.contains_key(key)
+    }
+
+    fn scan(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Box<dyn DoubleEndedIterator<Item = (Bytes, Bytes)> + '_> {
+        self.scan(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn size(&self) -> usize {
+        self.size()
+    }
+
+    fn export_all(&mut self) -> Bytes {
+        self.export_all()
+    }
+
+    fn import_all(&mut self, bytes: Bytes) -> Result<(), String> {
+        self.import_all(bytes)
+    }
+
+    /// The files cannot be shared, so the clone is an in-memory store with the same content.
+    fn clone_store(&self) -> Arc<Mutex<dyn KvStore>> {
+        Arc::new(Mutex::new(self.to_mem_store()))
+    }
+}
+
+/// A view of the entries under a one-byte key prefix in a shared [KvStore].
+///
+/// The mutations are buffered in memory until [PrefixedKvStore::persist] is called,
+/// so the shared store only sees the result of a complete save.
+#[derive(Debug)]
+pub(crate) struct PrefixedKvStore {
+    store: Arc<Mutex<dyn KvStore>>,
+    prefix: u8,
+    /// The mutations that are not persisted yet. An empty value means the key is removed.
+    dirty: BTreeMap<Bytes, Bytes>,
+    /// Whether all the entries in the shared store should be removed on persist
+    cleared: bool,
+}
+
+impl PrefixedKvStore {
+    pub fn new(store: Arc<Mutex<dyn KvStore>>, prefix: u8) -> Self {
+        Self {
+            store,
+            prefix,
+            dirty: BTreeMap::new(),
+            cleared: false,
+        }
+    }
+
+    /// Remove all the entries in this view
+    pub fn clear(&mut self) {
+        self.dirty.clear();
+        self.cleared = true;
+    }
+
+    /// Write the buffered mutations into the shared store. The unchanged entries are skipped.
+    pub fn persist(&mut self) {
+        let mut store = self.store.lock().unwrap();
+        if self.cleared {
+            let (start, end) = ([self.prefix], [self.prefix + 1]);
+            let keys: Vec<Bytes> = store
+                .scan(Bound::Included(&start[..]), Bound::Excluded(&end[..]))
+                .map(|(k, _)| k)
+                .collect();
+            for k in keys {
+                store.remove(&k);
+            }
+            self.cleared = false;
+        }
+
+        for (k, v) in std::mem::take(&mut self.dirty) {
+            let key = prefixed_key(self.prefix, &k);
+            if v.is_empty() {
+                store.remove(&key);
+            } else if store.get(&key).as_ref() != Some(&v) {
+                store.set(&key, v);
+            }
+        }
+    }
+
+    fn to_mem_store(&self) -> MemKvStore {
+        let mut mem = MemKvStore::new(MemKvConfig::default());
+        for (k, v) in self.scan(Bound::Unbounded, Bound::Unbounded) {
+            mem.set(&k, v);
+        }
+        mem
+    }
+}
+
+fn prefixed_key(prefix: u8, key: &[u8]) -> Bytes {
+    let mut ans = Vec::with_capacity(key.len() + 1);
+    ans.push(prefix);
+    ans.extend_from_slice(key);
+    ans.into()
+}
+
+fn is_empty_range(start: Bound<&[u8]>, end: Bound<&[u8]>) -> bool {
+    match (start, end) {
+        (Bound::Included(s), Bound::Included(e)) => s > e,
+        (Bound::Included(s), Bound::Excluded(e))
+        | (Bound::Excluded(s), Bound::Included(e))
+        | (Bound::Excluded(s), Bound::Excluded(e)) => s >= e,
+        _ => false,
+    }
+}
+
+fn as_slice_bound(bound: &Bound<Bytes>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(k) => Bound::Included(k),
+        Bound::Excluded(k) => Bound::Excluded(k),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// Iterate the entries of a [PrefixedKvStore] in the shared store.
+///
+/// The lock of the shared store is only held within each step,
+/// so the entries are not collected in advance.
+struct SharedStoreIter {
+    store: Arc<Mutex<dyn KvStore>>,
+    start: Bound<Bytes>,
+    end: Bound<Bytes>,
+}
+
+impl SharedStoreIter {
+    fn new(view: &PrefixedKvStore, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Self {
+        let prefix = view.prefix;
+        let start = match start {
+            Bound::Included(k) => Bound::Included(prefixed_key(prefix, k)),
+            Bound::Excluded(k) => Bound::Excluded(prefixed_key(prefix, k)),
+            Bound::Unbounded => Bound::Included(Bytes::from(vec![prefix])),
+        };
+        let end = match end {
+            Bound::Included(k) => Bound::Included(prefixed_key(prefix, k)),
+            Bound::Excluded(k) => Bound::Excluded(prefixed_key(prefix, k)),
+            Bound::Unbounded => Bound::Excluded(Bytes::from(vec![prefix + 1])),
+        };
+        Self {
+            store: view.store.clone(),
+            start,
+            end,
+        }
+    }
+
+    fn step(&mut self, from_back: bool) -> Option<(Bytes, Bytes)> {
+        let (start, end) = (as_slice_bound(&self.start), as_slice_bound(&self.end));
+        if is_empty_range(start, end) {
+            return None;
+        }
+
+        let store = self.store.lock().unwrap();
+        let mut iter = store.scan(start, end);
+        let (k, v) = if from_back {
+            iter.next_back()
+        } else {
+            iter.next()
+        }?;
+        drop(iter);
+        drop(store);
+        if from_back {
+            self.end = Bound::Excluded(k.clone());
+        } else {
+            self.start = Bound::Excluded(k.clone());
+        }
+
+        Some((k.slice(1..), v))
+    }
+}
+
+impl Iterator for SharedStoreIter {
+    type Item = (Bytes, Bytes);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step(false)
+    }
+}
+
+impl DoubleEndedIterator for SharedStoreIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.step(true)
+    }
+}
+
+impl KvStore for PrefixedKvStore {
+    fn get(&self, key: &[u8]) -> Option<Bytes> {
+        if let Some(v) = self.dirty.get(key) {
+            return if v.is_empty() { None } else { Some(v.clone()) };
+        }
+
+        if self.cleared {
+            return None;
+        }
+
+        self.store
+            .lock()
+            .unwrap()
+            .get(&prefixed_key(self.prefix, key))
+    }
+
+    fn set(&mut self, key: &[u8], value: Bytes) {
+        self.dirty.insert(Bytes::copy_from_slice(key), value);
+    }
+
+    fn compare_and_swap(&mut self, key: &[u8], old: Option<Bytes>, new: Bytes) -> bool {
+        if self.get(key) != old {
+            return false;
+        }
+
+        self.set(key, new);
+        true
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<Bytes> {
+        let ans = self.get(key);
+        self.set(key, Bytes::new());
+        ans
+    }
+
+    fn contains_key(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+
+    fn scan(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Box<dyn DoubleEndedIterator<Item = (Bytes, Bytes)> + '_> {
+        let dirty: Box<dyn DoubleEndedIterator<Item = (Bytes, Bytes)> + '_> =
+            if is_empty_range(start, end) {
+                Box::new(std::iter::empty())
+            } else {
+                Box::new(
+                    self.dirty
+                        .range::<[u8], _>((start, end))
+                        .map(|(k, v)| (k.clone(), v.clone())),
+                )
+            };
+        let shared: Box<dyn DoubleEndedIterator<Item = (Bytes, Bytes)>> = if self.cleared {
+            Box::new(std::iter::empty())
+        } else {
+            Box::new(SharedStoreIter::new(self, start, end))
+        };
+        Box::new(MemStoreIterator::new(dirty, shared, true))
+    }
+
+    fn len(&self) -> usize {
+        self.scan(Bound::Unbounded, Bound::Unbounded).count()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.scan(Bound::Unbounded, Bound::Unbounded)
+            .next()
+            .is_none()
+    }
+
+    fn size(&self) -> usize {
+        self.scan(Bound::Unbounded, Bound::Unbounded)
+            .fold(0, |acc, (k, v)| acc + k.len() + v.len())
+    }
+
+    fn export_all(&mut self) -> Bytes {
+        self.to_mem_store().export_all()
+    }
+
+    fn import_all(&mut self, bytes: Bytes) -> Result<(), String> {
+        let mut mem = MemKvStore::new(MemKvConfig::default());
+        mem.import_all(bytes)?;
+        for (k, v) in mem.scan(Bound::Unbounded, Bound::Unbounded) {
+            self.set(&k, v);
+        }
+
+        Ok(())
+    }
+
+    /// The clone is an in-memory store with the same content, it's detached from the shared store.
+    fn clone_store(&self) -> Arc<Mutex<dyn KvStore>> {
+        Arc::new(Mutex::new(self.to_mem_store()))
+    }
+}
+
 mod default_binary_format {
     //! Default binary format for the key-value store.
     //!
@@ -29,6 +29,7 @@ pub use handler::{
 pub use loro_common;
 pub use oplog::OpLog;
 pub use state::DocState;
+pub use state::{DocDeserializer, StateDeserializer};
 pub use state::{TreeNode, TreeNodeWithChildren, TreeParentId};
 use subscription::{LocalUpdateCallback, Observer, PeerIdUpdateCallback};
 use txn::Transaction;
@@ -51,7 +52,10 @@ pub mod kv_store;
 pub mod loro;
 pub mod op;
 pub mod oplog;
+pub mod schema;
 pub mod subscription;
+pub mod sync;
+pub mod tree_constraint;
 pub mod txn;
 pub mod version;
 
@@ -122,4 +126,6 @@ pub struct LoroDoc {
     detached: AtomicBool,
     local_update_subs: SubscriberSetWithQueue<(), LocalUpdateCallback, Vec<u8>>,
     peer_id_change_subs: SubscriberSetWithQueue<(), PeerIdUpdateCallback, ID>,
+    /// The kv store the doc is opened on by [LoroDoc::from_kv_store]
+    kv_store: Option<encoding::fast_snapshot::DocKvStore>,
 }
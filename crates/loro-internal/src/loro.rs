@@ -1,3 +1,4 @@
+use bytes::Bytes;
 use either::Either;
 use fxhash::{FxHashMap, FxHashSet};
 use itertools::Itertools;
@@ -29,7 +30,10 @@ use crate::{
         idx::ContainerIdx, list::list_op::InnerListOp, richtext::config::StyleConfigMap,
         IntoContainerId,
     },
-    cursor::{AbsolutePosition, CannotFindRelativePosition, Cursor, PosQueryResult},
+    cursor::{
+        AbsolutePosition, CannotFindRelativePosition, Cursor, CursorRange, PosQueryResult,
+        RangeQueryResult,
+    },
     dag::Dag,
     diff_calc::DiffCalculator,
     encoding::{
@@ -41,19 +45,21 @@ use crate::{
     event::{str_to_path, EventTriggerKind, Index, InternalDocDiff},
     handler::{Handler, MovableListHandler, TextHandler, TreeHandler, ValueOrHandler},
     id::PeerID,
+    kv_store::KvStore,
     op::InnerContent,
     oplog::{loro_dag::FrontiersNotIncluded, OpLog},
-    state::DocState,
+    state::{DocDeserializer, DocState},
     subscription::{LocalUpdateCallback, Observer, Subscriber},
+    tree_constraint::{self, TreeChanges},
     txn::Transaction,
-    undo::DiffBatch,
+    undo::{DiffBatch, UndoScope},
     utils::subscription::{SubscriberSetWithQueue, Subscription},
     version::{shrink_frontiers, Frontiers, ImVersionVector},
     ChangeMeta, DocDiff, HandlerTrait, InternalString, ListHandler, LoroError, MapHandler,
     VersionVector,
 };
 
-pub use crate::encoding::ExportMode;
+pub use crate::encoding::{ExportMode, HistoryRetention};
 pub use crate::state::analyzer::{ContainerAnalysisInfo, DocAnalysis};
 pub(crate) use crate::LoroDoc;
 
@@ -93,6 +99,7 @@ impl LoroDoc {
             arena,
             local_update_subs: SubscriberSetWithQueue::new(),
             peer_id_change_subs: SubscriberSetWithQueue::new(),
+            kv_store: None,
         }
     }
 
@@ -197,6 +204,71 @@ impl LoroDoc {
         }
     }
 
+    /// Create a doc from a snapshot and the incremental snapshots exported after it.
+    ///
+    /// The incremental snapshots are layered onto the base snapshot in the given order.
+    /// Each of them should start from a version included by the previous layer, and
+    /// end at a version that includes it.
+    pub fn from_incremental_snapshots(base: &[u8], layers: &[&[u8]]) -> LoroResult<Self> {
+        let ParsedHeaderAndBody { mode, body, .. } = parse_header_and_body(base)?;
+        if mode != EncodeMode::FastSnapshot {
+            return Err(LoroError::DecodeError(
+                "Invalid encode mode".to_string().into(),
+            ));
+        }
+
+        let base = Bytes::copy_from_slice(body);
+        let layers = layers
+            .iter()
+            .map(|layer| {
+                let parsed = parse_header_and_body(layer)?;
+                if parsed.mode != EncodeMode::FastIncrementalSnapshot {
+                    return Err(LoroError::DecodeError(
+                        "Invalid encode mode".to_string().into(),
+                    ));
+                }
+
+                Ok(Bytes::copy_from_slice(parsed.body))
+            })
+            .collect::<LoroResult<Vec<_>>>()?;
+        let doc = Self::new();
+        encoding::fast_snapshot::decode_incremental_snapshots(&doc, base, layers)?;
+        Ok(doc)
+    }
+
+    /// Open a doc on the kv store, e.g. a [crate::kv_store::FileKvStore].
+    ///
+    /// The history and the container states are read from the store when they are accessed,
+    /// and [LoroDoc::save_to_kv_store] writes the modified entries back.
+    /// An empty doc is returned if nothing has been saved in the store.
+    ///
+    /// Only one doc can be opened on a store at a time,
+    /// and the store should not be locked by the caller while the doc is in use.
+    pub fn from_kv_store(kv: Arc<Mutex<dyn KvStore>>) -> LoroResult<Self> {
+        let mut doc = Self::new();
+        doc.kv_store = Some(encoding::fast_snapshot::open_kv_store(&doc, kv)?);
+        Ok(doc)
+    }
+
+    /// Save the changes and the container states modified since the last save
+    /// into the kv store the doc is opened on.
+    ///
+    /// It's cheap to call it periodically. The caller is responsible for flushing the store.
+    ///
+    /// # Errors
+    ///
+    /// [LoroError::NotOpenedOnKvStore] if the doc is not opened by [LoroDoc::from_kv_store].
+    pub fn save_to_kv_store(&self) -> LoroResult<()> {
+        let Some(kv_store) = &self.kv_store else {
+            return Err(LoroError::NotOpenedOnKvStore);
+        };
+
+        self.commit_then_stop();
+        let ans = kv_store.save(self);
+        self.renew_txn_if_auto_commit();
+        ans
+    }
+
     /// Is the document empty? (no ops)
     #[inline(always)]
     pub fn can_reset_with_snapshot(&self) -> bool {
@@ -410,7 +482,13 @@ impl LoroDoc {
         self.commit_then_stop();
         let ans = self._import_with(bytes, origin);
         self.renew_txn_if_auto_commit();
-        ans
+        ans.map(|mut status| {
+            if !status.tree_violations.is_empty() {
+                status.tree_violations =
+                    self.resolve_tree_violations(std::mem::take(&mut status.tree_violations));
+            }
+            status
+        })
     }
 
     #[tracing::instrument(skip_all)]
@@ -422,7 +500,11 @@ impl LoroDoc {
         ensure_cov::notify_cov("loro_internal::import");
         let parsed = parse_header_and_body(bytes)?;
         info!("Importing with mode={:?}", &parsed.mode);
-        let result = match parsed.mode {
+        let reset_by_snapshot = matches!(
+            parsed.mode,
+            EncodeMode::OutdatedSnapshot | EncodeMode::FastSnapshot
+        ) && self.can_reset_with_snapshot();
+        let mut result = match parsed.mode {
             EncodeMode::OutdatedRle => {
                 if self.state.try_lock().unwrap().is_in_txn() {
                     return Err(LoroError::ImportWhenInTxn);
@@ -471,11 +553,27 @@ impl LoroDoc {
                 |oplog| oplog.decode(parsed),
                 origin,
             ),
+            EncodeMode::FastIncrementalSnapshot => {
+                Err(encoding::incremental_snapshot_import_error())
+            }
             EncodeMode::Auto => {
                 unreachable!()
             }
         };
 
+        if reset_by_snapshot {
+            // The whole state is replaced, there is no diff to tell which containers are changed
+            if let Ok(status) = &mut result {
+                let mut state = self.state.try_lock().unwrap();
+                if let Some(schema) = self.config.schema() {
+                    status.schema_violations = schema.check_state(&mut state);
+                }
+                if self.config.has_tree_constraints() {
+                    status.tree_violations = tree_constraint::check_all_trees(&mut state);
+                }
+            }
+        }
+
         self.emit_events();
         result
     }
@@ -490,7 +588,7 @@ impl LoroDoc {
         if !self.is_detached() {
             let old_vv = oplog.vv().clone();
             let old_frontiers = oplog.frontiers().clone();
-            let result = f(&mut oplog);
+            let mut result = f(&mut oplog);
             if &old_vv != oplog.vv() {
                 let mut diff = DiffCalculator::new(false);
                 let (diff, diff_mode) = diff.calc_diff_internal(
@@ -501,6 +599,16 @@ impl LoroDoc {
                     oplog.dag.get_frontiers(),
                     None,
                 );
+                let schema = self.config.schema();
+                let changed: Vec<ContainerIdx> = match &schema {
+                    Some(_) => diff.iter().map(|d| d.idx).collect(),
+                    None => Vec::new(),
+                };
+                let tree_changes = if self.config.has_tree_constraints() {
+                    TreeChanges::from_diff(&diff)
+                } else {
+                    TreeChanges::default()
+                };
                 let mut state = self.state.try_lock().unwrap();
                 state.apply_diff(
                     InternalDocDiff {
@@ -511,6 +619,13 @@ impl LoroDoc {
                     },
                     diff_mode,
                 );
+                if let Ok(status) = &mut result {
+                    status.tree_violations =
+                        tree_constraint::check_changed_trees(&mut state, &tree_changes);
+                    if let Some(schema) = schema {
+                        status.schema_violations = schema.check_containers(&mut state, changed);
+                    }
+                }
             }
             result
         } else {
@@ -731,6 +846,7 @@ impl LoroDoc {
         &self,
         id_span: IdSpan,
         container_remap: &mut FxHashMap<ContainerID, ContainerID>,
+        scope: Option<&UndoScope>,
         post_transform_base: Option<&DiffBatch>,
         before_diff: &mut dyn FnMut(&DiffBatch),
     ) -> LoroResult<CommitWhenDrop> {
@@ -775,7 +891,11 @@ impl LoroDoc {
                 let mut state = self.state.try_lock().unwrap();
                 let e = state.take_events();
                 state.stop_and_clear_recording();
-                DiffBatch::new(e)
+                let mut diff = DiffBatch::new(e);
+                if let Some(scope) = scope {
+                    diff.retain_scope(scope, &self.arena);
+                }
+                diff
             },
             before_diff,
         );
@@ -1015,6 +1135,111 @@ impl LoroDoc {
         self.state.try_lock().unwrap().get_deep_value_with_id()
     }
 
+    /// Get a serde deserializer over the current state of the container.
+    ///
+    /// Unlike [LoroDoc::get_deep_value], the child containers are only read when the
+    /// deserialized type visits them. The doc state is locked until the deserializer is dropped.
+    pub fn deserializer_at(&self, id: &ContainerID) -> DocDeserializer<'_> {
+        DocDeserializer::new(self.state.try_lock().unwrap(), id.clone())
+    }
+
+    /// Get the value of the container at the given version without checking out the doc.
+    ///
+    /// The child containers are represented by their ids, as in [LoroDoc::get_value].
+    /// The pending transaction is committed first.
+    pub fn get_value_at(&self, id: &ContainerID, frontiers: &Frontiers) -> LoroResult<LoroValue> {
+        let value = self.with_container_state_at(id, frontiers, false, |state, idx| {
+            state.get_value_by_idx(idx)
+        })?;
+        Ok(value.unwrap_or_else(|| id.container_type().default_value()))
+    }
+
+    /// Get the deep value of the container at the given version without checking out the doc.
+    ///
+    /// The pending transaction is committed first.
+    pub fn get_deep_value_at(
+        &self,
+        id: &ContainerID,
+        frontiers: &Frontiers,
+    ) -> LoroResult<LoroValue> {
+        let value = self.with_container_state_at(id, frontiers, true, |state, idx| {
+            state.get_container_deep_value(idx)
+        })?;
+        Ok(value.unwrap_or_else(|| id.container_type().default_value()))
+    }
+
+    /// Calculate the state of the container (and its descendants if `with_descendants` is true)
+    /// at the given version on a fork of these containers, so the doc state is untouched.
+    ///
+    /// The pending transaction is committed first, because the diff is calculated from the
+    /// version of the state, which doesn't include the uncommitted ops.
+    ///
+    /// Return `None` if the container is a root container that has never been used, and
+    /// [LoroError::NotFoundError] if it's an unknown non-root container.
+    fn with_container_state_at<R>(
+        &self,
+        id: &ContainerID,
+        frontiers: &Frontiers,
+        with_descendants: bool,
+        f: impl FnOnce(&mut DocState, ContainerIdx) -> R,
+    ) -> LoroResult<Option<R>> {
+        self.commit_then_renew();
+        let oplog = self.oplog.try_lock().unwrap();
+        if oplog.dag.is_before_shallow_root(frontiers) {
+            return Err(LoroError::SwitchToVersionBeforeShallowRoot);
+        }
+
+        for id in frontiers.iter() {
+            if !oplog.dag.contains(id) {
+                return Err(LoroError::FrontiersNotFound(id));
+            }
+        }
+
+        let after = oplog.dag.frontiers_to_vv(frontiers).unwrap();
+        let Some(idx) = self.arena.id_to_idx(id) else {
+            if id.is_root() {
+                return Ok(None);
+            }
+
+            return Err(LoroError::NotFoundError(id.to_string().into()));
+        };
+
+        let containers: FxHashSet<ContainerIdx> = if with_descendants {
+            self.arena
+                .get_descendants_with_self(idx)
+                .into_iter()
+                .collect()
+        } else {
+            [idx].into_iter().collect()
+        };
+
+        let mut state = self.state.try_lock().unwrap();
+        let before_frontiers = state.frontiers.clone();
+        let before = oplog.dag.frontiers_to_vv(&before_frontiers).unwrap();
+        let forked = state.fork_containers(containers.iter().copied());
+        drop(state);
+        let mut forked_state = forked.try_lock().unwrap();
+        let (diff, diff_mode) = DiffCalculator::new(true).calc_diff_internal(
+            &oplog,
+            &before,
+            &before_frontiers,
+            &after,
+            frontiers,
+            Some(&|idx| containers.contains(&idx)),
+        );
+        drop(oplog);
+        forked_state.apply_diff(
+            InternalDocDiff {
+                origin: "checkout".into(),
+                diff: Cow::Owned(diff),
+                by: EventTriggerKind::Checkout,
+                new_version: Cow::Owned(frontiers.clone()),
+            },
+            diff_mode,
+        );
+        Ok(Some(f(&mut forked_state, idx)))
+    }
+
     pub fn checkout_to_latest(&self) {
         self.commit_then_renew();
         if !self.is_detached() {
@@ -1271,6 +1496,83 @@ impl LoroDoc {
         self.query_pos_internal(pos, true)
     }
 
+    /// Get the positions of a batch of cursors.
+    ///
+    /// The cursors whose target elements are still in the current state are resolved in one pass
+    /// while the state is locked. The others need to trace back the history, and the `update`
+    /// fields of their results contain the new cursors to replace the stale ones. The stale
+    /// cursors in the same container are resolved together with one diff calculation.
+    pub fn query_pos_batch(
+        &self,
+        cursors: &[Cursor],
+    ) -> Vec<Result<PosQueryResult, CannotFindRelativePosition>> {
+        let resolved: Vec<Option<usize>> = {
+            let mut state = self.state.try_lock().unwrap();
+            cursors
+                .iter()
+                .map(|c| state.get_relative_position(c, true))
+                .collect()
+        };
+
+        let mut stale: FxHashMap<&ContainerID, Vec<usize>> = FxHashMap::default();
+        for (i, (pos, cursor)) in resolved.iter().zip(cursors).enumerate() {
+            if pos.is_none() {
+                stale.entry(&cursor.container).or_default().push(i);
+            }
+        }
+
+        let mut ans: Vec<Option<Result<PosQueryResult, CannotFindRelativePosition>>> = resolved
+            .into_iter()
+            .zip(cursors)
+            .map(|(pos, cursor)| {
+                pos.map(|pos| {
+                    Ok(PosQueryResult {
+                        update: None,
+                        current: AbsolutePosition {
+                            pos,
+                            side: cursor.side,
+                        },
+                    })
+                })
+            })
+            .collect();
+        if !stale.is_empty() {
+            // commit the txn to make sure we can query the history correctly
+            self.commit_then_renew();
+            for indexes in stale.into_values() {
+                let group: Vec<&Cursor> = indexes.iter().map(|&i| &cursors[i]).collect();
+                for (i, result) in indexes.into_iter().zip(self.query_stale_pos(&group)) {
+                    ans[i] = Some(result);
+                }
+            }
+        }
+
+        ans.into_iter().map(|x| x.unwrap()).collect()
+    }
+
+    /// Get the positions of a batch of cursor ranges.
+    ///
+    /// See [LoroDoc::query_pos_batch].
+    pub fn query_range_batch(
+        &self,
+        ranges: &[CursorRange],
+    ) -> Vec<Result<RangeQueryResult, CannotFindRelativePosition>> {
+        let cursors: Vec<Cursor> = ranges
+            .iter()
+            .flat_map(|r| [r.start.clone(), r.end.clone()])
+            .collect();
+        let mut results = self.query_pos_batch(&cursors).into_iter();
+        let mut ans = Vec::with_capacity(ranges.len());
+        while let (Some(start), Some(end)) = (results.next(), results.next()) {
+            ans.push(match (start, end) {
+                (Ok(start), Ok(end)) => Ok(RangeQueryResult { start, end }),
+                (Err(e), _) | (_, Err(e)) => Err(e),
+            });
+        }
+
+        ans
+    }
+
     /// Get position in a seq container
     pub(crate) fn query_pos_internal(
         &self,
@@ -1287,150 +1589,193 @@ impl LoroDoc {
                 },
             })
         } else {
-            // We need to trace back to the version where the relative position is valid.
-            // The optimal way to find that version is to have succ info like Automerge.
-            //
-            // But we don't have that info now, so an alternative way is to trace back
-            // to version with frontiers of `[pos.id]`. But this may be very slow even if
-            // the target is just deleted a few versions ago.
-            //
-            // What we need is to trace back to the latest version that deletes the target
-            // id.
-
             // commit the txn to make sure we can query the history correctly
             drop(state);
             self.commit_then_renew();
-            let oplog = self.oplog().try_lock().unwrap();
-            // TODO: assert pos.id is not unknown
-            if let Some(id) = pos.id {
-                let idx = oplog
-                    .arena
-                    .id_to_idx(&pos.container)
-                    .ok_or(CannotFindRelativePosition::ContainerDeleted)?;
-                // We know where the target id is when we trace back to the delete_op_id.
-                let Some(delete_op_id) = find_last_delete_op(&oplog, id, idx) else {
-                    if oplog.shallow_since_vv().includes_id(id) {
-                        return Err(CannotFindRelativePosition::HistoryCleared);
-                    }
+            self.query_stale_pos(&[pos]).pop().unwrap()
+        }
+    }
 
+    /// Get the positions of the cursors in the same container whose target elements are no
+    /// longer in the current state. The pending txn should be committed before calling this.
+    fn query_stale_pos(
+        &self,
+        cursors: &[&Cursor],
+    ) -> Vec<Result<PosQueryResult, CannotFindRelativePosition>> {
+        // We need to trace back to the version where the relative position is valid.
+        // The optimal way to find that version is to have succ info like Automerge.
+        //
+        // But we don't have that info now, so an alternative way is to trace back
+        // to version with frontiers of `[pos.id]`. But this may be very slow even if
+        // the target is just deleted a few versions ago.
+        //
+        // What we need is to trace back to the latest version that deletes the target
+        // id. For a batch of cursors, we trace back to the latest version before all of
+        // these deletions, so that a single diff calculation covers all of them.
+        let Some(container) = cursors.first().map(|c| &c.container) else {
+            return Vec::new();
+        };
+        debug_assert!(cursors.iter().all(|c| &c.container == container));
+        let oplog = self.oplog().try_lock().unwrap();
+        let idx = oplog.arena.id_to_idx(container);
+        let mut ans: Vec<Option<Result<PosQueryResult, CannotFindRelativePosition>>> =
+            Vec::with_capacity(cursors.len());
+        // The target ids that need to be traced back, with the index of their cursors
+        let mut targets: Vec<(usize, ID)> = Vec::new();
+        let mut before: Option<VersionVector> = None;
+        for (i, pos) in cursors.iter().enumerate() {
+            // TODO: assert pos.id is not unknown
+            let Some(id) = pos.id else {
+                ans.push(Some(Ok(self.query_pos_at_end(pos))));
+                continue;
+            };
+            let Some(idx) = idx else {
+                ans.push(Some(Err(CannotFindRelativePosition::ContainerDeleted)));
+                continue;
+            };
+            // We know where the target id is when we trace back to the delete_op_id.
+            let Some(delete_op_id) = find_last_delete_op(&oplog, id, idx) else {
+                if oplog.shallow_since_vv().includes_id(id) {
+                    ans.push(Some(Err(CannotFindRelativePosition::HistoryCleared)));
+                } else {
                     tracing::error!("Cannot find id {}", id);
-                    return Err(CannotFindRelativePosition::IdNotFound);
-                };
-                // Should use persist mode so that it will force all the diff calculators to use the `checkout` mode
-                let mut diff_calc = DiffCalculator::new(true);
-                let before_frontiers: Frontiers = oplog.dag.find_deps_of_id(delete_op_id);
-                let before = &oplog.dag.frontiers_to_vv(&before_frontiers).unwrap();
-                // TODO: PERF: it doesn't need to calc the effects here
-                diff_calc.calc_diff_internal(
-                    &oplog,
-                    before,
-                    &before_frontiers,
-                    oplog.vv(),
-                    oplog.frontiers(),
-                    Some(&|target| idx == target),
-                );
-                // TODO: remove depth info
-                let depth = self.arena.get_depth(idx);
-                let (_, diff_calc) = &mut diff_calc.get_or_create_calc(idx, depth);
-                match diff_calc {
+                    ans.push(Some(Err(CannotFindRelativePosition::IdNotFound)));
+                }
+                continue;
+            };
+
+            let deps = oplog.dag.find_deps_of_id(delete_op_id);
+            let deps_vv = oplog.dag.frontiers_to_vv(&deps).unwrap();
+            before = Some(match before {
+                Some(vv) => vv.intersection(&deps_vv),
+                None => deps_vv,
+            });
+            targets.push((i, id));
+            ans.push(None);
+        }
+
+        if let (Some(before), Some(idx)) = (before, idx) {
+            // Should use persist mode so that it will force all the diff calculators to use the `checkout` mode
+            let mut diff_calc = DiffCalculator::new(true);
+            let before_frontiers: Frontiers = oplog.dag.vv_to_frontiers(&before);
+            // TODO: PERF: it doesn't need to calc the effects here
+            diff_calc.calc_diff_internal(
+                &oplog,
+                &before,
+                &before_frontiers,
+                oplog.vv(),
+                oplog.frontiers(),
+                Some(&|target| idx == target),
+            );
+            // TODO: remove depth info
+            let depth = self.arena.get_depth(idx);
+            let (_, diff_calc) = &mut diff_calc.get_or_create_calc(idx, depth);
+            for (i, id) in targets {
+                let result = match diff_calc {
                     crate::diff_calc::ContainerDiffCalculator::Richtext(text) => {
                         let c = text.get_id_latest_pos(id).unwrap();
                         let new_pos = c.pos;
-                        let handler = self.get_text(&pos.container);
+                        let handler = self.get_text(container);
                         let current_pos = handler.convert_entity_index_to_event_index(new_pos);
-                        Ok(PosQueryResult {
+                        PosQueryResult {
                             update: handler.get_cursor(current_pos, c.side),
                             current: AbsolutePosition {
                                 pos: current_pos,
                                 side: c.side,
                             },
-                        })
+                        }
                     }
                     crate::diff_calc::ContainerDiffCalculator::List(list) => {
                         let c = list.get_id_latest_pos(id).unwrap();
                         let new_pos = c.pos;
-                        let handler = self.get_list(&pos.container);
-                        Ok(PosQueryResult {
+                        let handler = self.get_list(container);
+                        PosQueryResult {
                             update: handler.get_cursor(new_pos, c.side),
                             current: AbsolutePosition {
                                 pos: new_pos,
                                 side: c.side,
                             },
-                        })
+                        }
                     }
                     crate::diff_calc::ContainerDiffCalculator::MovableList(list) => {
                         let c = list.get_id_latest_pos(id).unwrap();
                         let new_pos = c.pos;
-                        let handler = self.get_movable_list(&pos.container);
+                        let handler = self.get_movable_list(container);
                         let new_pos = handler.op_pos_to_user_pos(new_pos);
-                        Ok(PosQueryResult {
+                        PosQueryResult {
                             update: handler.get_cursor(new_pos, c.side),
                             current: AbsolutePosition {
                                 pos: new_pos,
                                 side: c.side,
                             },
-                        })
+                        }
                     }
                     crate::diff_calc::ContainerDiffCalculator::Tree(_) => unreachable!(),
                     crate::diff_calc::ContainerDiffCalculator::Map(_) => unreachable!(),
                     #[cfg(feature = "counter")]
                     crate::diff_calc::ContainerDiffCalculator::Counter(_) => unreachable!(),
                     crate::diff_calc::ContainerDiffCalculator::Unknown(_) => unreachable!(),
+                };
+                ans[i] = Some(Ok(result));
+            }
+        }
+
+        ans.into_iter().map(|x| x.unwrap()).collect()
+    }
+
+    /// Get the position of a cursor without target id, which points to the end of the container
+    fn query_pos_at_end(&self, pos: &Cursor) -> PosQueryResult {
+        match pos.container.container_type() {
+            ContainerType::Text => {
+                let text = self.get_text(&pos.container);
+                PosQueryResult {
+                    update: Some(Cursor {
+                        id: None,
+                        container: text.id(),
+                        side: pos.side,
+                        origin_pos: text.len_unicode(),
+                    }),
+                    current: AbsolutePosition {
+                        pos: text.len_event(),
+                        side: pos.side,
+                    },
                 }
-            } else {
-                match pos.container.container_type() {
-                    ContainerType::Text => {
-                        let text = self.get_text(&pos.container);
-                        Ok(PosQueryResult {
-                            update: Some(Cursor {
-                                id: None,
-                                container: text.id(),
-                                side: pos.side,
-                                origin_pos: text.len_unicode(),
-                            }),
-                            current: AbsolutePosition {
-                                pos: text.len_event(),
-                                side: pos.side,
-                            },
-                        })
-                    }
-                    ContainerType::List => {
-                        let list = self.get_list(&pos.container);
-                        Ok(PosQueryResult {
-                            update: Some(Cursor {
-                                id: None,
-                                container: list.id(),
-                                side: pos.side,
-                                origin_pos: list.len(),
-                            }),
-                            current: AbsolutePosition {
-                                pos: list.len(),
-                                side: pos.side,
-                            },
-                        })
-                    }
-                    ContainerType::MovableList => {
-                        let list = self.get_movable_list(&pos.container);
-                        Ok(PosQueryResult {
-                            update: Some(Cursor {
-                                id: None,
-                                container: list.id(),
-                                side: pos.side,
-                                origin_pos: list.len(),
-                            }),
-                            current: AbsolutePosition {
-                                pos: list.len(),
-                                side: pos.side,
-                            },
-                        })
-                    }
-                    ContainerType::Map | ContainerType::Tree | ContainerType::Unknown(_) => {
-                        unreachable!()
-                    }
-                    #[cfg(feature = "counter")]
-                    ContainerType::Counter => unreachable!(),
+            }
+            ContainerType::List => {
+                let list = self.get_list(&pos.container);
+                PosQueryResult {
+                    update: Some(Cursor {
+                        id: None,
+                        container: list.id(),
+                        side: pos.side,
+                        origin_pos: list.len(),
+                    }),
+                    current: AbsolutePosition {
+                        pos: list.len(),
+                        side: pos.side,
+                    },
+                }
+            }
+            ContainerType::MovableList => {
+                let list = self.get_movable_list(&pos.container);
+                PosQueryResult {
+                    update: Some(Cursor {
+                        id: None,
+                        container: list.id(),
+                        side: pos.side,
+                        origin_pos: list.len(),
+                    }),
+                    current: AbsolutePosition {
+                        pos: list.len(),
+                        side: pos.side,
+                    },
                 }
             }
+            ContainerType::Map | ContainerType::Tree | ContainerType::Unknown(_) => {
+                unreachable!()
+            }
+            #[cfg(feature = "counter")]
+            ContainerType::Counter => unreachable!(),
         }
     }
 
@@ -1477,6 +1822,60 @@ impl LoroDoc {
         state.get_path(idx)
     }
 
+    /// Deep clone the container `src` of this doc into `dest_parent`.
+    ///
+    /// `dest_parent` can belong to another doc. The copy is created by new ops,
+    /// so it gets a new container id. Text styles and tree node meta are kept.
+    ///
+    /// - If `dest_parent` is a map, `index` should be [`Index::Key`]
+    /// - If `dest_parent` is a list or movable list, `index` should be [`Index::Seq`]
+    /// - If `dest_parent` is a tree, `index` should be [`Index::Node`]; the source
+    ///   should be a map, and its entries are copied into the meta of the node
+    pub fn clone_container(
+        &self,
+        src: &ContainerID,
+        dest_parent: &Handler,
+        index: Index,
+    ) -> LoroResult<Handler> {
+        if !src.is_root() && self.arena.id_to_idx(src).is_none() {
+            return Err(LoroError::NotFoundError(src.to_string().into()));
+        }
+
+        let copy = self.get_handler(src.clone()).to_detached()?;
+        match (dest_parent, index) {
+            (Handler::Map(m), Index::Key(key)) => m.insert_container(&key, copy),
+            (Handler::List(l), Index::Seq(pos)) => l.insert_container(pos, copy),
+            (Handler::MovableList(l), Index::Seq(pos)) => l.insert_container(pos, copy),
+            (Handler::Tree(t), Index::Node(node)) => {
+                let Handler::Map(copy) = copy else {
+                    return Err(LoroError::ArgErr(
+                        "Only a map can be cloned into the meta of a tree node".into(),
+                    ));
+                };
+                let meta = t.get_meta(node)?;
+                let mut entries = Vec::new();
+                copy.for_each(|k, v| entries.push((k.to_string(), v)));
+                for (k, v) in entries {
+                    match v {
+                        ValueOrHandler::Value(v) => meta.insert(&k, v)?,
+                        ValueOrHandler::Handler(h) => {
+                            meta.insert_container(&k, h)?;
+                        }
+                    }
+                }
+                Ok(Handler::Map(meta))
+            }
+            (parent, index) => Err(LoroError::ArgErr(
+                format!(
+                    "Cannot clone a container into {:?} at {:?}",
+                    parent.id(),
+                    index
+                )
+                .into_boxed_str(),
+            )),
+        }
+    }
+
     #[instrument(skip(self))]
     pub fn export(&self, mode: ExportMode) -> Result<Vec<u8>, LoroEncodeError> {
         self.commit_then_stop();
@@ -1498,6 +1897,21 @@ impl LoroDoc {
         Ok(ans)
     }
 
+    /// Export the history blocks and the container states that may have changed since `since`.
+    ///
+    /// `since` should be the oplog version vector of the base, i.e. the value of [LoroDoc::oplog_vv]
+    /// when the base snapshot or the previous incremental snapshot was exported.
+    /// Use [LoroDoc::from_incremental_snapshots] to layer the result onto the base.
+    pub fn export_incremental_snapshot(
+        &self,
+        since: &VersionVector,
+    ) -> Result<Vec<u8>, LoroEncodeError> {
+        self.commit_then_stop();
+        let ans = encoding::export_incremental_snapshot(self, since);
+        self.renew_txn_if_auto_commit();
+        ans
+    }
+
     /// The doc only contains the history since the shallow history start version vector.
     ///
     /// This is empty if the doc is not shallow.
@@ -1525,6 +1939,46 @@ impl LoroDoc {
             .is_empty()
     }
 
+    /// Trim the history of the doc in place according to the retention policy.
+    ///
+    /// Afterwards the doc is the same as the one imported from a shallow snapshot
+    /// at the trimming point, but the state, the handlers and the subscriptions are kept.
+    /// The versions before the trimming point can no longer be checked out or exported.
+    ///
+    /// Return the new start version of the history, or `None` if nothing is trimmed.
+    /// It fails with [LoroError::CompactHistoryWhenDetached] if the doc is detached.
+    pub fn compact_history(&self, retention: &HistoryRetention) -> LoroResult<Option<Frontiers>> {
+        if self.is_detached() {
+            return Err(LoroError::CompactHistoryWhenDetached);
+        }
+
+        self.commit_then_stop();
+        let ans = self._compact_history(retention);
+        self.renew_txn_if_auto_commit();
+        ans
+    }
+
+    fn _compact_history(&self, retention: &HistoryRetention) -> LoroResult<Option<Frontiers>> {
+        let start = self
+            .oplog
+            .try_lock()
+            .unwrap()
+            .find_retention_start(retention)?;
+        let Some(start) = start else {
+            return Ok(None);
+        };
+
+        let start = encoding::compact_history(self, &start).map_err(|e| match e {
+            LoroEncodeError::UnknownContainer => {
+                LoroError::NotImplemented("Compacting the history of a doc with unknown containers")
+            }
+            e => e.into(),
+        })?;
+        // The cached diff calculator may refer to the trimmed history
+        self.free_diff_calculator();
+        Ok(Some(start))
+    }
+
     /// Get the number of operations in the pending transaction.
     ///
     /// The pending transaction is the one that is not committed yet. It will be committed
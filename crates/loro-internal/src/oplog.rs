@@ -7,7 +7,7 @@ use std::borrow::Cow;
 use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::rc::Rc;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use tracing::{debug, trace, trace_span};
 
 use self::change_store::iter::MergedChangeIter;
@@ -18,20 +18,22 @@ use crate::configure::Configure;
 use crate::container::list::list_op;
 use crate::dag::{Dag, DagUtils};
 use crate::diff_calc::DiffMode;
-use crate::encoding::{decode_oplog, encode_oplog, EncodeMode};
+use crate::encoding::{decode_oplog, encode_oplog, EncodeMode, HistoryRetention};
 use crate::encoding::{ImportStatus, ParsedHeaderAndBody};
 use crate::history_cache::ContainerHistoryCache;
 use crate::id::{Counter, PeerID, ID};
+use crate::kv_store::{KvStore, PrefixedKvStore};
 use crate::op::{FutureInnerContent, ListSlice, RawOpContent, RemoteOp, RichOp};
 use crate::span::{HasCounterSpan, HasLamportSpan};
 use crate::version::{Frontiers, ImVersionVector, VersionVector};
 use crate::LoroError;
-use change_store::BlockOpRef;
-use loro_common::{IdLp, IdSpan};
+use change_store::{BatchDecodeInfo, BlockOpRef};
+use loro_common::{HasIdSpan, IdLp, IdSpan, LoroResult};
 use rle::{HasLength, RleVec, Sliceable};
 use smallvec::SmallVec;
 
 pub use self::loro_dag::{AppDag, AppDagNode, FrontiersNotIncluded};
+pub(crate) use change_store::VV_KEY as CHANGE_STORE_VV_KEY;
 pub use change_store::{BlockChangeRef, ChangeStore};
 
 /// [OpLog] store all the ops i.e. the history.
@@ -54,6 +56,8 @@ pub struct OpLog {
     /// If so the Dag's frontiers won't be updated until the batch is finished.
     pub(crate) batch_importing: bool,
     pub(crate) configure: Configure,
+    /// The kv store the change store is opened on, see [OpLog::open_kv_store]
+    kv_store: Option<Arc<Mutex<PrefixedKvStore>>>,
 }
 
 impl std::fmt::Debug for OpLog {
@@ -79,6 +83,7 @@ impl OpLog {
             pending_changes: Default::default(),
             batch_importing: false,
             configure: cfg,
+            kv_store: None,
         }
     }
 
@@ -566,6 +571,137 @@ impl OpLog {
             .flush_and_compact(self.dag.vv(), self.dag.frontiers());
     }
 
+    /// Replace the history by the change store encoded in a shallow snapshot.
+    ///
+    /// The arena is kept, so the container indexes remain valid.
+    pub(crate) fn replace_change_store(&mut self, bytes: Bytes) -> LoroResult<()> {
+        let change_store = match &self.kv_store {
+            Some(kv) => {
+                kv.try_lock().unwrap().clear();
+                ChangeStore::new_with_kv(
+                    &self.arena,
+                    self.configure.merge_interval.clone(),
+                    kv.clone(),
+                )
+            }
+            None => ChangeStore::new_mem(&self.arena, self.configure.merge_interval.clone()),
+        };
+        let v = change_store.import_all(bytes)?;
+        self.set_change_store(change_store, Some(v));
+        Ok(())
+    }
+
+    /// Store the changes in the kv store. The blocks already in it are loaded lazily.
+    ///
+    /// It should only be called on an empty oplog.
+    pub(crate) fn open_kv_store(&mut self, kv: Arc<Mutex<PrefixedKvStore>>) -> LoroResult<()> {
+        assert!(self.is_empty());
+        let change_store = ChangeStore::new_with_kv(
+            &self.arena,
+            self.configure.merge_interval.clone(),
+            kv.clone(),
+        );
+        let v = if kv.try_lock().unwrap().is_empty() {
+            None
+        } else {
+            Some(change_store.load_external_kv()?)
+        };
+        self.set_change_store(change_store, v);
+        self.kv_store = Some(kv);
+        Ok(())
+    }
+
+    /// Write the new changes into the kv store opened by [OpLog::open_kv_store]
+    pub(crate) fn persist_kv_store(&mut self) {
+        let Some(kv) = self.kv_store.clone() else {
+            return;
+        };
+
+        self.compact_change_store();
+        kv.try_lock().unwrap().persist();
+    }
+
+    fn set_change_store(&mut self, change_store: ChangeStore, v: Option<BatchDecodeInfo>) {
+        let mut dag = AppDag::new(change_store.clone());
+        if let Some(v) = v {
+            dag.set_version_by_fast_snapshot_import(v);
+        }
+
+        self.history_cache = Mutex::new(ContainerHistoryCache::new(change_store.clone(), None));
+        self.dag = dag;
+        self.change_store = change_store;
+    }
+
+    /// Find the version to trim the history at, so that the history kept satisfies the retention policy.
+    ///
+    /// Return `None` if nothing needs to be trimmed.
+    pub(crate) fn find_retention_start(
+        &self,
+        retention: &HistoryRetention,
+    ) -> LoroResult<Option<Frontiers>> {
+        let start = match retention {
+            HistoryRetention::After(f) => {
+                for id in f.iter() {
+                    if !self.dag.vv().includes_id(id) {
+                        return Err(LoroError::FrontiersNotFound(id));
+                    }
+                }
+
+                f.clone()
+            }
+            HistoryRetention::LastDays(days) => {
+                let now = (get_sys_timestamp() + 500) / 1000;
+                let cutoff = now - *days as Timestamp * 24 * 60 * 60;
+                let changes = self.changes_sorted_by_lamport();
+                if changes.iter().all(|(_, timestamp, _)| *timestamp == 0) {
+                    return Err(LoroError::ArgErr(
+                        "`HistoryRetention::LastDays` requires the timestamps of the changes, \
+                        but they are not recorded"
+                            .into(),
+                    ));
+                }
+
+                let kept = changes
+                    .iter()
+                    .position(|(_, timestamp, _)| *timestamp >= cutoff)
+                    .unwrap_or(changes.len());
+                if kept == 0 {
+                    return Ok(None);
+                }
+
+                Frontiers::from_id(changes[kept - 1].2)
+            }
+            HistoryRetention::LastChanges(n) => {
+                let changes = self.changes_sorted_by_lamport();
+                if changes.len() <= *n {
+                    return Ok(None);
+                }
+
+                Frontiers::from_id(changes[changes.len() - n - 1].2)
+            }
+        };
+
+        if start.is_empty()
+            || self.dag.is_before_shallow_root(&start)
+            || &start == self.shallow_since_frontiers()
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(start))
+    }
+
+    /// The (lamport, timestamp, last id) of the changes in the doc, sorted by lamport
+    fn changes_sorted_by_lamport(&self) -> Vec<(Lamport, Timestamp, ID)> {
+        let from = self.shallow_since_vv().to_vv();
+        let mut ans: Vec<_> = self
+            .iter_changes_peer_by_peer(&from, self.vv())
+            .map(|c| (c.lamport, c.timestamp, c.id_last()))
+            .collect();
+        ans.sort_unstable_by_key(|(lamport, _, id)| (*lamport, id.peer));
+        ans
+    }
+
     #[inline]
     pub fn change_store_kv_size(&self) -> usize {
         self.change_store.kv_size()
@@ -576,6 +712,11 @@ impl OpLog {
             .encode_all(self.dag.vv(), self.dag.frontiers())
     }
 
+    pub(crate) fn encode_change_store_since(&self, since_vv: &VersionVector) -> bytes::Bytes {
+        self.change_store
+            .encode_blocks_since(since_vv, self.dag.vv(), self.dag.frontiers())
+    }
+
     pub fn check_dag_correctness(&self) {
         self.dag.check_dag_correctness();
     }
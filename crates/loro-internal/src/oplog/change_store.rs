@@ -127,6 +127,28 @@ impl ChangeStore {
         }
     }
 
+    /// Create a change store whose blocks are stored in the given kv store.
+    ///
+    /// The blocks already in the store are loaded lazily, [ChangeStore::load_external_kv]
+    /// should be called to read the version of them.
+    pub fn new_with_kv(
+        a: &SharedArena,
+        merge_interval: Arc<AtomicI64>,
+        kv: Arc<Mutex<dyn KvStore>>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ChangeStoreInner {
+                start_vv: ImVersionVector::new(),
+                start_frontiers: Frontiers::default(),
+                mem_parsed_kv: BTreeMap::new(),
+            })),
+            arena: a.clone(),
+            external_vv: Arc::new(Mutex::new(VersionVector::new())),
+            external_kv: kv,
+            merge_interval,
+        }
+    }
+
     #[cfg(test)]
     fn new_for_test() -> Self {
         Self::new_mem(&SharedArena::new(), Arc::new(AtomicI64::new(0)))
@@ -138,6 +160,48 @@ impl ChangeStore {
         kv.export_all()
     }
 
+    /// Encode the kv entries of the blocks that contain the changes after `since_vv`.
+    ///
+    /// The meta entries (version vector and frontiers) are always included, so the output
+    /// can be layered on top of the encoded store at `since_vv` by [KvStore::import_all].
+    pub(super) fn encode_blocks_since(
+        &self,
+        since_vv: &VersionVector,
+        vv: &VersionVector,
+        frontiers: &Frontiers,
+    ) -> Bytes {
+        self.flush_and_compact(vv, frontiers);
+        let kv = self.external_kv.try_lock().unwrap();
+        let mut ans = MemKvStore::new(MemKvConfig::default());
+        for span in vv.sub_iter(since_vv) {
+            let start = ID::new(span.peer, span.counter.start).to_bytes();
+            let end = ID::new(span.peer, Counter::MAX).to_bytes();
+            // The block that contains the start counter may start before it
+            let peer_start = ID::new(span.peer, 0).to_bytes();
+            if let Some((k, v)) = kv
+                .scan(
+                    Bound::Included(&peer_start[..]),
+                    Bound::Excluded(&start[..]),
+                )
+                .next_back()
+            {
+                ans.set(&k, v);
+            }
+
+            for (k, v) in kv.scan(Bound::Included(&start[..]), Bound::Included(&end[..])) {
+                ans.set(&k, v);
+            }
+        }
+
+        for key in [VV_KEY, FRONTIERS_KEY, START_VV_KEY, START_FRONTIERS_KEY] {
+            if let Some(v) = kv.get(key) {
+                ans.set(key, v);
+            }
+        }
+
+        ans.export_all()
+    }
+
     #[tracing::instrument(skip(self), level = "debug")]
     pub(super) fn export_from(
         &self,
@@ -573,6 +637,14 @@ mod mut_external_kv {
             kv_store
                 .import_all(bytes)
                 .map_err(|e| LoroError::DecodeError(e.into_boxed_str()))?;
+            drop(kv_store);
+            self.load_external_kv()
+        }
+
+        /// Read the version of the changes in the external kv store
+        pub(crate) fn load_external_kv(&self) -> Result<BatchDecodeInfo, LoroError> {
+            #[allow(unused_mut)]
+            let mut kv_store = self.external_kv.try_lock().unwrap();
             let vv_bytes = kv_store.get(VV_KEY).unwrap_or_default();
             let vv = VersionVector::decode(&vv_bytes).unwrap();
             let start_vv_bytes = kv_store.get(START_VV_KEY).unwrap_or_default();
@@ -0,0 +1,477 @@
+//! Optional schema of the document structure.
+//!
+//! A [DocSchema] describes the expected kinds of the root containers, the keys and value types
+//! of maps, the element types of lists and the meta shape of tree nodes. Containers and keys
+//! that are not described by the schema are not constrained.
+//!
+//! After a schema is attached by [LoroDoc::set_schema]:
+//!
+//! - Local edits that violate it are rejected with [LoroError::SchemaViolation] before
+//!   they are applied to the transaction.
+//! - Imported changes cannot be rejected, otherwise the doc would diverge from the other peers.
+//!   The violations they introduce are reported in [ImportStatus::schema_violations].
+//!
+//! [ImportStatus::schema_violations]: crate::encoding::ImportStatus::schema_violations
+use std::sync::Arc;
+
+use fxhash::FxHashMap;
+use loro_common::{ContainerID, ContainerType, InternalString, LoroError, LoroResult, LoroValue};
+
+use crate::{
+    container::{idx::ContainerIdx, list::list_op::ListOp},
+    event::Index,
+    op::{ListSlice, RawOpContent},
+    state::DocState,
+    LoroDoc,
+};
+
+/// The schema of a [LoroDoc], keyed by the names of the root containers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocSchema {
+    roots: FxHashMap<InternalString, ContainerSchema>,
+}
+
+/// The expected kind and content of a container.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContainerSchema {
+    Map(MapSchema),
+    /// A list whose elements match the schema
+    List(ValueSchema),
+    /// A movable list whose elements match the schema
+    MovableList(ValueSchema),
+    Text,
+    /// A tree whose node meta maps match the schema
+    Tree(MapSchema),
+    #[cfg(feature = "counter")]
+    Counter,
+}
+
+/// The keys and value types of a map.
+///
+/// By default, the keys that are not declared are rejected.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapSchema {
+    fields: FxHashMap<InternalString, ValueSchema>,
+    allow_unknown_keys: bool,
+}
+
+/// The expected type of a value in a map or a list.
+///
+/// Integers and floating point numbers are different types in Loro, use [ValueSchema::Number]
+/// to accept both.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueSchema {
+    Any,
+    Null,
+    Bool,
+    I64,
+    Double,
+    Number,
+    String,
+    Binary,
+    /// A plain list value whose elements match the schema
+    List(Box<ValueSchema>),
+    /// A plain map value whose values match the schema
+    Map(Box<ValueSchema>),
+    /// A child container
+    Container(Box<ContainerSchema>),
+    /// `null` or the inner schema
+    Nullable(Box<ValueSchema>),
+}
+
+/// A place in the doc that doesn't match the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    pub container: ContainerID,
+    /// The key, the position or the tree node of the invalid child.
+    /// It's `None` if the container itself is invalid.
+    pub index: Option<Index>,
+    pub reason: String,
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.index {
+            Some(index) => write!(f, "{} at {}: {}", self.container, index, self.reason),
+            None => write!(f, "{}: {}", self.container, self.reason),
+        }
+    }
+}
+
+impl From<SchemaViolation> for LoroError {
+    fn from(value: SchemaViolation) -> Self {
+        LoroError::SchemaViolation(value.to_string().into_boxed_str())
+    }
+}
+
+impl DocSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare the schema of the root container with the given name
+    pub fn root(mut self, name: impl Into<InternalString>, schema: ContainerSchema) -> Self {
+        self.roots.insert(name.into(), schema);
+        self
+    }
+
+    pub fn get_root(&self, name: &str) -> Option<&ContainerSchema> {
+        self.roots.get(&InternalString::from(name))
+    }
+
+    /// Find the shape of the container by its path from the root.
+    ///
+    /// Returns `Ok(None)` if the container is not constrained by the schema.
+    fn resolve<'a>(
+        &'a self,
+        path: &[(ContainerID, Index)],
+    ) -> Result<Option<Shape<'a>>, SchemaViolation> {
+        let mut iter = path.iter();
+        let Some((root, Index::Key(name))) = iter.next() else {
+            return Ok(None);
+        };
+        let Some(schema) = self.roots.get(name) else {
+            return Ok(None);
+        };
+        check_container_type(root, schema)?;
+        let mut shape = Shape::from(schema);
+        for (id, index) in iter {
+            let child = match (shape, index) {
+                (Shape::Map(map), Index::Key(key)) => match map.fields.get(key) {
+                    Some(child) => child,
+                    None => return Ok(None),
+                },
+                (Shape::Seq(elem), Index::Seq(_)) => elem,
+                (Shape::Tree(meta), Index::Node(_)) => {
+                    shape = Shape::Map(meta);
+                    continue;
+                }
+                _ => return Ok(None),
+            };
+            let Some(schema) = child.container_schema() else {
+                return Ok(None);
+            };
+            check_container_type(id, schema)?;
+            shape = Shape::from(schema);
+        }
+
+        Ok(Some(shape))
+    }
+
+    /// Check the op before it's applied in a local transaction
+    pub(crate) fn check_local_op(
+        &self,
+        state: &mut DocState,
+        idx: ContainerIdx,
+        content: &RawOpContent,
+    ) -> LoroResult<()> {
+        let Some(path) = state.get_path(idx) else {
+            return Ok(());
+        };
+        let Some(shape) = self.resolve(&path)? else {
+            return Ok(());
+        };
+        let id = &path.last().unwrap().0;
+        match (shape, content) {
+            (Shape::Map(map), RawOpContent::Map(set)) => {
+                if let Some(value) = &set.value {
+                    map.check_entry(id, &set.key, value)?;
+                }
+            }
+            (Shape::Seq(elem), RawOpContent::List(ListOp::Insert { slice, pos })) => {
+                if let ListSlice::RawData(values) = slice {
+                    for (i, value) in values.iter().enumerate() {
+                        check_value(id, Index::Seq(pos + i), elem, value)?;
+                    }
+                }
+            }
+            (Shape::Seq(elem), RawOpContent::List(ListOp::Set { value, .. })) => {
+                if !elem.matches(value) {
+                    return Err(SchemaViolation {
+                        container: id.clone(),
+                        index: None,
+                        reason: mismatch_reason(elem, value),
+                    }
+                    .into());
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Check the current values of the given containers. The child containers are not checked.
+    pub(crate) fn check_containers(
+        &self,
+        state: &mut DocState,
+        containers: impl IntoIterator<Item = ContainerIdx>,
+    ) -> Vec<SchemaViolation> {
+        let mut ans = Vec::new();
+        for idx in containers {
+            self.check_container(state, idx, &mut ans);
+        }
+        ans
+    }
+
+    /// Check all the alive containers in the doc state
+    pub(crate) fn check_state(&self, state: &mut DocState) -> Vec<SchemaViolation> {
+        let mut ans = Vec::new();
+        let mut stack = state.arena.root_containers();
+        while let Some(idx) = stack.pop() {
+            if let Some(value) = self.check_container(state, idx, &mut ans) {
+                collect_child_containers(&value, &mut |id| {
+                    if let Some(idx) = state.arena.id_to_idx(id) {
+                        stack.push(idx);
+                    }
+                });
+            }
+        }
+        ans
+    }
+
+    /// Returns the value of the container if it exists
+    fn check_container(
+        &self,
+        state: &mut DocState,
+        idx: ContainerIdx,
+        ans: &mut Vec<SchemaViolation>,
+    ) -> Option<LoroValue> {
+        let path = state.get_path(idx)?;
+        let value = state.get_value_by_idx(idx);
+        let shape = match self.resolve(&path) {
+            Ok(Some(shape)) => shape,
+            Ok(None) => return Some(value),
+            Err(e) => {
+                // Getting a root container by a wrong kind shouldn't be reported
+                // until something is written into it
+                if !is_empty_container_value(&value) {
+                    ans.push(e);
+                }
+                return Some(value);
+            }
+        };
+        let id = &path.last().unwrap().0;
+        match (shape, &value) {
+            (Shape::Map(map), LoroValue::Map(entries)) => {
+                for (key, value) in entries.iter() {
+                    if let Err(e) = map.check_entry(id, &InternalString::from(key.as_str()), value)
+                    {
+                        ans.push(e);
+                    }
+                }
+            }
+            (Shape::Seq(elem), LoroValue::List(list)) => {
+                for (i, value) in list.iter().enumerate() {
+                    if let Err(e) = check_value(id, Index::Seq(i), elem, value) {
+                        ans.push(e);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Some(value)
+    }
+}
+
+impl ContainerSchema {
+    pub fn container_type(&self) -> ContainerType {
+        match self {
+            ContainerSchema::Map(_) => ContainerType::Map,
+            ContainerSchema::List(_) => ContainerType::List,
+            ContainerSchema::MovableList(_) => ContainerType::MovableList,
+            ContainerSchema::Text => ContainerType::Text,
+            ContainerSchema::Tree(_) => ContainerType::Tree,
+            #[cfg(feature = "counter")]
+            ContainerSchema::Counter => ContainerType::Counter,
+        }
+    }
+}
+
+impl MapSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare the value type of the key
+    pub fn field(mut self, key: impl Into<InternalString>, schema: ValueSchema) -> Self {
+        self.fields.insert(key.into(), schema);
+        self
+    }
+
+    /// Whether the keys that are not declared are allowed. Their values are not constrained.
+    pub fn allow_unknown_keys(mut self, allow: bool) -> Self {
+        self.allow_unknown_keys = allow;
+        self
+    }
+
+    pub fn get_field(&self, key: &str) -> Option<&ValueSchema> {
+        self.fields.get(&InternalString::from(key))
+    }
+
+    fn check_entry(
+        &self,
+        container: &ContainerID,
+        key: &InternalString,
+        value: &LoroValue,
+    ) -> Result<(), SchemaViolation> {
+        match self.fields.get(key) {
+            Some(schema) => check_value(container, Index::Key(key.clone()), schema, value),
+            None if self.allow_unknown_keys => Ok(()),
+            None => Err(SchemaViolation {
+                container: container.clone(),
+                index: Some(Index::Key(key.clone())),
+                reason: "unknown key".to_string(),
+            }),
+        }
+    }
+}
+
+impl ValueSchema {
+    pub fn container(schema: ContainerSchema) -> Self {
+        ValueSchema::Container(Box::new(schema))
+    }
+
+    pub fn nullable(schema: ValueSchema) -> Self {
+        ValueSchema::Nullable(Box::new(schema))
+    }
+
+    /// Whether the value matches the schema. Child containers only need to be of the expected kind.
+    pub fn matches(&self, value: &LoroValue) -> bool {
+        match (self, value) {
+            (ValueSchema::Any, _) => true,
+            (ValueSchema::Null, LoroValue::Null) => true,
+            (ValueSchema::Bool, LoroValue::Bool(_)) => true,
+            (ValueSchema::I64, LoroValue::I64(_)) => true,
+            (ValueSchema::Double, LoroValue::Double(_)) => true,
+            (ValueSchema::Number, LoroValue::I64(_) | LoroValue::Double(_)) => true,
+            (ValueSchema::String, LoroValue::String(_)) => true,
+            (ValueSchema::Binary, LoroValue::Binary(_)) => true,
+            (ValueSchema::List(elem), LoroValue::List(list)) => {
+                list.iter().all(|v| elem.matches(v))
+            }
+            (ValueSchema::Map(elem), LoroValue::Map(map)) => map.values().all(|v| elem.matches(v)),
+            (ValueSchema::Container(schema), LoroValue::Container(id)) => {
+                schema.container_type() == id.container_type()
+            }
+            (ValueSchema::Nullable(_), LoroValue::Null) => true,
+            (ValueSchema::Nullable(inner), value) => inner.matches(value),
+            _ => false,
+        }
+    }
+
+    fn container_schema(&self) -> Option<&ContainerSchema> {
+        match self {
+            ValueSchema::Container(schema) => Some(schema),
+            ValueSchema::Nullable(inner) => inner.container_schema(),
+            _ => None,
+        }
+    }
+}
+
+/// What a container's children should look like
+#[derive(Debug, Clone, Copy)]
+enum Shape<'a> {
+    Map(&'a MapSchema),
+    Seq(&'a ValueSchema),
+    Tree(&'a MapSchema),
+    Leaf,
+}
+
+impl<'a> From<&'a ContainerSchema> for Shape<'a> {
+    fn from(schema: &'a ContainerSchema) -> Self {
+        match schema {
+            ContainerSchema::Map(map) => Shape::Map(map),
+            ContainerSchema::List(elem) | ContainerSchema::MovableList(elem) => Shape::Seq(elem),
+            ContainerSchema::Tree(meta) => Shape::Tree(meta),
+            ContainerSchema::Text => Shape::Leaf,
+            #[cfg(feature = "counter")]
+            ContainerSchema::Counter => Shape::Leaf,
+        }
+    }
+}
+
+fn check_container_type(id: &ContainerID, schema: &ContainerSchema) -> Result<(), SchemaViolation> {
+    if id.container_type() != schema.container_type() {
+        return Err(SchemaViolation {
+            container: id.clone(),
+            index: None,
+            reason: format!(
+                "expected a {} container, found a {} container",
+                schema.container_type(),
+                id.container_type()
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+fn check_value(
+    container: &ContainerID,
+    index: Index,
+    schema: &ValueSchema,
+    value: &LoroValue,
+) -> Result<(), SchemaViolation> {
+    if schema.matches(value) {
+        return Ok(());
+    }
+
+    Err(SchemaViolation {
+        container: container.clone(),
+        index: Some(index),
+        reason: mismatch_reason(schema, value),
+    })
+}
+
+fn mismatch_reason(schema: &ValueSchema, value: &LoroValue) -> String {
+    match value {
+        LoroValue::Container(id) => format!(
+            "expected {:?}, found a {} container",
+            schema,
+            id.container_type()
+        ),
+        value => format!("expected {:?}, found {:?}", schema, value),
+    }
+}
+
+fn is_empty_container_value(value: &LoroValue) -> bool {
+    match value {
+        LoroValue::Map(map) => map.is_empty(),
+        LoroValue::List(list) => list.is_empty(),
+        LoroValue::String(s) => s.is_empty(),
+        _ => false,
+    }
+}
+
+fn collect_child_containers(value: &LoroValue, f: &mut dyn FnMut(&ContainerID)) {
+    match value {
+        LoroValue::Container(id) => f(id),
+        LoroValue::List(list) => list.iter().for_each(|v| collect_child_containers(v, f)),
+        LoroValue::Map(map) => map.values().for_each(|v| collect_child_containers(v, f)),
+        _ => {}
+    }
+}
+
+impl LoroDoc {
+    /// Attach the schema to the doc, or detach it with `None`.
+    ///
+    /// The existing content is not checked. Use [LoroDoc::validate_schema] to check it.
+    pub fn set_schema(&self, schema: Option<DocSchema>) {
+        self.config.set_schema(schema.map(Arc::new));
+    }
+
+    pub fn schema(&self) -> Option<Arc<DocSchema>> {
+        self.config.schema()
+    }
+
+    /// Check the whole doc state against the attached schema
+    pub fn validate_schema(&self) -> Vec<SchemaViolation> {
+        let Some(schema) = self.schema() else {
+            return Vec::new();
+        };
+        let mut state = self.app_state().try_lock().unwrap();
+        schema.check_state(&mut state)
+    }
+}
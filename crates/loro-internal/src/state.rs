@@ -39,6 +39,7 @@ pub(crate) mod container_store;
 #[cfg(feature = "counter")]
 mod counter_state;
 mod dead_containers_cache;
+mod deserializer;
 mod list_state;
 mod map_state;
 mod movable_list_state;
@@ -48,6 +49,7 @@ mod unknown_state;
 
 pub(crate) use self::movable_list_state::{IndexType, MovableListState};
 pub(crate) use container_store::GcStore;
+pub use deserializer::{DocDeserializer, StateDeserializer};
 pub(crate) use list_state::ListState;
 pub(crate) use map_state::MapState;
 pub(crate) use richtext_state::RichtextState;
@@ -414,6 +416,26 @@ impl DocState {
         })
     }
 
+    /// Fork the given containers into a new state at the same version.
+    ///
+    /// The other containers are empty in the new state. It's used to calculate the values of
+    /// the containers at another version without touching this state.
+    pub(crate) fn fork_containers(
+        &mut self,
+        containers: impl Iterator<Item = ContainerIdx>,
+    ) -> Arc<Mutex<Self>> {
+        let ans = Self::new_arc(self.arena.clone(), Weak::new(), self.config.clone());
+        {
+            let mut state = ans.try_lock().unwrap();
+            let peer = state.peer.clone();
+            state.store = self
+                .store
+                .fork_containers(containers, peer, self.config.clone());
+            state.frontiers = self.frontiers.clone();
+        }
+        ans
+    }
+
     pub fn start_recording(&mut self) {
         if self.is_recording() {
             return;
@@ -441,6 +463,12 @@ impl DocState {
     }
 
     /// Take all the diffs that are recorded and convert them to events.
+    /// Whether there are recorded diffs that are not taken as events yet
+    pub(crate) fn has_pending_events(&self) -> bool {
+        self.is_recording()
+            && !(self.event_recorder.diffs.is_empty() && self.event_recorder.events.is_empty())
+    }
+
     pub fn take_events(&mut self) -> Vec<DocDiff> {
         if !self.is_recording() {
             return vec![];
@@ -810,6 +838,22 @@ impl DocState {
         ans
     }
 
+    /// Ensure the alive children of the modified containers are created,
+    /// so that they will be flushed together with their parents.
+    ///
+    /// Unlike [DocState::ensure_all_alive_containers], it only visits the modified containers.
+    /// It's enough when the other containers were flushed before.
+    pub(crate) fn ensure_children_of_unflushed_containers(&mut self) {
+        let mut children = Vec::new();
+        for id in self.store.unflushed_container_ids() {
+            self.get_alive_children_of(&id, &mut children);
+        }
+
+        for id in children.iter() {
+            self.ensure_container(id);
+        }
+    }
+
     pub(crate) fn get_value_by_idx(&mut self, container_idx: ContainerIdx) -> LoroValue {
         self.store
             .get_value(container_idx)
@@ -92,7 +92,6 @@ impl ContainerStore {
             .map(|x| x.get_state_mut(idx, ctx!(self)))
     }
 
-    #[allow(unused)]
     pub fn get_container(&mut self, idx: ContainerIdx) -> Option<&State> {
         self.store
             .get_mut(idx)
@@ -127,6 +126,15 @@ impl ContainerStore {
         self.store.decode(bytes)
     }
 
+    /// Load the states of the containers lazily from the kv store, see [InnerStore::open_kv]
+    pub(crate) fn open_kv(&mut self, kv: KvWrapper) {
+        self.store.open_kv(kv)
+    }
+
+    pub(crate) fn unflushed_container_ids(&self) -> Vec<ContainerID> {
+        self.store.unflushed_container_ids()
+    }
+
     pub(crate) fn decode_gc(
         &mut self,
         shallow_bytes: Bytes,
@@ -142,6 +150,16 @@ impl ContainerStore {
         Ok(f)
     }
 
+    /// Replace the shallow root store after the history is trimmed in place
+    pub(crate) fn replace_shallow_root(
+        &mut self,
+        shallow_bytes: Bytes,
+        start_frontiers: Frontiers,
+    ) -> LoroResult<Option<Frontiers>> {
+        self.shallow_root_store = None;
+        self.decode_gc(shallow_bytes, start_frontiers)
+    }
+
     pub(crate) fn decode_state_by_two_bytes(
         &mut self,
         shallow_bytes: Bytes,
@@ -244,6 +262,31 @@ impl ContainerStore {
         }
     }
 
+    /// Fork only the given containers. The other containers are empty in the new store.
+    pub(crate) fn fork_containers(
+        &mut self,
+        containers: impl Iterator<Item = ContainerIdx>,
+        peer: Arc<AtomicU64>,
+        config: Configure,
+    ) -> ContainerStore {
+        let mut store = InnerStore::new(self.arena.clone());
+        for idx in containers {
+            let Some(state) = self.get_container(idx).map(|s| s.fork(&config)) else {
+                continue;
+            };
+
+            store.ensure_container(idx, || ContainerWrapper::new(state, &self.arena));
+        }
+
+        ContainerStore {
+            store,
+            arena: self.arena.clone(),
+            conf: config,
+            peer,
+            shallow_root_store: None,
+        }
+    }
+
     #[allow(unused)]
     fn check_eq_after_parsing(&mut self, other: &mut ContainerStore) {
         if self.store.len() != other.store.len() {
@@ -63,6 +63,15 @@ impl InnerStore {
             return;
         }
 
+        // The container is stored in kv but not loaded yet. It must not be replaced by
+        // an empty one, otherwise the stored state is overwritten in the next flush
+        if !self.all_loaded {
+            let id = self.arena.get_container_id(idx).unwrap();
+            if self.kv.contains_key(&id.to_bytes()) {
+                return;
+            }
+        }
+
         let c = f();
         self.store.insert(idx, c);
         self.len += 1;
@@ -119,6 +128,15 @@ impl InnerStore {
             }));
     }
 
+    /// The ids of the containers that are modified since the last flush
+    pub(crate) fn unflushed_container_ids(&self) -> Vec<ContainerID> {
+        self.store
+            .iter()
+            .filter(|(_, c)| !c.is_flushed())
+            .map(|(idx, _)| self.arena.get_container_id(*idx).unwrap())
+            .collect()
+    }
+
     pub(crate) fn get_kv(&self) -> &KvWrapper {
         &self.kv
     }
@@ -196,6 +214,35 @@ impl InnerStore {
         Ok(())
     }
 
+    /// Use the containers stored in the kv store.
+    ///
+    /// Only the ids and the parents of the containers are registered in the arena,
+    /// their states are loaded from the kv store when they are accessed.
+    pub(crate) fn open_kv(&mut self, kv: KvWrapper) {
+        assert!(self.store.is_empty());
+        self.kv = kv;
+        let mut count = 0;
+        self.kv.with_kv(|kv| {
+            self.arena.with_guards(|guards| {
+                for (k, v) in kv.scan(Bound::Unbounded, Bound::Unbounded) {
+                    if k == FRONTIERS_KEY {
+                        continue;
+                    }
+
+                    count += 1;
+                    let cid = ContainerID::from_bytes(&k);
+                    let idx = guards.register_container(&cid);
+                    let p =
+                        ContainerWrapper::decode_parent(&v).map(|p| guards.register_container(&p));
+                    guards.set_parent(idx, p);
+                }
+            });
+        });
+
+        self.len = count;
+        self.all_loaded = count == 0;
+    }
+
     fn load_all(&mut self) {
         if self.all_loaded {
             return;
@@ -0,0 +1,275 @@
+use std::{
+    ops::DerefMut,
+    sync::{Arc, MutexGuard},
+};
+
+use loro_common::{ContainerID, ContainerType, LoroError, LoroValue};
+use serde::de::{
+    self, value::StringDeserializer, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess,
+    SeqAccess, VariantAccess, Visitor,
+};
+
+use super::DocState;
+
+/// A serde [Deserializer](de::Deserializer) over the current state of a container.
+///
+/// The child containers are read lazily: only the shallow value of a container is built
+/// when it's visited, and the containers skipped by the target type are never read.
+///
+/// It holds the lock of the doc state until it's dropped.
+pub type DocDeserializer<'a> = StateDeserializer<MutexGuard<'a, DocState>>;
+
+/// A serde [Deserializer](de::Deserializer) over a value in the doc state,
+/// whose child containers are resolved on demand.
+pub struct StateDeserializer<S> {
+    state: S,
+    value: LoroValue,
+}
+
+impl<'a> DocDeserializer<'a> {
+    pub(crate) fn new(state: MutexGuard<'a, DocState>, id: ContainerID) -> Self {
+        Self {
+            state,
+            value: LoroValue::Container(id),
+        }
+    }
+}
+
+impl<S: DerefMut<Target = DocState>> StateDeserializer<S> {
+    /// Replace the container by its shallow value
+    fn resolve(&mut self) -> LoroValue {
+        match std::mem::take(&mut self.value) {
+            LoroValue::Container(id) => read_container(&mut self.state, &id),
+            value => value,
+        }
+    }
+}
+
+fn read_container(state: &mut DocState, id: &ContainerID) -> LoroValue {
+    if let ContainerType::Unknown(_) = id.container_type() {
+        return LoroValue::Null;
+    }
+
+    let idx = state.arena.register_container(id);
+    state.get_value_by_idx(idx)
+}
+
+impl<'de, S: DerefMut<Target = DocState>> de::Deserializer<'de> for StateDeserializer<S> {
+    type Error = LoroError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, LoroError> {
+        match self.resolve() {
+            LoroValue::Null => visitor.visit_unit(),
+            LoroValue::Bool(b) => visitor.visit_bool(b),
+            LoroValue::Double(d) => visitor.visit_f64(d),
+            LoroValue::I64(i) => visitor.visit_i64(i),
+            LoroValue::String(s) => visitor.visit_string(Arc::unwrap_or_clone(s)),
+            LoroValue::Binary(b) => visitor.visit_byte_buf(Arc::unwrap_or_clone(b)),
+            LoroValue::List(list) => {
+                let list = Arc::unwrap_or_clone(list);
+                let len = list.len();
+                let mut seq = SeqDeserializer {
+                    state: &mut self.state,
+                    iter: list.into_iter(),
+                };
+                let ans = visitor.visit_seq(&mut seq)?;
+                if seq.iter.len() != 0 {
+                    return Err(de::Error::invalid_length(
+                        len,
+                        &"fewer elements in the list",
+                    ));
+                }
+                Ok(ans)
+            }
+            LoroValue::Map(map) => visitor.visit_map(MapDeserializer {
+                state: &mut self.state,
+                iter: Arc::unwrap_or_clone(map).into_iter(),
+                value: None,
+            }),
+            LoroValue::Container(id) => Err(de::Error::custom(format!(
+                "unexpected container {} in the state",
+                id
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, LoroError> {
+        match self.resolve() {
+            LoroValue::Null => visitor.visit_none(),
+            value => {
+                self.value = value;
+                visitor.visit_some(self)
+            }
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, LoroError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, LoroError> {
+        match self.resolve() {
+            LoroValue::String(s) => visitor.visit_enum(Arc::unwrap_or_clone(s).into_deserializer()),
+            LoroValue::Map(map) if map.len() == 1 => {
+                let (variant, value) = Arc::unwrap_or_clone(map).into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer {
+                    state: &mut self.state,
+                    variant,
+                    value,
+                })
+            }
+            value => Err(de::Error::invalid_type(
+                unexpected(&value),
+                &"a string or a map with a single key",
+            )),
+        }
+    }
+
+    /// The ignored values are not read, so the skipped child containers are never visited
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, LoroError> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+fn unexpected(value: &LoroValue) -> de::Unexpected<'_> {
+    match value {
+        LoroValue::Null => de::Unexpected::Unit,
+        LoroValue::Bool(b) => de::Unexpected::Bool(*b),
+        LoroValue::Double(d) => de::Unexpected::Float(*d),
+        LoroValue::I64(i) => de::Unexpected::Signed(*i),
+        LoroValue::String(s) => de::Unexpected::Str(s),
+        LoroValue::Binary(b) => de::Unexpected::Bytes(b),
+        LoroValue::List(_) => de::Unexpected::Seq,
+        LoroValue::Map(_) => de::Unexpected::Map,
+        LoroValue::Container(_) => de::Unexpected::Other("container"),
+    }
+}
+
+struct SeqDeserializer<'a> {
+    state: &'a mut DocState,
+    iter: std::vec::IntoIter<LoroValue>,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer<'_> {
+    type Error = LoroError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, LoroError> {
+        match self.iter.next() {
+            Some(value) => seed
+                .deserialize(StateDeserializer {
+                    state: &mut *self.state,
+                    value,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer<'a, I> {
+    state: &'a mut DocState,
+    iter: I,
+    value: Option<LoroValue>,
+}
+
+impl<'de, I: Iterator<Item = (String, LoroValue)>> MapAccess<'de> for MapDeserializer<'_, I> {
+    type Error = LoroError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, LoroError> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, LoroError> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| LoroError::DeserializeError("value is missing".into()))?;
+        seed.deserialize(StateDeserializer {
+            state: &mut *self.state,
+            value,
+        })
+    }
+}
+
+struct EnumDeserializer<'a> {
+    state: &'a mut DocState,
+    variant: String,
+    value: LoroValue,
+}
+
+impl<'de, 'a> EnumAccess<'de> for EnumDeserializer<'a> {
+    type Error = LoroError;
+    type Variant = StateDeserializer<&'a mut DocState>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), LoroError> {
+        let variant = seed.deserialize(StringDeserializer::<LoroError>::new(self.variant))?;
+        Ok((
+            variant,
+            StateDeserializer {
+                state: self.state,
+                value: self.value,
+            },
+        ))
+    }
+}
+
+impl<'de> VariantAccess<'de> for StateDeserializer<&mut DocState> {
+    type Error = LoroError;
+
+    fn unit_variant(self) -> Result<(), LoroError> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, LoroError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, LoroError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, LoroError> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
@@ -18,7 +18,7 @@ use crate::{
             richtext_state::{
                 DrainInfo, EntityRangeInfo, IterRangeItem, PosType, RichtextStateChunk,
             },
-            AnchorType, RichtextState as InnerState, StyleOp, Styles,
+            AnchorType, RichtextState as InnerState, StyleKey, StyleOp, Styles,
         },
     },
     delta::{StyleMeta, StyleMetaItem},
@@ -815,6 +815,62 @@ impl RichtextState {
             .get_entity_range_and_text_styles_at_range(range, pos_type)
     }
 
+    /// Get the resolved values of the style `key` of the text spans in the given event range.
+    ///
+    /// It returns the event length of each span and the style value of it.
+    pub(crate) fn get_style_values_at_event_range(
+        &mut self,
+        range: Range<usize>,
+        key: &InternalString,
+    ) -> Vec<(usize, Option<LoroValue>)> {
+        if range.is_empty() {
+            return Vec::new();
+        }
+
+        let state = self.state.get_mut();
+        let (entity_range, _) =
+            state.get_entity_range_and_text_styles_at_range(range, PosType::Event);
+        let style_key = StyleKey::Key(key.clone());
+        let mut ans = Vec::new();
+        for IterRangeItem {
+            chunk,
+            styles,
+            event_len,
+            ..
+        } in state.iter_range(entity_range)
+        {
+            if let RichtextStateChunk::Text(_) = chunk {
+                ans.push((event_len, styles.get(&style_key).and_then(|v| v.to_value())));
+            }
+        }
+
+        ans
+    }
+
+    /// Get the styles of the char at the event index `pos` that match `filter`,
+    /// with their values and event ranges.
+    #[inline]
+    pub(crate) fn get_style_ranges_at_event_index(
+        &mut self,
+        pos: usize,
+        filter: impl FnMut(&StyleKey) -> bool,
+    ) -> Vec<(StyleKey, Range<usize>, LoroValue)> {
+        self.state
+            .get_mut()
+            .get_style_ranges_at_event_index(pos, filter)
+    }
+
+    /// Get the event range and the value of the first span that has the style `key`.
+    #[inline]
+    pub(crate) fn get_style_event_range(
+        &mut self,
+        key: &InternalString,
+    ) -> Option<(Range<usize>, LoroValue)> {
+        self.state
+            .get_mut()
+            .get_style_event_range(&StyleKey::Key(key.clone()))
+    }
+
     #[inline]
     pub(crate) fn get_styles_at_entity_index(&mut self, entity_index: usize) -> StyleMeta {
         self.state
@@ -898,6 +898,150 @@ impl TreeState {
             .map_or(false, |x| x.parent == *parent)
     }
 
+    /// Get the ancestors of the node, from its parent to the root-level node.
+    ///
+    /// Return None if the node does not exist or is deleted.
+    ///
+    /// O(depth)
+    pub fn ancestors(&self, target: &TreeID) -> Option<Vec<TreeID>> {
+        let mut ans = vec![];
+        self.for_each_ancestor(target, |p| ans.push(p))?;
+        Some(ans)
+    }
+
+    /// Get the depth of the node. The root-level nodes have depth 0.
+    ///
+    /// Return None if the node does not exist or is deleted.
+    ///
+    /// O(depth)
+    pub fn depth(&self, target: &TreeID) -> Option<usize> {
+        let mut depth = 0;
+        self.for_each_ancestor(target, |_| depth += 1)?;
+        Some(depth)
+    }
+
+    fn for_each_ancestor(&self, target: &TreeID, mut f: impl FnMut(TreeID)) -> Option<()> {
+        let mut parent = self.parent(target)?;
+        loop {
+            match parent {
+                TreeParentId::Node(p) => {
+                    f(p);
+                    parent = self.parent(&p)?;
+                }
+                TreeParentId::Root => return Some(()),
+                TreeParentId::Deleted => return None,
+                TreeParentId::Unexist => unreachable!(),
+            }
+        }
+    }
+
+    /// Get the lowest common ancestor of the two nodes. A node is regarded as an ancestor of itself.
+    ///
+    /// Return [TreeParentId::Root] if the nodes are under different root-level nodes,
+    /// or None if any of them does not exist or is deleted.
+    ///
+    /// O(depth)
+    pub fn lowest_common_ancestor(&self, a: &TreeID, b: &TreeID) -> Option<TreeParentId> {
+        let mut a_path = self.ancestors(a)?;
+        let mut b_path = self.ancestors(b)?;
+        a_path.reverse();
+        a_path.push(*a);
+        b_path.reverse();
+        b_path.push(*b);
+        Some(
+            a_path
+                .iter()
+                .zip(b_path.iter())
+                .take_while(|(x, y)| x == y)
+                .last()
+                .map_or(TreeParentId::Root, |(x, _)| TreeParentId::Node(*x)),
+        )
+    }
+
+    /// Get all the descendants of `parent` in pre-order, i.e. a node comes before its
+    /// descendants and the siblings are in order. `parent` itself is not included.
+    pub fn preorder_nodes_under(&self, parent: TreeParentId) -> Vec<TreeID> {
+        let mut ans = vec![];
+        let mut stack = vec![];
+        self.push_children_reversed(&parent, &mut stack, |id| id);
+        while let Some(node) = stack.pop() {
+            ans.push(node);
+            self.push_children_reversed(&TreeParentId::Node(node), &mut stack, |id| id);
+        }
+        ans
+    }
+
+    /// Get all the descendants of `parent` in post-order, i.e. a node comes after its
+    /// descendants and the siblings are in order. `parent` itself is not included.
+    pub fn postorder_nodes_under(&self, parent: TreeParentId) -> Vec<TreeID> {
+        let mut ans = vec![];
+        let mut stack = vec![];
+        self.push_children_reversed(&parent, &mut stack, |id| (id, false));
+        while let Some((node, visited)) = stack.pop() {
+            if visited {
+                ans.push(node);
+                continue;
+            }
+            stack.push((node, true));
+            self.push_children_reversed(&TreeParentId::Node(node), &mut stack, |id| (id, false));
+        }
+        ans
+    }
+
+    fn push_children_reversed<T>(
+        &self,
+        parent: &TreeParentId,
+        stack: &mut Vec<T>,
+        f: impl Fn(TreeID) -> T,
+    ) {
+        if let Some(children) = self.children.get(parent) {
+            let start = stack.len();
+            stack.extend(children.iter().map(|(_, id)| f(*id)));
+            stack[start..].reverse();
+        }
+    }
+
+    /// Get the node by its index path. `path[0]` is the index of the root-level node,
+    /// `path[1]` is the index of the child of that node, and so on.
+    ///
+    /// Return None if the path is empty or out of bound.
+    pub fn get_node_by_index_path(&self, path: &[usize]) -> Option<TreeID> {
+        let mut ans = None;
+        let mut parent = TreeParentId::Root;
+        for &index in path {
+            let id = self.get_id_by_index(&parent, index)?;
+            ans = Some(id);
+            parent = TreeParentId::Node(id);
+        }
+        ans
+    }
+
+    /// Get the index path of the node. It's the inverse of [Self::get_node_by_index_path].
+    ///
+    /// Return None if the node does not exist or is deleted.
+    pub fn get_index_path(&self, target: &TreeID) -> Option<Vec<usize>> {
+        let ancestors = self.ancestors(target)?;
+        let mut ans = Vec::with_capacity(ancestors.len() + 1);
+        for node in ancestors.iter().rev().chain(std::iter::once(target)) {
+            ans.push(self.get_index_by_tree_id(node)?);
+        }
+        Some(ans)
+    }
+
+    /// The max depth of the descendants of the node relative to the node.
+    /// It's 0 if the node has no children.
+    pub(crate) fn subtree_height(&self, target: &TreeID) -> usize {
+        let mut ans = 0;
+        let mut stack = vec![(*target, 0)];
+        while let Some((node, height)) = stack.pop() {
+            ans = ans.max(height);
+            if let Some(children) = self.children.get(&TreeParentId::Node(node)) {
+                stack.extend(children.iter().map(|(_, id)| (*id, height + 1)));
+            }
+        }
+        ans
+    }
+
     /// Delete the position cache of the node
     pub(crate) fn delete_position(&mut self, parent: &TreeParentId, target: &TreeID) {
         if let Some(x) = self.children.get_mut(parent) {
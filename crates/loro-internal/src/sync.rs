@@ -0,0 +1,285 @@
+//! A transport-agnostic sync protocol built on top of [LoroDoc::export] and [LoroDoc::import].
+//!
+//! Two peers each hold a [SyncSession]. The session produces framed messages that
+//! should be delivered to the other side in order, and consumes the messages it receives.
+//!
+//! The protocol works as follows:
+//!
+//! 1. Handshake: each side sends a [SyncMessage::Hello] with its oplog version vector
+//!    and the start version of its shallow history.
+//! 2. When a side receives the hello, it replies with the updates the other side is
+//!    missing. If the remote peer is behind our shallow history start version, the
+//!    updates cannot be applied on its side, so a snapshot is sent instead.
+//! 3. After the handshake, local changes can be flushed with [SyncSession::poll_local].
+//!    Each received update is acknowledged with the new version of the receiver.
+//! 4. When the connection is lost, call [SyncSession::disconnect] and then
+//!    [SyncSession::start] again after reconnecting. The handshake will figure out
+//!    what has been lost in between.
+use loro_common::{InternalString, LoroError, LoroResult, ID};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    encoding::ImportStatus, loro::ExportMode, version::VersionRange, LoroDoc, VersionVector,
+};
+
+const SYNC_PROTOCOL_VERSION: u8 = 1;
+
+/// The message exchanged between two [SyncSession]s.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SyncMessage {
+    /// Sent when the session starts or resumes.
+    Hello {
+        /// The oplog version vector of the sender
+        vv: VersionVector,
+        /// The shallow history start version of the sender.
+        /// It's empty if the sender has the full history.
+        shallow_since_vv: VersionVector,
+    },
+    /// The updates the receiver is missing, exported by [ExportMode::Updates].
+    Updates { data: Vec<u8> },
+    /// A snapshot sent when the receiver is behind the sender's shallow history start version.
+    Snapshot { data: Vec<u8> },
+    /// Acknowledges the received data with the new oplog version vector of the sender.
+    Ack { vv: VersionVector },
+}
+
+impl SyncMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut ans = vec![SYNC_PROTOCOL_VERSION];
+        ans.extend_from_slice(&postcard::to_allocvec(self).unwrap());
+        ans
+    }
+
+    pub fn decode(bytes: &[u8]) -> LoroResult<Self> {
+        let Some((&version, body)) = bytes.split_first() else {
+            return Err(LoroError::DecodeError("Empty sync message".into()));
+        };
+        if version != SYNC_PROTOCOL_VERSION {
+            return Err(LoroError::IncompatibleFutureEncodingError(version as usize));
+        }
+        postcard::from_bytes(body)
+            .map_err(|e| LoroError::DecodeError(format!("Invalid sync message: {}", e).into()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncState {
+    /// The session is not connected. Call [SyncSession::start] to begin the handshake.
+    #[default]
+    Disconnected,
+    /// The hello message has been sent or received, but not both.
+    Handshaking,
+    /// Both sides have exchanged their versions. Local updates can be sent incrementally.
+    Synced,
+}
+
+/// The state machine of the sync protocol between the local doc and one remote peer.
+///
+/// It doesn't own the doc or the transport. Feed it with the received messages
+/// and deliver the returned messages to the remote peer.
+#[derive(Debug, Clone)]
+pub struct SyncSession {
+    state: SyncState,
+    hello_sent: bool,
+    hello_received: bool,
+    /// The version we believe the remote peer has, including the updates in flight.
+    remote_vv: VersionVector,
+    /// The version the remote peer has acknowledged.
+    acked_remote_vv: VersionVector,
+    remote_shallow_since_vv: VersionVector,
+    origin: InternalString,
+    last_import_status: Option<ImportStatus>,
+}
+
+impl Default for SyncSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyncSession {
+    pub fn new() -> Self {
+        Self {
+            state: SyncState::Disconnected,
+            hello_sent: false,
+            hello_received: false,
+            remote_vv: Default::default(),
+            acked_remote_vv: Default::default(),
+            remote_shallow_since_vv: Default::default(),
+            origin: "sync".into(),
+            last_import_status: None,
+        }
+    }
+
+    /// Set the origin of the imports triggered by this session. The default value is `"sync"`.
+    pub fn set_origin(&mut self, origin: &str) {
+        self.origin = origin.into();
+    }
+
+    pub fn state(&self) -> SyncState {
+        self.state
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.state == SyncState::Synced
+    }
+
+    /// The version that the remote peer is known to have.
+    pub fn remote_vv(&self) -> &VersionVector {
+        &self.acked_remote_vv
+    }
+
+    /// The import status of the last received updates or snapshot.
+    pub fn last_import_status(&self) -> Option<&ImportStatus> {
+        self.last_import_status.as_ref()
+    }
+
+    /// Start or resume the session. Returns the hello message that should be sent to the remote peer.
+    pub fn start(&mut self, doc: &LoroDoc) -> Vec<u8> {
+        self.hello_sent = true;
+        self.update_state();
+        Self::hello(doc).encode()
+    }
+
+    /// Mark the connection as lost.
+    ///
+    /// The updates that have not been acknowledged are considered lost. They will be
+    /// resent after the next handshake if the remote peer doesn't have them.
+    pub fn disconnect(&mut self) {
+        self.state = SyncState::Disconnected;
+        self.hello_sent = false;
+        self.hello_received = false;
+        self.remote_vv = self.acked_remote_vv.clone();
+    }
+
+    /// Handle a message from the remote peer. Returns the messages that should be sent back.
+    pub fn receive(&mut self, doc: &LoroDoc, bytes: &[u8]) -> LoroResult<Vec<Vec<u8>>> {
+        let msg = SyncMessage::decode(bytes)?;
+        let mut ans = Vec::new();
+        match msg {
+            SyncMessage::Hello {
+                vv,
+                shallow_since_vv,
+            } => {
+                if !self.hello_sent {
+                    self.hello_sent = true;
+                    ans.push(Self::hello(doc).encode());
+                }
+
+                self.hello_received = true;
+                self.remote_vv = vv.clone();
+                self.acked_remote_vv = vv;
+                self.remote_shallow_since_vv = shallow_since_vv;
+                self.update_state();
+                if let Some(msg) = self.export_missing_updates(doc)? {
+                    ans.push(msg.encode());
+                }
+            }
+            SyncMessage::Updates { data } => {
+                let status = doc.import_with(&data, self.origin.clone())?;
+                // The sender has all the ops it sent
+                extend_vv_by_range(&mut self.remote_vv, &status.success);
+                extend_vv_by_range(&mut self.acked_remote_vv, &status.success);
+                let has_pending = status.pending.is_some();
+                self.last_import_status = Some(status);
+                if has_pending {
+                    // The remote peer has a wrong assumption of our version.
+                    // Redo the handshake so that it can send the missing updates.
+                    ans.push(Self::hello(doc).encode());
+                } else {
+                    ans.push(SyncMessage::Ack { vv: doc.oplog_vv() }.encode());
+                }
+            }
+            SyncMessage::Snapshot { data } => {
+                let local_vv = doc.oplog_vv();
+                if !local_vv.is_empty() && !local_vv.includes_vv(&self.remote_shallow_since_vv) {
+                    return Err(LoroError::SyncBehindRemoteShallowRoot);
+                }
+
+                let status = doc.import_with(&data, self.origin.clone())?;
+                extend_vv_by_range(&mut self.remote_vv, &status.success);
+                extend_vv_by_range(&mut self.acked_remote_vv, &status.success);
+                self.last_import_status = Some(status);
+                ans.push(SyncMessage::Ack { vv: doc.oplog_vv() }.encode());
+            }
+            SyncMessage::Ack { vv } => {
+                self.acked_remote_vv.merge(&vv);
+                self.remote_vv.merge(&vv);
+            }
+        }
+
+        Ok(ans)
+    }
+
+    /// Export the local updates that haven't been sent to the remote peer.
+    ///
+    /// It returns `None` if the handshake is not finished or there is nothing new.
+    pub fn poll_local(&mut self, doc: &LoroDoc) -> LoroResult<Option<Vec<u8>>> {
+        if self.state != SyncState::Synced {
+            return Ok(None);
+        }
+
+        Ok(self.export_missing_updates(doc)?.map(|x| x.encode()))
+    }
+
+    fn hello(doc: &LoroDoc) -> SyncMessage {
+        SyncMessage::Hello {
+            vv: doc.oplog_vv(),
+            shallow_since_vv: doc.shallow_since_vv().to_vv(),
+        }
+    }
+
+    fn update_state(&mut self) {
+        self.state = if self.hello_sent && self.hello_received {
+            SyncState::Synced
+        } else {
+            SyncState::Handshaking
+        };
+    }
+
+    fn export_missing_updates(&mut self, doc: &LoroDoc) -> LoroResult<Option<SyncMessage>> {
+        let local_vv = doc.oplog_vv();
+        if self.remote_vv.includes_vv(&local_vv) {
+            return Ok(None);
+        }
+
+        let shallow_since_vv = doc.shallow_since_vv().to_vv();
+        let msg = if !shallow_since_vv.is_empty() && !self.remote_vv.includes_vv(&shallow_since_vv)
+        {
+            // The remote peer cannot apply the updates that depend on the history we don't have
+            let data = doc.export(ExportMode::Snapshot)?;
+            SyncMessage::Snapshot { data }
+        } else {
+            let data = doc.export(ExportMode::updates(&self.remote_vv))?;
+            SyncMessage::Updates { data }
+        };
+
+        self.remote_vv.merge(&local_vv);
+        Ok(Some(msg))
+    }
+}
+
+fn extend_vv_by_range(vv: &mut VersionVector, range: &VersionRange) {
+    for (&peer, &(_, end)) in range.iter() {
+        vv.extend_to_include_end_id(ID::new(peer, end));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_decode_message() {
+        let mut vv = VersionVector::new();
+        vv.insert(1, 10);
+        let msg = SyncMessage::Hello {
+            vv,
+            shallow_since_vv: Default::default(),
+        };
+        let bytes = msg.encode();
+        assert_eq!(SyncMessage::decode(&bytes).unwrap(), msg);
+        assert!(SyncMessage::decode(&[]).is_err());
+        assert!(SyncMessage::decode(&[SYNC_PROTOCOL_VERSION + 1, 0]).is_err());
+    }
+}
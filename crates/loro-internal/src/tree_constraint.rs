@@ -0,0 +1,741 @@
+//! Application-defined invariants of movable trees.
+//!
+//! [TreeConstraints] limit where the nodes of a tree can be placed: the max depth of the nodes,
+//! the max number of children of a parent, and a predicate on the meta of a node and the meta
+//! of its parent, e.g. "folders can only be placed under folders". Cycles are always rejected
+//! by the tree itself.
+//!
+//! After the constraints of a tree are set by [LoroDoc::set_tree_constraints]:
+//!
+//! - Local creations and moves that violate them are rejected with
+//!   [LoroTreeError::ConstraintViolation] before they are applied to the transaction.
+//!   Reordering a node inside its parent is always allowed. The local edits of the entries
+//!   of a meta are checked against `allow_child` as well.
+//! - Imported moves cannot be rejected, otherwise the doc would diverge from the other peers.
+//!   The nodes affected by the import are checked: the created or moved nodes with their
+//!   descendants and new siblings, and the nodes whose metas are changed with their children.
+//!   The violations are passed to the resolver of the constraints, and the returned
+//!   [TreeResolution]s are committed as new local ops with the origin
+//!   [TREE_RESOLUTION_ORIGIN]. The violations that remain are reported in
+//!   [ImportStatus::tree_violations].
+//!
+//! The violations are computed from the state of the tree and sorted by the nodes, so a
+//! resolver that only depends on its input makes the same decision on every peer that
+//! reaches the same state.
+//!
+//! The resolutions are not deduplicated across peers: every peer that imports the violating
+//! ops commits its own [TreeResolution], so N peers create N equivalent moves or deletions.
+//! They are concurrent edits of the same nodes that lead to the same state, so the peers still
+//! converge, and importing them doesn't introduce new violations.
+//!
+//! [ImportStatus::tree_violations]: crate::encoding::ImportStatus::tree_violations
+use std::{collections::BTreeMap, sync::Arc};
+
+use loro_common::{
+    ContainerID, ContainerType, LoroError, LoroResult, LoroTreeError, LoroValue, TreeID,
+};
+
+use crate::{
+    container::{idx::ContainerIdx, map::MapSet, tree::tree_op::TreeOp},
+    delta::{TreeExternalDiff, TreeInternalDiff},
+    event::{Diff, DiffVariant, InternalContainerDiff, InternalDiff},
+    loro::CommitOptions,
+    state::{DocState, TreeParentId},
+    FxHashMap, LoroDoc,
+};
+
+/// The origin of the commit that applies the resolutions of the violations introduced by
+/// an import.
+pub const TREE_RESOLUTION_ORIGIN: &str = "loro-tree-resolution";
+
+type ChildPredicate = dyn Fn(&LoroValue, Option<&LoroValue>) -> bool + Send + Sync;
+type Resolver = dyn Fn(&TreeViolation) -> TreeResolution + Send + Sync;
+/// The deep values of the metas of the nodes, loaded on demand
+type Metas = FxHashMap<TreeID, LoroValue>;
+
+/// The invariants of a tree. All of them are optional.
+#[derive(Clone, Default)]
+pub struct TreeConstraints {
+    max_depth: Option<usize>,
+    max_children: Option<usize>,
+    allow_child: Option<Arc<ChildPredicate>>,
+    resolver: Option<Arc<Resolver>>,
+}
+
+/// A node of a tree that doesn't satisfy the constraints of the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeViolation {
+    pub tree: ContainerID,
+    pub node: TreeID,
+    pub parent: TreeParentId,
+    pub kind: TreeViolationKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeViolationKind {
+    /// The node, or one of its descendants, is deeper than the max depth
+    MaxDepth { depth: usize, max: usize },
+    /// The node is at `index` of its parent, which exceeds the max number of children
+    MaxChildren { index: usize, max: usize },
+    /// The node is not allowed to be a child of its parent
+    ChildNotAllowed,
+}
+
+/// How to fix a violation introduced by an import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeResolution {
+    /// Leave the node where it is. The violation is reported in the import status.
+    Keep,
+    /// Move the node to the end of the children of the parent
+    MoveTo(TreeParentId),
+    /// Delete the node and its descendants
+    Delete,
+}
+
+impl std::fmt::Debug for TreeConstraints {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TreeConstraints")
+            .field("max_depth", &self.max_depth)
+            .field("max_children", &self.max_children)
+            .field("allow_child", &self.allow_child.is_some())
+            .field("resolver", &self.resolver.is_some())
+            .finish()
+    }
+}
+
+impl std::fmt::Display for TreeViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "node {} of {}: ", self.node, self.tree)?;
+        match self.kind {
+            TreeViolationKind::MaxDepth { depth, max } => {
+                write!(f, "depth {} exceeds the max depth {}", depth, max)
+            }
+            TreeViolationKind::MaxChildren { index, max } => {
+                write!(
+                    f,
+                    "index {} exceeds the max number of children {}",
+                    index, max
+                )
+            }
+            TreeViolationKind::ChildNotAllowed => {
+                write!(f, "it's not allowed to be a child of {:?}", self.parent)
+            }
+        }
+    }
+}
+
+impl From<TreeViolation> for LoroError {
+    fn from(value: TreeViolation) -> Self {
+        LoroTreeError::ConstraintViolation(value.to_string().into_boxed_str()).into()
+    }
+}
+
+impl TreeConstraints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The max depth of the nodes. The root-level nodes have depth 0.
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// The max number of children of a node. It also limits the number of root-level nodes.
+    pub fn max_children(mut self, max: usize) -> Self {
+        self.max_children = Some(max);
+        self
+    }
+
+    /// Whether a node can be a child of a parent, given the deep values of their metas.
+    /// The parent meta is `None` for the root-level nodes.
+    ///
+    /// A new node is checked with an empty meta when it's created.
+    pub fn allow_child(
+        mut self,
+        f: impl Fn(&LoroValue, Option<&LoroValue>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.allow_child = Some(Arc::new(f));
+        self
+    }
+
+    /// Decide how to fix the violations introduced by imports.
+    ///
+    /// It should only depend on its input, so that the peers make the same decision.
+    /// Without a resolver, the violations are only reported.
+    pub fn resolver(
+        mut self,
+        f: impl Fn(&TreeViolation) -> TreeResolution + Send + Sync + 'static,
+    ) -> Self {
+        self.resolver = Some(Arc::new(f));
+        self
+    }
+
+    /// Check the op before it's applied in a local transaction
+    pub(crate) fn check_local_op(
+        &self,
+        state: &mut DocState,
+        idx: ContainerIdx,
+        op: &TreeOp,
+    ) -> LoroResult<()> {
+        let (target, parent) = match op {
+            TreeOp::Create { .. } | TreeOp::Move { .. } => (op.target(), op.parent_id()),
+            TreeOp::Delete { .. } => return Ok(()),
+        };
+        let (old_parent, depth, height, children_num) = state.with_state(idx, |s| {
+            let tree = s.as_tree_state().unwrap();
+            let depth = match parent {
+                TreeParentId::Node(p) => tree.depth(&p).map(|d| d + 1),
+                TreeParentId::Root => Some(0),
+                TreeParentId::Deleted | TreeParentId::Unexist => None,
+            };
+            (
+                tree.parent(&target),
+                depth,
+                tree.subtree_height(&target),
+                tree.children_num(&parent).unwrap_or(0),
+            )
+        });
+        // The parent is invalid, or the node is reordered inside its parent
+        let Some(depth) = depth else {
+            return Ok(());
+        };
+        if old_parent == Some(parent) {
+            return Ok(());
+        }
+
+        let tree = state.arena.idx_to_id(idx).unwrap();
+        let violation = |kind| TreeViolation {
+            tree: tree.clone(),
+            node: target,
+            parent,
+            kind,
+        };
+        if let Some(max) = self.max_depth {
+            if depth + height > max {
+                return Err(violation(TreeViolationKind::MaxDepth {
+                    depth: depth + height,
+                    max,
+                })
+                .into());
+            }
+        }
+        if let Some(max) = self.max_children {
+            if children_num >= max {
+                return Err(violation(TreeViolationKind::MaxChildren {
+                    index: children_num,
+                    max,
+                })
+                .into());
+            }
+        }
+        if !self.is_child_allowed(state, &mut Metas::default(), target, parent) {
+            return Err(violation(TreeViolationKind::ChildNotAllowed).into());
+        }
+
+        Ok(())
+    }
+
+    /// Check a map op on the meta of a node before it's applied in a local transaction.
+    ///
+    /// It's rejected if the new meta makes the node not allowed under its parent, or one of its
+    /// children not allowed under it. The relations that are already violated are ignored.
+    /// Only the entries of the meta are checked, not the content of its child containers.
+    pub(crate) fn check_local_meta_op(
+        &self,
+        state: &mut DocState,
+        idx: ContainerIdx,
+        meta: ContainerIdx,
+        op: &MapSet,
+    ) -> LoroResult<()> {
+        let Some(allow_child) = &self.allow_child else {
+            return Ok(());
+        };
+        let Some(node) = meta_to_node(state.arena.idx_to_id(meta).unwrap()) else {
+            return Ok(());
+        };
+        let place = state.with_state(idx, |s| {
+            let tree = s.as_tree_state().unwrap();
+            tree.depth(&node)?;
+            let children: Vec<TreeID> = tree
+                .get_children(&TreeParentId::Node(node))
+                .map(|x| x.collect())
+                .unwrap_or_default();
+            Some((tree.parent(&node).unwrap(), children))
+        });
+        // The node is deleted
+        let Some((parent, children)) = place else {
+            return Ok(());
+        };
+
+        let mut metas = Metas::default();
+        let old_meta = get_meta(state, &mut metas, node);
+        let mut new_meta = old_meta.as_map().map(|m| (**m).clone()).unwrap_or_default();
+        match &op.value {
+            Some(LoroValue::Container(id)) => {
+                new_meta.insert(op.key.to_string(), id.container_type().default_value())
+            }
+            Some(value) => new_meta.insert(op.key.to_string(), value.clone()),
+            None => new_meta.remove(op.key.as_str()),
+        };
+        let new_meta = LoroValue::Map(Arc::new(new_meta));
+
+        let tree = state.arena.idx_to_id(idx).unwrap();
+        let violation = |node, parent| TreeViolation {
+            tree: tree.clone(),
+            node,
+            parent,
+            kind: TreeViolationKind::ChildNotAllowed,
+        };
+        let parent_meta = parent.as_node().map(|p| get_meta(state, &mut metas, *p));
+        if allow_child(&old_meta, parent_meta.as_ref())
+            && !allow_child(&new_meta, parent_meta.as_ref())
+        {
+            return Err(violation(node, parent).into());
+        }
+        for child in children {
+            let child_meta = get_meta(state, &mut metas, child);
+            if allow_child(&child_meta, Some(&old_meta))
+                && !allow_child(&child_meta, Some(&new_meta))
+            {
+                return Err(violation(child, TreeParentId::Node(node)).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check all the alive nodes of the tree, in the breadth-first order
+    pub(crate) fn check_tree(&self, state: &mut DocState, idx: ContainerIdx) -> Vec<TreeViolation> {
+        let tree = state.arena.idx_to_id(idx).unwrap();
+        let nodes = state.with_state(idx, |s| s.as_tree_state().unwrap().tree_nodes());
+        let mut depths: FxHashMap<TreeID, usize> = FxHashMap::default();
+        let mut metas = Metas::default();
+        let mut ans = Vec::new();
+        for node in nodes {
+            let depth = match node.parent {
+                TreeParentId::Node(p) => depths[&p] + 1,
+                _ => 0,
+            };
+            depths.insert(node.id, depth);
+            for kind in self.check_node(state, &mut metas, node.id, node.parent, depth, node.index)
+            {
+                ans.push(TreeViolation {
+                    tree: tree.clone(),
+                    node: node.id,
+                    parent: node.parent,
+                    kind,
+                });
+            }
+        }
+        ans
+    }
+
+    /// Check the nodes affected by the changes of the tree.
+    ///
+    /// - The `moved` nodes, i.e. the created or moved ones, are fully checked. Their
+    ///   descendants are checked against the max depth, and their new siblings against the
+    ///   max number of children.
+    /// - The nodes in `metas_changed` and their children are checked against `allow_child`.
+    ///
+    /// The violations are sorted by the nodes.
+    pub(crate) fn check_changes(
+        &self,
+        state: &mut DocState,
+        idx: ContainerIdx,
+        moved: &[TreeID],
+        metas_changed: &[TreeID],
+    ) -> Vec<TreeViolation> {
+        let tree = state.arena.idx_to_id(idx).unwrap();
+        let mut ans: BTreeMap<(TreeID, u8), TreeViolation> = BTreeMap::new();
+        let push = |ans: &mut BTreeMap<_, _>,
+                    node: TreeID,
+                    parent: TreeParentId,
+                    kind: TreeViolationKind| {
+            let v = TreeViolation {
+                tree: tree.clone(),
+                node,
+                parent,
+                kind,
+            };
+            ans.insert((node, kind_order(&kind)), v);
+        };
+
+        let allow_checks = state.with_state(idx, |s| {
+            let t = s.as_tree_state().unwrap();
+            let mut allow_checks = Vec::new();
+            let mut parents = Vec::new();
+            for &node in moved {
+                // The node is deleted
+                let Some(depth) = t.depth(&node) else {
+                    continue;
+                };
+                let parent = t.parent(&node).unwrap();
+                if let Some(max) = self.max_depth {
+                    let mut depths = FxHashMap::default();
+                    depths.insert(node, depth);
+                    let check_depth = |ans: &mut BTreeMap<_, _>,
+                                       node: TreeID,
+                                       parent: TreeParentId,
+                                       depth: usize| {
+                        if depth > max {
+                            push(
+                                ans,
+                                node,
+                                parent,
+                                TreeViolationKind::MaxDepth { depth, max },
+                            );
+                        }
+                    };
+                    check_depth(&mut ans, node, parent, depth);
+                    for child in t.preorder_nodes_under(TreeParentId::Node(node)) {
+                        let child_parent = t.parent(&child).unwrap();
+                        let child_depth = depths[&child_parent.tree_id().unwrap()] + 1;
+                        depths.insert(child, child_depth);
+                        check_depth(&mut ans, child, child_parent, child_depth);
+                    }
+                }
+                if !parents.contains(&parent) {
+                    parents.push(parent);
+                }
+                allow_checks.push((node, parent));
+            }
+
+            if let Some(max) = self.max_children {
+                for parent in parents {
+                    let children = t.get_children(&parent).into_iter().flatten();
+                    for (index, child) in children.enumerate().skip(max) {
+                        push(
+                            &mut ans,
+                            child,
+                            parent,
+                            TreeViolationKind::MaxChildren { index, max },
+                        );
+                    }
+                }
+            }
+
+            for &node in metas_changed {
+                if t.depth(&node).is_none() {
+                    continue;
+                }
+                allow_checks.push((node, t.parent(&node).unwrap()));
+                for child in t
+                    .get_children(&TreeParentId::Node(node))
+                    .into_iter()
+                    .flatten()
+                {
+                    allow_checks.push((child, TreeParentId::Node(node)));
+                }
+            }
+            allow_checks
+        });
+
+        if self.allow_child.is_some() {
+            let mut metas = Metas::default();
+            for (node, parent) in allow_checks {
+                if !self.is_child_allowed(state, &mut metas, node, parent) {
+                    push(&mut ans, node, parent, TreeViolationKind::ChildNotAllowed);
+                }
+            }
+        }
+
+        ans.into_values().collect()
+    }
+
+    /// Whether the violation still exists in the current state
+    fn is_violated(&self, state: &mut DocState, idx: ContainerIdx, v: &TreeViolation) -> bool {
+        let place = state.with_state(idx, |s| {
+            let tree = s.as_tree_state().unwrap();
+            Some((
+                tree.parent(&v.node)?,
+                tree.depth(&v.node)?,
+                tree.get_index_by_tree_id(&v.node)?,
+            ))
+        });
+        let Some((parent, depth, index)) = place else {
+            return false;
+        };
+        self.check_node(state, &mut Metas::default(), v.node, parent, depth, index)
+            .iter()
+            .any(|kind| std::mem::discriminant(kind) == std::mem::discriminant(&v.kind))
+    }
+
+    fn check_node(
+        &self,
+        state: &mut DocState,
+        metas: &mut Metas,
+        node: TreeID,
+        parent: TreeParentId,
+        depth: usize,
+        index: usize,
+    ) -> Vec<TreeViolationKind> {
+        let mut ans = Vec::new();
+        if let Some(max) = self.max_depth {
+            if depth > max {
+                ans.push(TreeViolationKind::MaxDepth { depth, max });
+            }
+        }
+        if let Some(max) = self.max_children {
+            if index >= max {
+                ans.push(TreeViolationKind::MaxChildren { index, max });
+            }
+        }
+        if !self.is_child_allowed(state, metas, node, parent) {
+            ans.push(TreeViolationKind::ChildNotAllowed);
+        }
+        ans
+    }
+
+    fn is_child_allowed(
+        &self,
+        state: &mut DocState,
+        metas: &mut Metas,
+        node: TreeID,
+        parent: TreeParentId,
+    ) -> bool {
+        let Some(allow_child) = &self.allow_child else {
+            return true;
+        };
+        let meta = get_meta(state, metas, node);
+        let parent_meta = parent.as_node().map(|p| get_meta(state, metas, *p));
+        allow_child(&meta, parent_meta.as_ref())
+    }
+}
+
+/// The order of the violations of the same node
+fn kind_order(kind: &TreeViolationKind) -> u8 {
+    match kind {
+        TreeViolationKind::MaxDepth { .. } => 0,
+        TreeViolationKind::MaxChildren { .. } => 1,
+        TreeViolationKind::ChildNotAllowed => 2,
+    }
+}
+
+/// Get the deep value of the meta of the node. The containers are not registered for it.
+fn get_meta(state: &mut DocState, metas: &mut Metas, node: TreeID) -> LoroValue {
+    if let Some(meta) = metas.get(&node) {
+        return meta.clone();
+    }
+
+    let meta = match state.arena.id_to_idx(&node.associated_meta_container()) {
+        Some(idx) => state.get_container_deep_value(idx),
+        // The meta has never been used
+        None => ContainerType::Map.default_value(),
+    };
+    metas.insert(node, meta.clone());
+    meta
+}
+
+/// Get the node of the meta container
+fn meta_to_node(meta: ContainerID) -> Option<TreeID> {
+    match meta {
+        ContainerID::Normal { peer, counter, .. } => Some(TreeID::new(peer, counter)),
+        ContainerID::Root { .. } => None,
+    }
+}
+
+/// The tree nodes changed by an import, collected from its diff
+#[derive(Debug, Default)]
+pub(crate) struct TreeChanges {
+    /// The created or moved nodes, with the trees they belong to
+    nodes: Vec<(ContainerIdx, TreeID)>,
+    /// The changed maps, some of which are the metas of the nodes
+    maps: Vec<ContainerIdx>,
+}
+
+impl TreeChanges {
+    pub(crate) fn from_diff(diff: &[InternalContainerDiff]) -> Self {
+        let mut ans = Self::default();
+        for d in diff {
+            match &d.diff {
+                DiffVariant::Internal(InternalDiff::Tree(t)) => {
+                    for item in t.diff.iter() {
+                        if let TreeInternalDiff::Create { .. } | TreeInternalDiff::Move { .. } =
+                            item.action
+                        {
+                            ans.nodes.push((d.idx, item.target));
+                        }
+                    }
+                }
+                DiffVariant::External(Diff::Tree(t)) => {
+                    for item in t.diff.iter() {
+                        if let TreeExternalDiff::Create { .. } | TreeExternalDiff::Move { .. } =
+                            item.action
+                        {
+                            ans.nodes.push((d.idx, item.target));
+                        }
+                    }
+                }
+                _ if d.idx.get_type() == ContainerType::Map => ans.maps.push(d.idx),
+                _ => {}
+            }
+        }
+        ans
+    }
+}
+
+/// Check the nodes changed by an import in the constrained trees
+pub(crate) fn check_changed_trees(
+    state: &mut DocState,
+    changes: &TreeChanges,
+) -> Vec<TreeViolation> {
+    let mut metas = Vec::new();
+    for &idx in changes.maps.iter() {
+        let Some(tree) = state
+            .arena
+            .get_parent(idx)
+            .filter(|p| p.get_type() == ContainerType::Tree)
+        else {
+            continue;
+        };
+        if let Some(node) = meta_to_node(state.arena.idx_to_id(idx).unwrap()) {
+            metas.push((tree, node));
+        }
+    }
+
+    let mut trees: Vec<ContainerIdx> = Vec::new();
+    for &(idx, _) in changes.nodes.iter().chain(metas.iter()) {
+        if !trees.contains(&idx) {
+            trees.push(idx);
+        }
+    }
+
+    let mut ans = Vec::new();
+    for idx in trees {
+        let id = state.arena.idx_to_id(idx).unwrap();
+        let Some(constraints) = state.config.tree_constraints(&id) else {
+            continue;
+        };
+        let nodes_of_tree = |changes: &[(ContainerIdx, TreeID)]| -> Vec<TreeID> {
+            changes
+                .iter()
+                .filter(|(tree, _)| *tree == idx)
+                .map(|(_, node)| *node)
+                .collect()
+        };
+        ans.extend(constraints.check_changes(
+            state,
+            idx,
+            &nodes_of_tree(&changes.nodes),
+            &nodes_of_tree(&metas),
+        ));
+    }
+    ans
+}
+
+/// Check all the constrained trees in the doc state
+pub(crate) fn check_all_trees(state: &mut DocState) -> Vec<TreeViolation> {
+    let trees = state
+        .config
+        .tree_constraint_trees()
+        .iter()
+        .filter_map(|id| state.arena.id_to_idx(id))
+        .collect();
+    check_trees(state, trees)
+}
+
+fn check_trees(state: &mut DocState, trees: Vec<ContainerIdx>) -> Vec<TreeViolation> {
+    let mut ans = Vec::new();
+    for idx in trees {
+        let id = state.arena.idx_to_id(idx).unwrap();
+        if let Some(constraints) = state.config.tree_constraints(&id) {
+            ans.extend(constraints.check_tree(state, idx));
+        }
+    }
+    ans
+}
+
+impl LoroDoc {
+    /// Set the constraints of the tree, or remove them with `None`.
+    ///
+    /// The existing nodes are not checked. Use [LoroDoc::validate_tree_constraints] to check them.
+    pub fn set_tree_constraints(&self, tree: &ContainerID, constraints: Option<TreeConstraints>) {
+        self.config
+            .set_tree_constraints(tree, constraints.map(Arc::new));
+    }
+
+    pub fn tree_constraints(&self, tree: &ContainerID) -> Option<Arc<TreeConstraints>> {
+        self.config.tree_constraints(tree)
+    }
+
+    /// Check all the alive nodes of the tree against its constraints
+    pub fn validate_tree_constraints(&self, tree: &ContainerID) -> Vec<TreeViolation> {
+        let Some(constraints) = self.tree_constraints(tree) else {
+            return Vec::new();
+        };
+        let mut state = self.app_state().lock().unwrap();
+        let Some(idx) = state.arena.id_to_idx(tree) else {
+            return Vec::new();
+        };
+        constraints.check_tree(&mut state, idx)
+    }
+
+    /// Apply the resolutions of the violations, and return the violations that remain
+    pub(crate) fn resolve_tree_violations(
+        &self,
+        violations: Vec<TreeViolation>,
+    ) -> Vec<TreeViolation> {
+        // The nodes moved by the resolutions, which may introduce new violations
+        let mut moved: Vec<(&ContainerID, TreeID)> = Vec::new();
+        let mut resolved = false;
+        for v in violations.iter() {
+            let Some(constraints) = self.tree_constraints(&v.tree) else {
+                continue;
+            };
+            let Some(resolver) = &constraints.resolver else {
+                continue;
+            };
+            {
+                let mut state = self.app_state().lock().unwrap();
+                let idx = state.arena.id_to_idx(&v.tree).unwrap();
+                // It may be fixed by the earlier resolutions
+                if !constraints.is_violated(&mut state, idx, v) {
+                    continue;
+                }
+            }
+
+            let tree = self.get_handler(v.tree.clone()).into_tree().unwrap();
+            let result = match resolver(v) {
+                TreeResolution::Keep => continue,
+                TreeResolution::MoveTo(parent) => {
+                    let result = tree.mov(v.node, parent);
+                    if result.is_ok() {
+                        moved.push((&v.tree, v.node));
+                    }
+                    result
+                }
+                TreeResolution::Delete => tree.delete(v.node),
+            };
+            resolved |= result.is_ok();
+        }
+
+        if !resolved {
+            return violations;
+        }
+
+        self.commit_with(
+            CommitOptions::new()
+                .origin(TREE_RESOLUTION_ORIGIN)
+                .immediate_renew(true),
+        );
+        // Recheck the nodes of the violations and the nodes moved by the resolutions
+        let mut trees: Vec<(&ContainerID, Vec<TreeID>)> = Vec::new();
+        let nodes = violations.iter().map(|v| (&v.tree, v.node));
+        for (tree, node) in nodes.chain(moved.iter().copied()) {
+            match trees.iter_mut().find(|(t, _)| *t == tree) {
+                Some((_, nodes)) => nodes.push(node),
+                None => trees.push((tree, vec![node])),
+            }
+        }
+
+        let mut state = self.app_state().lock().unwrap();
+        let mut ans = Vec::new();
+        for (tree, nodes) in trees {
+            let constraints = self.tree_constraints(tree).unwrap();
+            let idx = state.arena.id_to_idx(tree).unwrap();
+            ans.extend(constraints.check_changes(&mut state, idx, &nodes, &[]));
+        }
+        ans
+    }
+}
@@ -188,6 +188,8 @@ pub(super) enum EventHint {
         start: u32,
         end: u32,
         style: Style,
+        /// The merged values of the spans in the range, if the style merges map values
+        merged: Option<Vec<(u32, LoroValue)>>,
     },
     InsertText {
         /// pos is a Unicode index. If wasm, it's a UTF-16 index.
@@ -518,6 +520,35 @@ impl Transaction {
             });
         }
 
+        if let Some(schema) = state.config.schema() {
+            schema.check_local_op(&mut state, container, &raw_op.content)?;
+        }
+
+        if let (RawOpContent::Tree(op), true) =
+            (&raw_op.content, state.config.has_tree_constraints())
+        {
+            let id = state.arena.idx_to_id(container).unwrap();
+            if let Some(constraints) = state.config.tree_constraints(&id) {
+                constraints.check_local_op(&mut state, container, op)?;
+            }
+        }
+
+        if let (RawOpContent::Map(op), true) =
+            (&raw_op.content, state.config.has_tree_constraints())
+        {
+            // The op may change the meta of a node in a constrained tree
+            if let Some(tree) = state
+                .arena
+                .get_parent(container)
+                .filter(|p| p.get_type() == ContainerType::Tree)
+            {
+                let id = state.arena.idx_to_id(tree).unwrap();
+                if let Some(constraints) = state.config.tree_constraints(&id) {
+                    constraints.check_local_meta_op(&mut state, tree, container, op)?;
+                }
+            }
+        }
+
         let op = self.arena.convert_raw_op(&raw_op);
         state.apply_local_op(&raw_op, &op)?;
         {
@@ -716,20 +747,38 @@ fn change_to_diff(
             }
         }
         match hint {
-            EventHint::Mark { start, end, style } => {
-                let mut meta = StyleMeta::default();
-                meta.insert(
-                    style.key.clone(),
-                    StyleMetaItem {
-                        lamport,
-                        peer: change.id.peer,
-                        value: style.data,
-                    },
-                );
-                let diff = DeltaRopeBuilder::new()
-                    .retain(start as usize, Default::default())
-                    .retain((end - start) as usize, meta)
-                    .build();
+            EventHint::Mark {
+                start,
+                end,
+                style,
+                merged,
+            } => {
+                let new_meta = |value: LoroValue| {
+                    let mut meta = StyleMeta::default();
+                    meta.insert(
+                        style.key.clone(),
+                        StyleMetaItem {
+                            lamport,
+                            peer: change.id.peer,
+                            value,
+                        },
+                    );
+                    meta
+                };
+                let mut builder =
+                    DeltaRopeBuilder::new().retain(start as usize, Default::default());
+                match merged {
+                    Some(merged) => {
+                        for (len, value) in merged {
+                            builder = builder.retain(len as usize, new_meta(value));
+                        }
+                    }
+                    None => {
+                        builder =
+                            builder.retain((end - start) as usize, new_meta(style.data.clone()));
+                    }
+                }
+                let diff = builder.build();
                 ans.push(TxnContainerDiff {
                     idx: op.container,
                     diff: Diff::Text(diff),
@@ -1,22 +1,26 @@
 use std::{
     collections::VecDeque,
-    sync::{atomic::AtomicU64, Arc, Mutex},
+    sync::{atomic::AtomicU64, Arc, Mutex, MutexGuard},
 };
 
 use either::Either;
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use loro_common::{
-    ContainerID, Counter, CounterSpan, HasIdSpan, IdSpan, LoroError, LoroResult, LoroValue, PeerID,
+    ContainerID, Counter, CounterSpan, HasCounterSpan, HasIdSpan, IdSpan, LoroError, LoroResult,
+    LoroValue, PeerID, ID,
 };
+use serde::{Deserialize, Serialize};
 use tracing::{debug_span, info_span, instrument};
 
 use crate::{
-    change::get_sys_timestamp,
-    cursor::{AbsolutePosition, Cursor},
+    arena::SharedArena,
+    change::{get_sys_timestamp, Lamport},
+    cursor::{AbsolutePosition, Cursor, Side},
     delta::TreeExternalDiff,
-    event::{Diff, EventTriggerKind},
+    event::{Diff, DiffEvent, EventTriggerKind},
+    oplog::OpLog,
     version::Frontiers,
-    ContainerDiff, DocDiff, LoroDoc, Subscription,
+    ChangeMeta, ContainerDiff, DocDiff, LoroDoc, Subscription, VersionVector,
 };
 
 #[derive(Debug, Clone, Default)]
@@ -64,6 +68,61 @@ impl DiffBatch {
     pub fn clear(&mut self) {
         self.0.clear();
     }
+
+    /// Remove the diffs of the containers that are out of the scope
+    pub(crate) fn retain_scope(&mut self, scope: &UndoScope, arena: &SharedArena) {
+        self.0.retain(|id, _| scope.contains(id, arena));
+    }
+}
+
+/// The containers that an [UndoManager] is limited to.
+///
+/// A scoped [UndoManager] only records and reverts the local ops on the containers in the scope.
+/// The other local changes are treated like remote changes.
+#[derive(Debug, Clone, Default)]
+pub struct UndoScope {
+    containers: FxHashSet<ContainerID>,
+    include_descendants: bool,
+}
+
+impl UndoScope {
+    pub fn new(containers: impl IntoIterator<Item = ContainerID>) -> Self {
+        Self {
+            containers: containers.into_iter().collect(),
+            include_descendants: false,
+        }
+    }
+
+    /// Whether the descendants of the containers are in the scope as well
+    pub fn include_descendants(mut self, include: bool) -> Self {
+        self.include_descendants = include;
+        self
+    }
+
+    pub(crate) fn contains(&self, id: &ContainerID, arena: &SharedArena) -> bool {
+        if self.containers.contains(id) {
+            return true;
+        }
+
+        if !self.include_descendants {
+            return false;
+        }
+
+        let Some(idx) = arena.id_to_idx(id) else {
+            return false;
+        };
+
+        let mut parent = arena.get_parent(idx);
+        while let Some(p) = parent {
+            if self.containers.contains(&arena.idx_to_id(p).unwrap()) {
+                return true;
+            }
+
+            parent = arena.get_parent(p);
+        }
+
+        false
+    }
 }
 
 fn transform_cursor(
@@ -122,8 +181,10 @@ fn transform_cursor(
 /// PeerID cannot be changed during the lifetime of the UndoManager
 pub struct UndoManager {
     peer: Arc<AtomicU64>,
+    scope: Option<UndoScope>,
     container_remap: Arc<Mutex<FxHashMap<ContainerID, ContainerID>>>,
     inner: Arc<Mutex<UndoManagerInner>>,
+    event_handler: Arc<UndoEventHandler>,
     _peer_id_change_sub: Subscription,
     _undo_sub: Subscription,
 }
@@ -132,6 +193,7 @@ impl std::fmt::Debug for UndoManager {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("UndoManager")
             .field("peer", &self.peer)
+            .field("scope", &self.scope)
             .field("container_remap", &self.container_remap)
             .field("inner", &self.inner)
             .finish()
@@ -157,6 +219,8 @@ impl UndoOrRedo {
 /// The returned cursors will be recorded for a new pushed undo item.
 pub type OnPush = Box<dyn Fn(UndoOrRedo, CounterSpan) -> UndoItemMeta + Send + Sync>;
 pub type OnPop = Box<dyn Fn(UndoOrRedo, CounterSpan, UndoItemMeta) + Send + Sync>;
+/// Decide whether a change should be recorded by a global UndoManager.
+pub type ChangeFilter = Box<dyn Fn(&ChangeMeta) -> bool + Send + Sync>;
 
 struct UndoManagerInner {
     next_counter: Option<Counter>,
@@ -170,6 +234,15 @@ struct UndoManagerInner {
     last_popped_selection: Option<Vec<CursorWithPos>>,
     on_push: Option<OnPush>,
     on_pop: Option<OnPop>,
+    /// The version that has been recorded by a global UndoManager.
+    /// It's `None` if the UndoManager only records the local changes.
+    global_vv: Option<VersionVector>,
+    change_filter: Option<ChangeFilter>,
+    /// The depth of the nested groups started by [UndoManager::group_start]
+    group_depth: usize,
+    /// The end counter of the local ops recorded inside the current group.
+    /// They are pushed as a single item when the outermost group ends.
+    group_end: Option<(PeerID, Counter)>,
 }
 
 impl std::fmt::Debug for UndoManagerInner {
@@ -183,6 +256,8 @@ impl std::fmt::Debug for UndoManagerInner {
             .field("merge_interval", &self.merge_interval)
             .field("max_stack_size", &self.max_stack_size)
             .field("exclude_origin_prefixes", &self.exclude_origin_prefixes)
+            .field("global_vv", &self.global_vv)
+            .field("group_depth", &self.group_depth)
             .finish()
     }
 }
@@ -195,8 +270,13 @@ struct Stack {
 
 #[derive(Debug, Clone)]
 struct StackItem {
-    span: CounterSpan,
+    span: IdSpan,
     meta: UndoItemMeta,
+    /// Whether the item is restored by [UndoManager::import_state].
+    ///
+    /// The remote changes before the import are unknown for the restored items,
+    /// so they are transformed based on the latest version of the doc instead.
+    restored: bool,
 }
 
 /// The metadata of an undo item.
@@ -278,18 +358,22 @@ impl Stack {
         // Cursor position transformation relies on the remote diff in the same row.
     }
 
-    pub fn push(&mut self, span: CounterSpan, meta: UndoItemMeta) {
+    pub fn push(&mut self, span: IdSpan, meta: UndoItemMeta) {
         self.push_with_merge(span, meta, false)
     }
 
-    pub fn push_with_merge(&mut self, span: CounterSpan, meta: UndoItemMeta, can_merge: bool) {
+    pub fn push_with_merge(&mut self, span: IdSpan, meta: UndoItemMeta, can_merge: bool) {
         let last = self.stack.back_mut().unwrap();
         let last_remote_diff = last.1.try_lock().unwrap();
         if !last_remote_diff.0.is_empty() {
             // If the remote diff is not empty, we cannot merge
             drop(last_remote_diff);
             let mut v = VecDeque::new();
-            v.push_back(StackItem { span, meta });
+            v.push_back(StackItem {
+                span,
+                meta,
+                restored: false,
+            });
             self.stack
                 .push_back((v, Arc::new(Mutex::new(DiffBatch::default()))));
 
@@ -297,19 +381,38 @@ impl Stack {
         } else {
             if can_merge {
                 if let Some(last_span) = last.0.back_mut() {
-                    if last_span.span.end == span.start {
+                    if !last_span.restored
+                        && last_span.span.peer == span.peer
+                        && last_span.span.counter.end == span.counter.start
+                    {
                         // merge the span
-                        last_span.span.end = span.end;
+                        last_span.span.counter.end = span.counter.end;
                         return;
                     }
                 }
             }
 
             self.size += 1;
-            last.0.push_back(StackItem { span, meta });
+            last.0.push_back(StackItem {
+                span,
+                meta,
+                restored: false,
+            });
         }
     }
 
+    fn iter(&self) -> impl Iterator<Item = &StackItem> {
+        self.stack.iter().flat_map(|(items, _)| items.iter())
+    }
+
+    fn from_restored_items(items: impl IntoIterator<Item = StackItem>) -> Self {
+        let mut stack = Stack::new();
+        let row = &mut stack.stack.back_mut().unwrap().0;
+        row.extend(items);
+        stack.size = row.len();
+        stack
+    }
+
     pub fn compose_remote_event(&mut self, diff: &[&ContainerDiff]) {
         if self.is_empty() {
             return;
@@ -383,10 +486,14 @@ impl UndoManagerInner {
             last_popped_selection: None,
             on_pop: None,
             on_push: None,
+            global_vv: None,
+            change_filter: None,
+            group_depth: 0,
+            group_end: None,
         }
     }
 
-    fn record_checkpoint(&mut self, latest_counter: Counter) {
+    fn record_checkpoint(&mut self, peer: PeerID, latest_counter: Counter) {
         if Some(latest_counter) == self.next_counter {
             return;
         }
@@ -397,12 +504,28 @@ impl UndoManagerInner {
         }
 
         assert!(self.next_counter.unwrap() < latest_counter);
+        if self.group_depth > 0 {
+            self.group_end = Some((peer, latest_counter));
+            return;
+        }
+
+        self.push_checkpoint(peer, latest_counter);
+    }
+
+    /// Push the local ops recorded inside the current group as a single undo item.
+    fn flush_group(&mut self) {
+        if let Some((peer, end)) = self.group_end.take() {
+            self.push_checkpoint(peer, end);
+        }
+    }
+
+    fn push_checkpoint(&mut self, peer: PeerID, latest_counter: Counter) {
         let now = get_sys_timestamp();
-        let span = CounterSpan::new(self.next_counter.unwrap(), latest_counter);
+        let span = IdSpan::new(peer, self.next_counter.unwrap(), latest_counter);
         let meta = self
             .on_push
             .as_ref()
-            .map(|x| x(UndoOrRedo::Undo, span))
+            .map(|x| x(UndoOrRedo::Undo, span.counter))
             .unwrap_or_default();
 
         if !self.undo_stack.is_empty() && now - self.last_undo_time < self.merge_interval {
@@ -418,75 +541,277 @@ impl UndoManagerInner {
             self.undo_stack.pop_front();
         }
     }
+
+    /// Record the changes from all the peers that are not recorded yet.
+    ///
+    /// Each change is pushed as a separate undo item. Returns whether any change is recorded.
+    fn record_global_changes(&mut self, oplog: &OpLog) -> bool {
+        let Some(recorded_vv) = self.global_vv.as_ref() else {
+            return false;
+        };
+
+        let mut spans: Vec<(Lamport, IdSpan)> = Vec::new();
+        for span in oplog.vv().sub_iter(recorded_vv) {
+            for change in oplog.change_store().iter_changes(span) {
+                let start = change.id.counter.max(span.counter.start);
+                let end = change.ctr_end().min(span.counter.end);
+                if let Some(filter) = self.change_filter.as_ref() {
+                    if !filter(&ChangeMeta::from_change(&change)) {
+                        continue;
+                    }
+                }
+
+                spans.push((
+                    change.lamport + (start - change.id.counter) as Lamport,
+                    IdSpan::new(span.peer, start, end),
+                ));
+            }
+        }
+
+        self.global_vv = Some(oplog.vv().clone());
+        if spans.is_empty() {
+            return false;
+        }
+
+        spans.sort_unstable_by_key(|(lamport, span)| (*lamport, span.peer));
+        for (_, span) in spans {
+            let meta = self
+                .on_push
+                .as_ref()
+                .map(|x| x(UndoOrRedo::Undo, span.counter))
+                .unwrap_or_default();
+            self.undo_stack.push(span, meta);
+        }
+
+        self.redo_stack.clear();
+        while self.undo_stack.len() > self.max_stack_size {
+            self.undo_stack.pop_front();
+        }
+        true
+    }
 }
 
-fn get_counter_end(doc: &LoroDoc, peer: PeerID) -> Counter {
-    doc.oplog()
-        .try_lock()
-        .unwrap()
-        .vv()
-        .get(&peer)
-        .cloned()
-        .unwrap_or(0)
+/// Version 1 added the peer of each stack item, which is required by the global mode.
+const UNDO_STATE_ENCODING_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct EncodedUndoState {
+    peer: PeerID,
+    undo: Vec<EncodedStackItem>,
+    redo: Vec<EncodedStackItem>,
 }
 
-impl UndoManager {
-    pub fn new(doc: &LoroDoc) -> Self {
-        let peer = Arc::new(AtomicU64::new(doc.peer_id()));
-        let peer_clone = peer.clone();
-        let peer_clone2 = peer.clone();
-        let inner = Arc::new(Mutex::new(UndoManagerInner::new(get_counter_end(
-            doc,
-            doc.peer_id(),
-        ))));
-        let inner_clone = inner.clone();
-        let inner_clone2 = inner.clone();
-        let remap_containers = Arc::new(Mutex::new(FxHashMap::default()));
-        let remap_containers_clone = remap_containers.clone();
-        let undo_sub = doc.subscribe_root(Arc::new(move |event| match event.event_meta.by {
+#[derive(Serialize, Deserialize)]
+struct EncodedStackItem {
+    peer: PeerID,
+    start: Counter,
+    end: Counter,
+    value: LoroValue,
+    cursors: Vec<EncodedCursor>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncodedCursor {
+    cursor: Cursor,
+    pos: usize,
+    side: Side,
+}
+
+/// The undo state exported before the global mode, where all the items belong to `peer`
+#[derive(Deserialize)]
+struct EncodedUndoStateV0 {
+    peer: PeerID,
+    undo: Vec<EncodedStackItemV0>,
+    redo: Vec<EncodedStackItemV0>,
+}
+
+#[derive(Deserialize)]
+struct EncodedStackItemV0 {
+    start: Counter,
+    end: Counter,
+    value: LoroValue,
+    cursors: Vec<EncodedCursor>,
+}
+
+impl From<EncodedUndoStateV0> for EncodedUndoState {
+    fn from(state: EncodedUndoStateV0) -> Self {
+        let peer = state.peer;
+        let convert = |items: Vec<EncodedStackItemV0>| {
+            items
+                .into_iter()
+                .map(|x| EncodedStackItem {
+                    peer,
+                    start: x.start,
+                    end: x.end,
+                    value: x.value,
+                    cursors: x.cursors,
+                })
+                .collect()
+        };
+        EncodedUndoState {
+            peer,
+            undo: convert(state.undo),
+            redo: convert(state.redo),
+        }
+    }
+}
+
+impl EncodedStackItem {
+    fn new(item: &StackItem) -> Self {
+        Self {
+            peer: item.span.peer,
+            start: item.span.counter.start,
+            end: item.span.counter.end,
+            value: item.meta.value.clone(),
+            cursors: item
+                .meta
+                .cursors
+                .iter()
+                .map(|c| EncodedCursor {
+                    cursor: c.cursor.clone(),
+                    pos: c.pos.pos,
+                    side: c.pos.side,
+                })
+                .collect(),
+        }
+    }
+
+    fn into_stack_item(self) -> StackItem {
+        StackItem {
+            span: IdSpan::new(self.peer, self.start, self.end),
+            meta: UndoItemMeta {
+                value: self.value,
+                cursors: self
+                    .cursors
+                    .into_iter()
+                    .map(|c| CursorWithPos {
+                        cursor: c.cursor,
+                        pos: AbsolutePosition {
+                            pos: c.pos,
+                            side: c.side,
+                        },
+                    })
+                    .collect(),
+            },
+            restored: true,
+        }
+    }
+}
+
+/// Records the events of the doc into the stacks of an [UndoManager]
+struct UndoEventHandler {
+    global: bool,
+    peer: Arc<AtomicU64>,
+    scope: Option<UndoScope>,
+    arena: SharedArena,
+    oplog: Arc<Mutex<OpLog>>,
+    container_remap: Arc<Mutex<FxHashMap<ContainerID, ContainerID>>>,
+    /// The events that arrive when the inner state is locked, e.g. by another thread or
+    /// by the callbacks of the UndoManager that edit the doc.
+    /// They are handled in order the next time the inner state is locked.
+    deferred: Mutex<Vec<DocDiff>>,
+}
+
+impl UndoEventHandler {
+    fn on_event(&self, inner: &Mutex<UndoManagerInner>, event: DiffEvent) {
+        let Ok(mut inner) = inner.try_lock() else {
+            let meta = event.event_meta;
+            self.deferred.lock().unwrap().push(DocDiff {
+                from: meta.from.clone(),
+                to: meta.to.clone(),
+                origin: meta.origin.clone(),
+                by: meta.by,
+                diff: event.events.iter().map(|&e| e.clone()).collect(),
+            });
+            return;
+        };
+
+        self.handle_deferred(&mut inner);
+        self.handle(&mut inner, event.event_meta, event.events);
+    }
+
+    fn handle_deferred(&self, inner: &mut UndoManagerInner) {
+        let deferred = std::mem::take(&mut *self.deferred.lock().unwrap());
+        for event in deferred.iter() {
+            let events: Vec<&ContainerDiff> = event.diff.iter().collect();
+            self.handle(inner, event, &events);
+        }
+    }
+
+    fn handle(&self, inner: &mut UndoManagerInner, meta: &DocDiff, events: &[&ContainerDiff]) {
+        match meta.by {
+            EventTriggerKind::Local | EventTriggerKind::Import if self.global => {
+                if inner.processing_undo {
+                    return;
+                }
+
+                let excluded = inner
+                    .exclude_origin_prefixes
+                    .iter()
+                    .any(|x| meta.origin.starts_with(&**x));
+                let oplog = self.oplog.lock().unwrap();
+                let recorded = if excluded {
+                    inner.global_vv = Some(oplog.vv().clone());
+                    false
+                } else {
+                    inner.record_global_changes(&oplog)
+                };
+                drop(oplog);
+                if !recorded {
+                    // The changes that are not recorded are treated like remote changes
+                    inner.undo_stack.compose_remote_event(events);
+                    inner.redo_stack.compose_remote_event(events);
+                }
+            }
             EventTriggerKind::Local => {
                 // TODO: PERF undo can be significantly faster if we can get
                 // the DiffBatch for undo here
-                let Ok(mut inner) = inner_clone.try_lock() else {
-                    return;
-                };
                 if inner.processing_undo {
                     return;
                 }
-                if let Some(id) = event
-                    .event_meta
+                if let Some(id) = meta
                     .to
                     .iter()
-                    .find(|x| x.peer == peer_clone.load(std::sync::atomic::Ordering::Relaxed))
+                    .find(|x| x.peer == self.peer.load(std::sync::atomic::Ordering::Relaxed))
                 {
-                    if inner
-                        .exclude_origin_prefixes
-                        .iter()
-                        .any(|x| event.event_meta.origin.starts_with(&**x))
+                    let out_of_scope = self.scope.as_ref().is_some_and(|scope| {
+                        !events.iter().any(|e| scope.contains(&e.id, &self.arena))
+                    });
+                    if out_of_scope
+                        || inner
+                            .exclude_origin_prefixes
+                            .iter()
+                            .any(|x| meta.origin.starts_with(&**x))
                     {
-                        // If the event is from the excluded origin, we don't record it
-                        // in the undo stack. But we need to record its effect like it's
+                        // If the event is from the excluded origin or out of the scope, we don't
+                        // record it in the undo stack. But we need to record its effect like it's
                         // a remote event.
-                        inner.undo_stack.compose_remote_event(event.events);
-                        inner.redo_stack.compose_remote_event(event.events);
+                        //
+                        // The ops recorded in the current group before it have to be pushed first,
+                        // because the span of an undo item cannot contain these ops.
+                        inner.flush_group();
+                        inner.undo_stack.compose_remote_event(events);
+                        inner.redo_stack.compose_remote_event(events);
                         inner.next_counter = Some(id.counter + 1);
                     } else {
-                        inner.record_checkpoint(id.counter + 1);
+                        inner.record_checkpoint(id.peer, id.counter + 1);
                     }
                 }
             }
             EventTriggerKind::Import => {
-                let mut inner = inner_clone.try_lock().unwrap();
+                // The ops recorded in the current group before the import have to be pushed
+                // first, so that they are transformed by the remote changes
+                inner.flush_group();
 
-                for e in event.events {
+                for e in events {
                     if let Diff::Tree(tree) = &e.diff {
                         for item in &tree.diff {
                             let target = item.target;
                             if let TreeExternalDiff::Create { .. } = &item.action {
                                 // If the concurrent event is a create event, it may bring the deleted tree node back,
                                 // so we need to remove it from the remap of the container.
-                                remap_containers_clone
-                                    .try_lock()
+                                self.container_remap
+                                    .lock()
                                     .unwrap()
                                     .remove(&target.associated_meta_container());
                             }
@@ -494,51 +819,126 @@ impl UndoManager {
                     }
                 }
 
-                inner.undo_stack.compose_remote_event(event.events);
-                inner.redo_stack.compose_remote_event(event.events);
+                inner.undo_stack.compose_remote_event(events);
+                inner.redo_stack.compose_remote_event(events);
             }
             EventTriggerKind::Checkout => {
-                let mut inner = inner_clone.try_lock().unwrap();
                 inner.undo_stack.clear();
                 inner.redo_stack.clear();
                 inner.next_counter = None;
+                inner.group_end = None;
+                if inner.global_vv.is_some() {
+                    inner.global_vv = Some(self.oplog.lock().unwrap().vv().clone());
+                }
             }
+        }
+    }
+}
+
+fn get_counter_end(doc: &LoroDoc, peer: PeerID) -> Counter {
+    doc.oplog()
+        .try_lock()
+        .unwrap()
+        .vv()
+        .get(&peer)
+        .cloned()
+        .unwrap_or(0)
+}
+
+impl UndoManager {
+    pub fn new(doc: &LoroDoc) -> Self {
+        Self::new_inner(doc, None, false)
+    }
+
+    /// Create an UndoManager that only records and reverts the local ops on the containers in the scope.
+    ///
+    /// The local changes on the other containers are treated like remote changes,
+    /// i.e. they are not undone but the undo stack is transformed against them.
+    pub fn new_with_scope(doc: &LoroDoc, scope: UndoScope) -> Self {
+        Self::new_inner(doc, Some(scope), false)
+    }
+
+    /// Create an UndoManager that records the changes made by all the peers.
+    ///
+    /// Each new change, either local or imported, is pushed as an undo item.
+    /// Undoing it reverts the change with a new local change, no matter which peer made it.
+    /// Use [UndoManager::add_exclude_origin_prefix] and [UndoManager::set_change_filter]
+    /// to skip some of the changes.
+    pub fn new_global(doc: &LoroDoc) -> Self {
+        Self::new_inner(doc, None, true)
+    }
+
+    fn new_inner(doc: &LoroDoc, scope: Option<UndoScope>, global: bool) -> Self {
+        let peer = Arc::new(AtomicU64::new(doc.peer_id()));
+        let peer_clone = peer.clone();
+        let mut undo_inner = UndoManagerInner::new(get_counter_end(doc, doc.peer_id()));
+        if global {
+            undo_inner.global_vv = Some(doc.oplog_vv());
+        }
+        let inner = Arc::new(Mutex::new(undo_inner));
+        let inner_clone = inner.clone();
+        let inner_clone2 = inner.clone();
+        let remap_containers = Arc::new(Mutex::new(FxHashMap::default()));
+        let event_handler = Arc::new(UndoEventHandler {
+            global,
+            peer: peer.clone(),
+            scope: scope.clone(),
+            arena: doc.arena().clone(),
+            oplog: doc.oplog().clone(),
+            container_remap: remap_containers.clone(),
+            deferred: Default::default(),
+        });
+        let event_handler_clone = event_handler.clone();
+        let undo_sub = doc.subscribe_root(Arc::new(move |event| {
+            event_handler_clone.on_event(&inner_clone, event);
         }));
 
         let sub = doc.subscribe_peer_id_change(Box::new(move |id| {
-            let mut inner = inner_clone2.try_lock().unwrap();
-            inner.undo_stack.clear();
-            inner.redo_stack.clear();
+            let mut inner = inner_clone2.lock().unwrap();
+            if inner.global_vv.is_none() {
+                // A global UndoManager can undo the changes from any peer,
+                // so its stacks are still valid after the peer id changes
+                inner.undo_stack.clear();
+                inner.redo_stack.clear();
+            }
             inner.next_counter = Some(id.counter);
-            peer_clone2.store(id.peer, std::sync::atomic::Ordering::Relaxed);
+            inner.group_end = None;
+            peer_clone.store(id.peer, std::sync::atomic::Ordering::Relaxed);
             true
         }));
 
         UndoManager {
             peer,
+            scope,
             container_remap: remap_containers,
             inner,
+            event_handler,
             _peer_id_change_sub: sub,
             _undo_sub: undo_sub,
         }
     }
 
+    /// Lock the inner state, and handle the events that are deferred while it was locked
+    fn lock_inner(&self) -> MutexGuard<'_, UndoManagerInner> {
+        let mut inner = self.inner.lock().unwrap();
+        self.event_handler.handle_deferred(&mut inner);
+        inner
+    }
+
     pub fn peer(&self) -> PeerID {
         self.peer.load(std::sync::atomic::Ordering::Relaxed)
     }
 
     pub fn set_merge_interval(&mut self, interval: i64) {
-        self.inner.try_lock().unwrap().merge_interval = interval;
+        self.lock_inner().merge_interval = interval;
     }
 
     pub fn set_max_undo_steps(&mut self, size: usize) {
-        self.inner.try_lock().unwrap().max_stack_size = size;
+        self.lock_inner().max_stack_size = size;
     }
 
     pub fn add_exclude_origin_prefix(&mut self, prefix: &str) {
-        self.inner
-            .try_lock()
-            .unwrap()
+        self.lock_inner()
             .exclude_origin_prefixes
             .push(prefix.into());
     }
@@ -552,11 +952,64 @@ impl UndoManager {
         }
 
         doc.commit_then_renew();
-        let counter = get_counter_end(doc, self.peer());
-        self.inner.try_lock().unwrap().record_checkpoint(counter);
+        let mut inner = self.lock_inner();
+        if inner.global_vv.is_some() {
+            inner.record_global_changes(&doc.oplog().try_lock().unwrap());
+        } else {
+            let counter = get_counter_end(doc, self.peer());
+            inner.record_checkpoint(self.peer(), counter);
+        }
+        Ok(())
+    }
+
+    /// Start a group of undo items.
+    ///
+    /// All the local commits until the matching [UndoManager::group_end] are merged into
+    /// a single undo item. Groups can be nested, and only the outermost group creates the item.
+    ///
+    /// The pending ops before the group are committed and recorded as a separate item.
+    /// The commits with the excluded origins, the ones out of the scope and the imports
+    /// split the group.
+    /// A global UndoManager doesn't group the changes.
+    pub fn group_start(&mut self, doc: &LoroDoc) -> LoroResult<()> {
+        if self.lock_inner().group_depth == 0 {
+            self.record_new_checkpoint(doc)?;
+        }
+
+        self.lock_inner().group_depth += 1;
         Ok(())
     }
 
+    /// End the group started by [UndoManager::group_start].
+    ///
+    /// When the outermost group ends, the pending ops are committed and all the ops in the group
+    /// are pushed into the undo stack as one item. It's a no-op if there is no active group.
+    pub fn group_end(&mut self, doc: &LoroDoc) -> LoroResult<()> {
+        if self.lock_inner().group_depth == 0 {
+            return Ok(());
+        }
+
+        if self.lock_inner().group_depth == 1 {
+            self.record_new_checkpoint(doc)?;
+            let mut inner = self.lock_inner();
+            inner.group_depth = 0;
+            inner.flush_group();
+        } else {
+            self.lock_inner().group_depth -= 1;
+        }
+
+        Ok(())
+    }
+
+    /// Set the filter of the changes recorded by a global UndoManager created by
+    /// [UndoManager::new_global].
+    ///
+    /// The changes that the filter returns false for are not recorded. They are treated like
+    /// remote changes instead.
+    pub fn set_change_filter(&mut self, filter: Option<ChangeFilter>) {
+        self.lock_inner().change_filter = filter;
+    }
+
     #[instrument(skip_all)]
     pub fn undo(&mut self, doc: &LoroDoc) -> LoroResult<bool> {
         self.perform(
@@ -639,9 +1092,15 @@ impl UndoManager {
         // rather than using the current selection directly.
 
         self.record_new_checkpoint(doc)?;
+        {
+            // Undo/redo ends the active group
+            let mut inner = self.lock_inner();
+            inner.group_depth = 0;
+            inner.flush_group();
+        }
         let end_counter = get_counter_end(doc, self.peer());
         let mut top = {
-            let mut inner = self.inner.try_lock().unwrap();
+            let mut inner = self.lock_inner();
             inner.processing_undo = true;
             get_stack(&mut inner).pop()
         };
@@ -653,23 +1112,31 @@ impl UndoManager {
                 let inner = self.inner.clone();
                 // We need to clone this because otherwise <transform_delta> will be applied to the same remote diff
                 let remote_change_clone = remote_diff.try_lock().unwrap().clone();
+                // The stacks of a global UndoManager don't track the changes after the items
+                // as remote diffs, and neither do the items restored by `import_state`.
+                // Without a remote diff, `undo_internal` calculates the diff from the item to the
+                // latest version from the history, so these items are still transformed against
+                // all the changes made after them, including the remote ones.
+                let is_global = self.lock_inner().global_vv.is_some();
                 let commit = doc.undo_internal(
-                    IdSpan {
-                        peer: self.peer(),
-                        counter: span.span,
-                    },
+                    span.span,
                     &mut self.container_remap.try_lock().unwrap(),
-                    Some(&remote_change_clone),
+                    self.scope.as_ref(),
+                    if span.restored || is_global {
+                        None
+                    } else {
+                        Some(&remote_change_clone)
+                    },
                     &mut |diff| {
                         info_span!("transform remote diff").in_scope(|| {
-                            let mut inner = inner.try_lock().unwrap();
+                            let mut inner = inner.lock().unwrap();
                             // <transform_delta>
                             get_stack(&mut inner).transform_based_on_this_delta(diff);
                         });
                     },
                 )?;
                 drop(commit);
-                let mut inner = self.inner.try_lock().unwrap();
+                let mut inner = self.lock_inner();
                 if let Some(x) = inner.on_pop.as_ref() {
                     for cursor in span.meta.cursors.iter_mut() {
                         // <cursor_transform> We need to transform cursor here.
@@ -683,7 +1150,7 @@ impl UndoManager {
                         );
                     }
 
-                    x(kind, span.span, span.meta.clone());
+                    x(kind, span.span.counter, span.meta.clone());
                     let take = inner.last_popped_selection.take();
                     next_push_selection = take;
                     inner.last_popped_selection = Some(span.meta.cursors);
@@ -691,7 +1158,10 @@ impl UndoManager {
             }
             let new_counter = get_counter_end(doc, self.peer());
             if end_counter != new_counter {
-                let mut inner = self.inner.try_lock().unwrap();
+                let mut inner = self.lock_inner();
+                if inner.global_vv.is_some() {
+                    inner.global_vv = Some(doc.oplog_vv());
+                }
                 let mut meta = inner
                     .on_push
                     .as_ref()
@@ -704,40 +1174,106 @@ impl UndoManager {
                     meta.cursors = inner;
                 }
 
-                get_opposite(&mut inner).push(CounterSpan::new(end_counter, new_counter), meta);
+                get_opposite(&mut inner)
+                    .push(IdSpan::new(self.peer(), end_counter, new_counter), meta);
                 inner.next_counter = Some(new_counter);
                 executed = true;
                 break;
             } else {
                 // continue to pop the undo item as this undo is a no-op
-                top = get_stack(&mut self.inner.try_lock().unwrap()).pop();
+                top = get_stack(&mut self.lock_inner()).pop();
                 continue;
             }
         }
 
-        self.inner.try_lock().unwrap().processing_undo = false;
+        self.lock_inner().processing_undo = false;
         Ok(executed)
     }
 
     pub fn can_undo(&self) -> bool {
-        !self.inner.try_lock().unwrap().undo_stack.is_empty()
+        !self.lock_inner().undo_stack.is_empty()
     }
 
     pub fn can_redo(&self) -> bool {
-        !self.inner.try_lock().unwrap().redo_stack.is_empty()
+        !self.lock_inner().redo_stack.is_empty()
     }
 
     pub fn set_on_push(&self, on_push: Option<OnPush>) {
-        self.inner.try_lock().unwrap().on_push = on_push;
+        self.lock_inner().on_push = on_push;
     }
 
     pub fn set_on_pop(&self, on_pop: Option<OnPop>) {
-        self.inner.try_lock().unwrap().on_pop = on_pop;
+        self.lock_inner().on_pop = on_pop;
     }
 
     pub fn clear(&self) {
-        self.inner.try_lock().unwrap().undo_stack.clear();
-        self.inner.try_lock().unwrap().redo_stack.clear();
+        self.lock_inner().undo_stack.clear();
+        self.lock_inner().redo_stack.clear();
+    }
+
+    /// Export the undo/redo stacks, so that they can be restored by [UndoManager::import_state]
+    /// after the app restarts.
+    ///
+    /// It contains the spans of the undo/redo items and their metadata, including the cursors.
+    pub fn export_state(&self) -> Vec<u8> {
+        let inner = self.lock_inner();
+        let state = EncodedUndoState {
+            peer: self.peer(),
+            undo: inner.undo_stack.iter().map(EncodedStackItem::new).collect(),
+            redo: inner.redo_stack.iter().map(EncodedStackItem::new).collect(),
+        };
+        let mut ans = vec![UNDO_STATE_ENCODING_VERSION];
+        ans.extend_from_slice(&postcard::to_allocvec(&state).unwrap());
+        ans
+    }
+
+    /// Restore the undo/redo stacks exported by [UndoManager::export_state].
+    ///
+    /// The current stacks are replaced. The doc must use the same peer id as the exported
+    /// UndoManager, and it must contain all the ops referred by the exported stacks.
+    ///
+    /// The remote changes before the import cannot be tracked precisely, so the restored
+    /// items are transformed based on the latest version of the doc when they are popped.
+    pub fn import_state(&mut self, doc: &LoroDoc, bytes: &[u8]) -> LoroResult<()> {
+        let Some((&version, body)) = bytes.split_first() else {
+            return Err(LoroError::DecodeError("Empty undo state".into()));
+        };
+        let state: EncodedUndoState = match version {
+            0 => postcard::from_bytes::<EncodedUndoStateV0>(body).map(Into::into),
+            UNDO_STATE_ENCODING_VERSION => postcard::from_bytes(body),
+            _ => return Err(LoroError::IncompatibleFutureEncodingError(version as usize)),
+        }
+        .map_err(|e| LoroError::DecodeError(format!("Invalid undo state: {}", e).into()))?;
+        if state.peer != self.peer() {
+            return Err(LoroError::UndoWithDifferentPeerId {
+                expected: state.peer,
+                actual: self.peer(),
+            });
+        }
+
+        doc.commit_then_renew();
+        let vv = doc.oplog_vv();
+        for item in state.undo.iter().chain(state.redo.iter()) {
+            let last = ID::new(item.peer, item.end - 1);
+            if item.start >= item.end || !vv.includes_id(last) {
+                return Err(LoroError::UndoInvalidIdSpan(last));
+            }
+        }
+
+        let mut inner = self.lock_inner();
+        inner.undo_stack =
+            Stack::from_restored_items(state.undo.into_iter().map(|x| x.into_stack_item()));
+        inner.redo_stack =
+            Stack::from_restored_items(state.redo.into_iter().map(|x| x.into_stack_item()));
+        while inner.undo_stack.len() > inner.max_stack_size {
+            inner.undo_stack.pop_front();
+        }
+        inner.next_counter = Some(get_counter_end(doc, state.peer));
+        inner.last_popped_selection = None;
+        if inner.global_vv.is_some() {
+            inner.global_vv = Some(vv);
+        }
+        Ok(())
     }
 }
 
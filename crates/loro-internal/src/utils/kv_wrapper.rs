@@ -34,6 +34,10 @@ impl KvWrapper {
         }
     }
 
+    pub fn new(kv: Arc<Mutex<dyn KvStore>>) -> Self {
+        Self { kv }
+    }
+
     pub fn import(&self, bytes: Bytes) {
         let mut kv = self.kv.try_lock().unwrap();
         kv.import_all(bytes).unwrap();
@@ -61,7 +65,6 @@ impl KvWrapper {
         }
     }
 
-    #[allow(unused)]
     pub(crate) fn contains_key(&self, key: &[u8]) -> bool {
         self.kv.try_lock().unwrap().contains_key(key)
     }
@@ -4,7 +4,6 @@ use fxhash::FxHashMap;
 use loro_common::{ContainerID, ContainerType, LoroError, LoroResult, LoroValue, PeerID, ID};
 use loro_internal::{
     delta::ResolvedMapValue,
-    encoding::ImportStatus,
     event::{Diff, EventTriggerKind},
     fx_map,
     handler::{Handler, TextDelta, ValueOrHandler},
@@ -1281,20 +1280,13 @@ fn import_status() -> LoroResult<()> {
 
     let status1 = doc.import(&update2)?;
     let status2 = doc.import(&update1)?;
+    assert_eq!(status1.success, VersionRange::default());
     assert_eq!(
-        status1,
-        ImportStatus {
-            success: Default::default(),
-            pending: Some(VersionRange::from_map(fx_map!(1=>(1, 2))))
-        }
-    );
-    assert_eq!(
-        status2,
-        ImportStatus {
-            success: VersionRange::from_map(fx_map!(1=>(0, 2))),
-            pending: None
-        }
+        status1.pending,
+        Some(VersionRange::from_map(fx_map!(1=>(1, 2))))
     );
+    assert_eq!(status2.success, VersionRange::from_map(fx_map!(1=>(0, 2))));
+    assert_eq!(status2.pending, None);
 
     Ok(())
 }
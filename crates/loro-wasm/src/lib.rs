@@ -153,7 +153,9 @@ extern "C" {
     pub type JsLoroTreeOrUndefined;
     #[wasm_bindgen(typescript_type = "[string, Value | Container]")]
     pub type MapEntry;
-    #[wasm_bindgen(typescript_type = "{[key: string]: { expand: 'before'|'after'|'none'|'both' }}")]
+    #[wasm_bindgen(
+        typescript_type = "{[key: string]: { expand: 'before'|'after'|'none'|'both', mergeMap?: boolean }}"
+    )]
     pub type JsTextStyles;
     #[wasm_bindgen(typescript_type = "Delta<string>[]")]
     pub type JsDelta;
@@ -406,6 +408,9 @@ impl LoroDoc {
     /// - `none`: the mark will not be expanded to include the inserted text at the boundaries
     /// - `both`: when inserting text either right before or right after the given range, the mark will be expanded to include the inserted text
     ///
+    /// If `mergeMap` is true, the map values of the style are merged per field, so concurrent
+    /// marks that edit different fields of the same style don't override each other.
+    ///
     /// @example
     /// ```ts
     /// const doc = new LoroDoc();
@@ -442,13 +447,19 @@ impl LoroDoc {
             // read expand value from value
             let expand = Reflect::get(&value, &"expand".into()).expect("`expand` not specified");
             let expand_str = expand.as_string().unwrap();
-            // read allowOverlap value from value
+            // read mergeMap value from value
+            let merge_map = Reflect::get(&value, &"mergeMap".into())
+                .ok()
+                .and_then(|x| x.as_bool())
+                .unwrap_or(false);
             style_config.insert(
                 key.into(),
-                StyleConfig {
-                    expand: ExpandType::try_from_str(&expand_str)
-                        .expect("`expand` must be one of `none`, `start`, `end`, `both`"),
-                },
+                StyleConfig::new()
+                    .expand(
+                        ExpandType::try_from_str(&expand_str)
+                            .expect("`expand` must be one of `none`, `start`, `end`, `both`"),
+                    )
+                    .merge_map(merge_map),
             );
         }
 
@@ -3,36 +3,46 @@
 #![warn(missing_debug_implementations)]
 use event::{DiffEvent, Subscriber};
 use loro_common::InternalString;
+use loro_internal::awareness::{
+    ChangedKeys, RemoteSelections as InnerRemoteSelections, SelectionRange,
+};
 pub use loro_internal::cursor::CannotFindRelativePosition;
 use loro_internal::cursor::Cursor;
+use loro_internal::cursor::CursorRange;
 use loro_internal::cursor::PosQueryResult;
+use loro_internal::cursor::RangeQueryResult;
 use loro_internal::cursor::Side;
 pub use loro_internal::encoding::ImportStatus;
 use loro_internal::handler::HandlerTrait;
 use loro_internal::handler::ValueOrHandler;
 use loro_internal::loro::ChangeTravelError;
-use loro_internal::undo::{OnPop, OnPush};
+use loro_internal::undo::{ChangeFilter, OnPop, OnPush, UndoScope};
 use loro_internal::version::shrink_frontiers;
 pub use loro_internal::version::ImVersionVector;
 use loro_internal::DocState;
+use loro_internal::FxHashMap;
 use loro_internal::LoroDoc as InnerLoroDoc;
 use loro_internal::OpLog;
 use loro_internal::{
-    handler::Handler as InnerHandler, ListHandler as InnerListHandler,
-    MapHandler as InnerMapHandler, MovableListHandler as InnerMovableListHandler,
-    TextHandler as InnerTextHandler, TreeHandler as InnerTreeHandler,
-    UnknownHandler as InnerUnknownHandler,
+    handler::Handler as InnerHandler, handler::TreeSubtree as InnerTreeSubtree,
+    ListHandler as InnerListHandler, MapHandler as InnerMapHandler,
+    MovableListHandler as InnerMovableListHandler, TextHandler as InnerTextHandler,
+    TreeHandler as InnerTreeHandler, UnknownHandler as InnerUnknownHandler,
 };
 use std::cmp::Ordering;
 use std::ops::ControlFlow;
 use std::ops::Range;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tracing::info;
 
 pub use loro_internal::subscription::LocalUpdateCallback;
 pub use loro_internal::subscription::PeerIdUpdateCallback;
 pub use loro_internal::ChangeMeta;
 pub mod event;
+pub mod mapping;
+pub mod sync;
+#[cfg(feature = "derive")]
+pub use loro_derive::{LoroDeserialize, LoroSerialize};
 pub use loro_internal::awareness;
 pub use loro_internal::change::Timestamp;
 pub use loro_internal::configure::Configure;
@@ -42,8 +52,10 @@ pub use loro_internal::container::{ContainerID, ContainerType, IntoContainerId};
 pub use loro_internal::cursor;
 pub use loro_internal::delta::{TreeDeltaItem, TreeDiff, TreeDiffItem, TreeExternalDiff};
 pub use loro_internal::encoding::ExportMode;
+pub use loro_internal::encoding::HistoryRetention;
 pub use loro_internal::encoding::ImportBlobMetadata;
 pub use loro_internal::event::{EventTriggerKind, Index};
+pub use loro_internal::handler::TextAnnotation;
 pub use loro_internal::handler::TextDelta;
 pub use loro_internal::json;
 pub use loro_internal::json::{
@@ -51,10 +63,16 @@ pub use loro_internal::json::{
     JsonOpContent, JsonSchema, ListOp as JsonListOp, MapOp as JsonMapOp,
     MovableListOp as JsonMovableListOp, TextOp as JsonTextOp, TreeOp as JsonTreeOp,
 };
-pub use loro_internal::kv_store::{KvStore, MemKvStore};
+pub use loro_internal::kv_store::{FileKvStore, KvStore, MemKvStore};
 pub use loro_internal::loro::CommitOptions;
 pub use loro_internal::loro::DocAnalysis;
 pub use loro_internal::oplog::FrontiersNotIncluded;
+pub use loro_internal::schema::{
+    ContainerSchema, DocSchema, MapSchema, SchemaViolation, ValueSchema,
+};
+pub use loro_internal::tree_constraint::{
+    TreeConstraints, TreeResolution, TreeViolation, TreeViolationKind, TREE_RESOLUTION_ORIGIN,
+};
 pub use loro_internal::undo;
 pub use loro_internal::version::{Frontiers, VersionRange, VersionVector, VersionVectorDiff};
 pub use loro_internal::ApplyDiff;
@@ -64,8 +82,10 @@ pub use loro_internal::{loro_value, to_value};
 pub use loro_internal::{
     Counter, CounterSpan, FractionalIndex, IdLp, IdSpan, Lamport, PeerID, TreeID, TreeParentId, ID,
 };
+pub use loro_internal::{DocDeserializer, StateDeserializer};
 pub use loro_internal::{LoroEncodeError, LoroError, LoroResult, LoroTreeError, LoroValue, ToJson};
 pub use loro_kv_store as kv_store;
+pub use mapping::{LoroDeserialize, LoroSerialize};
 
 #[cfg(feature = "jsonpath")]
 pub use loro_internal::jsonpath;
@@ -81,7 +101,7 @@ pub use counter::LoroCounter;
 /// When it's dropped, all the associated [`Handler`]s will be invalidated.
 #[derive(Debug)]
 pub struct LoroDoc {
-    doc: InnerLoroDoc,
+    doc: Arc<InnerLoroDoc>,
     #[cfg(debug_assertions)]
     _temp: u8,
 }
@@ -96,7 +116,7 @@ impl LoroDoc {
     #[inline(always)]
     fn _new(doc: InnerLoroDoc) -> Self {
         Self {
-            doc,
+            doc: Arc::new(doc),
             #[cfg(debug_assertions)]
             _temp: 0,
         }
@@ -120,6 +140,53 @@ impl LoroDoc {
         LoroDoc::_new(doc)
     }
 
+    /// Create a document from a snapshot and the incremental snapshots exported after it.
+    ///
+    /// The incremental snapshots exported by [LoroDoc::export_incremental_snapshot] are
+    /// layered onto the base snapshot in the given order.
+    pub fn from_incremental_snapshots(base: &[u8], layers: &[&[u8]]) -> LoroResult<Self> {
+        let doc = InnerLoroDoc::from_incremental_snapshots(base, layers)?;
+        doc.start_auto_commit();
+        Ok(LoroDoc::_new(doc))
+    }
+
+    /// Open a document on the kv store, e.g. a [FileKvStore].
+    ///
+    /// The history and the container states are loaded from the store when they are accessed,
+    /// so it's cheap to open a large document. An empty document is returned if nothing has been
+    /// saved in the store.
+    ///
+    /// Only one document can be opened on a store at a time, and the store should not be locked
+    /// while the document is in use.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::sync::{Arc, Mutex};
+    /// # use loro::{LoroDoc, MemKvStore};
+    /// let store = Arc::new(Mutex::new(MemKvStore::new(Default::default())));
+    /// let doc = LoroDoc::from_kv_store(store.clone()).unwrap();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.save_to_kv_store().unwrap();
+    /// drop(doc);
+    /// let doc = LoroDoc::from_kv_store(store).unwrap();
+    /// assert_eq!(doc.get_text("text").to_string(), "Hello");
+    /// ```
+    pub fn from_kv_store(kv: Arc<Mutex<dyn KvStore>>) -> LoroResult<Self> {
+        let doc = InnerLoroDoc::from_kv_store(kv)?;
+        doc.start_auto_commit();
+        Ok(LoroDoc::_new(doc))
+    }
+
+    /// Save the changes and the container states modified since the last save into the
+    /// kv store the document is opened on by [LoroDoc::from_kv_store].
+    ///
+    /// The store should be flushed afterwards to persist the changes, e.g. by [FileKvStore::flush].
+    #[inline]
+    pub fn save_to_kv_store(&self) -> LoroResult<()> {
+        self.doc.save_to_kv_store()
+    }
+
     /// Get the configurations of the document.
     #[inline]
     pub fn config(&self) -> &Configure {
@@ -216,6 +283,84 @@ impl LoroDoc {
         self.doc.config_text_style(text_style)
     }
 
+    /// Attach a schema to the document, or detach it with `None`.
+    ///
+    /// Local edits that violate the schema are rejected with [LoroError::SchemaViolation].
+    /// Imported changes are always applied, and the violations they introduce are reported in
+    /// [ImportStatus::schema_violations].
+    ///
+    /// The existing content is not checked. Use [LoroDoc::validate_schema] to check it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{ContainerSchema, DocSchema, LoroDoc, MapSchema, ValueSchema};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_schema(Some(DocSchema::new().root(
+    ///     "settings",
+    ///     ContainerSchema::Map(MapSchema::new().field("theme", ValueSchema::String)),
+    /// )));
+    /// let settings = doc.get_map("settings");
+    /// settings.insert("theme", "dark").unwrap();
+    /// assert!(settings.insert("theme", 1).is_err());
+    /// assert!(settings.insert("unknown", "value").is_err());
+    /// ```
+    #[inline]
+    pub fn set_schema(&self, schema: Option<DocSchema>) {
+        self.doc.set_schema(schema)
+    }
+
+    /// Get the schema attached to the document.
+    #[inline]
+    pub fn schema(&self) -> Option<Arc<DocSchema>> {
+        self.doc.schema()
+    }
+
+    /// Check the whole document state against the attached schema.
+    #[inline]
+    pub fn validate_schema(&self) -> Vec<SchemaViolation> {
+        self.doc.validate_schema()
+    }
+
+    /// Set the constraints of a tree, or remove them with `None`.
+    ///
+    /// Local creations and moves that violate the constraints are rejected with
+    /// [LoroTreeError::ConstraintViolation]. Imported moves are always applied. The violations
+    /// they introduce are fixed by the resolver of the constraints, and the remaining ones are
+    /// reported in [ImportStatus::tree_violations].
+    ///
+    /// The existing nodes are not checked. Use [LoroDoc::validate_tree_constraints] to check them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, TreeConstraints};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let tree = doc.get_tree("tree");
+    /// doc.set_tree_constraints(&tree.id(), Some(TreeConstraints::new().max_depth(1)));
+    /// let root = tree.create(None).unwrap();
+    /// let child = tree.create(root).unwrap();
+    /// assert!(tree.create(child).is_err());
+    /// ```
+    #[inline]
+    pub fn set_tree_constraints(&self, tree: &ContainerID, constraints: Option<TreeConstraints>) {
+        self.doc.set_tree_constraints(tree, constraints)
+    }
+
+    /// Get the constraints of a tree.
+    #[inline]
+    pub fn tree_constraints(&self, tree: &ContainerID) -> Option<Arc<TreeConstraints>> {
+        self.doc.tree_constraints(tree)
+    }
+
+    /// Check all the nodes of a tree against its constraints.
+    #[inline]
+    pub fn validate_tree_constraints(&self, tree: &ContainerID) -> Vec<TreeViolation> {
+        self.doc.validate_tree_constraints(tree)
+    }
+
     /// Attach the document state to the latest known version.
     ///
     /// > The document becomes detached during a `checkout` operation.
@@ -511,6 +656,37 @@ impl LoroDoc {
         self.doc.shallow_since_frontiers()
     }
 
+    /// Trim the history of the doc in place according to the retention policy.
+    ///
+    /// Afterwards the doc is the same as the one imported from a shallow snapshot at the
+    /// trimming point, i.e. [LoroDoc::shallow_since_vv] is updated, but the state,
+    /// the containers and the subscriptions of the doc are kept.
+    /// The versions before the trimming point can no longer be checked out or exported.
+    ///
+    /// Return the new start version of the history, or `None` if nothing is trimmed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{HistoryRetention, LoroDoc};
+    /// let doc = LoroDoc::new();
+    /// // Don't merge the changes, so that every commit is a change
+    /// doc.set_change_merge_interval(0);
+    /// let text = doc.get_text("text");
+    /// for i in 0..10 {
+    ///     text.insert(0, &i.to_string()).unwrap();
+    ///     doc.commit();
+    /// }
+    ///
+    /// doc.compact_history(&HistoryRetention::LastChanges(3)).unwrap();
+    /// assert!(doc.is_shallow());
+    /// assert_eq!(text.to_string(), "9876543210");
+    /// ```
+    #[inline]
+    pub fn compact_history(&self, retention: &HistoryRetention) -> LoroResult<Option<Frontiers>> {
+        self.doc.compact_history(retention)
+    }
+
     /// Get the total number of operations in the `OpLog`
     #[inline]
     pub fn len_ops(&self) -> usize {
@@ -544,6 +720,75 @@ impl LoroDoc {
             .get_deep_value_with_id()
     }
 
+    /// Get a serde deserializer over the current state of the container.
+    ///
+    /// The child containers are only read when the deserialized type visits them,
+    /// so it's cheaper than deserializing [LoroDoc::get_deep_value] when only a part of
+    /// the doc is needed. The doc state is locked until the deserializer is dropped.
+    ///
+    /// ```
+    /// # use loro::LoroDoc;
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize, Debug, PartialEq)]
+    /// struct User {
+    ///     name: String,
+    ///     tags: Vec<String>,
+    /// }
+    ///
+    /// let doc = LoroDoc::new();
+    /// let user = doc.get_map("user");
+    /// user.insert("name", "Alice").unwrap();
+    /// let tags = user.insert_container("tags", loro::LoroList::new()).unwrap();
+    /// tags.push("admin").unwrap();
+    /// let id = user.id();
+    /// let value = User::deserialize(doc.deserializer_at(&id)).unwrap();
+    /// assert_eq!(value.tags, vec!["admin".to_string()]);
+    /// ```
+    pub fn deserializer_at(&self, id: &ContainerID) -> DocDeserializer<'_> {
+        self.doc.deserializer_at(id)
+    }
+
+    /// Get the value of the container at the given version.
+    ///
+    /// Unlike [LoroDoc::checkout], it only calculates the history of this container,
+    /// and the doc stays attached. The child containers are represented by their ids.
+    /// The pending transaction is committed first.
+    ///
+    /// It returns [LoroError::NotFoundError] if the container is not a root container and
+    /// doesn't exist in the doc.
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, LoroValue};
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// let v = doc.oplog_frontiers();
+    /// text.insert(5, " world").unwrap();
+    /// doc.commit();
+    ///
+    /// let value = doc.get_value_at(&text.id(), &v).unwrap();
+    /// assert_eq!(value, LoroValue::from("Hello"));
+    /// assert!(!doc.is_detached());
+    /// ```
+    #[inline]
+    pub fn get_value_at(&self, id: &ContainerID, frontiers: &Frontiers) -> LoroResult<LoroValue> {
+        self.doc.get_value_at(id, frontiers)
+    }
+
+    /// Get the deep value of the container at the given version.
+    ///
+    /// Unlike [LoroDoc::checkout], it only calculates the history of this container
+    /// and its descendants, and the doc stays attached. The pending transaction is committed first.
+    #[inline]
+    pub fn get_deep_value_at(
+        &self,
+        id: &ContainerID,
+        frontiers: &Frontiers,
+    ) -> LoroResult<LoroValue> {
+        self.doc.get_deep_value_at(id, frontiers)
+    }
+
     /// Get the `Frontiers` version of `OpLog`
     #[inline]
     pub fn oplog_frontiers(&self) -> Frontiers {
@@ -707,6 +952,44 @@ impl LoroDoc {
         self.doc.query_pos(cursor)
     }
 
+    /// Get the absolute positions of a batch of cursors.
+    ///
+    /// It's faster than calling [`LoroDoc::get_cursor_pos`] for each cursor. If the target of a
+    /// cursor is deleted, the `update` field of its result contains the new cursor to replace it.
+    pub fn get_cursor_pos_batch(
+        &self,
+        cursors: &[Cursor],
+    ) -> Vec<Result<PosQueryResult, CannotFindRelativePosition>> {
+        self.doc.query_pos_batch(cursors)
+    }
+
+    /// Get the absolute positions of a batch of cursor ranges.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{cursor::{CursorRange, Side}, LoroDoc};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello world").unwrap();
+    /// let range = CursorRange::new(
+    ///     text.get_cursor(6, Side::Left).unwrap(),
+    ///     text.get_cursor(11, Side::Left).unwrap(),
+    /// );
+    /// text.insert(0, "> ").unwrap();
+    /// let ans = doc.get_cursor_range_pos_batch(&[range]);
+    /// let ans = ans[0].as_ref().unwrap();
+    /// assert_eq!((ans.start.current.pos, ans.end.current.pos), (8, 13));
+    /// assert!(!ans.is_collapsed());
+    /// ```
+    pub fn get_cursor_range_pos_batch(
+        &self,
+        ranges: &[CursorRange],
+    ) -> Vec<Result<RangeQueryResult, CannotFindRelativePosition>> {
+        self.doc.query_range_batch(ranges)
+    }
+
     /// Get the inner LoroDoc ref.
     #[inline]
     pub fn inner(&self) -> &InnerLoroDoc {
@@ -747,6 +1030,35 @@ impl LoroDoc {
         self.doc.export(mode)
     }
 
+    /// Export the history and the container states that may have changed since the given version.
+    ///
+    /// `since` should be the [LoroDoc::oplog_vv] at the time the base snapshot or the previous
+    /// incremental snapshot was exported. The result is much smaller than a full snapshot when
+    /// only a few changes have been made since then.
+    ///
+    /// Use [LoroDoc::from_incremental_snapshots] to layer it onto the base snapshot.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{ExportMode, LoroDoc};
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// let base = doc.export(ExportMode::Snapshot).unwrap();
+    /// let base_vv = doc.oplog_vv();
+    /// doc.get_text("text").insert(5, " world").unwrap();
+    /// let layer = doc.export_incremental_snapshot(&base_vv).unwrap();
+    /// let new_doc = LoroDoc::from_incremental_snapshots(&base, &[&layer]).unwrap();
+    /// assert_eq!(new_doc.get_text("text").to_string(), "Hello world");
+    /// ```
+    #[inline]
+    pub fn export_incremental_snapshot(
+        &self,
+        since: &VersionVector,
+    ) -> Result<Vec<u8>, LoroEncodeError> {
+        self.doc.export_incremental_snapshot(since)
+    }
+
     /// Analyze the container info of the doc
     ///
     /// This is used for development and debugging. It can be slow.
@@ -759,6 +1071,53 @@ impl LoroDoc {
         self.doc.get_path_to_container(id)
     }
 
+    /// Deep clone the container `src` of this doc into `dest_parent`.
+    ///
+    /// The whole container graph under `src` is recreated by new ops, including
+    /// the rich text styles and the meta of tree nodes. `dest_parent` can be a container
+    /// of this doc or of another doc.
+    ///
+    /// - If `dest_parent` is a [LoroMap], `index` should be [`Index::Key`]
+    /// - If `dest_parent` is a [LoroList] or [LoroMovableList], `index` should be [`Index::Seq`]
+    /// - If `dest_parent` is a [LoroTree], `index` should be [`Index::Node`]. The source
+    ///   should be a map, and its entries are copied into the meta of the node.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{ContainerTrait, Index, LoroDoc, LoroText, ToJson};
+    /// use serde_json::json;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let map = doc.get_map("map");
+    /// let text = map.insert_container("text", LoroText::new()).unwrap();
+    /// text.insert(0, "Hello").unwrap();
+    /// text.mark(0..5, "bold", true).unwrap();
+    ///
+    /// let other = LoroDoc::new();
+    /// let list = other.get_list("list");
+    /// let copy = doc
+    ///     .clone_container(&map.id(), &list.to_container(), Index::Seq(0))
+    ///     .unwrap();
+    /// let copy = copy.into_map().unwrap();
+    /// let text = copy.get("text").unwrap().into_container().unwrap();
+    /// let text = text.into_text().unwrap();
+    /// assert_eq!(
+    ///     text.to_delta().to_json_value(),
+    ///     json!([{ "insert": "Hello", "attributes": { "bold": true } }])
+    /// );
+    /// ```
+    pub fn clone_container(
+        &self,
+        src: &ContainerID,
+        dest_parent: &Container,
+        index: Index,
+    ) -> LoroResult<Container> {
+        self.doc
+            .clone_container(src, &dest_parent.to_handler(), index)
+            .map(Container::from_handler)
+    }
+
     /// Evaluate a JSONPath expression on the document and return matching values or handlers.
     ///
     /// This method allows querying the document structure using JSONPath syntax.
@@ -1461,7 +1820,11 @@ impl LoroText {
     ///
     /// *You should make sure that a key is always associated with the same expand type.*
     ///
-    /// Note: this is not suitable for unmergeable annotations like comments.
+    /// If the key is configured with [`StyleConfig::merge_map()`], a map value only overrides
+    /// the given fields of the existing value, and a field can be removed by setting it to null.
+    ///
+    /// Note: this is not suitable for unmergeable annotations like comments. Use
+    /// [`LoroText::annotate`] for them instead.
     pub fn mark(
         &self,
         range: Range<usize>,
@@ -1491,6 +1854,59 @@ impl LoroText {
         self.handler.unmark(range.start, range.end, key)
     }
 
+    /// Attach an annotation with the given key and payload to a range of text, and return
+    /// the unique id of the annotation.
+    ///
+    /// Unlike [`LoroText::mark`], overlapping annotations with the same key don't merge, so
+    /// it can be used to attach threads of comments to the text. The range of an annotation
+    /// is tracked by anchors and survives concurrent edits.
+    ///
+    /// The key should be configured by [`LoroDoc::config_text_style`] (`"comment"` is
+    /// configured by default) and must not contain `':'`.
+    ///
+    /// # Example
+    /// ```
+    /// # use loro::LoroDoc;
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello world!").unwrap();
+    /// let a = text.annotate(0..5, "comment", "first").unwrap();
+    /// let b = text.annotate(3..8, "comment", "second").unwrap();
+    /// text.insert(0, "> ").unwrap();
+    /// let annotations = text.get_annotations_at(6);
+    /// assert_eq!(annotations.len(), 2);
+    /// assert_eq!(annotations[0].id, a);
+    /// assert_eq!(annotations[0].range, 2..7);
+    /// assert_eq!(annotations[1].id, b);
+    /// assert_eq!(annotations[1].range, 5..10);
+    /// text.remove_annotation("comment", a).unwrap();
+    /// assert_eq!(text.get_annotations().len(), 1);
+    /// ```
+    pub fn annotate(
+        &self,
+        range: Range<usize>,
+        key: &str,
+        value: impl Into<LoroValue>,
+    ) -> LoroResult<ID> {
+        self.handler
+            .annotate(range.start, range.end, key, value.into())
+    }
+
+    /// Remove the annotation with the given key and id.
+    pub fn remove_annotation(&self, key: &str, id: ID) -> LoroResult<()> {
+        self.handler.remove_annotation(key, id)
+    }
+
+    /// Get all the visible annotations, sorted by their start positions.
+    pub fn get_annotations(&self) -> Vec<TextAnnotation> {
+        self.handler.get_annotations()
+    }
+
+    /// Get the annotations that cover the given position.
+    pub fn get_annotations_at(&self, pos: usize) -> Vec<TextAnnotation> {
+        self.handler.get_annotations_at(pos)
+    }
+
     /// Get the text in [Delta](https://quilljs.com/docs/delta/) format.
     ///
     /// # Example
@@ -1613,6 +2029,123 @@ impl ContainerTrait for LoroTree {
     }
 }
 
+/// A self-contained copy of a tree node and its descendants, created by
+/// [LoroTree::export_subtree].
+///
+/// It keeps the order of the nodes and their meta, including the child containers of the meta.
+/// It can be imported into any [LoroTree], including the trees of other documents, by
+/// [LoroTree::import_subtree].
+#[derive(Debug, Clone)]
+pub struct TreeSubtree {
+    /// ID of the node in the tree it's exported from.
+    pub id: TreeID,
+    /// A detached copy of the meta of the node.
+    pub meta: LoroMap,
+    /// The subtrees of the children of the node, in order.
+    pub children: Vec<TreeSubtree>,
+}
+
+impl From<InnerTreeSubtree> for TreeSubtree {
+    fn from(value: InnerTreeSubtree) -> Self {
+        Self {
+            id: value.id,
+            meta: LoroMap {
+                handler: value.meta,
+            },
+            children: value.children.into_iter().map(Self::from).collect(),
+        }
+    }
+}
+
+impl From<&TreeSubtree> for InnerTreeSubtree {
+    fn from(value: &TreeSubtree) -> Self {
+        Self {
+            id: value.id,
+            meta: value.meta.handler.clone(),
+            children: value.children.iter().map(Self::from).collect(),
+        }
+    }
+}
+
+impl TreeSubtree {
+    /// Encode the subtree into bytes, which can be decoded by [TreeSubtree::decode].
+    ///
+    /// The subtree is encoded as the snapshot of a document that only contains it, so the child
+    /// containers of the meta, e.g. the styles of a text, are kept.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loro::{LoroDoc, TreeSubtree};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let tree = doc.get_tree("tree");
+    /// let root = tree.create(None).unwrap();
+    /// let child = tree.create(root).unwrap();
+    /// tree.get_meta(child).unwrap().insert("name", "child").unwrap();
+    ///
+    /// let bytes = tree.export_subtree(root).unwrap().encode().unwrap();
+    /// let subtree = TreeSubtree::decode(&bytes).unwrap();
+    /// assert_eq!(subtree.id, root);
+    /// assert_eq!(subtree.children[0].id, child);
+    /// assert_eq!(
+    ///     subtree.children[0].meta.get_deep_value(),
+    ///     loro::loro_value!({"name": "child"})
+    /// );
+    /// ```
+    pub fn encode(&self) -> LoroResult<Vec<u8>> {
+        let doc = LoroDoc::new();
+        let tree = doc.get_tree(SUBTREE_ENCODING_TREE);
+        tree.enable_fractional_index(0);
+        let mapping = tree.import_subtree(None, 0, self)?;
+        // The nodes get new ids in the document, so the original ids are stored along with them
+        let ids = doc.get_map(SUBTREE_ENCODING_IDS);
+        for (id, new_id) in mapping {
+            ids.insert(&new_id.to_string(), id.to_string())?;
+        }
+
+        Ok(doc.export(ExportMode::Snapshot)?)
+    }
+
+    /// Decode the subtree encoded by [TreeSubtree::encode].
+    pub fn decode(bytes: &[u8]) -> LoroResult<Self> {
+        let doc = LoroDoc::new();
+        doc.import(bytes)?;
+        let tree = doc.get_tree(SUBTREE_ENCODING_TREE);
+        let ids = doc.get_map(SUBTREE_ENCODING_IDS);
+        let roots = tree.roots();
+        let [root] = roots.as_slice() else {
+            return Err(LoroError::DecodeError(
+                "The encoded subtree should have exactly one root".into(),
+            ));
+        };
+
+        let mut ans = tree.export_subtree(*root)?;
+        ans.restore_ids(&ids)?;
+        Ok(ans)
+    }
+
+    fn restore_ids(&mut self, ids: &LoroMap) -> LoroResult<()> {
+        let Some(ValueOrContainer::Value(LoroValue::String(id))) = ids.get(&self.id.to_string())
+        else {
+            return Err(LoroError::DecodeError(
+                "The original id of a subtree node is missing".into(),
+            ));
+        };
+
+        self.id = TreeID::try_from(id.as_str())?;
+        for child in self.children.iter_mut() {
+            child.restore_ids(ids)?;
+        }
+        Ok(())
+    }
+}
+
+/// The name of the tree in the document that a [TreeSubtree] is encoded into
+const SUBTREE_ENCODING_TREE: &str = "subtree";
+/// The name of the map from the new node ids to the original ones in an encoded [TreeSubtree]
+const SUBTREE_ENCODING_IDS: &str = "ids";
+
 /// A tree node in the [LoroTree].
 #[derive(Debug, Clone)]
 pub struct TreeNode {
@@ -1887,6 +2420,80 @@ impl LoroTree {
         self.handler.children_num(&parent)
     }
 
+    /// Return the ancestors of the target node, from its parent to the root-level node.
+    ///
+    /// If the target node does not exist or is deleted, return `None`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let tree = doc.get_tree("tree");
+    /// let root = tree.create(None).unwrap();
+    /// let child = tree.create(root).unwrap();
+    /// let grandchild = tree.create(child).unwrap();
+    /// assert_eq!(tree.ancestors(grandchild), Some(vec![child, root]));
+    /// assert_eq!(tree.depth(grandchild), Some(2));
+    /// ```
+    pub fn ancestors(&self, target: TreeID) -> Option<Vec<TreeID>> {
+        self.handler.ancestors(&target)
+    }
+
+    /// Return the depth of the target node. The root-level nodes have depth 0.
+    ///
+    /// If the target node does not exist or is deleted, return `None`.
+    pub fn depth(&self, target: TreeID) -> Option<usize> {
+        self.handler.depth(&target)
+    }
+
+    /// Return the lowest common ancestor of the two nodes. A node is regarded as an ancestor of itself.
+    ///
+    /// - If the nodes are under different root-level nodes, return `Some(TreeParentId::Root)`.
+    /// - If any of the nodes does not exist or is deleted, return `None`.
+    pub fn lowest_common_ancestor(&self, a: TreeID, b: TreeID) -> Option<TreeParentId> {
+        self.handler.lowest_common_ancestor(&a, &b)
+    }
+
+    /// Return all the descendants of the parent in pre-order: a node comes before its descendants
+    /// and the siblings are in order. The parent itself is not included.
+    pub fn preorder_nodes_under<T: Into<TreeParentId>>(&self, parent: T) -> Vec<TreeID> {
+        self.handler.preorder_nodes_under(parent.into())
+    }
+
+    /// Return all the descendants of the parent in post-order: a node comes after its descendants
+    /// and the siblings are in order. The parent itself is not included.
+    pub fn postorder_nodes_under<T: Into<TreeParentId>>(&self, parent: T) -> Vec<TreeID> {
+        self.handler.postorder_nodes_under(parent.into())
+    }
+
+    /// Return the node at the index path, e.g. `[2, 0, 5]` is the 6th child of the 1st child
+    /// of the 3rd root-level node.
+    ///
+    /// If the path is empty or out of bound, return `None`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let tree = doc.get_tree("tree");
+    /// let root = tree.create(None).unwrap();
+    /// let child = tree.create(root).unwrap();
+    /// assert_eq!(tree.get_node_by_index_path(&[0, 0]), Some(child));
+    /// assert_eq!(tree.get_index_path(child), Some(vec![0, 0]));
+    /// ```
+    pub fn get_node_by_index_path(&self, path: &[usize]) -> Option<TreeID> {
+        self.handler.get_node_by_index_path(path)
+    }
+
+    /// Return the index path of the target node. It's the inverse of [LoroTree::get_node_by_index_path].
+    ///
+    /// If the target node does not exist or is deleted, return `None`.
+    pub fn get_index_path(&self, target: TreeID) -> Option<Vec<usize>> {
+        self.handler.get_index_path(&target)
+    }
+
     /// Return container id of the tree.
     pub fn id(&self) -> ContainerID {
         self.handler.id()
@@ -1912,6 +2519,58 @@ impl LoroTree {
         self.handler.get_deep_value()
     }
 
+    /// Export the `node` and its descendants, with their order and meta, as a [TreeSubtree].
+    ///
+    /// The subtree is a detached copy, so it's not affected by the later changes of the tree.
+    /// It can be imported into another tree by [LoroTree::import_subtree].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let tree = doc.get_tree("tree");
+    /// let root = tree.create(None).unwrap();
+    /// let child = tree.create(root).unwrap();
+    /// tree.get_meta(child).unwrap().insert("name", "child").unwrap();
+    /// let subtree = tree.export_subtree(root).unwrap();
+    ///
+    /// let other = LoroDoc::new();
+    /// let other_tree = other.get_tree("tree");
+    /// other_tree.enable_fractional_index(0);
+    /// let mapping = other_tree.import_subtree(None, 0, &subtree).unwrap();
+    /// let new_child = mapping[&child];
+    /// assert_eq!(other_tree.parent(new_child), Some(mapping[&root].into()));
+    /// assert_eq!(
+    ///     other_tree.get_meta(new_child).unwrap().get_deep_value(),
+    ///     loro::loro_value!({"name": "child"})
+    /// );
+    /// ```
+    pub fn export_subtree(&self, node: TreeID) -> LoroResult<TreeSubtree> {
+        self.handler.export_subtree(node).map(TreeSubtree::from)
+    }
+
+    /// Recreate the exported [TreeSubtree] as the `index`-th child of `parent`.
+    ///
+    /// The nodes are created with new [TreeID]s. It returns the mapping from the ids in `data`
+    /// to the new ids.
+    ///
+    /// The fractional index must be enabled, because it's required to create a node at the
+    /// given index. See [LoroTree::create_at].
+    pub fn import_subtree<T: Into<TreeParentId>>(
+        &self,
+        parent: T,
+        index: usize,
+        data: &TreeSubtree,
+    ) -> LoroResult<FxHashMap<TreeID, TreeID>> {
+        if !self.handler.is_fractional_index_enabled() {
+            return Err(LoroTreeError::FractionalIndexNotEnabled.into());
+        }
+        self.handler
+            .import_subtree(parent.into(), index, &InnerTreeSubtree::from(data))
+    }
+
     // This method is used for testing only.
     #[doc(hidden)]
     #[allow(non_snake_case)]
@@ -2460,6 +3119,64 @@ impl UndoManager {
         Self(inner)
     }
 
+    /// Create a new UndoManager that only records and reverts the local changes on the
+    /// containers in the given scope.
+    ///
+    /// The local changes on the other containers are treated like remote changes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, UndoManager, undo::UndoScope};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let title = doc.get_text("title");
+    /// let body = doc.get_text("body");
+    /// let mut undo = UndoManager::new_with_scope(&doc, UndoScope::new([title.id()]));
+    /// title.insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// body.insert(0, "World").unwrap();
+    /// doc.commit();
+    /// undo.undo(&doc).unwrap();
+    /// assert_eq!(title.to_string(), "");
+    /// assert_eq!(body.to_string(), "World");
+    /// ```
+    pub fn new_with_scope(doc: &LoroDoc, scope: UndoScope) -> Self {
+        let mut inner = InnerUndoManager::new_with_scope(&doc.doc, scope);
+        inner.set_max_undo_steps(100);
+        Self(inner)
+    }
+
+    /// Create a new UndoManager that records the changes made by all the peers.
+    ///
+    /// Every new change, whether it's local or imported, becomes an undo item. Undoing it
+    /// reverts the change with a new local change, no matter which peer made it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, UndoManager};
+    ///
+    /// let doc_a = LoroDoc::new();
+    /// let doc_b = LoroDoc::new();
+    /// let mut undo = UndoManager::new_global(&doc_a);
+    /// doc_a.get_text("text").insert(0, "Hello").unwrap();
+    /// doc_a.commit();
+    /// doc_b.import(&doc_a.export(loro::ExportMode::all_updates()).unwrap()).unwrap();
+    /// doc_b.get_text("text").insert(5, " world").unwrap();
+    /// doc_b.commit();
+    /// doc_a.import(&doc_b.export(loro::ExportMode::all_updates()).unwrap()).unwrap();
+    ///
+    /// // Revert the change imported from doc_b
+    /// undo.undo(&doc_a).unwrap();
+    /// assert_eq!(doc_a.get_text("text").to_string(), "Hello");
+    /// ```
+    pub fn new_global(doc: &LoroDoc) -> Self {
+        let mut inner = InnerUndoManager::new_global(&doc.doc);
+        inner.set_max_undo_steps(100);
+        Self(inner)
+    }
+
     /// Undo the last change made by the peer.
     pub fn undo(&mut self, doc: &LoroDoc) -> LoroResult<bool> {
         self.0.undo(&doc.doc)
@@ -2501,6 +3218,45 @@ impl UndoManager {
         self.0.set_merge_interval(interval)
     }
 
+    /// Start a group of undo items.
+    ///
+    /// All the local commits until the matching [`UndoManager::group_end`] are merged into
+    /// one undo item. Groups can be nested, and only the outermost group creates the item.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, UndoManager};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let mut undo = UndoManager::new(&doc);
+    /// let text = doc.get_text("text");
+    /// undo.group_start(&doc).unwrap();
+    /// text.insert(0, "hello").unwrap();
+    /// doc.commit();
+    /// text.insert(0, "> ").unwrap();
+    /// doc.commit();
+    /// undo.group_end(&doc).unwrap();
+    /// undo.undo(&doc).unwrap();
+    /// assert_eq!(text.to_string(), "");
+    /// ```
+    pub fn group_start(&mut self, doc: &LoroDoc) -> LoroResult<()> {
+        self.0.group_start(&doc.doc)
+    }
+
+    /// End the group started by [`UndoManager::group_start`].
+    pub fn group_end(&mut self, doc: &LoroDoc) -> LoroResult<()> {
+        self.0.group_end(&doc.doc)
+    }
+
+    /// Set the filter of the changes recorded by an UndoManager created by [`UndoManager::new_global`].
+    ///
+    /// The changes that the filter returns false for are not recorded,
+    /// e.g. it can skip the changes by their commit messages.
+    pub fn set_change_filter(&mut self, filter: Option<ChangeFilter>) {
+        self.0.set_change_filter(filter)
+    }
+
     /// Set the listener for push events.
     /// The listener will be called when a new undo/redo item is pushed into the stack.
     pub fn set_on_push(&mut self, on_push: Option<OnPush>) {
@@ -2517,4 +3273,121 @@ impl UndoManager {
     pub fn clear(&self) {
         self.0.clear();
     }
+
+    /// Export the undo stack and the redo stack, so that they can be restored by
+    /// [`UndoManager::import_state`] after the app restarts.
+    pub fn export_state(&self) -> Vec<u8> {
+        self.0.export_state()
+    }
+
+    /// Restore the undo stack and the redo stack exported by [`UndoManager::export_state`].
+    ///
+    /// The doc must use the same peer id as the exported UndoManager and include all the
+    /// changes referred by the exported stacks.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{ExportMode, LoroDoc, UndoManager};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// let mut undo = UndoManager::new(&doc);
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// let undo_state = undo.export_state();
+    /// let snapshot = doc.export(ExportMode::Snapshot).unwrap();
+    ///
+    /// // After restarting
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// doc.import(&snapshot).unwrap();
+    /// let mut undo = UndoManager::new(&doc);
+    /// undo.import_state(&doc, &undo_state).unwrap();
+    /// undo.undo(&doc).unwrap();
+    /// assert_eq!(doc.get_text("text").to_string(), "");
+    /// ```
+    pub fn import_state(&mut self, doc: &LoroDoc, bytes: &[u8]) -> LoroResult<()> {
+        self.0.import_state(&doc.doc, bytes)
+    }
+}
+
+/// RemoteSelections shares the selections of the peers through an ephemeral store and
+/// resolves them to the up-to-date positions in a text or list container.
+///
+/// # Example
+///
+/// ```
+/// use loro::{LoroDoc, RemoteSelections};
+///
+/// let doc_a = LoroDoc::new();
+/// doc_a.set_peer_id(1).unwrap();
+/// let text_a = doc_a.get_text("text");
+/// text_a.insert(0, "Hello world").unwrap();
+/// let mut selections_a = RemoteSelections::new(&doc_a, 30_000);
+/// let anchor = text_a.get_cursor(6, Default::default()).unwrap();
+/// let focus = text_a.get_cursor(11, Default::default()).unwrap();
+/// selections_a.set_local_selection(&anchor, &focus).unwrap();
+///
+/// let doc_b = LoroDoc::new();
+/// doc_b.import(&doc_a.export(loro::ExportMode::all_updates()).unwrap()).unwrap();
+/// let mut selections_b = RemoteSelections::new(&doc_b, 30_000);
+/// selections_b.apply(&selections_a.encode_delta()).unwrap();
+/// doc_b.get_text("text").insert(0, "> ").unwrap();
+/// doc_b.commit();
+/// let ranges = selections_b.get_selections(&text_a.id());
+/// assert_eq!(ranges[0].0, 1);
+/// assert_eq!((ranges[0].1.start(), ranges[0].1.end()), (8, 13));
+/// ```
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct RemoteSelections(InnerRemoteSelections);
+
+impl RemoteSelections {
+    /// Create a new RemoteSelections. The selections are removed after `timeout` milliseconds
+    /// since they are updated, see [`RemoteSelections::remove_outdated`].
+    ///
+    /// It doesn't keep the doc alive, and the local selections follow the peer id of the doc.
+    pub fn new(doc: &LoroDoc, timeout: i64) -> Self {
+        Self(InnerRemoteSelections::new(&doc.doc, timeout))
+    }
+
+    /// Set the selection of the local peer. The anchor and the focus must be in the same container.
+    pub fn set_local_selection(&mut self, anchor: &Cursor, focus: &Cursor) -> LoroResult<()> {
+        self.0.set_local_selection(anchor, focus)
+    }
+
+    /// Remove the selection of the local peer in the given container.
+    pub fn remove_local_selection(&mut self, container: &ContainerID) {
+        self.0.remove_local_selection(container)
+    }
+
+    /// Encode the local selections changed since the last call.
+    pub fn encode_delta(&mut self) -> Vec<u8> {
+        self.0.encode_delta()
+    }
+
+    /// Encode all the selections that are not outdated.
+    pub fn encode_all(&self) -> Vec<u8> {
+        self.0.encode_all()
+    }
+
+    /// Apply the encoded selections from the other peers.
+    pub fn apply(&mut self, encoded: &[u8]) -> LoroResult<ChangedKeys> {
+        self.0.apply(encoded)
+    }
+
+    /// Remove the selections that are not updated within the timeout.
+    pub fn remove_outdated(&mut self) -> ChangedKeys {
+        self.0.remove_outdated()
+    }
+
+    /// Get the up-to-date selections of all the peers in the given container, sorted by peer id.
+    ///
+    /// The selection of the local peer is included.
+    pub fn get_selections(&self, container: &ContainerID) -> Vec<(PeerID, SelectionRange)> {
+        let mut ans: Vec<_> = self.0.get_selections(container).into_iter().collect();
+        ans.sort_unstable_by_key(|(peer, _)| *peer);
+        ans
+    }
 }
@@ -0,0 +1,547 @@
+//! Map Rust types onto Loro containers.
+//!
+//! [LoroSerialize] writes a value into a container, and [LoroDeserialize] reads it back.
+//! With the `derive` feature, both traits can be derived for structs with named fields:
+//!
+//! - A struct is stored in a [LoroMap], with each field under its name.
+//! - `Vec<T>` is stored in a [LoroList], or in a [LoroMovableList] with `#[loro(movable)]`.
+//! - `String` is stored as a plain value, or in a [LoroText] with `#[loro(text)]`.
+//! - `HashMap<String, T>` and `BTreeMap<String, T>` are stored in a [LoroMap].
+//! - `None` removes the key from the map.
+//!
+//! The other field attributes are `#[loro(rename = "key")]`, `#[loro(default)]`, which uses
+//! [Default] when the key is missing, and `#[loro(skip)]`, which neither writes nor reads the field.
+//!
+//! Writing is diff-based. Unchanged values are not written again, child containers are updated
+//! in place, texts are updated by [LoroText::update], and only the changed range of a list is
+//! touched. The keys of the map that are not declared by the struct are kept.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use loro::{mapping, LoroDeserialize, LoroDoc, LoroSerialize};
+//!
+//! #[derive(Debug, PartialEq, LoroSerialize, LoroDeserialize)]
+//! struct Note {
+//!     #[loro(text)]
+//!     title: String,
+//!     tags: Vec<String>,
+//!     pinned: Option<bool>,
+//! }
+//!
+//! let doc = LoroDoc::new();
+//! let map = doc.get_map("note");
+//! let mut note = Note {
+//!     title: "Hello".into(),
+//!     tags: vec!["a".into()],
+//!     pinned: None,
+//! };
+//! mapping::write(&map, &note).unwrap();
+//! note.title.push_str(" world");
+//! note.tags.push("b".into());
+//! mapping::write(&map, &note).unwrap();
+//! assert_eq!(mapping::read::<_, Note>(&map).unwrap(), note);
+//! # }
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+
+use crate::{
+    Container, ContainerTrait, ContainerType, LoroError, LoroList, LoroMap, LoroMovableList,
+    LoroResult, LoroText, LoroValue, ValueOrContainer,
+};
+
+/// A type that can be written into Loro containers.
+pub trait LoroSerialize {
+    /// The type of the child container that stores the value,
+    /// or `None` if the value is stored as a plain [LoroValue].
+    const CONTAINER_TYPE: Option<ContainerType> = None;
+
+    /// Convert to a plain value. For the types stored in containers, it's the deep value.
+    fn to_loro_value(&self) -> LoroValue;
+
+    /// Update the container to match the value.
+    ///
+    /// It's only called when [LoroSerialize::CONTAINER_TYPE] is not `None`.
+    fn update_container(&self, container: &Container) -> LoroResult<()> {
+        Err(LoroError::ArgErr(
+            format!(
+                "The value is not stored in a container, found {:?}",
+                container.get_type()
+            )
+            .into_boxed_str(),
+        ))
+    }
+
+    /// Write the value into the entry of the map.
+    ///
+    /// The existing child container is reused if it has the expected type.
+    fn write_to_map(&self, map: &LoroMap, key: &str) -> LoroResult<()> {
+        match Self::CONTAINER_TYPE {
+            None => {
+                let value = self.to_loro_value();
+                if let Some(ValueOrContainer::Value(old)) = map.get(key) {
+                    if old == value {
+                        return Ok(());
+                    }
+                }
+                map.insert(key, value)
+            }
+            Some(kind) => {
+                let child = match map.get(key) {
+                    Some(ValueOrContainer::Container(c)) if c.get_type() == kind => c,
+                    _ => map.insert_container(key, Container::new(kind))?,
+                };
+                self.update_container(&child)
+            }
+        }
+    }
+}
+
+/// A type that can be read from the deep value of Loro containers.
+pub trait LoroDeserialize: Sized {
+    /// Convert from the deep value.
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self>;
+
+    /// The value used when the key is missing in the map. It's an error by default.
+    fn from_missing_key(key: &str) -> LoroResult<Self> {
+        Err(LoroError::DeserializeError(
+            format!("missing key {:?}", key).into_boxed_str(),
+        ))
+    }
+}
+
+/// Write the value into the container.
+///
+/// The container should have the type of [LoroSerialize::CONTAINER_TYPE].
+pub fn write<C: ContainerTrait, T: LoroSerialize + ?Sized>(
+    container: &C,
+    value: &T,
+) -> LoroResult<()> {
+    value.update_container(&container.to_container())
+}
+
+/// Read the value from the deep value of the container.
+pub fn read<C: ContainerTrait, T: LoroDeserialize>(container: &C) -> LoroResult<T> {
+    T::from_loro_value(&ValueOrContainer::Container(container.to_container()).get_deep_value())
+}
+
+/// Get the map from the container, used by the derived [LoroSerialize] implementations.
+pub fn expect_map(container: &Container) -> LoroResult<&LoroMap> {
+    container
+        .as_map()
+        .ok_or_else(|| container_type_error(ContainerType::Map, container))
+}
+
+/// Write the string into the [LoroText] of the map entry.
+pub fn write_text_entry(map: &LoroMap, key: &str, text: &str) -> LoroResult<()> {
+    let child = match map.get(key) {
+        Some(ValueOrContainer::Container(Container::Text(text))) => text,
+        _ => map.insert_container(key, LoroText::new())?,
+    };
+    if child.to_string() != text {
+        child.update(text);
+    }
+
+    Ok(())
+}
+
+/// Write the items into the [LoroMovableList] of the map entry.
+pub fn write_movable_list_entry<T: LoroSerialize>(
+    map: &LoroMap,
+    key: &str,
+    items: &[T],
+) -> LoroResult<()> {
+    let child = match map.get(key) {
+        Some(ValueOrContainer::Container(Container::MovableList(list))) => list,
+        _ => map.insert_container(key, LoroMovableList::new())?,
+    };
+    write_movable_list(&child, items)
+}
+
+/// Read the entry of a map value, used by the derived [LoroDeserialize] implementations.
+pub fn read_entry<T: LoroDeserialize>(map: &LoroValue, key: &str) -> LoroResult<T> {
+    let LoroValue::Map(entries) = map else {
+        return Err(value_type_error("a map", map));
+    };
+    match entries.get(key) {
+        Some(value) => T::from_loro_value(value),
+        None => T::from_missing_key(key),
+    }
+}
+
+/// Read the entry of a map value, or use the default value if the key is missing.
+pub fn read_entry_or_default<T: LoroDeserialize + Default>(
+    map: &LoroValue,
+    key: &str,
+) -> LoroResult<T> {
+    let LoroValue::Map(entries) = map else {
+        return Err(value_type_error("a map", map));
+    };
+    match entries.get(key) {
+        Some(value) => T::from_loro_value(value),
+        None => Ok(T::default()),
+    }
+}
+
+/// Update the list to match the items. Only the changed range is touched.
+pub fn write_list<T: LoroSerialize>(list: &LoroList, items: &[T]) -> LoroResult<()> {
+    let Some(kind) = T::CONTAINER_TYPE else {
+        let old: Vec<_> = (0..list.len()).map(|i| plain_value(list.get(i))).collect();
+        let new: Vec<_> = items.iter().map(|x| x.to_loro_value()).collect();
+        let (start, old_end, new_end) = changed_range(&old, &new);
+        if old_end > start {
+            list.delete(start, old_end - start)?;
+        }
+        for (i, value) in new[start..new_end].iter().enumerate() {
+            list.insert(start + i, value.clone())?;
+        }
+        return Ok(());
+    };
+
+    let old_len = list.len();
+    for (i, item) in items.iter().enumerate() {
+        let child = match list.get(i) {
+            Some(ValueOrContainer::Container(c)) if c.get_type() == kind => c,
+            Some(_) => {
+                list.delete(i, 1)?;
+                list.insert_container(i, Container::new(kind))?
+            }
+            None => list.insert_container(i, Container::new(kind))?,
+        };
+        item.update_container(&child)?;
+    }
+    if old_len > items.len() {
+        list.delete(items.len(), old_len - items.len())?;
+    }
+
+    Ok(())
+}
+
+/// Update the movable list to match the items. Only the changed range is touched.
+///
+/// The elements in the changed range are overwritten by [LoroMovableList::set] when possible.
+pub fn write_movable_list<T: LoroSerialize>(list: &LoroMovableList, items: &[T]) -> LoroResult<()> {
+    let Some(kind) = T::CONTAINER_TYPE else {
+        let old: Vec<_> = (0..list.len()).map(|i| plain_value(list.get(i))).collect();
+        let new: Vec<_> = items.iter().map(|x| x.to_loro_value()).collect();
+        let (start, old_end, new_end) = changed_range(&old, &new);
+        let set_end = start + (old_end - start).min(new_end - start);
+        for i in start..set_end {
+            if old[i].as_ref() != Some(&new[i]) {
+                list.set(i, new[i].clone())?;
+            }
+        }
+        if old_end > set_end {
+            list.delete(set_end, old_end - set_end)?;
+        }
+        for (i, value) in new.iter().enumerate().take(new_end).skip(set_end) {
+            list.insert(i, value.clone())?;
+        }
+        return Ok(());
+    };
+
+    let old_len = list.len();
+    for (i, item) in items.iter().enumerate() {
+        let child = match list.get(i) {
+            Some(ValueOrContainer::Container(c)) if c.get_type() == kind => c,
+            Some(_) => list.set_container(i, Container::new(kind))?,
+            None => list.insert_container(i, Container::new(kind))?,
+        };
+        item.update_container(&child)?;
+    }
+    if old_len > items.len() {
+        list.delete(items.len(), old_len - items.len())?;
+    }
+
+    Ok(())
+}
+
+fn plain_value(v: Option<ValueOrContainer>) -> Option<LoroValue> {
+    match v {
+        Some(ValueOrContainer::Value(v)) => Some(v),
+        _ => None,
+    }
+}
+
+/// Returns `(start, old_end, new_end)`, the range outside the common prefix and suffix
+fn changed_range(old: &[Option<LoroValue>], new: &[LoroValue]) -> (usize, usize, usize) {
+    let start = old
+        .iter()
+        .zip(new)
+        .take_while(|(a, b)| a.as_ref() == Some(*b))
+        .count();
+    let suffix = old[start..]
+        .iter()
+        .rev()
+        .zip(new[start..].iter().rev())
+        .take_while(|(a, b)| a.as_ref() == Some(*b))
+        .count();
+    (start, old.len() - suffix, new.len() - suffix)
+}
+
+fn value_type_error(expected: &str, value: &LoroValue) -> LoroError {
+    LoroError::DeserializeError(
+        format!("expected {}, found {:?}", expected, value).into_boxed_str(),
+    )
+}
+
+fn container_type_error(expected: ContainerType, container: &Container) -> LoroError {
+    LoroError::ArgErr(
+        format!(
+            "Expected a {} container, found a {} container",
+            expected,
+            container.get_type()
+        )
+        .into_boxed_str(),
+    )
+}
+
+macro_rules! impl_int {
+    ($($t:ty),*) => {$(
+        impl LoroSerialize for $t {
+            fn to_loro_value(&self) -> LoroValue {
+                LoroValue::I64(i64::from(*self))
+            }
+        }
+
+        impl LoroDeserialize for $t {
+            fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+                match value {
+                    LoroValue::I64(v) => <$t>::try_from(*v).map_err(|_| {
+                        LoroError::DeserializeError(
+                            format!("{} is out of the range of {}", v, stringify!($t))
+                                .into_boxed_str(),
+                        )
+                    }),
+                    _ => Err(value_type_error(stringify!($t), value)),
+                }
+            }
+        }
+    )*};
+}
+
+impl_int!(i8, i16, i32, u8, u16, u32);
+
+impl LoroSerialize for i64 {
+    fn to_loro_value(&self) -> LoroValue {
+        LoroValue::I64(*self)
+    }
+}
+
+impl LoroDeserialize for i64 {
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+        match value {
+            LoroValue::I64(v) => Ok(*v),
+            _ => Err(value_type_error("i64", value)),
+        }
+    }
+}
+
+impl LoroSerialize for f64 {
+    fn to_loro_value(&self) -> LoroValue {
+        LoroValue::Double(*self)
+    }
+}
+
+impl LoroDeserialize for f64 {
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+        match value {
+            LoroValue::Double(v) => Ok(*v),
+            LoroValue::I64(v) => Ok(*v as f64),
+            _ => Err(value_type_error("f64", value)),
+        }
+    }
+}
+
+impl LoroSerialize for f32 {
+    fn to_loro_value(&self) -> LoroValue {
+        LoroValue::Double(f64::from(*self))
+    }
+}
+
+impl LoroDeserialize for f32 {
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+        f64::from_loro_value(value).map(|v| v as f32)
+    }
+}
+
+impl LoroSerialize for bool {
+    fn to_loro_value(&self) -> LoroValue {
+        LoroValue::Bool(*self)
+    }
+}
+
+impl LoroDeserialize for bool {
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+        match value {
+            LoroValue::Bool(v) => Ok(*v),
+            _ => Err(value_type_error("bool", value)),
+        }
+    }
+}
+
+impl LoroSerialize for String {
+    fn to_loro_value(&self) -> LoroValue {
+        LoroValue::from(self.as_str())
+    }
+}
+
+impl LoroDeserialize for String {
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+        match value {
+            LoroValue::String(v) => Ok(v.to_string()),
+            _ => Err(value_type_error("a string", value)),
+        }
+    }
+}
+
+impl LoroSerialize for LoroValue {
+    fn to_loro_value(&self) -> LoroValue {
+        self.clone()
+    }
+}
+
+impl LoroDeserialize for LoroValue {
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl<T: LoroSerialize> LoroSerialize for Option<T> {
+    fn to_loro_value(&self) -> LoroValue {
+        match self {
+            Some(v) => v.to_loro_value(),
+            None => LoroValue::Null,
+        }
+    }
+
+    fn write_to_map(&self, map: &LoroMap, key: &str) -> LoroResult<()> {
+        match self {
+            Some(v) => v.write_to_map(map, key),
+            None => {
+                if map.get(key).is_some() {
+                    map.delete(key)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<T: LoroDeserialize> LoroDeserialize for Option<T> {
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+        match value {
+            LoroValue::Null => Ok(None),
+            v => T::from_loro_value(v).map(Some),
+        }
+    }
+
+    fn from_missing_key(_key: &str) -> LoroResult<Self> {
+        Ok(None)
+    }
+}
+
+impl<T: LoroSerialize> LoroSerialize for Vec<T> {
+    const CONTAINER_TYPE: Option<ContainerType> = Some(ContainerType::List);
+
+    fn to_loro_value(&self) -> LoroValue {
+        LoroValue::from(
+            self.iter()
+                .map(|x| x.to_loro_value())
+                .collect::<Vec<LoroValue>>(),
+        )
+    }
+
+    fn update_container(&self, container: &Container) -> LoroResult<()> {
+        match container {
+            Container::List(list) => write_list(list, self),
+            Container::MovableList(list) => write_movable_list(list, self),
+            _ => Err(container_type_error(ContainerType::List, container)),
+        }
+    }
+}
+
+impl<T: LoroDeserialize> LoroDeserialize for Vec<T> {
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+        match value {
+            LoroValue::List(list) => list.iter().map(T::from_loro_value).collect(),
+            _ => Err(value_type_error("a list", value)),
+        }
+    }
+}
+
+fn write_map_entries<'a, T: LoroSerialize + 'a>(
+    container: &Container,
+    entries: impl Iterator<Item = (&'a String, &'a T)>,
+    contains_key: impl Fn(&str) -> bool,
+) -> LoroResult<()> {
+    let map = expect_map(container)?;
+    let removed: Vec<_> = map.keys().filter(|k| !contains_key(&**k)).collect();
+    for key in removed {
+        map.delete(&key)?;
+    }
+    for (key, value) in entries {
+        value.write_to_map(map, key)?;
+    }
+
+    Ok(())
+}
+
+impl<T: LoroSerialize, S: BuildHasher> LoroSerialize for HashMap<String, T, S> {
+    const CONTAINER_TYPE: Option<ContainerType> = Some(ContainerType::Map);
+
+    fn to_loro_value(&self) -> LoroValue {
+        LoroValue::from(
+            self.iter()
+                .map(|(k, v)| (k.clone(), v.to_loro_value()))
+                .collect::<HashMap<String, LoroValue>>(),
+        )
+    }
+
+    fn update_container(&self, container: &Container) -> LoroResult<()> {
+        write_map_entries(container, self.iter(), |k| self.contains_key(k))
+    }
+}
+
+impl<T: LoroDeserialize, S: BuildHasher + Default> LoroDeserialize for HashMap<String, T, S> {
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+        read_map_entries(value)
+    }
+}
+
+impl<T: LoroSerialize> LoroSerialize for BTreeMap<String, T> {
+    const CONTAINER_TYPE: Option<ContainerType> = Some(ContainerType::Map);
+
+    fn to_loro_value(&self) -> LoroValue {
+        LoroValue::from(
+            self.iter()
+                .map(|(k, v)| (k.clone(), v.to_loro_value()))
+                .collect::<HashMap<String, LoroValue>>(),
+        )
+    }
+
+    fn update_container(&self, container: &Container) -> LoroResult<()> {
+        write_map_entries(container, self.iter(), |k| self.contains_key(k))
+    }
+}
+
+impl<T: LoroDeserialize> LoroDeserialize for BTreeMap<String, T> {
+    fn from_loro_value(value: &LoroValue) -> LoroResult<Self> {
+        read_map_entries(value)
+    }
+}
+
+fn read_map_entries<T: LoroDeserialize, M: FromIterator<(String, T)>>(
+    value: &LoroValue,
+) -> LoroResult<M> {
+    match value {
+        LoroValue::Map(map) => map
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), T::from_loro_value(v)?)))
+            .collect(),
+        _ => Err(value_type_error("a map", value)),
+    }
+}
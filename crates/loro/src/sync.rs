@@ -0,0 +1,108 @@
+//! A transport-agnostic sync protocol between two [LoroDoc]s.
+//!
+//! # Example
+//!
+//! ```
+//! # use loro::{LoroDoc, sync::SyncSession};
+//! let doc_a = LoroDoc::new();
+//! let doc_b = LoroDoc::new();
+//! doc_a.get_text("text").insert(0, "hello").unwrap();
+//! doc_a.commit();
+//!
+//! let mut a = SyncSession::new();
+//! let mut b = SyncSession::new();
+//! let mut to_b = vec![a.start(&doc_a)];
+//! while !to_b.is_empty() {
+//!     let mut to_a = Vec::new();
+//!     for msg in to_b.drain(..) {
+//!         to_a.extend(b.receive(&doc_b, &msg).unwrap());
+//!     }
+//!     for msg in to_a {
+//!         to_b.extend(a.receive(&doc_a, &msg).unwrap());
+//!     }
+//! }
+//!
+//! assert_eq!(doc_b.get_text("text").to_string(), "hello");
+//! ```
+use loro_internal::sync::SyncSession as InnerSyncSession;
+pub use loro_internal::sync::{SyncMessage, SyncState};
+
+use crate::{ImportStatus, LoroDoc, LoroResult, VersionVector};
+
+/// The state machine of the sync protocol between a local [LoroDoc] and one remote peer.
+///
+/// It doesn't own the doc or the transport. Deliver the returned messages to the
+/// remote peer in order, and feed the messages received from it to [SyncSession::receive].
+///
+/// - The handshake exchanges the oplog version vectors of both sides.
+/// - After the handshake, use [SyncSession::poll_local] to get the local updates
+///   that haven't been sent yet.
+/// - If the remote peer is behind the start version of the local shallow history,
+///   a snapshot is sent instead of the updates.
+/// - After reconnecting, call [SyncSession::disconnect] and [SyncSession::start] to resume.
+#[derive(Debug, Clone, Default)]
+#[repr(transparent)]
+pub struct SyncSession(InnerSyncSession);
+
+impl SyncSession {
+    /// Create a new session.
+    pub fn new() -> Self {
+        Self(InnerSyncSession::new())
+    }
+
+    /// Set the origin of the imports triggered by this session. The default value is `"sync"`.
+    ///
+    /// It can be used to distinguish the events triggered by the sync.
+    pub fn set_origin(&mut self, origin: &str) {
+        self.0.set_origin(origin)
+    }
+
+    /// Get the current state of the session.
+    pub fn state(&self) -> SyncState {
+        self.0.state()
+    }
+
+    /// Whether the handshake is finished.
+    pub fn is_synced(&self) -> bool {
+        self.0.is_synced()
+    }
+
+    /// The version that the remote peer is known to have.
+    pub fn remote_vv(&self) -> &VersionVector {
+        self.0.remote_vv()
+    }
+
+    /// The import status of the last received updates or snapshot.
+    pub fn last_import_status(&self) -> Option<&ImportStatus> {
+        self.0.last_import_status()
+    }
+
+    /// Start or resume the session.
+    ///
+    /// Returns the hello message that should be sent to the remote peer.
+    pub fn start(&mut self, doc: &LoroDoc) -> Vec<u8> {
+        self.0.start(&doc.doc)
+    }
+
+    /// Mark the connection as lost.
+    ///
+    /// The updates that were not acknowledged by the remote peer will be
+    /// resent after the next handshake if they are still missing.
+    pub fn disconnect(&mut self) {
+        self.0.disconnect()
+    }
+
+    /// Handle a message received from the remote peer.
+    ///
+    /// Returns the messages that should be sent back to the remote peer.
+    pub fn receive(&mut self, doc: &LoroDoc, msg: &[u8]) -> LoroResult<Vec<Vec<u8>>> {
+        self.0.receive(&doc.doc, msg)
+    }
+
+    /// Get the local updates that haven't been sent to the remote peer.
+    ///
+    /// Returns `None` if the handshake is not finished or there are no new updates.
+    pub fn poll_local(&mut self, doc: &LoroDoc) -> LoroResult<Option<Vec<u8>>> {
+        self.0.poll_local(&doc.doc)
+    }
+}
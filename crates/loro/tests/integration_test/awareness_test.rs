@@ -0,0 +1,398 @@
+use std::sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc, Mutex,
+};
+
+use loro::{
+    awareness::{Awareness, AwarenessEvent, AwarenessEventBy, EphemeralEvent, EphemeralStore},
+    cursor::Side,
+    LoroDoc, LoroError, LoroValue, RemoteSelections,
+};
+use loro_common::InternalString;
+
+#[test]
+fn ephemeral_store_per_key_sync() -> anyhow::Result<()> {
+    let mut a = EphemeralStore::new(1, 30_000);
+    let mut b = EphemeralStore::new(2, 30_000);
+    a.set("cursor", 1);
+    a.set("user", "Alice");
+    let changed = b.apply(&a.encode_delta())?;
+    let mut keys = changed.get(&1).unwrap().clone();
+    keys.sort();
+    assert_eq!(
+        keys,
+        vec![InternalString::from("cursor"), InternalString::from("user")]
+    );
+    assert_eq!(b.get(1, "user"), Some("Alice".into()));
+    assert_eq!(b.get_peer_states(1).len(), 2);
+
+    // Only the changed keys are encoded in the delta
+    a.set("cursor", 2);
+    let delta = a.encode_delta();
+    assert!(delta.len() < a.encode_all().len());
+    let changed = b.apply(&delta)?;
+    assert_eq!(
+        changed.get(&1).unwrap(),
+        &vec![InternalString::from("cursor")]
+    );
+    assert_eq!(b.get(1, "cursor"), Some(LoroValue::I64(2)));
+    // Nothing is changed since the last delta
+    assert!(b.apply(&a.encode_delta())?.is_empty());
+
+    // Deletions are propagated
+    a.delete("user");
+    assert_eq!(a.get_local("user"), None);
+    let changed = b.apply(&a.encode_delta())?;
+    assert_eq!(
+        changed.get(&1).unwrap(),
+        &vec![InternalString::from("user")]
+    );
+    assert_eq!(b.get(1, "user"), None);
+    assert_eq!(b.get(1, "cursor"), Some(LoroValue::I64(2)));
+
+    // Stale states are ignored
+    let mut c = EphemeralStore::new(3, 30_000);
+    assert!(c.apply(&b.encode(&[1]))?.contains_key(&1));
+    assert_eq!(c.get(1, "user"), None);
+    assert!(b.apply(&c.encode_all())?.is_empty());
+
+    // The local states cannot be overridden by the others
+    b.set("cursor", 10);
+    a.apply(&b.encode_all())?;
+    assert!(b.apply(&a.encode_all())?.get(&2).is_none());
+    assert_eq!(b.get_local("cursor"), Some(LoroValue::I64(10)));
+    Ok(())
+}
+
+#[test]
+fn ephemeral_store_remove_outdated() -> anyhow::Result<()> {
+    let now = Arc::new(AtomicI64::new(0));
+    let now_clone = now.clone();
+    let clock = Arc::new(move || now_clone.load(Ordering::SeqCst));
+    let mut a = EphemeralStore::new(1, 10).with_clock(clock.clone());
+    let mut b = EphemeralStore::new(2, 10).with_clock(clock);
+    a.set("cursor", 1);
+    a.set("user", "Alice");
+    a.delete("user");
+    b.apply(&a.encode_all())?;
+    assert_eq!(b.get_all_states().get(&1).unwrap().len(), 2);
+    now.store(10, Ordering::SeqCst);
+    assert!(b.remove_outdated().is_empty());
+    assert_eq!(b.get_all_states().get(&1).unwrap().len(), 2);
+
+    now.store(11, Ordering::SeqCst);
+    let removed = b.remove_outdated();
+    // The tombstones are removed silently
+    assert_eq!(
+        removed.get(&1).unwrap(),
+        &vec![InternalString::from("cursor")]
+    );
+    assert!(b.get_all_states().is_empty());
+    assert!(EphemeralStore::new(3, 10)
+        .apply(&a.encode_all())?
+        .is_empty());
+    Ok(())
+}
+
+fn ephemeral_event(by: AwarenessEventBy, changed: &[(u64, &[&str])]) -> EphemeralEvent {
+    EphemeralEvent {
+        by,
+        changed: changed
+            .iter()
+            .map(|(peer, keys)| (*peer, keys.iter().map(|&k| k.into()).collect()))
+            .collect(),
+    }
+}
+
+#[test]
+fn ephemeral_store_events() -> anyhow::Result<()> {
+    let now = Arc::new(AtomicI64::new(0));
+    let now_clone = now.clone();
+    let clock = Arc::new(move || now_clone.load(Ordering::SeqCst));
+    let mut a = EphemeralStore::new(1, 10).with_clock(clock.clone());
+    let mut b = EphemeralStore::new(2, 10).with_clock(clock);
+    let events: Arc<Mutex<Vec<EphemeralEvent>>> = Default::default();
+    let events_clone = events.clone();
+    let sub = b.subscribe(Box::new(move |e| {
+        events_clone.try_lock().unwrap().push(e.clone());
+        true
+    }));
+
+    b.set("cursor", 1);
+    b.delete("cursor");
+    // Deleting a missing key changes nothing
+    b.delete("user");
+    a.set("cursor", 2);
+    a.set("user", "Alice");
+    b.apply(&a.encode_delta())?;
+    // Nothing changed, so no event is emitted
+    b.apply(&a.encode_all())?;
+    a.delete("user");
+    b.apply(&a.encode_delta())?;
+    now.store(11, Ordering::SeqCst);
+    b.remove_outdated();
+
+    use AwarenessEventBy::*;
+    let mut recorded = std::mem::take(&mut *events.try_lock().unwrap());
+    for e in recorded.iter_mut() {
+        for keys in e.changed.values_mut() {
+            keys.sort();
+        }
+    }
+    assert_eq!(
+        recorded,
+        vec![
+            ephemeral_event(Local, &[(2, &["cursor"])]),
+            ephemeral_event(Local, &[(2, &["cursor"])]),
+            ephemeral_event(Import, &[(1, &["cursor", "user"])]),
+            ephemeral_event(Import, &[(1, &["user"])]),
+            // The tombstones are removed silently
+            ephemeral_event(Timeout, &[(1, &["cursor"])]),
+        ]
+    );
+
+    sub.unsubscribe();
+    b.set("cursor", 3);
+    assert!(events.try_lock().unwrap().is_empty());
+    Ok(())
+}
+
+#[test]
+fn ephemeral_store_invalid_input() {
+    let mut a = EphemeralStore::new(1, 30_000);
+    assert!(a.apply(&[1, 2, 3]).is_err());
+}
+
+fn event(
+    by: AwarenessEventBy,
+    origin: &str,
+    added: &[u64],
+    updated: &[u64],
+    removed: &[u64],
+) -> AwarenessEvent {
+    AwarenessEvent {
+        by,
+        origin: origin.into(),
+        added: added.to_vec(),
+        updated: updated.to_vec(),
+        removed: removed.to_vec(),
+    }
+}
+
+#[test]
+fn awareness_events() {
+    let now = Arc::new(AtomicI64::new(0));
+    let now_clone = now.clone();
+    let mut a = Awareness::new(1, 1000);
+    let mut b =
+        Awareness::new(2, 1000).with_clock(Arc::new(move || now_clone.load(Ordering::SeqCst)));
+    let events: Arc<Mutex<Vec<AwarenessEvent>>> = Default::default();
+    let events_clone = events.clone();
+    let _sub = b.subscribe(Box::new(move |e| {
+        events_clone.try_lock().unwrap().push(e.clone());
+        true
+    }));
+
+    b.set_local_state("b");
+    b.set_local_state("b2");
+    a.set_local_state("a");
+    b.apply_with_origin(&a.encode_all(), "ws");
+    // Nothing changed, so no event is emitted
+    b.apply(&a.encode_all());
+    a.set_local_state("a2");
+    b.apply(&a.encode_all());
+    now.store(600, Ordering::SeqCst);
+    b.set_local_state("b3");
+    now.store(1500, Ordering::SeqCst);
+    assert_eq!(b.remove_outdated(), vec![1]);
+
+    use AwarenessEventBy::*;
+    assert_eq!(
+        *events.try_lock().unwrap(),
+        vec![
+            event(Local, "", &[2], &[], &[]),
+            event(Local, "", &[], &[2], &[]),
+            event(Import, "ws", &[1], &[], &[]),
+            event(Import, "", &[], &[1], &[]),
+            event(Local, "", &[], &[2], &[]),
+            event(Timeout, "", &[], &[], &[1]),
+        ]
+    );
+}
+
+#[test]
+fn awareness_injected_clock() {
+    let now = Arc::new(AtomicI64::new(0));
+    let now_clone = now.clone();
+    let clock = Arc::new(move || now_clone.load(Ordering::SeqCst));
+    let mut a = Awareness::new(1, 100).with_clock(clock.clone());
+    let mut b = Awareness::new(2, 100).with_clock(clock);
+    let count = Arc::new(AtomicI64::new(0));
+    let count_clone = count.clone();
+    let sub = b.subscribe(Box::new(move |_| {
+        count_clone.fetch_add(1, Ordering::SeqCst);
+        true
+    }));
+
+    a.set_local_state(1);
+    b.apply(&a.encode_all());
+    now.store(100, Ordering::SeqCst);
+    assert!(b.remove_outdated().is_empty());
+    // Outdated states are not encoded
+    now.store(101, Ordering::SeqCst);
+    assert_eq!(a.encode_all(), Awareness::new(1, 100).encode_all());
+    assert_eq!(b.remove_outdated(), vec![1]);
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+
+    sub.unsubscribe();
+    a.set_local_state(2);
+    b.apply(&a.encode_all());
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn remote_selections_follow_edits() -> anyhow::Result<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    let text_a = doc_a.get_text("text");
+    text_a.insert(0, "Hello world")?;
+    let list_a = doc_a.get_list("list");
+    for i in 0..5 {
+        list_a.push(i)?;
+    }
+    doc_a.commit();
+    doc_b.import(&doc_a.export(loro::ExportMode::all_updates())?)?;
+
+    let mut sel_a = RemoteSelections::new(&doc_a, 30_000);
+    let mut sel_b = RemoteSelections::new(&doc_b, 30_000);
+    sel_a.set_local_selection(
+        &text_a.get_cursor(0, Side::Middle).unwrap(),
+        &text_a.get_cursor(5, Side::Middle).unwrap(),
+    )?;
+    sel_a.set_local_selection(
+        &list_a.get_cursor(3, Side::Middle).unwrap(),
+        &list_a.get_cursor(1, Side::Middle).unwrap(),
+    )?;
+    let text_b = doc_b.get_text("text");
+    sel_b.set_local_selection(
+        &text_b.get_cursor(6, Side::Middle).unwrap(),
+        &text_b.get_cursor(6, Side::Middle).unwrap(),
+    )?;
+    sel_b.apply(&sel_a.encode_delta())?;
+    sel_a.apply(&sel_b.encode_delta())?;
+
+    let ranges = sel_b.get_selections(&text_b.id());
+    assert_eq!(ranges.len(), 2);
+    assert_eq!(
+        (ranges[0].0, ranges[0].1.start(), ranges[0].1.end()),
+        (1, 0, 5)
+    );
+    assert!(ranges[1].1.is_collapsed());
+
+    // The cached positions are updated after the doc changes
+    text_b.insert(0, ">> ")?;
+    doc_b.commit();
+    doc_b.get_list("list").insert(0, "x")?;
+    doc_b.commit();
+    let ranges = sel_b.get_selections(&text_b.id());
+    assert_eq!((ranges[0].1.start(), ranges[0].1.end()), (3, 8));
+    assert_eq!(ranges[1].1.focus.pos, 9);
+    let ranges = sel_b.get_selections(&doc_b.get_list("list").id());
+    assert_eq!(ranges.len(), 1);
+    assert_eq!((ranges[0].1.anchor.pos, ranges[0].1.focus.pos), (4, 2));
+
+    // The remote edits are reflected on the other side as well
+    doc_a.import(&doc_b.export(loro::ExportMode::all_updates())?)?;
+    let ranges = sel_a.get_selections(&text_a.id());
+    assert_eq!(ranges[1].0, 2);
+    assert_eq!(ranges[1].1.focus.pos, 9);
+
+    // Removed selections are propagated
+    sel_a.remove_local_selection(&text_a.id());
+    sel_b.apply(&sel_a.encode_delta())?;
+    let ranges = sel_b.get_selections(&text_b.id());
+    assert_eq!(ranges.len(), 1);
+    assert_eq!(ranges[0].0, 2);
+    Ok(())
+}
+
+#[test]
+fn remote_selection_in_different_containers() {
+    let doc = LoroDoc::new();
+    let text = doc.get_text("text");
+    text.insert(0, "abc").unwrap();
+    let list = doc.get_list("list");
+    list.push(1).unwrap();
+    let mut sel = RemoteSelections::new(&doc, 30_000);
+    assert!(matches!(
+        sel.set_local_selection(
+            &text.get_cursor(0, Side::Middle).unwrap(),
+            &list.get_cursor(0, Side::Middle).unwrap(),
+        ),
+        Err(LoroError::ArgErr(..))
+    ));
+    assert!(sel.get_selections(&text.id()).is_empty());
+}
+
+#[test]
+fn remote_selection_follows_peer_id() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let text = doc.get_text("text");
+    text.insert(0, "Hello")?;
+    doc.commit();
+    let mut sel = RemoteSelections::new(&doc, 30_000);
+    sel.set_local_selection(
+        &text.get_cursor(0, Side::Middle).unwrap(),
+        &text.get_cursor(5, Side::Middle).unwrap(),
+    )?;
+    doc.set_peer_id(2)?;
+    let ranges = sel.get_selections(&text.id());
+    assert_eq!(ranges.len(), 1);
+    assert_eq!(ranges[0].0, 2);
+
+    let other = LoroDoc::new();
+    other.import(&doc.export(loro::ExportMode::all_updates())?)?;
+    let mut other_sel = RemoteSelections::new(&other, 30_000);
+    other_sel.apply(&sel.encode_delta())?;
+    let ranges = other_sel.get_selections(&text.id());
+    assert_eq!(ranges.len(), 1);
+    assert_eq!(ranges[0].0, 2);
+
+    // It doesn't keep the doc alive
+    drop(doc);
+    assert!(sel.get_selections(&text.id()).is_empty());
+    Ok(())
+}
+
+#[test]
+fn remote_selections_follow_pending_edits() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let text = doc.get_text("text");
+    text.insert(0, "Hello world")?;
+    doc.commit();
+    let mut sel = RemoteSelections::new(&doc, 30_000);
+    sel.set_local_selection(
+        &text.get_cursor(6, Side::Middle).unwrap(),
+        &text.get_cursor(11, Side::Middle).unwrap(),
+    )?;
+    let ranges = sel.get_selections(&text.id());
+    assert_eq!((ranges[0].1.start(), ranges[0].1.end()), (6, 11));
+
+    // The uncommitted edits don't emit events, but they move the selections
+    text.insert(0, "> ")?;
+    assert!(doc.get_pending_txn_len() > 0);
+    let ranges = sel.get_selections(&text.id());
+    assert_eq!((ranges[0].1.start(), ranges[0].1.end()), (8, 13));
+    text.insert(0, ">")?;
+    let ranges = sel.get_selections(&text.id());
+    assert_eq!((ranges[0].1.start(), ranges[0].1.end()), (9, 14));
+
+    doc.commit();
+    let ranges = sel.get_selections(&text.id());
+    assert_eq!((ranges[0].1.start(), ranges[0].1.end()), (9, 14));
+    Ok(())
+}
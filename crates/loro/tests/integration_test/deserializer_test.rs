@@ -0,0 +1,150 @@
+use loro::{LoroDoc, LoroError, LoroList, LoroMap, LoroMovableList, LoroText, ToJson};
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Doc {
+    title: String,
+    body: String,
+    version: i32,
+    score: Option<f64>,
+    authors: Vec<Author>,
+    tags: Vec<String>,
+    status: Status,
+    kind: Kind,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Author {
+    name: String,
+    email: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Draft,
+    Published,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+enum Kind {
+    Article { words: u32 },
+    Note(String),
+}
+
+fn init_doc() -> anyhow::Result<LoroDoc> {
+    let doc = LoroDoc::new();
+    let root = doc.get_map("doc");
+    root.insert("title", "Hello")?;
+    let body = root.insert_container("body", LoroText::new())?;
+    body.insert(0, "Hello world")?;
+    root.insert("version", 3)?;
+    root.insert("score", loro::LoroValue::Null)?;
+    let authors = root.insert_container("authors", LoroList::new())?;
+    let author = authors.insert_container(0, LoroMap::new())?;
+    author.insert("name", "Alice")?;
+    author.insert("email", "alice@example.com")?;
+    let author = authors.insert_container(1, LoroMap::new())?;
+    author.insert("name", "Bob")?;
+    let tags = root.insert_container("tags", LoroMovableList::new())?;
+    tags.push("crdt")?;
+    tags.push("rust")?;
+    root.insert("status", "published")?;
+    let kind = root.insert_container("kind", LoroMap::new())?;
+    let article = kind.insert_container("Article", LoroMap::new())?;
+    article.insert("words", 120)?;
+    // Not read by the deserialized types
+    let extra = root.insert_container("extra", LoroList::new())?;
+    for i in 0..100 {
+        extra.push(i)?;
+    }
+    doc.commit();
+    Ok(doc)
+}
+
+#[test]
+fn deserialize_struct_from_root() -> anyhow::Result<()> {
+    let doc = init_doc()?;
+    let id = doc.get_map("doc").id();
+    let value = Doc::deserialize(doc.deserializer_at(&id))?;
+    assert_eq!(
+        value,
+        Doc {
+            title: "Hello".into(),
+            body: "Hello world".into(),
+            version: 3,
+            score: None,
+            authors: vec![
+                Author {
+                    name: "Alice".into(),
+                    email: Some("alice@example.com".into()),
+                },
+                Author {
+                    name: "Bob".into(),
+                    email: None,
+                },
+            ],
+            tags: vec!["crdt".into(), "rust".into()],
+            status: Status::Published,
+            kind: Kind::Article { words: 120 },
+        }
+    );
+
+    // The lock is released after the deserializer is dropped
+    doc.get_map("doc")
+        .insert("kind", loro::loro_value!({"Note": "short"}))?;
+    let value = Doc::deserialize(doc.deserializer_at(&id))?;
+    assert_eq!(value.kind, Kind::Note("short".into()));
+    Ok(())
+}
+
+#[test]
+fn deserialize_nested_container() -> anyhow::Result<()> {
+    let doc = init_doc()?;
+    let authors = doc
+        .get_map("doc")
+        .get("authors")
+        .unwrap()
+        .into_container()
+        .unwrap();
+    let value = Vec::<Author>::deserialize(doc.deserializer_at(&authors.id()))?;
+    assert_eq!(value.len(), 2);
+    assert_eq!(value[1].name, "Bob");
+
+    let body = doc
+        .get_map("doc")
+        .get("body")
+        .unwrap()
+        .into_container()
+        .unwrap();
+    let value = String::deserialize(doc.deserializer_at(&body.id()))?;
+    assert_eq!(value, "Hello world");
+    Ok(())
+}
+
+#[test]
+fn same_as_deep_value() -> anyhow::Result<()> {
+    let doc = init_doc()?;
+    let tree = doc.get_tree("tree");
+    let root = tree.create(None)?;
+    let child = tree.create(root)?;
+    tree.get_meta(child)?.insert("name", "child")?;
+    doc.commit();
+
+    let deep_value = doc.get_deep_value().to_json_value();
+    for (name, id) in [("doc", doc.get_map("doc").id()), ("tree", tree.id())] {
+        let value = serde_json::Value::deserialize(doc.deserializer_at(&id))?;
+        assert_eq!(value, deep_value[name]);
+    }
+    Ok(())
+}
+
+#[test]
+fn type_mismatch() -> anyhow::Result<()> {
+    let doc = init_doc()?;
+    let id = doc.get_map("doc").id();
+    doc.get_map("doc").insert("version", "3")?;
+    let err = Doc::deserialize(doc.deserializer_at(&id)).unwrap_err();
+    assert!(matches!(err, LoroError::DeserializeError(_)));
+    Ok(())
+}
@@ -1,8 +1,9 @@
 use pretty_assertions::assert_eq;
 use std::sync::{Arc, Mutex};
 
-use loro::{Frontiers, LoroDoc, LoroError, UndoManager, ID};
+use loro::{loro_value, Frontiers, LoroDoc, LoroError, LoroText, ToJson, UndoManager, ID};
 use loro_internal::vv;
+use serde_json::json;
 
 #[test]
 fn disallow_editing_on_detached_mode_by_default() {
@@ -268,3 +269,116 @@ fn undo_still_works_after_detached_editing() {
     undo.redo(&doc).unwrap();
     assert_eq!(doc.get_text("text").to_string(), "Hello alice!");
 }
+
+#[test]
+fn detached_text_matches_attached_text() {
+    let doc = LoroDoc::new();
+    let attached = doc.get_text("text");
+    let detached = LoroText::new();
+    for text in [&attached, &detached] {
+        text.insert(0, "Hello world").unwrap();
+        text.mark(0..5, "bold", true).unwrap();
+        text.unmark(0..5, "bold").unwrap();
+        text.mark(6..11, "link", "https://loro.dev").unwrap();
+        text.apply_delta(&[
+            loro::TextDelta::Retain {
+                retain: 5,
+                attributes: None,
+            },
+            loro::TextDelta::Insert {
+                insert: "!".into(),
+                attributes: Some([("bold".to_string(), true.into())].into_iter().collect()),
+            },
+            loro::TextDelta::Delete { delete: 1 },
+        ])
+        .unwrap();
+        assert!(matches!(
+            text.insert(100, "x"),
+            Err(LoroError::OutOfBound { .. })
+        ));
+        assert!(matches!(
+            text.delete(10, 10),
+            Err(LoroError::OutOfBound { .. })
+        ));
+    }
+
+    let expected = json!([
+        {"insert": "Hello"},
+        {"insert": "!", "attributes": {"bold": true}},
+        {"insert": "world", "attributes": {"link": "https://loro.dev"}},
+    ]);
+    assert_eq!(attached.to_delta().to_json_value(), expected);
+    assert_eq!(detached.to_delta().to_json_value(), expected);
+
+    let new_doc = LoroDoc::new();
+    let text = new_doc
+        .get_map("map")
+        .insert_container("text", detached)
+        .unwrap();
+    assert_eq!(text.to_delta().to_json_value(), expected);
+}
+
+#[test]
+fn detached_tree_queries() {
+    let tree = loro::LoroTree::new();
+    let root = tree.create(None).unwrap();
+    let child = tree.create(root).unwrap();
+    let grandchild = tree.create(child).unwrap();
+    tree.get_meta(child)
+        .unwrap()
+        .insert("name", "child")
+        .unwrap();
+    assert!(matches!(
+        tree.mov(root, grandchild),
+        Err(LoroError::TreeError(loro::LoroTreeError::CyclicMoveError))
+    ));
+    assert_eq!(tree.get_nodes(false).len(), 3);
+    assert_eq!(tree.fractional_index(child), None);
+    assert_eq!(
+        tree.get_value_with_meta().to_json_value(),
+        json!([{
+            "id": root.to_string(),
+            "parent": null,
+            "meta": {},
+            "index": 0,
+            "fractional_index": null,
+            "children": [{
+                "id": child.to_string(),
+                "parent": root.to_string(),
+                "meta": {"name": "child"},
+                "index": 0,
+                "fractional_index": null,
+                "children": [{
+                    "id": grandchild.to_string(),
+                    "parent": child.to_string(),
+                    "meta": {},
+                    "index": 0,
+                    "fractional_index": null,
+                    "children": [],
+                }],
+            }],
+        }])
+    );
+
+    let other = tree.create(None).unwrap();
+    let other_child = tree.create(other).unwrap();
+    tree.delete(other).unwrap();
+    assert!(!tree.contains(other_child));
+    assert!(tree.is_node_deleted(&other_child).is_err());
+    assert!(!tree.is_node_deleted(&grandchild).unwrap());
+    assert_eq!(tree.get_nodes(false).len(), 3);
+
+    tree.enable_fractional_index(0);
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let attached = doc.get_map("map").insert_container("tree", tree).unwrap();
+    assert!(attached.is_fractional_index_enabled());
+    let roots = attached.roots();
+    assert_eq!(roots.len(), 1);
+    let children = attached.children(roots[0]).unwrap();
+    assert_eq!(
+        attached.get_meta(children[0]).unwrap().get_deep_value(),
+        loro_value!({"name": "child"})
+    );
+    assert_eq!(attached.children(children[0]).unwrap().len(), 1);
+}
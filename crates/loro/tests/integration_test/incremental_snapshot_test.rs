@@ -0,0 +1,114 @@
+use loro::{ExportMode, LoroDoc, LoroEncodeError, LoroError};
+
+use super::gen_action;
+
+#[test]
+fn incremental_snapshots_layered_onto_base() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    gen_action(&doc, 1, 1000);
+    doc.commit();
+    let base = doc.export(ExportMode::Snapshot)?;
+    let mut since = doc.oplog_vv();
+    let mut layers = Vec::new();
+    for seed in 2..5 {
+        gen_action(&doc, seed, 10);
+        doc.commit();
+        let layer = doc.export_incremental_snapshot(&since)?;
+        since = doc.oplog_vv();
+        layers.push(layer);
+    }
+
+    let full = doc.export(ExportMode::Snapshot)?;
+    assert!(layers[0].len() < full.len());
+    let layers: Vec<&[u8]> = layers.iter().map(|x| x.as_slice()).collect();
+    let new_doc = LoroDoc::from_incremental_snapshots(&base, &layers)?;
+    assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+    assert_eq!(new_doc.oplog_vv(), doc.oplog_vv());
+    assert_eq!(new_doc.oplog_frontiers(), doc.oplog_frontiers());
+
+    // The history is complete
+    new_doc.checkout(&Default::default())?;
+    assert_eq!(new_doc.get_text("text").to_string(), "");
+    new_doc.checkout_to_latest();
+    new_doc.get_text("text").insert(0, "123")?;
+    new_doc.commit();
+    let other = LoroDoc::new();
+    other.import(&new_doc.export(ExportMode::Snapshot)?)?;
+    assert_eq!(other.get_deep_value(), new_doc.get_deep_value());
+    Ok(())
+}
+
+#[test]
+fn incremental_snapshot_with_concurrent_peers() -> anyhow::Result<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    gen_action(&doc_a, 1, 100);
+    doc_a.commit();
+    let base = doc_a.export(ExportMode::Snapshot)?;
+    let since = doc_a.oplog_vv();
+    gen_action(&doc_b, 2, 100);
+    doc_b.commit();
+    doc_a.import(&doc_b.export(ExportMode::all_updates())?)?;
+    doc_a.get_map("map").insert("key", "value")?;
+    doc_a.commit();
+    let layer = doc_a.export_incremental_snapshot(&since)?;
+    let new_doc = LoroDoc::from_incremental_snapshots(&base, &[&layer])?;
+    assert_eq!(new_doc.get_deep_value(), doc_a.get_deep_value());
+    Ok(())
+}
+
+#[test]
+fn incremental_snapshot_from_detached_doc() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.get_text("text").insert(0, "hello")?;
+    doc.commit();
+    let base = doc.export(ExportMode::Snapshot)?;
+    let since = doc.oplog_vv();
+    let frontiers = doc.oplog_frontiers();
+    doc.get_text("text").insert(5, " world")?;
+    doc.commit();
+    doc.checkout(&frontiers)?;
+    let layer = doc.export_incremental_snapshot(&since)?;
+    assert!(doc.is_detached());
+    assert_eq!(doc.get_text("text").to_string(), "hello");
+    let new_doc = LoroDoc::from_incremental_snapshots(&base, &[&layer])?;
+    assert_eq!(new_doc.get_text("text").to_string(), "hello world");
+    Ok(())
+}
+
+#[test]
+fn incremental_snapshot_mismatch() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.get_text("text").insert(0, "hello")?;
+    doc.commit();
+    let base = doc.export(ExportMode::Snapshot)?;
+    let v0 = doc.oplog_vv();
+    doc.get_text("text").insert(5, " world")?;
+    doc.commit();
+    let v1 = doc.oplog_vv();
+    doc.get_text("text").insert(0, ">")?;
+    doc.commit();
+    // Skips the changes between v0 and v1
+    let layer = doc.export_incremental_snapshot(&v1)?;
+    let err = LoroDoc::from_incremental_snapshots(&base, &[&layer]).unwrap_err();
+    assert_eq!(err, LoroError::IncrementalSnapshotMismatch);
+
+    let layer = doc.export_incremental_snapshot(&v0)?;
+    // The layer cannot be imported directly
+    assert!(LoroDoc::new().import(&layer).is_err());
+    // The base should be a snapshot
+    assert!(LoroDoc::from_incremental_snapshots(&layer, &[]).is_err());
+
+    let mut future_vv = doc.oplog_vv();
+    future_vv.insert(2, 10);
+    assert!(matches!(
+        doc.export_incremental_snapshot(&future_vv),
+        Err(LoroEncodeError::IncrementalSnapshotStartNotFound(_))
+    ));
+    Ok(())
+}
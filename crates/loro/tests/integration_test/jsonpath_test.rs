@@ -101,7 +101,6 @@ fn test_all_authors() -> anyhow::Result<()> {
 }
 
 #[test]
-#[ignore = "filter syntax not implemented"]
 fn test_books_with_isbn() -> anyhow::Result<()> {
     let doc = setup_test_doc();
     let ans = doc.jsonpath("$..book[?(@.isbn)]")?;
@@ -216,7 +215,6 @@ fn test_book_number_two_from_tail() -> anyhow::Result<()> {
 }
 
 #[test]
-#[ignore = "filter syntax not implemented"]
 fn test_books_cheaper_than_10() -> anyhow::Result<()> {
     let doc = setup_test_doc();
     let ans = doc.jsonpath("$.store.book[?(@.price < 10)]")?;
@@ -225,14 +223,94 @@ fn test_books_cheaper_than_10() -> anyhow::Result<()> {
 }
 
 #[test]
-#[ignore = "filter syntax not implemented"]
 fn test_books_not_expensive() -> anyhow::Result<()> {
     let doc = setup_test_doc();
-    let ans = doc.jsonpath("$..book[?(@.price <= $.expensive)]")?;
+    let ans = doc.jsonpath("$..book[?(@.price <= $.store.expensive)]")?;
     assert_eq!(ans.len(), 2);
     Ok(())
 }
 
+#[test]
+fn test_filter_with_logical_operators() -> anyhow::Result<()> {
+    let doc = setup_test_doc();
+    let ans = doc.jsonpath("$.store.book[?(@.price < 10 && @.category == 'fiction')].title")?;
+    assert_eq!(to_json(ans), json!(["Moby Dick"]));
+
+    let ans = doc.jsonpath(
+        "$.store.book[?(@.author == 'Nigel Rees' || (@.price > 20 && !(@.price >= 30)))].title",
+    )?;
+    assert_eq!(
+        to_json(ans),
+        json!(["Sayings of the Century", "The Lord of the Rings"])
+    );
+
+    let ans = doc.jsonpath("$.store.book[?(@.category != \"fiction\")].title")?;
+    assert_eq!(to_json(ans), json!(["Sayings of the Century"]));
+    Ok(())
+}
+
+#[test]
+fn test_filter_in_and_regex() -> anyhow::Result<()> {
+    let doc = setup_test_doc();
+    let ans =
+        doc.jsonpath("$.store.book[?(@.author in ['Evelyn Waugh', 'Herman Melville'])].title")?;
+    assert_eq!(to_json(ans), json!(["Sword of Honour", "Moby Dick"]));
+
+    let ans = doc.jsonpath("$.store.book[?(@.title =~ /^the lord/i)].author")?;
+    assert_eq!(to_json(ans), json!(["J. R. R. Tolkien"]));
+
+    let ans = doc.jsonpath("$.store.book[?(@.isbn =~ /X$/)].title")?;
+    assert_eq!(to_json(ans), json!(["Moby Dick"]));
+    Ok(())
+}
+
+#[test]
+fn test_filter_missing_field() -> anyhow::Result<()> {
+    let doc = setup_test_doc();
+    let ans = doc.jsonpath("$.store[?(@.color)].price")?;
+    assert_eq!(to_json(ans), json!([19.95]));
+    let ans = doc.jsonpath("$.store.book[?(!@.isbn)]")?;
+    assert_eq!(ans.len(), 0);
+    let ans = doc.jsonpath("$.store.book[?(@.missing != 1)]")?;
+    assert_eq!(ans.len(), 4);
+    Ok(())
+}
+
+#[test]
+fn test_invalid_filter() {
+    let doc = setup_test_doc();
+    assert!(doc.jsonpath("$.store.book[?(@.price <)]").is_err());
+    assert!(doc.jsonpath("$.store.book[?(@.title =~ /[/)]").is_err());
+    assert!(doc.jsonpath("$.store.book[?(@.title == 'abc)]").is_err());
+}
+
+#[test]
+fn test_filter_tree_nodes_by_meta() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let tree = doc.get_tree("tasks");
+    let root = tree.create(None)?;
+    tree.get_meta(root)?.insert("status", "done")?;
+    let child = tree.create(root)?;
+    tree.get_meta(child)?.insert("status", "todo")?;
+    let grandchild = tree.create(child)?;
+    tree.get_meta(grandchild)?.insert("status", "done")?;
+    let deleted = tree.create(None)?;
+    tree.get_meta(deleted)?.insert("status", "done")?;
+    tree.delete(deleted)?;
+
+    let ans = doc.jsonpath("$.tasks[?(@.status == 'done')]")?;
+    let ids: Vec<_> = ans.iter().map(|x| x.as_container().unwrap().id()).collect();
+    assert_eq!(
+        ids,
+        vec![tree.get_meta(root)?.id(), tree.get_meta(grandchild)?.id()]
+    );
+
+    let key = child.to_string();
+    let ans = doc.jsonpath(&format!("$.tasks['{}'].status", key))?;
+    assert_eq!(to_json(ans), json!(["todo"]));
+    Ok(())
+}
+
 #[test]
 fn test_everything() -> anyhow::Result<()> {
     let doc = setup_test_doc();
@@ -0,0 +1,220 @@
+use std::sync::{Arc, Mutex};
+
+use loro::{
+    ExportMode, FileKvStore, HistoryRetention, LoroDoc, LoroError, LoroText, MemKvStore, ToJson,
+};
+
+use super::gen_action;
+
+#[test]
+fn save_and_open_from_kv_store() -> anyhow::Result<()> {
+    let store = Arc::new(Mutex::new(MemKvStore::new(Default::default())));
+    let doc = LoroDoc::from_kv_store(store.clone())?;
+    doc.set_peer_id(1)?;
+    gen_action(&doc, 42, 100);
+    doc.commit();
+    doc.save_to_kv_store()?;
+    drop(doc);
+
+    let doc = LoroDoc::from_kv_store(store.clone())?;
+    let expected = LoroDoc::new();
+    expected.set_peer_id(1)?;
+    gen_action(&expected, 42, 100);
+    expected.commit();
+    assert_eq!(doc.get_deep_value(), expected.get_deep_value());
+    assert_eq!(doc.oplog_vv(), expected.oplog_vv());
+    // The opened doc is editable
+    doc.get_text("text").insert(0, "abc")?;
+    doc.commit();
+    doc.save_to_kv_store()?;
+    let value = doc.get_deep_value();
+    drop(doc);
+    assert_eq!(LoroDoc::from_kv_store(store)?.get_deep_value(), value);
+    Ok(())
+}
+
+#[test]
+fn open_empty_kv_store() -> anyhow::Result<()> {
+    let store = Arc::new(Mutex::new(MemKvStore::new(Default::default())));
+    let doc = LoroDoc::from_kv_store(store)?;
+    assert!(doc.oplog_vv().is_empty());
+    Ok(())
+}
+
+#[test]
+fn save_doc_not_opened_on_kv_store() {
+    let doc = LoroDoc::new();
+    assert_eq!(
+        doc.save_to_kv_store().unwrap_err(),
+        LoroError::NotOpenedOnKvStore
+    );
+}
+
+#[test]
+fn save_imported_snapshot_to_kv_store() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    gen_action(&doc, 5, 100);
+    doc.commit();
+    let store = Arc::new(Mutex::new(MemKvStore::new(Default::default())));
+    let new_doc = LoroDoc::from_kv_store(store.clone())?;
+    new_doc.import(&doc.export(ExportMode::Snapshot)?)?;
+    new_doc.save_to_kv_store()?;
+    drop(new_doc);
+    let new_doc = LoroDoc::from_kv_store(store)?;
+    assert_eq!(doc.get_deep_value(), new_doc.get_deep_value());
+    assert_eq!(doc.oplog_vv(), new_doc.oplog_vv());
+    Ok(())
+}
+
+#[test]
+fn save_parent_without_loading_its_children() -> anyhow::Result<()> {
+    let store = Arc::new(Mutex::new(MemKvStore::new(Default::default())));
+    let doc = LoroDoc::from_kv_store(store.clone())?;
+    let map = doc.get_map("map");
+    let text = map.insert_container("text", LoroText::new())?;
+    text.insert(0, "hello")?;
+    doc.commit();
+    doc.save_to_kv_store()?;
+    drop(doc);
+
+    // Only the parent is loaded and modified, the stored child must not be
+    // replaced by an empty state when the parent is saved
+    let doc = LoroDoc::from_kv_store(store.clone())?;
+    doc.get_map("map").insert("key", 1)?;
+    doc.commit();
+    doc.save_to_kv_store()?;
+    drop(doc);
+
+    let doc = LoroDoc::from_kv_store(store)?;
+    assert_eq!(
+        doc.get_deep_value().to_json_value(),
+        serde_json::json!({"map": {"text": "hello", "key": 1}})
+    );
+    Ok(())
+}
+
+#[test]
+fn save_to_kv_store_only_writes_changed_entries() -> anyhow::Result<()> {
+    let dir =
+        std::env::temp_dir().join(format!("loro-kv-store-incremental-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = Arc::new(Mutex::new(FileKvStore::open(&dir)?));
+    let doc = LoroDoc::from_kv_store(store.clone())?;
+    doc.set_peer_id(1)?;
+    gen_action(&doc, 7, 3000);
+    doc.commit();
+    doc.save_to_kv_store()?;
+    store.lock().unwrap().flush()?;
+    let len = store.lock().unwrap().len();
+    let wal_size = store.lock().unwrap().wal_size();
+
+    // Nothing is written if nothing has changed
+    doc.save_to_kv_store()?;
+    store.lock().unwrap().flush()?;
+    assert_eq!(store.lock().unwrap().len(), len);
+    assert_eq!(store.lock().unwrap().wal_size(), wal_size);
+
+    // Only the modified block and container are written
+    doc.get_map("new_root").insert("new_key", 1)?;
+    doc.commit();
+    doc.save_to_kv_store()?;
+    store.lock().unwrap().flush()?;
+    assert!(store.lock().unwrap().wal_size() - wal_size < wal_size / 2);
+
+    let value = doc.get_deep_value();
+    drop(doc);
+    assert_eq!(
+        LoroDoc::from_kv_store(store.clone())?.get_deep_value(),
+        value
+    );
+    drop(store);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn save_shallow_doc_to_kv_store() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    gen_action(&doc, 3, 50);
+    doc.commit();
+    let frontiers = doc.oplog_frontiers();
+    gen_action(&doc, 4, 50);
+    doc.commit();
+    let store = Arc::new(Mutex::new(MemKvStore::new(Default::default())));
+    let shallow = LoroDoc::from_kv_store(store.clone())?;
+    shallow.import(&doc.export(ExportMode::shallow_snapshot(&frontiers))?)?;
+    shallow.save_to_kv_store()?;
+    let shallow_since_vv = shallow.shallow_since_vv().to_vv();
+    drop(shallow);
+
+    let new_doc = LoroDoc::from_kv_store(store)?;
+    assert!(new_doc.is_shallow());
+    assert_eq!(new_doc.shallow_since_vv().to_vv(), shallow_since_vv);
+    assert_eq!(doc.get_deep_value(), new_doc.get_deep_value());
+    Ok(())
+}
+
+#[test]
+fn save_compacted_doc_to_kv_store() -> anyhow::Result<()> {
+    let store = Arc::new(Mutex::new(MemKvStore::new(Default::default())));
+    let doc = LoroDoc::from_kv_store(store.clone())?;
+    doc.set_peer_id(1)?;
+    gen_action(&doc, 8, 50);
+    doc.commit();
+    doc.save_to_kv_store()?;
+    let frontiers = doc.oplog_frontiers();
+    gen_action(&doc, 9, 50);
+    doc.commit();
+    doc.compact_history(&HistoryRetention::After(frontiers))?;
+    doc.save_to_kv_store()?;
+    let shallow_since_vv = doc.shallow_since_vv().to_vv();
+    let value = doc.get_deep_value();
+    drop(doc);
+
+    let new_doc = LoroDoc::from_kv_store(store)?;
+    assert!(new_doc.is_shallow());
+    assert_eq!(new_doc.shallow_since_vv().to_vv(), shallow_since_vv);
+    assert_eq!(new_doc.get_deep_value(), value);
+    Ok(())
+}
+
+#[test]
+fn persist_doc_in_file_kv_store() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("loro-file-kv-store-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let saved_value = {
+        let store = Arc::new(Mutex::new(FileKvStore::open(&dir)?));
+        let doc = LoroDoc::from_kv_store(store.clone())?;
+        doc.set_peer_id(1)?;
+        gen_action(&doc, 11, 200);
+        doc.commit();
+        doc.save_to_kv_store()?;
+        store.lock().unwrap().flush()?;
+        let saved_value = doc.get_deep_value();
+        // The unsaved changes are lost
+        gen_action(&doc, 12, 20);
+        doc.commit();
+        saved_value
+    };
+
+    let value = {
+        let store = Arc::new(Mutex::new(FileKvStore::open(&dir)?));
+        let doc = LoroDoc::from_kv_store(store.clone())?;
+        assert_eq!(doc.get_deep_value(), saved_value);
+        gen_action(&doc, 12, 20);
+        doc.commit();
+        doc.save_to_kv_store()?;
+        store.lock().unwrap().flush()?;
+        doc.get_deep_value()
+    };
+
+    let store = Arc::new(Mutex::new(FileKvStore::open(&dir)?));
+    let doc = LoroDoc::from_kv_store(store.clone())?;
+    assert_eq!(doc.get_deep_value(), value);
+    drop(doc);
+    drop(store);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use loro::{
+    mapping, Container, LoroDeserialize, LoroDoc, LoroError, LoroSerialize, ToJson,
+    ValueOrContainer,
+};
+use serde_json::json;
+
+#[derive(Debug, Clone, PartialEq, LoroSerialize, LoroDeserialize)]
+struct Task {
+    title: String,
+    done: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, LoroSerialize, LoroDeserialize)]
+struct Project {
+    #[loro(text)]
+    name: String,
+    #[loro(rename = "desc")]
+    description: Option<String>,
+    tasks: Vec<Task>,
+    #[loro(movable)]
+    tags: Vec<String>,
+    #[loro(default)]
+    priority: i32,
+    labels: HashMap<String, f64>,
+    #[loro(skip)]
+    cached: Option<usize>,
+}
+
+fn project() -> Project {
+    Project {
+        name: "Loro".into(),
+        description: Some("CRDTs".into()),
+        tasks: vec![
+            Task {
+                title: "a".into(),
+                done: false,
+            },
+            Task {
+                title: "b".into(),
+                done: true,
+            },
+        ],
+        tags: vec!["rust".into(), "crdt".into()],
+        priority: 1,
+        labels: HashMap::from([("weight".to_string(), 0.5)]),
+        cached: None,
+    }
+}
+
+#[test]
+fn struct_round_trip() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let map = doc.get_map("project");
+    let mut value = project();
+    value.cached = Some(10);
+    mapping::write(&map, &value)?;
+    doc.commit();
+    assert_eq!(
+        doc.get_deep_value().to_json_value(),
+        json!({
+            "project": {
+                "name": "Loro",
+                "desc": "CRDTs",
+                "tasks": [{"title": "a", "done": false}, {"title": "b", "done": true}],
+                "tags": ["rust", "crdt"],
+                "priority": 1,
+                "labels": {"weight": 0.5},
+            }
+        })
+    );
+    assert!(matches!(
+        map.get("name"),
+        Some(ValueOrContainer::Container(Container::Text(_)))
+    ));
+    assert!(matches!(
+        map.get("tags"),
+        Some(ValueOrContainer::Container(Container::MovableList(_)))
+    ));
+    assert!(matches!(
+        map.get("tasks"),
+        Some(ValueOrContainer::Container(Container::List(_)))
+    ));
+
+    let read: Project = mapping::read(&map)?;
+    value.cached = None;
+    assert_eq!(read, value);
+
+    // The changes are synced to the other peers as ordinary container updates
+    let other = LoroDoc::new();
+    other.import(&doc.export(loro::ExportMode::Snapshot)?)?;
+    let read: Project = mapping::read(&other.get_map("project"))?;
+    assert_eq!(read, value);
+    Ok(())
+}
+
+#[test]
+fn updates_are_diff_based() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let map = doc.get_map("project");
+    let mut value = project();
+    mapping::write(&map, &value)?;
+    doc.commit();
+    let tasks_id = map.get("tasks").unwrap().into_container().unwrap().id();
+    let name_id = map.get("name").unwrap().into_container().unwrap().id();
+
+    // Writing the same value creates no ops
+    let ops = doc.len_ops();
+    mapping::write(&map, &value)?;
+    doc.commit();
+    assert_eq!(doc.len_ops(), ops);
+
+    value.name.push('!');
+    value.tasks[1].done = false;
+    value.tags.insert(1, "local-first".into());
+    doc.commit();
+    let ops = doc.len_ops();
+    mapping::write(&map, &value)?;
+    doc.commit();
+    // One insertion in the text, one map update in the task and one list insertion
+    assert_eq!(doc.len_ops(), ops + 3);
+    assert_eq!(
+        map.get("tasks").unwrap().into_container().unwrap().id(),
+        tasks_id
+    );
+    assert_eq!(
+        map.get("name").unwrap().into_container().unwrap().id(),
+        name_id
+    );
+
+    value.tasks.remove(0);
+    value.description = None;
+    mapping::write(&map, &value)?;
+    doc.commit();
+    assert!(map.get("desc").is_none());
+    let read: Project = mapping::read(&map)?;
+    assert_eq!(read, value);
+    Ok(())
+}
+
+#[test]
+fn concurrent_field_updates_are_merged() -> anyhow::Result<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let mut value = project();
+    mapping::write(&doc_a.get_map("project"), &value)?;
+    doc_a.commit();
+    let doc_b = doc_a.fork();
+    doc_b.set_peer_id(2)?;
+
+    let mut value_a = value.clone();
+    value_a.name = "Loro!".into();
+    value_a.tasks[0].done = true;
+    mapping::write(&doc_a.get_map("project"), &value_a)?;
+    let mut value_b = value.clone();
+    value_b.name = "The Loro".into();
+    value_b.tags.push("sync".into());
+    mapping::write(&doc_b.get_map("project"), &value_b)?;
+
+    doc_a.import(&doc_b.export(loro::ExportMode::all_updates())?)?;
+    doc_b.import(&doc_a.export(loro::ExportMode::all_updates())?)?;
+    value.name = "The Loro!".into();
+    value.tasks[0].done = true;
+    value.tags.push("sync".into());
+    assert_eq!(
+        mapping::read::<_, Project>(&doc_a.get_map("project"))?,
+        value
+    );
+    assert_eq!(
+        mapping::read::<_, Project>(&doc_b.get_map("project"))?,
+        value
+    );
+    Ok(())
+}
+
+#[test]
+fn unknown_keys_are_kept() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let map = doc.get_map("task");
+    map.insert("extra", 1)?;
+    mapping::write(
+        &map,
+        &Task {
+            title: "a".into(),
+            done: false,
+        },
+    )?;
+    assert_eq!(
+        map.get_deep_value().to_json_value(),
+        json!({"title": "a", "done": false, "extra": 1})
+    );
+    Ok(())
+}
+
+#[test]
+fn deserialize_errors() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let map = doc.get_map("task");
+    map.insert("title", "a")?;
+    assert!(matches!(
+        mapping::read::<_, Task>(&map),
+        Err(LoroError::DeserializeError(_))
+    ));
+    map.insert("done", "yes")?;
+    assert!(matches!(
+        mapping::read::<_, Task>(&map),
+        Err(LoroError::DeserializeError(_))
+    ));
+    map.insert("done", true)?;
+    assert_eq!(
+        mapping::read::<_, Task>(&map)?,
+        Task {
+            title: "a".into(),
+            done: true
+        }
+    );
+    assert!(matches!(
+        mapping::read::<_, Task>(&doc.get_list("list")),
+        Err(LoroError::DeserializeError(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn write_plain_list() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let list = doc.get_list("list");
+    mapping::write(&list, &vec![1, 2, 3, 4])?;
+    doc.commit();
+    let ops = doc.len_ops();
+    mapping::write(&list, &vec![1, 5, 3, 4])?;
+    doc.commit();
+    // Delete 2 and insert 5
+    assert_eq!(doc.len_ops(), ops + 2);
+    assert_eq!(mapping::read::<_, Vec<i32>>(&list)?, vec![1, 5, 3, 4]);
+
+    let list = doc.get_movable_list("movable");
+    mapping::write(&list, &vec![1, 2, 3, 4])?;
+    doc.commit();
+    let ops = doc.len_ops();
+    mapping::write(&list, &vec![1, 5, 3])?;
+    doc.commit();
+    // Set 2 to 5 and delete 4
+    assert_eq!(doc.len_ops(), ops + 2);
+    assert_eq!(mapping::read::<_, Vec<i32>>(&list)?, vec![1, 5, 3]);
+    Ok(())
+}
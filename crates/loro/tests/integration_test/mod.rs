@@ -1,12 +1,22 @@
 use loro::LoroDoc;
 
+mod awareness_test;
+mod deserializer_test;
 mod detached_editing_test;
+mod incremental_snapshot_test;
 #[cfg(feature = "jsonpath")]
 mod jsonpath_test;
+mod kv_store_test;
+#[cfg(feature = "derive")]
+mod mapping_test;
 mod redact_test;
+mod schema_test;
 mod shallow_snapshot_test;
 mod snapshot_at_test;
+mod sync_test;
 mod text_update_test;
+mod time_travel_test;
+mod tree_constraint_test;
 mod undo_test;
 
 fn gen_action(doc: &LoroDoc, seed: u64, mut ops_len: usize) {
@@ -0,0 +1,187 @@
+use loro::{
+    ContainerSchema, DocSchema, ExportMode, Index, LoroDoc, LoroError, LoroList, LoroMap, LoroText,
+    MapSchema, ValueSchema,
+};
+
+fn app_schema() -> DocSchema {
+    DocSchema::new()
+        .root(
+            "settings",
+            ContainerSchema::Map(
+                MapSchema::new()
+                    .field("theme", ValueSchema::String)
+                    .field("font_size", ValueSchema::Number)
+                    .field("nickname", ValueSchema::nullable(ValueSchema::String))
+                    .field(
+                        "recent",
+                        ValueSchema::container(ContainerSchema::List(ValueSchema::String)),
+                    ),
+            ),
+        )
+        .root(
+            "notes",
+            ContainerSchema::MovableList(ValueSchema::container(ContainerSchema::Text)),
+        )
+        .root(
+            "tasks",
+            ContainerSchema::Tree(
+                MapSchema::new()
+                    .field("status", ValueSchema::String)
+                    .allow_unknown_keys(true),
+            ),
+        )
+}
+
+fn is_schema_violation<T: std::fmt::Debug>(result: Result<T, LoroError>) -> bool {
+    matches!(result, Err(LoroError::SchemaViolation(_)))
+}
+
+#[test]
+fn local_edits_are_validated() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_schema(Some(app_schema()));
+    let settings = doc.get_map("settings");
+    settings.insert("theme", "dark")?;
+    settings.insert("font_size", 12)?;
+    settings.insert("font_size", 12.5)?;
+    settings.insert("nickname", "zx")?;
+    settings.insert("nickname", loro::LoroValue::Null)?;
+    assert!(is_schema_violation(settings.insert("theme", 1)));
+    assert!(is_schema_violation(settings.insert("unknown", "value")));
+    // Removing a key is always allowed
+    settings.delete("nickname")?;
+
+    let recent = settings.insert_container("recent", LoroList::new())?;
+    recent.push("a.txt")?;
+    assert!(is_schema_violation(recent.push(1)));
+    assert!(is_schema_violation(
+        settings.insert_container("recent", LoroMap::new())
+    ));
+
+    let notes = doc.get_movable_list("notes");
+    let note = notes.push_container(LoroText::new())?;
+    note.insert(0, "hello")?;
+    assert!(is_schema_violation(notes.push("plain string")));
+    assert!(is_schema_violation(notes.set(0, "plain string")));
+
+    // Unconstrained roots
+    doc.get_map("other").insert("key", 1)?;
+
+    doc.commit();
+    assert_eq!(
+        settings.get_deep_value().to_json_value(),
+        serde_json::json!({"theme": "dark", "font_size": 12.5, "recent": ["a.txt"]})
+    );
+    assert_eq!(
+        notes.get_deep_value().to_json_value(),
+        serde_json::json!(["hello"])
+    );
+    assert!(doc.validate_schema().is_empty());
+    Ok(())
+}
+
+#[test]
+fn root_container_kind_is_validated() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_schema(Some(app_schema()));
+    let list = doc.get_list("settings");
+    assert!(doc.validate_schema().is_empty());
+    assert!(is_schema_violation(list.push(1)));
+    assert!(is_schema_violation(doc.get_text("tasks").insert(0, "abc")));
+    Ok(())
+}
+
+#[test]
+fn tree_meta_is_validated() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_schema(Some(app_schema()));
+    let tree = doc.get_tree("tasks");
+    let root = tree.create(None)?;
+    let child = tree.create(root)?;
+    tree.get_meta(root)?.insert("status", "done")?;
+    tree.get_meta(child)?.insert("title", "write tests")?;
+    assert!(is_schema_violation(
+        tree.get_meta(child)?.insert("status", false)
+    ));
+    Ok(())
+}
+
+#[test]
+fn imported_violations_are_reported() -> anyhow::Result<()> {
+    let remote = LoroDoc::new();
+    remote.set_peer_id(1)?;
+    let settings = remote.get_map("settings");
+    settings.insert("theme", "dark")?;
+    remote.commit();
+
+    let doc = LoroDoc::new();
+    doc.set_peer_id(2)?;
+    doc.set_schema(Some(app_schema()));
+    let status = doc.import(&remote.export(ExportMode::all_updates())?)?;
+    assert!(status.schema_violations.is_empty());
+
+    // The remote peer doesn't know the schema
+    let vv = remote.oplog_vv();
+    settings.insert("theme", 1)?;
+    settings.insert("unknown", true)?;
+    remote.get_list("notes").push("a")?;
+    remote.commit();
+    let status = doc.import(&remote.export(ExportMode::updates(&vv))?)?;
+    // The changes are applied anyway
+    assert_eq!(
+        doc.get_map("settings")
+            .get("theme")
+            .unwrap()
+            .get_deep_value(),
+        1.into()
+    );
+    let settings_id = doc.get_map("settings").id();
+    let mut violations = status.schema_violations.clone();
+    violations.sort_by_key(|v| v.index.as_ref().map(|i| i.to_string()));
+    assert_eq!(violations.len(), 3);
+    assert_eq!(violations[0].container, remote.get_list("notes").id());
+    assert_eq!(violations[0].index, None);
+    assert_eq!(violations[1].container, settings_id);
+    assert_eq!(violations[1].index, Some(Index::Key("theme".into())));
+    assert_eq!(violations[2].container, settings_id);
+    assert_eq!(violations[2].index, Some(Index::Key("unknown".into())));
+    assert_eq!(doc.validate_schema().len(), 3);
+
+    // Only the containers changed by the import are checked
+    let vv = remote.oplog_vv();
+    remote.get_map("other").insert("key", 1)?;
+    remote.commit();
+    let status = doc.import(&remote.export(ExportMode::updates(&vv))?)?;
+    assert!(status.schema_violations.is_empty());
+    Ok(())
+}
+
+#[test]
+fn snapshot_import_is_validated() -> anyhow::Result<()> {
+    let remote = LoroDoc::new();
+    let tree = remote.get_tree("tasks");
+    let node = tree.create(None)?;
+    tree.get_meta(node)?.insert("status", 1)?;
+    remote.commit();
+
+    let doc = LoroDoc::new();
+    doc.set_schema(Some(app_schema()));
+    let status = doc.import(&remote.export(ExportMode::Snapshot)?)?;
+    assert_eq!(status.schema_violations.len(), 1);
+    assert_eq!(
+        status.schema_violations[0].container,
+        doc.get_tree("tasks").get_meta(node)?.id()
+    );
+
+    // The schema is kept by the forked doc
+    let forked = doc.fork();
+    assert!(forked.schema().is_some());
+    assert_eq!(forked.validate_schema(), status.schema_violations);
+
+    doc.set_schema(None);
+    assert!(doc.validate_schema().is_empty());
+    doc.get_tree("tasks")
+        .get_meta(node)?
+        .insert("status", false)?;
+    Ok(())
+}
@@ -1,10 +1,16 @@
 use std::{
     borrow::Cow,
-    sync::{atomic::AtomicBool, Arc},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use super::gen_action;
-use loro::{cursor::CannotFindRelativePosition, ExportMode, Frontiers, LoroDoc, ID};
+use loro::{
+    cursor::CannotFindRelativePosition, CommitOptions, ExportMode, Frontiers, HistoryRetention,
+    LoroDoc, LoroError, ID,
+};
 
 #[test]
 fn test_gc() -> anyhow::Result<()> {
@@ -381,3 +387,149 @@ fn test_export_snapshot_from_shallow_doc() {
     // using frontiers before its shallow version
     shallow_doc.export_snapshot();
 }
+
+#[test]
+fn compact_history_keeps_state_and_subscriptions() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.set_change_merge_interval(0);
+    let text = doc.get_text("text");
+    for i in 0..10 {
+        text.insert(0, &i.to_string())?;
+        doc.commit();
+    }
+    let other = doc.fork();
+    other.set_peer_id(2)?;
+    let count = Arc::new(AtomicUsize::new(0));
+    let count_clone = count.clone();
+    let _sub = doc.subscribe(
+        &text.id(),
+        Arc::new(move |_| {
+            count_clone.fetch_add(1, Ordering::SeqCst);
+        }),
+    );
+
+    let value = doc.get_deep_value();
+    let start = doc
+        .compact_history(&HistoryRetention::LastChanges(3))?
+        .unwrap();
+    assert!(doc.is_shallow());
+    assert_eq!(doc.shallow_since_frontiers(), start);
+    assert_eq!(doc.shallow_since_vv().get(&1).copied(), Some(6));
+    assert_eq!(doc.get_deep_value(), value);
+    assert!(doc.checkout(&Frontiers::from(ID::new(1, 3))).is_err());
+    assert_eq!(count.load(Ordering::SeqCst), 0);
+
+    // The doc keeps working as before
+    text.insert(0, "a")?;
+    doc.commit();
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    other.get_text("text").insert(0, "b")?;
+    doc.import(&other.export(ExportMode::updates(&doc.oplog_vv()))?)?;
+    other.import(&doc.export(ExportMode::updates(&other.oplog_vv()))?)?;
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+    assert_eq!(doc.get_deep_value(), other.get_deep_value());
+
+    let new_doc = LoroDoc::new();
+    new_doc.import(&doc.export(ExportMode::Snapshot)?)?;
+    assert!(new_doc.is_shallow());
+    assert_eq!(new_doc.shallow_since_frontiers(), start);
+    assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+    new_doc.checkout(&start)?;
+    assert_eq!(new_doc.get_text("text").to_string(), "6543210");
+    Ok(())
+}
+
+#[test]
+fn compact_history_by_days() -> anyhow::Result<()> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64;
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.set_change_merge_interval(0);
+    let text = doc.get_text("text");
+    for (i, days_ago) in [30, 20, 10, 1, 0].into_iter().enumerate() {
+        text.insert(0, &i.to_string())?;
+        doc.commit_with(CommitOptions::new().timestamp(now - days_ago * 24 * 60 * 60));
+    }
+
+    assert_eq!(doc.compact_history(&HistoryRetention::LastDays(40))?, None);
+    assert!(!doc.is_shallow());
+    let start = doc
+        .compact_history(&HistoryRetention::LastDays(15))?
+        .unwrap();
+    assert_eq!(start, Frontiers::from(ID::new(1, 1)));
+    // Compacting with a longer period doesn't restore the history
+    assert_eq!(doc.compact_history(&HistoryRetention::LastDays(25))?, None);
+    let start = doc
+        .compact_history(&HistoryRetention::LastDays(5))?
+        .unwrap();
+    assert_eq!(start, Frontiers::from(ID::new(1, 2)));
+    assert_eq!(doc.shallow_since_frontiers(), start);
+    assert_eq!(text.to_string(), "43210");
+    Ok(())
+}
+
+#[test]
+fn compact_history_by_days_without_timestamps() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.set_change_merge_interval(0);
+    let text = doc.get_text("text");
+    for i in 0..5 {
+        text.insert(0, &i.to_string())?;
+        doc.commit();
+    }
+
+    assert!(matches!(
+        doc.compact_history(&HistoryRetention::LastDays(1)),
+        Err(LoroError::ArgErr(_))
+    ));
+    assert!(!doc.is_shallow());
+    assert_eq!(doc.len_changes(), 5);
+    Ok(())
+}
+
+#[test]
+fn compact_history_after_version() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    gen_action(&doc, 123, 32);
+    doc.commit();
+    let frontiers = doc.oplog_frontiers();
+    gen_action(&doc, 123, 32);
+    doc.commit();
+
+    assert!(matches!(
+        doc.compact_history(&HistoryRetention::After(Frontiers::from(ID::new(2, 0)))),
+        Err(LoroError::FrontiersNotFound(..))
+    ));
+    assert_eq!(
+        doc.compact_history(&HistoryRetention::After(Frontiers::default()))?,
+        None
+    );
+
+    let expected = LoroDoc::new();
+    expected.import(&doc.export(ExportMode::shallow_snapshot(&frontiers))?)?;
+    let start = doc
+        .compact_history(&HistoryRetention::After(frontiers.clone()))?
+        .unwrap();
+    assert_eq!(start, expected.shallow_since_frontiers());
+    assert_eq!(
+        doc.shallow_since_vv().to_vv(),
+        expected.shallow_since_vv().to_vv()
+    );
+    assert_eq!(doc.get_deep_value(), expected.get_deep_value());
+    assert_eq!(
+        doc.compact_history(&HistoryRetention::After(frontiers))?,
+        None
+    );
+
+    doc.checkout(&start)?;
+    assert!(matches!(
+        doc.compact_history(&HistoryRetention::LastChanges(1)),
+        Err(LoroError::CompactHistoryWhenDetached)
+    ));
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use std::collections::VecDeque;
+
+use loro::{
+    sync::{SyncSession, SyncState},
+    ExportMode, LoroDoc, LoroError,
+};
+
+/// An in-memory duplex channel between two sessions
+struct Channel {
+    to_a: VecDeque<Vec<u8>>,
+    to_b: VecDeque<Vec<u8>>,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            to_a: VecDeque::new(),
+            to_b: VecDeque::new(),
+        }
+    }
+
+    fn run(
+        &mut self,
+        doc_a: &LoroDoc,
+        a: &mut SyncSession,
+        doc_b: &LoroDoc,
+        b: &mut SyncSession,
+    ) -> loro::LoroResult<()> {
+        while !self.to_a.is_empty() || !self.to_b.is_empty() {
+            while let Some(msg) = self.to_b.pop_front() {
+                self.to_a.extend(b.receive(doc_b, &msg)?);
+            }
+            while let Some(msg) = self.to_a.pop_front() {
+                self.to_b.extend(a.receive(doc_a, &msg)?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn sync_handshake_and_incremental_updates() -> anyhow::Result<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    doc_a.get_text("text").insert(0, "hello")?;
+    doc_b.get_map("map").insert("key", 1)?;
+    doc_a.commit();
+    doc_b.commit();
+
+    let mut a = SyncSession::new();
+    let mut b = SyncSession::new();
+    assert_eq!(a.state(), SyncState::Disconnected);
+    assert_eq!(a.poll_local(&doc_a)?, None);
+    let mut channel = Channel::new();
+    channel.to_b.push_back(a.start(&doc_a));
+    assert_eq!(a.state(), SyncState::Handshaking);
+    channel.run(&doc_a, &mut a, &doc_b, &mut b)?;
+    assert!(a.is_synced());
+    assert!(b.is_synced());
+    assert_eq!(doc_a.get_deep_value(), doc_b.get_deep_value());
+    assert_eq!(a.remote_vv(), &doc_b.oplog_vv());
+    assert_eq!(b.remote_vv(), &doc_a.oplog_vv());
+
+    doc_a.get_text("text").insert(5, " world")?;
+    doc_a.commit();
+    let msg = a.poll_local(&doc_a)?.unwrap();
+    // Nothing new to send
+    assert_eq!(a.poll_local(&doc_a)?, None);
+    channel.to_b.push_back(msg);
+    channel.run(&doc_a, &mut a, &doc_b, &mut b)?;
+    assert_eq!(doc_b.get_text("text").to_string(), "hello world");
+    assert_eq!(a.remote_vv(), &doc_a.oplog_vv());
+    Ok(())
+}
+
+#[test]
+fn sync_concurrent_start() -> anyhow::Result<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    doc_a.get_list("list").push(1)?;
+    doc_b.get_list("list").push(2)?;
+    doc_a.commit();
+    doc_b.commit();
+
+    let mut a = SyncSession::new();
+    let mut b = SyncSession::new();
+    let mut channel = Channel::new();
+    channel.to_b.push_back(a.start(&doc_a));
+    channel.to_a.push_back(b.start(&doc_b));
+    channel.run(&doc_a, &mut a, &doc_b, &mut b)?;
+    assert_eq!(doc_a.get_deep_value(), doc_b.get_deep_value());
+    assert_eq!(doc_a.get_list("list").len(), 2);
+    Ok(())
+}
+
+#[test]
+fn sync_resume_after_disconnect() -> anyhow::Result<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    let mut a = SyncSession::new();
+    let mut b = SyncSession::new();
+    let mut channel = Channel::new();
+    channel.to_b.push_back(a.start(&doc_a));
+    channel.run(&doc_a, &mut a, &doc_b, &mut b)?;
+
+    let text = doc_a.get_text("text");
+    text.insert(0, "123")?;
+    doc_a.commit();
+    // The message is lost
+    let _lost = a.poll_local(&doc_a)?.unwrap();
+    a.disconnect();
+    b.disconnect();
+    assert_eq!(a.state(), SyncState::Disconnected);
+
+    text.insert(3, "456")?;
+    doc_a.commit();
+    doc_b.get_text("text").insert(0, "abc")?;
+    doc_b.commit();
+    // Still disconnected
+    assert_eq!(a.poll_local(&doc_a)?, None);
+
+    channel.to_a.push_back(b.start(&doc_b));
+    channel.run(&doc_a, &mut a, &doc_b, &mut b)?;
+    assert_eq!(doc_a.get_deep_value(), doc_b.get_deep_value());
+    assert_eq!(doc_b.get_text("text").len_unicode(), 9);
+    Ok(())
+}
+
+#[test]
+fn sync_receives_pending_updates_then_recovers() -> anyhow::Result<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    let mut a = SyncSession::new();
+    let mut b = SyncSession::new();
+    let mut channel = Channel::new();
+    channel.to_b.push_back(a.start(&doc_a));
+    channel.run(&doc_a, &mut a, &doc_b, &mut b)?;
+
+    doc_a.get_text("text").insert(0, "1")?;
+    doc_a.commit();
+    let _lost = a.poll_local(&doc_a)?.unwrap();
+    doc_a.get_text("text").insert(1, "2")?;
+    doc_a.commit();
+    // This update depends on the lost one
+    channel.to_b.push_back(a.poll_local(&doc_a)?.unwrap());
+    channel.run(&doc_a, &mut a, &doc_b, &mut b)?;
+    assert!(b.last_import_status().is_some());
+    assert_eq!(doc_b.get_text("text").to_string(), "12");
+    Ok(())
+}
+
+#[test]
+fn sync_falls_back_to_snapshot_when_peer_is_behind_shallow_root() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.get_text("text").insert(0, "hello")?;
+    doc.commit();
+    let old_snapshot = doc.export(ExportMode::Snapshot)?;
+    doc.get_text("text").insert(5, " world")?;
+    doc.commit();
+    let frontiers = doc.oplog_frontiers();
+    doc.get_text("text").insert(0, "> ")?;
+    doc.commit();
+    let doc_a = LoroDoc::new();
+    doc_a.import(&doc.export(ExportMode::shallow_snapshot(&frontiers))?)?;
+    assert!(doc_a.is_shallow());
+
+    // An empty doc can be initialized by the snapshot
+    let doc_b = LoroDoc::new();
+    let mut a = SyncSession::new();
+    let mut b = SyncSession::new();
+    let mut channel = Channel::new();
+    channel.to_a.push_back(b.start(&doc_b));
+    channel.run(&doc_a, &mut a, &doc_b, &mut b)?;
+    assert_eq!(doc_b.get_text("text").to_string(), "> hello world");
+    assert!(doc_b.is_shallow());
+
+    // A doc with the history before the shallow root cannot be synced
+    let doc_c = LoroDoc::new();
+    doc_c.import(&old_snapshot)?;
+    let mut a = SyncSession::new();
+    let mut c = SyncSession::new();
+    let mut channel = Channel::new();
+    channel.to_a.push_back(c.start(&doc_c));
+    let err = channel.run(&doc_a, &mut a, &doc_c, &mut c).unwrap_err();
+    assert_eq!(err, LoroError::SyncBehindRemoteShallowRoot);
+    assert_eq!(doc_c.get_text("text").to_string(), "hello");
+    Ok(())
+}
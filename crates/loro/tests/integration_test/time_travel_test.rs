@@ -0,0 +1,160 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use super::gen_action;
+use loro::{
+    ContainerID, ContainerType, ExportMode, Frontiers, LoroDoc, LoroError, LoroList, LoroMap,
+    LoroText, ToJson, ID,
+};
+use serde_json::json;
+
+#[test]
+fn same_as_checkout() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let mut versions = vec![];
+    for seed in 0..5 {
+        gen_action(&doc, seed, 20);
+        doc.commit();
+        versions.push(doc.oplog_frontiers());
+    }
+
+    for v in versions.iter() {
+        let old = doc.fork_at(v);
+        assert_eq!(
+            doc.get_deep_value_at(&doc.get_map("root").id(), v)?,
+            old.get_map("root").get_deep_value()
+        );
+        assert_eq!(
+            doc.get_deep_value_at(&doc.get_list("list").id(), v)?,
+            old.get_list("list").get_deep_value()
+        );
+        assert_eq!(
+            doc.get_value_at(&doc.get_text("text").id(), v)?,
+            old.get_text("text").get_value()
+        );
+    }
+    assert!(!doc.is_detached());
+    Ok(())
+}
+
+#[test]
+fn nested_containers() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let root = doc.get_map("root");
+    let list = root.insert_container("list", LoroList::new())?;
+    let item = list.insert_container(0, LoroMap::new())?;
+    let text = item.insert_container("text", LoroText::new())?;
+    text.insert(0, "hello")?;
+    doc.commit();
+    let v0 = doc.oplog_frontiers();
+    text.insert(5, " world")?;
+    list.delete(0, 1)?;
+    root.insert("new", 1)?;
+    doc.commit();
+
+    assert_eq!(
+        doc.get_deep_value_at(&root.id(), &v0)?.to_json_value(),
+        json!({"list": [{"text": "hello"}]})
+    );
+    // The deleted text can be read at the old version
+    assert_eq!(doc.get_value_at(&text.id(), &v0)?, "hello".into());
+    assert_eq!(
+        doc.get_value_at(&text.id(), &doc.oplog_frontiers())?,
+        "hello world".into()
+    );
+    assert_eq!(
+        doc.get_value_at(&root.id(), &Frontiers::default())?
+            .to_json_value(),
+        json!({})
+    );
+    assert_eq!(
+        root.get_deep_value().to_json_value(),
+        json!({"list": [], "new": 1})
+    );
+    Ok(())
+}
+
+#[test]
+fn doc_stays_attached() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let text = doc.get_text("text");
+    text.insert(0, "a")?;
+    doc.commit();
+    let v0 = doc.oplog_frontiers();
+    let count = Arc::new(AtomicUsize::new(0));
+    let count_clone = count.clone();
+    let _sub = doc.subscribe_root(Arc::new(move |_| {
+        count_clone.fetch_add(1, Ordering::SeqCst);
+    }));
+
+    // The pending changes are committed first
+    text.insert(1, "b")?;
+    assert_eq!(doc.get_value_at(&text.id(), &v0)?, "a".into());
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    text.insert(2, "c")?;
+    doc.commit();
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+    assert_eq!(text.to_string(), "abc");
+    assert!(!doc.is_detached());
+
+    // It also works on a detached doc
+    doc.checkout(&v0)?;
+    assert_eq!(
+        doc.get_value_at(&text.id(), &doc.oplog_frontiers())?,
+        "abc".into()
+    );
+    assert_eq!(text.to_string(), "a");
+    Ok(())
+}
+
+#[test]
+fn invalid_versions() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let text = doc.get_text("text");
+    text.insert(0, "abc")?;
+    doc.commit();
+    let v0 = doc.oplog_frontiers();
+    text.insert(3, "def")?;
+    doc.commit();
+
+    assert!(matches!(
+        doc.get_value_at(&text.id(), &Frontiers::from(ID::new(2, 0))),
+        Err(LoroError::FrontiersNotFound(..))
+    ));
+
+    let shallow = LoroDoc::new();
+    shallow.import(&doc.export(ExportMode::shallow_snapshot(&v0))?)?;
+    assert_eq!(shallow.get_value_at(&text.id(), &v0)?, "abc".into());
+    assert!(matches!(
+        shallow.get_value_at(&text.id(), &Frontiers::from(ID::new(1, 0))),
+        Err(LoroError::SwitchToVersionBeforeShallowRoot)
+    ));
+    Ok(())
+}
+
+#[test]
+fn unknown_containers() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.get_text("text").insert(0, "abc")?;
+    doc.commit();
+    let v = doc.oplog_frontiers();
+
+    let unknown = ContainerID::new_normal(ID::new(9, 0), ContainerType::Map);
+    assert!(matches!(
+        doc.get_value_at(&unknown, &v),
+        Err(LoroError::NotFoundError(..))
+    ));
+    // An unused root container is empty, and it's not registered by the query
+    let unused = ContainerID::new_root("unused", ContainerType::List);
+    assert_eq!(
+        doc.get_deep_value_at(&unused, &v)?.to_json_value(),
+        json!([])
+    );
+    assert_eq!(doc.get_deep_value().to_json_value(), json!({"text": "abc"}));
+    Ok(())
+}
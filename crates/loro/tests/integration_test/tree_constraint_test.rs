@@ -0,0 +1,311 @@
+use std::sync::{Arc, Mutex};
+
+use loro::{
+    EventTriggerKind, ExportMode, LoroDoc, LoroError, LoroTreeError, LoroValue, TreeConstraints,
+    TreeParentId, TreeResolution, TreeViolationKind, TREE_RESOLUTION_ORIGIN,
+};
+
+fn is_constraint_violation<T: std::fmt::Debug>(result: Result<T, LoroError>) -> bool {
+    matches!(
+        result,
+        Err(LoroError::TreeError(LoroTreeError::ConstraintViolation(_)))
+    )
+}
+
+fn is_folder(meta: &LoroValue) -> bool {
+    meta.as_map().and_then(|m| m.get("type")) == Some(&LoroValue::from("folder"))
+}
+
+#[test]
+fn local_moves_are_validated() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let tree = doc.get_tree("tree");
+    tree.enable_fractional_index(0);
+    doc.set_tree_constraints(
+        &tree.id(),
+        Some(TreeConstraints::new().max_depth(2).max_children(2)),
+    );
+    let a = tree.create(None)?;
+    let b = tree.create(a)?;
+    let c = tree.create(b)?;
+    assert!(is_constraint_violation(tree.create(c)));
+    let d = tree.create(a)?;
+    assert!(is_constraint_violation(tree.create(a)));
+    // Moving b under d makes c deeper than the max depth
+    assert!(is_constraint_violation(tree.mov(b, d)));
+    // Reordering inside the parent is always allowed
+    tree.mov_before(d, b)?;
+    assert_eq!(tree.children(a), Some(vec![d, b]));
+
+    // The root-level nodes are limited as well
+    let e = tree.create(None)?;
+    assert!(is_constraint_violation(tree.create(None)));
+    assert!(is_constraint_violation(tree.mov(c, TreeParentId::Root)));
+    tree.delete(e)?;
+    tree.mov(c, TreeParentId::Root)?;
+    assert_eq!(tree.roots(), vec![a, c]);
+    assert!(doc.validate_tree_constraints(&tree.id()).is_empty());
+
+    doc.set_tree_constraints(&tree.id(), None);
+    tree.create(None)?;
+    Ok(())
+}
+
+#[test]
+fn meta_based_constraints() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let tree = doc.get_tree("tree");
+    let docs = tree.create(None)?;
+    tree.get_meta(docs)?.insert("type", "folder")?;
+    let file = tree.create(None)?;
+    tree.get_meta(file)?.insert("type", "file")?;
+
+    // Folders can only be placed under folders
+    doc.set_tree_constraints(
+        &tree.id(),
+        Some(
+            TreeConstraints::new()
+                .allow_child(|child, parent| !is_folder(child) || parent.map_or(true, is_folder)),
+        ),
+    );
+    let folder = tree.create(None)?;
+    tree.get_meta(folder)?.insert("type", "folder")?;
+    tree.mov(folder, docs)?;
+    tree.mov(file, folder)?;
+    let readme = tree.create(None)?;
+    tree.get_meta(readme)?.insert("type", "file")?;
+    assert!(is_constraint_violation(tree.mov(folder, readme)));
+    assert_eq!(tree.parent(folder), Some(TreeParentId::Node(docs)));
+
+    // The edits of the metas are checked as well
+    let docs_meta = tree.get_meta(docs)?;
+    assert!(is_constraint_violation(docs_meta.insert("type", "file")));
+    assert!(is_constraint_violation(docs_meta.delete("type")));
+    assert_eq!(
+        docs_meta.get("type").unwrap().into_value().unwrap(),
+        "folder".into()
+    );
+    tree.get_meta(folder)?.insert("type", "file")?;
+    assert!(is_constraint_violation(
+        tree.get_meta(file)?.insert("type", "folder")
+    ));
+    tree.get_meta(readme)?.insert("type", "folder")?;
+    tree.get_meta(file)?.insert("name", "a.txt")?;
+    assert!(doc.validate_tree_constraints(&tree.id()).is_empty());
+    Ok(())
+}
+
+#[test]
+fn imported_meta_changes_are_checked() -> anyhow::Result<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let tree_a = doc_a.get_tree("tree");
+    let parent = tree_a.create(None)?;
+    tree_a.get_meta(parent)?.insert("type", "folder")?;
+    let child = tree_a.create(parent)?;
+    tree_a.get_meta(child)?.insert("type", "folder")?;
+    doc_a.commit();
+
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    doc_b.import(&doc_a.export(ExportMode::all_updates())?)?;
+    let tree_b = doc_b.get_tree("tree");
+    doc_b.set_tree_constraints(
+        &tree_b.id(),
+        Some(
+            TreeConstraints::new()
+                .allow_child(|child, parent| !is_folder(child) || parent.map_or(true, is_folder)),
+        ),
+    );
+
+    // Peer A has no constraints, so it can turn the parent into a file
+    let vv = doc_b.oplog_vv();
+    tree_a.get_meta(parent)?.insert("type", "file")?;
+    doc_a.commit();
+    let status = doc_b.import(&doc_a.export(ExportMode::updates(&vv))?)?;
+    assert_eq!(status.tree_violations.len(), 1);
+    assert_eq!(status.tree_violations[0].node, child);
+    assert_eq!(status.tree_violations[0].parent, TreeParentId::Node(parent));
+    assert_eq!(
+        status.tree_violations[0].kind,
+        TreeViolationKind::ChildNotAllowed
+    );
+    assert_eq!(
+        doc_b.validate_tree_constraints(&tree_b.id()),
+        status.tree_violations
+    );
+    Ok(())
+}
+
+#[test]
+fn imported_violations_are_reported() -> anyhow::Result<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let tree_a = doc_a.get_tree("tree");
+    let root = tree_a.create(None)?;
+    tree_a.create(root)?;
+    doc_a.commit();
+
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    doc_b.import(&doc_a.export(ExportMode::all_updates())?)?;
+    let tree_b = doc_b.get_tree("tree");
+
+    let constraints = TreeConstraints::new().max_children(2);
+    doc_a.set_tree_constraints(&tree_a.id(), Some(constraints.clone()));
+    doc_b.set_tree_constraints(&tree_b.id(), Some(constraints));
+
+    // Both peers add a child concurrently
+    let vv_a = doc_a.oplog_vv();
+    let vv_b = doc_b.oplog_vv();
+    tree_a.create(root)?;
+    tree_b.create(root)?;
+    doc_a.commit();
+    doc_b.commit();
+
+    let status = doc_a.import(&doc_b.export(ExportMode::updates(&vv_a))?)?;
+    // The changes are applied anyway
+    assert_eq!(tree_a.children_num(root), Some(3));
+    assert_eq!(status.tree_violations.len(), 1);
+    assert_eq!(status.tree_violations[0].tree, tree_a.id());
+    assert_eq!(
+        status.tree_violations[0].node,
+        tree_a.children(root).unwrap()[2]
+    );
+    assert_eq!(
+        status.tree_violations[0].kind,
+        TreeViolationKind::MaxChildren { index: 2, max: 2 }
+    );
+    assert_eq!(
+        doc_a.validate_tree_constraints(&tree_a.id()),
+        status.tree_violations
+    );
+
+    let status = doc_b.import(&doc_a.export(ExportMode::updates(&vv_b))?)?;
+    assert_eq!(
+        status.tree_violations,
+        doc_a.validate_tree_constraints(&tree_a.id())
+    );
+
+    // Only the changed trees are checked
+    let vv = doc_b.oplog_vv();
+    doc_a.get_map("map").insert("key", 1)?;
+    doc_a.commit();
+    let status = doc_b.import(&doc_a.export(ExportMode::updates(&vv))?)?;
+    assert!(status.tree_violations.is_empty());
+    Ok(())
+}
+
+#[test]
+fn imported_violations_are_resolved() -> anyhow::Result<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let tree_a = doc_a.get_tree("tree");
+    let x = tree_a.create(None)?;
+    let y = tree_a.create(None)?;
+    let z = tree_a.create(None)?;
+    doc_a.commit();
+
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    doc_b.import(&doc_a.export(ExportMode::all_updates())?)?;
+    let tree_b = doc_b.get_tree("tree");
+
+    // The nodes that are too deep are moved back to the root level
+    let constraints = TreeConstraints::new()
+        .max_depth(1)
+        .resolver(|_| TreeResolution::MoveTo(TreeParentId::Root));
+    doc_a.set_tree_constraints(&tree_a.id(), Some(constraints.clone()));
+    doc_b.set_tree_constraints(&tree_b.id(), Some(constraints));
+
+    // Each move is valid locally, but x -> y -> z is too deep after merging
+    let vv_a = doc_a.oplog_vv();
+    let vv_b = doc_b.oplog_vv();
+    tree_a.mov(x, y)?;
+    tree_b.mov(y, z)?;
+    doc_a.commit();
+    doc_b.commit();
+
+    let origins = Arc::new(Mutex::new(Vec::new()));
+    let origins_clone = origins.clone();
+    let _sub = doc_a.subscribe_root(Arc::new(move |e| {
+        origins_clone
+            .lock()
+            .unwrap()
+            .push((e.triggered_by, e.origin.to_string()));
+    }));
+    let status = doc_a.import(&doc_b.export(ExportMode::updates(&vv_b))?)?;
+    assert!(status.tree_violations.is_empty());
+    assert_eq!(tree_a.parent(x), Some(TreeParentId::Root));
+    assert_eq!(tree_a.parent(y), Some(TreeParentId::Node(z)));
+    // The resolution is committed with its own origin
+    assert_eq!(
+        origins.lock().unwrap().last(),
+        Some(&(EventTriggerKind::Local, TREE_RESOLUTION_ORIGIN.to_string()))
+    );
+
+    // The resolution is synced as normal ops
+    let status = doc_b.import(&doc_a.export(ExportMode::updates(&vv_a))?)?;
+    assert!(status.tree_violations.is_empty());
+    assert_eq!(doc_a.get_deep_value(), doc_b.get_deep_value());
+    assert!(doc_b.validate_tree_constraints(&tree_b.id()).is_empty());
+    Ok(())
+}
+
+#[test]
+fn redundant_resolutions_converge() -> anyhow::Result<()> {
+    let docs: Vec<LoroDoc> = (1..=3).map(|_| LoroDoc::new()).collect();
+    for (i, doc) in docs.iter().enumerate() {
+        doc.set_peer_id(i as u64 + 1)?;
+    }
+    let tree_a = docs[0].get_tree("tree");
+    let x = tree_a.create(None)?;
+    let y = tree_a.create(None)?;
+    let z = tree_a.create(None)?;
+    docs[0].commit();
+    let base = docs[0].export(ExportMode::all_updates())?;
+    let constraints = TreeConstraints::new()
+        .max_depth(1)
+        .resolver(|_| TreeResolution::MoveTo(TreeParentId::Root));
+    for doc in docs.iter() {
+        doc.import(&base)?;
+        doc.set_tree_constraints(&tree_a.id(), Some(constraints.clone()));
+    }
+
+    // x -> y -> z is too deep after merging the moves of A and B
+    let vv = docs[0].oplog_vv();
+    tree_a.mov(x, y)?;
+    docs[0].commit();
+    docs[1].get_tree("tree").mov(y, z)?;
+    docs[1].commit();
+    let a = docs[0].export(ExportMode::updates(&vv))?;
+    let b = docs[1].export(ExportMode::updates(&vv))?;
+    docs[0].import(&b)?;
+    docs[1].import(&a)?;
+    docs[2].import(&a)?;
+    docs[2].import(&b)?;
+
+    // Every peer has committed its own resolution
+    for doc in docs.iter() {
+        assert_eq!(doc.get_tree("tree").parent(x), Some(TreeParentId::Root));
+        assert!(doc.oplog_vv().get(&doc.peer_id()).is_some());
+    }
+
+    // The redundant resolutions converge without introducing new violations
+    for i in 0..docs.len() {
+        for j in 0..docs.len() {
+            if i != j {
+                let status = docs[j].import(&docs[i].export(ExportMode::all_updates())?)?;
+                assert!(status.tree_violations.is_empty());
+            }
+        }
+    }
+    for doc in docs.iter() {
+        assert_eq!(doc.get_deep_value(), docs[0].get_deep_value());
+        assert!(doc.validate_tree_constraints(&tree_a.id()).is_empty());
+        let tree = doc.get_tree("tree");
+        assert_eq!(tree.parent(x), Some(TreeParentId::Root));
+        assert_eq!(tree.parent(y), Some(TreeParentId::Node(z)));
+    }
+    Ok(())
+}
@@ -4,8 +4,9 @@ use std::sync::{
 };
 
 use loro::{
-    undo::UndoItemMeta, LoroDoc, LoroError, LoroList, LoroMap, LoroResult, LoroText, LoroValue,
-    StyleConfigMap, ToJson, UndoManager,
+    undo::{UndoItemMeta, UndoScope},
+    CounterSpan, ExportMode, LoroDoc, LoroError, LoroList, LoroMap, LoroResult, LoroText,
+    LoroValue, StyleConfigMap, ToJson, UndoManager,
 };
 use loro_internal::{configure::StyleConfig, id::ID, loro::CommitOptions};
 use serde_json::json;
@@ -625,9 +626,7 @@ fn undo_richtext_conflict_set_style() -> LoroResult<()> {
     let mut config = StyleConfigMap::new();
     config.insert(
         "color".into(),
-        StyleConfig {
-            expand: loro::ExpandType::After,
-        },
+        StyleConfig::new().expand(loro::ExpandType::After),
     );
     doc_a.config_text_style(config.clone());
     let mut undo = UndoManager::new(&doc_a);
@@ -1639,3 +1638,479 @@ fn undo_transform_cursor_position() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn scoped_undo_ignores_out_of_scope_changes() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let text = doc.get_text("text");
+    let map = doc.get_map("map");
+    let mut undo = UndoManager::new_with_scope(&doc, UndoScope::new([text.id()]));
+    text.insert(0, "Hello")?;
+    doc.commit();
+    map.insert("a", 1)?;
+    doc.commit();
+    text.insert(5, " world")?;
+    map.insert("b", 2)?;
+    doc.commit();
+    map.insert("c", 3)?;
+    doc.commit();
+
+    assert!(undo.undo(&doc)?);
+    assert_eq!(text.to_string(), "Hello");
+    assert_eq!(
+        map.get_deep_value().to_json_value(),
+        json!({"a": 1, "b": 2, "c": 3})
+    );
+    assert!(undo.undo(&doc)?);
+    assert_eq!(text.to_string(), "");
+    assert!(!undo.can_undo());
+    assert_eq!(
+        map.get_deep_value().to_json_value(),
+        json!({"a": 1, "b": 2, "c": 3})
+    );
+
+    assert!(undo.redo(&doc)?);
+    assert!(undo.redo(&doc)?);
+    assert_eq!(text.to_string(), "Hello world");
+    assert!(!undo.can_redo());
+    Ok(())
+}
+
+#[test]
+fn scoped_undo_with_descendants() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let root = doc.get_map("root");
+    let other = doc.get_text("other");
+    let mut undo =
+        UndoManager::new_with_scope(&doc, UndoScope::new([root.id()]).include_descendants(true));
+    let child = root.insert_container("text", LoroText::new())?;
+    doc.commit();
+    child.insert(0, "abc")?;
+    doc.commit();
+    other.insert(0, "xyz")?;
+    doc.commit();
+
+    assert!(undo.undo(&doc)?);
+    assert_eq!(child.to_string(), "");
+    assert_eq!(other.to_string(), "xyz");
+    assert!(undo.undo(&doc)?);
+    assert_eq!(root.get_deep_value().to_json_value(), json!({}));
+    assert!(!undo.can_undo());
+
+    // Without descendants, the changes on the child are out of the scope
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let root = doc.get_map("root");
+    let child = root.insert_container("text", LoroText::new())?;
+    doc.commit();
+    let mut undo = UndoManager::new_with_scope(&doc, UndoScope::new([root.id()]));
+    child.insert(0, "abc")?;
+    doc.commit();
+    assert!(!undo.can_undo());
+    Ok(())
+}
+
+#[test]
+fn scoped_undo_transforms_against_other_changes() -> LoroResult<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    let text_a = doc_a.get_text("text");
+    let mut undo = UndoManager::new_with_scope(&doc_a, UndoScope::new([text_a.id()]));
+    text_a.insert(0, "Hello")?;
+    // A change touching both the scope and other containers
+    doc_a.get_map("map").insert("a", 1)?;
+    doc_a.commit();
+    sync(&doc_a, &doc_b);
+
+    doc_b.get_text("text").insert(0, "Hi ")?;
+    doc_b.get_text("text").insert(8, "!")?;
+    doc_b.commit();
+    sync(&doc_a, &doc_b);
+    assert_eq!(text_a.to_string(), "Hi Hello!");
+
+    assert!(undo.undo(&doc_a)?);
+    assert_eq!(text_a.to_string(), "Hi !");
+    assert_eq!(
+        doc_a.get_map("map").get_deep_value().to_json_value(),
+        json!({"a": 1})
+    );
+    assert!(undo.redo(&doc_a)?);
+    assert_eq!(text_a.to_string(), "Hi Hello!");
+    Ok(())
+}
+
+#[test]
+fn undo_state_survives_restart() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let text = doc.get_text("text");
+    let mut undo = UndoManager::new(&doc);
+    undo.set_on_push(Some(Box::new(|_, span| {
+        let mut meta = UndoItemMeta::new();
+        meta.set_value(LoroValue::I64(span.start as i64));
+        meta
+    })));
+    text.insert(0, "Hello")?;
+    doc.commit();
+    text.insert(5, " world")?;
+    doc.commit();
+    undo.undo(&doc)?;
+    assert_eq!(text.to_string(), "Hello");
+    let undo_state = undo.export_state();
+    let snapshot = doc.export(ExportMode::Snapshot).unwrap();
+
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.import(&snapshot)?;
+    let text = doc.get_text("text");
+    let mut undo = UndoManager::new(&doc);
+    undo.import_state(&doc, &undo_state)?;
+    let popped = Arc::new(Mutex::new(Vec::new()));
+    let popped_clone = popped.clone();
+    undo.set_on_pop(Some(Box::new(move |_, _, meta| {
+        popped_clone.try_lock().unwrap().push(meta.value);
+    })));
+    assert!(undo.can_undo());
+    assert!(undo.can_redo());
+
+    undo.redo(&doc)?;
+    assert_eq!(text.to_string(), "Hello world");
+    undo.undo(&doc)?;
+    assert_eq!(text.to_string(), "Hello");
+    undo.undo(&doc)?;
+    assert_eq!(text.to_string(), "");
+    assert!(!undo.can_undo());
+    assert_eq!(popped.try_lock().unwrap().last(), Some(&LoroValue::I64(0)));
+    undo.redo(&doc)?;
+    assert_eq!(text.to_string(), "Hello");
+    Ok(())
+}
+
+#[test]
+fn restored_undo_state_with_remote_changes() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let undo = UndoManager::new(&doc);
+    doc.get_text("text").insert(0, "Hello")?;
+    doc.commit();
+    let undo_state = undo.export_state();
+    let snapshot = doc.export(ExportMode::Snapshot).unwrap();
+
+    let remote = LoroDoc::new();
+    remote.set_peer_id(2)?;
+    remote.import(&snapshot)?;
+    remote.get_text("text").insert(0, "Hi ")?;
+    remote.get_text("text").insert(8, "!")?;
+    remote.commit();
+
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.import(&snapshot)?;
+    let mut undo = UndoManager::new(&doc);
+    undo.import_state(&doc, &undo_state)?;
+    // Remote changes before and after the import are both considered
+    doc.import(&remote.export(ExportMode::all_updates()).unwrap())?;
+    doc.get_text("text").insert(0, "> ")?;
+    doc.commit();
+    assert_eq!(doc.get_text("text").to_string(), "> Hi Hello!");
+    undo.undo(&doc)?;
+    assert_eq!(doc.get_text("text").to_string(), "Hi Hello!");
+    undo.undo(&doc)?;
+    assert_eq!(doc.get_text("text").to_string(), "Hi !");
+    Ok(())
+}
+
+#[test]
+fn import_invalid_undo_state() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let undo = UndoManager::new(&doc);
+    doc.get_text("text").insert(0, "Hello")?;
+    doc.commit();
+    let undo_state = undo.export_state();
+
+    let other = LoroDoc::new();
+    other.set_peer_id(2)?;
+    other.import(&doc.export(ExportMode::Snapshot).unwrap())?;
+    let mut other_undo = UndoManager::new(&other);
+    assert!(matches!(
+        other_undo.import_state(&other, &undo_state),
+        Err(LoroError::UndoWithDifferentPeerId { .. })
+    ));
+
+    // The doc doesn't include the ops in the undo stack
+    let empty = LoroDoc::new();
+    empty.set_peer_id(1)?;
+    let mut empty_undo = UndoManager::new(&empty);
+    assert!(matches!(
+        empty_undo.import_state(&empty, &undo_state),
+        Err(LoroError::UndoInvalidIdSpan(..))
+    ));
+    assert!(!empty_undo.can_undo());
+
+    assert!(matches!(
+        empty_undo.import_state(&empty, &[0, 255, 255]),
+        Err(LoroError::DecodeError(..))
+    ));
+    Ok(())
+}
+
+#[test]
+fn import_undo_state_of_version_0() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.get_text("text").insert(0, "Hello")?;
+    doc.commit();
+    let mut undo = UndoManager::new(&doc);
+    // Version 0 doesn't store the peer of each item: { peer: 1, undo: [0..5], redo: [] }
+    undo.import_state(&doc, &[0, 1, 1, 0, 10, 0, 0, 0])?;
+    assert!(undo.can_undo());
+    undo.undo(&doc)?;
+    assert_eq!(doc.get_text("text").to_string(), "");
+
+    assert!(matches!(
+        undo.import_state(&doc, &[2]),
+        Err(LoroError::IncompatibleFutureEncodingError(2))
+    ));
+    Ok(())
+}
+
+#[test]
+fn global_undo_reverts_changes_from_all_peers() -> LoroResult<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    let mut undo = UndoManager::new_global(&doc_a);
+    let text_a = doc_a.get_text("text");
+    text_a.insert(0, "Hello")?;
+    doc_a.commit();
+    sync(&doc_a, &doc_b);
+    doc_b.get_text("text").insert(5, " world")?;
+    doc_b.commit();
+    sync(&doc_a, &doc_b);
+    doc_a.get_map("map").insert("key", "value")?;
+    doc_a.commit();
+    doc_b.get_text("text").insert(0, "> ")?;
+    doc_b.commit();
+    sync(&doc_a, &doc_b);
+    assert_eq!(text_a.to_string(), "> Hello world");
+
+    assert!(undo.undo(&doc_a)?);
+    assert_eq!(text_a.to_string(), "Hello world");
+    assert!(undo.undo(&doc_a)?);
+    assert_eq!(
+        doc_a.get_map("map").get_deep_value().to_json_value(),
+        json!({})
+    );
+    assert!(undo.undo(&doc_a)?);
+    assert_eq!(text_a.to_string(), "Hello");
+    // Peer id can be changed for a global UndoManager
+    doc_a.set_peer_id(3)?;
+    assert!(undo.undo(&doc_a)?);
+    assert_eq!(text_a.to_string(), "");
+    assert!(!undo.can_undo());
+
+    assert!(undo.redo(&doc_a)?);
+    assert!(undo.redo(&doc_a)?);
+    assert_eq!(text_a.to_string(), "Hello world");
+    sync(&doc_a, &doc_b);
+    assert_eq!(doc_b.get_text("text").to_string(), "Hello world");
+    Ok(())
+}
+
+#[test]
+fn global_undo_with_filters() -> LoroResult<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    doc_a.set_change_merge_interval(0);
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    let mut undo = UndoManager::new_global(&doc_a);
+    undo.add_exclude_origin_prefix("sys:");
+    undo.set_change_filter(Some(Box::new(|meta| meta.message() != "layout")));
+    let text = doc_a.get_text("text");
+    text.insert(0, "abc")?;
+    doc_a.commit();
+    text.insert(3, "def")?;
+    doc_a.set_next_commit_message("layout");
+    doc_a.commit();
+    doc_b.get_text("text").insert(0, "xyz")?;
+    doc_b.commit();
+    doc_a.import_with(
+        &doc_b.export(ExportMode::all_updates()).unwrap(),
+        "sys:sync",
+    )?;
+    assert_eq!(text.to_string(), "xyzabcdef");
+
+    assert!(undo.undo(&doc_a)?);
+    assert_eq!(text.to_string(), "xyzdef");
+    assert!(!undo.can_undo());
+    Ok(())
+}
+
+#[test]
+fn undo_group() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let mut undo = UndoManager::new(&doc);
+    let pushed = Arc::new(Mutex::new(Vec::new()));
+    let pushed_clone = pushed.clone();
+    undo.set_on_push(Some(Box::new(move |kind, span| {
+        pushed_clone.try_lock().unwrap().push((kind, span));
+        UndoItemMeta::new()
+    })));
+    let text = doc.get_text("text");
+    text.insert(0, "a")?;
+    undo.group_start(&doc)?;
+    text.insert(1, "b")?;
+    doc.commit();
+    undo.group_start(&doc)?;
+    text.insert(2, "c")?;
+    doc.commit();
+    undo.group_end(&doc)?;
+    text.insert(3, "d")?;
+    doc.commit();
+    assert_eq!(pushed.try_lock().unwrap().len(), 1);
+    undo.group_end(&doc)?;
+    // Ending a group without an active group is a no-op
+    undo.group_end(&doc)?;
+    text.insert(4, "e")?;
+    doc.commit();
+
+    {
+        let pushed = pushed.try_lock().unwrap();
+        assert_eq!(pushed.len(), 3);
+        assert_eq!(pushed[0].1, CounterSpan::new(0, 1));
+        assert_eq!(pushed[1].1, CounterSpan::new(1, 4));
+        assert_eq!(pushed[2].1, CounterSpan::new(4, 5));
+    }
+    undo.undo(&doc)?;
+    assert_eq!(text.to_string(), "abcd");
+    undo.undo(&doc)?;
+    assert_eq!(text.to_string(), "a");
+    undo.redo(&doc)?;
+    assert_eq!(text.to_string(), "abcd");
+    undo.undo(&doc)?;
+    undo.undo(&doc)?;
+    assert_eq!(text.to_string(), "");
+    assert!(!undo.can_undo());
+    Ok(())
+}
+
+#[test]
+fn undo_group_with_remote_changes() -> LoroResult<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    let mut undo = UndoManager::new(&doc_a);
+    let text = doc_a.get_text("text");
+    undo.group_start(&doc_a)?;
+    text.insert(0, "Hello")?;
+    doc_a.commit();
+    sync(&doc_a, &doc_b);
+    doc_b.get_text("text").insert(5, "!")?;
+    doc_b.commit();
+    sync(&doc_a, &doc_b);
+    text.insert(0, "> ")?;
+    doc_a.commit();
+    undo.group_end(&doc_a)?;
+    assert_eq!(text.to_string(), "> Hello!");
+
+    // The import splits the group
+    undo.undo(&doc_a)?;
+    assert_eq!(text.to_string(), "Hello!");
+    undo.undo(&doc_a)?;
+    assert_eq!(text.to_string(), "!");
+    assert!(!undo.can_undo());
+    undo.redo(&doc_a)?;
+    undo.redo(&doc_a)?;
+    assert_eq!(text.to_string(), "> Hello!");
+    Ok(())
+}
+
+#[test]
+fn undo_group_with_concurrent_import() -> LoroResult<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    let mut undo = UndoManager::new(&doc_a);
+    let text = doc_a.get_text("text");
+    undo.group_start(&doc_a)?;
+    text.insert(0, "Hello")?;
+    doc_a.commit();
+    // The remote insertion shifts the text inserted in the group
+    sync(&doc_a, &doc_b);
+    doc_b.get_text("text").insert(0, "Hi ")?;
+    doc_b.commit();
+    sync(&doc_a, &doc_b);
+    text.insert(8, "!")?;
+    doc_a.commit();
+    undo.group_end(&doc_a)?;
+    assert_eq!(text.to_string(), "Hi Hello!");
+
+    undo.undo(&doc_a)?;
+    assert_eq!(text.to_string(), "Hi Hello");
+    undo.undo(&doc_a)?;
+    assert_eq!(text.to_string(), "Hi ");
+    assert!(!undo.can_undo());
+    Ok(())
+}
+
+#[test]
+fn undo_group_split_by_excluded_origin() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let mut undo = UndoManager::new(&doc);
+    undo.add_exclude_origin_prefix("sys:");
+    let text = doc.get_text("text");
+    undo.group_start(&doc)?;
+    text.insert(0, "a")?;
+    doc.commit();
+    text.insert(1, "b")?;
+    doc.commit_with(CommitOptions::new().origin("sys:init"));
+    text.insert(2, "c")?;
+    doc.commit();
+    undo.group_end(&doc)?;
+
+    undo.undo(&doc)?;
+    assert_eq!(text.to_string(), "ab");
+    undo.undo(&doc)?;
+    assert_eq!(text.to_string(), "b");
+    assert!(!undo.can_undo());
+    Ok(())
+}
+
+#[test]
+fn undo_records_changes_committed_while_locked() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let mut undo = UndoManager::new(&doc);
+    undo.set_merge_interval(0);
+    let text = doc.get_text("text");
+    std::thread::scope(|s| {
+        let handle = s.spawn(|| {
+            for _ in 0..100 {
+                text.insert(0, "a").unwrap();
+                doc.commit();
+            }
+        });
+        // Keep the stacks of the UndoManager locked while the other thread commits
+        while !handle.is_finished() {
+            undo.can_undo();
+        }
+    });
+
+    for i in (0..100).rev() {
+        assert!(undo.undo(&doc)?);
+        assert_eq!(text.len_unicode(), i);
+    }
+    assert!(!undo.can_undo());
+    Ok(())
+}
@@ -9,8 +9,10 @@ use std::{
 };
 
 use loro::{
-    awareness::Awareness, loro_value, Frontiers, FrontiersNotIncluded, LoroDoc, LoroError,
-    LoroList, LoroMap, LoroText, ToJson,
+    awareness::Awareness,
+    cursor::{CursorRange, Side},
+    loro_value, Frontiers, FrontiersNotIncluded, LoroDoc, LoroError, LoroList, LoroMap, LoroText,
+    LoroValue, StyleConfig, StyleConfigMap, ToJson,
 };
 use loro_internal::{handler::TextDelta, id::ID, vv, LoroResult};
 use rand::{Rng, SeedableRng};
@@ -439,6 +441,69 @@ fn richtext_test() {
     );
 }
 
+#[test]
+fn text_annotations() {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1).unwrap();
+    let text_a = doc_a.get_text("text");
+    text_a.insert(0, "Hello world!").unwrap();
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2).unwrap();
+    let text_b = doc_b.get_text("text");
+    doc_b.import(&doc_a.export_snapshot()).unwrap();
+
+    let a = text_a.annotate(0..5, "comment", "from a").unwrap();
+    let b = text_b.annotate(3..8, "comment", "from b").unwrap();
+    text_b.insert(6, "big ").unwrap();
+    assert!(text_a.annotate(0..1, "comment:x", "x").is_err());
+    assert!(matches!(
+        text_a.annotate(0..1, "comment", LoroValue::Null),
+        Err(LoroError::ArgErr(_))
+    ));
+    assert!(matches!(
+        text_a.annotate(0..1, "unknown", "x"),
+        Err(LoroError::StyleConfigMissing(_))
+    ));
+
+    doc_a
+        .import(&doc_b.export_from(&Default::default()))
+        .unwrap();
+    doc_b
+        .import(&doc_a.export_from(&Default::default()))
+        .unwrap();
+    assert_eq!(text_a.to_string(), "Hello big world!");
+    let annotations = text_a.get_annotations();
+    assert_eq!(annotations, text_b.get_annotations());
+    assert_eq!(annotations.len(), 2);
+    assert_eq!(annotations[0].id, a);
+    assert_eq!(annotations[0].key.as_str(), "comment");
+    assert_eq!(annotations[0].range, 0..5);
+    assert_eq!(annotations[0].value, "from a".into());
+    assert_eq!(annotations[1].id, b);
+    assert_eq!(annotations[1].range, 3..12);
+    assert_eq!(annotations[1].value, "from b".into());
+    assert_eq!(text_a.get_annotations_at(4).len(), 2);
+    let at_10 = text_a.get_annotations_at(10);
+    assert_eq!(at_10.len(), 1);
+    assert_eq!(at_10[0].id, b);
+    assert_eq!(at_10[0].range, 3..12);
+    assert_eq!(at_10[0].value, "from b".into());
+    assert!(text_a.get_annotations_at(13).is_empty());
+
+    text_a.remove_annotation("comment", b).unwrap();
+    assert!(text_a.get_annotations_at(10).is_empty());
+    assert_eq!(text_a.get_annotations_at(4).len(), 1);
+    text_a.remove_annotation("comment", b).unwrap();
+    doc_b.import(&doc_a.export_from(&doc_b.oplog_vv())).unwrap();
+    let annotations = text_b.get_annotations();
+    assert_eq!(annotations.len(), 1);
+    assert_eq!(annotations[0].id, a);
+
+    text_b.delete(0, 5).unwrap();
+    assert!(text_b.get_annotations().is_empty());
+    text_b.remove_annotation("comment", a).unwrap();
+}
+
 #[test]
 fn sync() {
     use loro::{LoroDoc, ToJson};
@@ -768,6 +833,122 @@ fn get_cursor_at_the_end() {
     assert_eq!(doc.get_cursor_pos(&pos).unwrap().current.pos, 5);
 }
 
+#[test]
+fn get_cursor_pos_batch() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "0123456789").unwrap();
+    let list = doc.get_list("list");
+    list.insert(0, 0).unwrap();
+    list.insert(1, 1).unwrap();
+    let cursors = vec![
+        text.get_cursor(2, Default::default()).unwrap(),
+        text.get_cursor(10, Default::default()).unwrap(),
+        list.get_cursor(1, Default::default()).unwrap(),
+        text.get_cursor(7, Default::default()).unwrap(),
+    ];
+    text.insert(0, "abc").unwrap();
+    list.insert(0, 2).unwrap();
+    text.delete(9, 2).unwrap();
+    let batch = doc.get_cursor_pos_batch(&cursors);
+    assert_eq!(batch.len(), cursors.len());
+    for (cursor, ans) in cursors.iter().zip(batch.iter()) {
+        assert_eq!(ans.as_ref().unwrap(), &doc.get_cursor_pos(cursor).unwrap());
+    }
+
+    let pos: Vec<usize> = batch
+        .iter()
+        .map(|x| x.as_ref().unwrap().current.pos)
+        .collect();
+    assert_eq!(pos, vec![5, 11, 2, 9]);
+    assert!(batch[3].as_ref().unwrap().update.is_some());
+}
+
+#[test]
+fn get_stale_cursor_pos_batch() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "0123456789").unwrap();
+    let list = doc.get_list("list");
+    list.insert(0, 0).unwrap();
+    list.insert(1, 1).unwrap();
+    list.insert(2, 2).unwrap();
+    doc.commit();
+    let cursors = vec![
+        text.get_cursor(1, Default::default()).unwrap(),
+        text.get_cursor(3, Default::default()).unwrap(),
+        list.get_cursor(1, Default::default()).unwrap(),
+        text.get_cursor(5, Default::default()).unwrap(),
+        text.get_cursor(7, Default::default()).unwrap(),
+    ];
+    // The targets are deleted by different changes
+    text.delete(3, 1).unwrap();
+    doc.commit();
+    text.insert(0, "ab").unwrap();
+    list.delete(1, 1).unwrap();
+    doc.commit();
+    text.delete(8, 1).unwrap();
+    doc.commit();
+    text.delete(3, 1).unwrap();
+    doc.commit();
+    assert_eq!(text.to_string(), "ab0245689");
+
+    let batch = doc.get_cursor_pos_batch(&cursors);
+    for (cursor, ans) in cursors.iter().zip(batch.iter()) {
+        assert_eq!(ans.as_ref().unwrap(), &doc.get_cursor_pos(cursor).unwrap());
+    }
+    let pos: Vec<usize> = batch
+        .iter()
+        .map(|x| x.as_ref().unwrap().current.pos)
+        .collect();
+    assert_eq!(pos, vec![3, 4, 1, 5, 7]);
+    let stale: Vec<bool> = batch
+        .iter()
+        .map(|x| x.as_ref().unwrap().update.is_some())
+        .collect();
+    assert_eq!(stale, vec![true, true, true, false, true]);
+}
+
+#[test]
+fn get_cursor_range_pos_batch() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "Hello world!").unwrap();
+    let hello = CursorRange::new(
+        text.get_cursor(0, Side::Left).unwrap(),
+        text.get_cursor(5, Side::Left).unwrap(),
+    );
+    let world = CursorRange::new(
+        text.get_cursor(6, Side::Left).unwrap(),
+        text.get_cursor(11, Side::Left).unwrap(),
+    );
+    let world = CursorRange::decode(&world.encode()).unwrap();
+
+    // Delete "world"
+    text.delete(6, 5).unwrap();
+    let ans = doc.get_cursor_range_pos_batch(&[hello.clone(), world.clone()]);
+    let hello_ans = ans[0].as_ref().unwrap();
+    assert!(!hello_ans.is_collapsed());
+    assert!(!hello_ans.is_anchor_deleted());
+    assert!(hello_ans.updated_range(&hello).is_none());
+    assert_eq!(
+        (hello_ans.start.current.pos, hello_ans.end.current.pos),
+        (0, 5)
+    );
+
+    let world_ans = ans[1].as_ref().unwrap();
+    assert!(world_ans.is_collapsed());
+    assert!(world_ans.is_anchor_deleted());
+    let updated = world_ans.updated_range(&world).unwrap();
+    let ans = doc.get_cursor_range_pos_batch(&[updated]);
+    let ans = ans[0].as_ref().unwrap();
+    assert!(!ans.is_anchor_deleted());
+    assert_eq!((ans.start.current.pos, ans.end.current.pos), (6, 6));
+}
+
 #[test]
 fn get_cursor_for_list() {
     let doc = LoroDoc::new();
@@ -1652,6 +1833,90 @@ fn richtext_map_value() {
     );
 }
 
+#[test]
+fn richtext_merge_map_style() {
+    let mut config = StyleConfigMap::default_rich_text_config();
+    config.insert("link".into(), StyleConfig::new().merge_map(true));
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1).unwrap();
+    doc_a.config_text_style(config.clone());
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2).unwrap();
+    doc_b.config_text_style(config.clone());
+    let mirror = LoroDoc::new();
+    mirror.config_text_style(config);
+    let text_a = doc_a.get_text("text");
+    let text_b = doc_b.get_text("text");
+    let mirror_text = mirror.get_text("text");
+    let _sub = doc_a.subscribe(
+        &text_a.id(),
+        Arc::new(move |x| {
+            for event in x.events {
+                if let Some(delta) = event.diff.as_text() {
+                    mirror_text.apply_delta(delta).unwrap();
+                }
+            }
+        }),
+    );
+
+    text_a.insert(0, "Hello world!").unwrap();
+    doc_a.commit();
+    doc_b.import(&doc_a.export_snapshot()).unwrap();
+
+    // Concurrent edits to different fields are merged
+    text_a
+        .mark(0..5, "link", loro_value!({"href": "https://loro.dev"}))
+        .unwrap();
+    text_b
+        .mark(0..5, "link", loro_value!({"title": "Loro"}))
+        .unwrap();
+    doc_a.commit();
+    doc_a.import(&doc_b.export_snapshot()).unwrap();
+    doc_b.import(&doc_a.export_snapshot()).unwrap();
+    let expected = loro_value!([
+        { "insert": "Hello", "attributes": { "link": { "href": "https://loro.dev", "title": "Loro" } } },
+        { "insert": " world!" }
+    ]);
+    assert_eq!(text_a.to_delta(), expected);
+    assert_eq!(text_b.to_delta(), expected);
+    assert_eq!(mirror.get_text("text").to_delta(), expected);
+
+    // Setting a field to null removes it
+    text_a
+        .mark(0..3, "link", loro_value!({"title": null}))
+        .unwrap();
+    doc_a.commit();
+    let expected = loro_value!([
+        { "insert": "Hel", "attributes": { "link": { "href": "https://loro.dev" } } },
+        { "insert": "lo", "attributes": { "link": { "href": "https://loro.dev", "title": "Loro" } } },
+        { "insert": " world!" }
+    ]);
+    assert_eq!(text_a.to_delta(), expected);
+    assert_eq!(mirror.get_text("text").to_delta(), expected);
+
+    // The attributes of a delta are full values
+    text_a
+        .apply_delta(&[TextDelta::Retain {
+            retain: 5,
+            attributes: Some(
+                [("link".to_string(), loro_value!({"title": "New"}))]
+                    .into_iter()
+                    .collect(),
+            ),
+        }])
+        .unwrap();
+    doc_a.commit();
+    let expected = loro_value!([
+        { "insert": "Hello", "attributes": { "link": { "title": "New" } } },
+        { "insert": " world!" }
+    ]);
+    assert_eq!(text_a.to_delta(), expected);
+    assert_eq!(mirror.get_text("text").to_delta(), expected);
+    doc_a.check_state_correctness_slow();
+    doc_b.import(&doc_a.export_snapshot()).unwrap();
+    assert_eq!(text_b.to_delta(), expected);
+}
+
 #[test]
 fn test_get_shallow_value() {
     let doc = LoroDoc::new();
@@ -1971,3 +2236,314 @@ fn test_fork_at_should_restore_attached_state() {
     doc.fork_at(&[ID::new(0, 0)].into());
     assert!(doc.is_detached());
 }
+
+#[test]
+fn tree_subtree_export_import() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let tree = doc.get_tree("tree");
+    let root = tree.create(None).unwrap();
+    let a = tree.create(root).unwrap();
+    let b = tree.create(root).unwrap();
+    let c = tree.create(a).unwrap();
+    let meta = tree.get_meta(a).unwrap();
+    meta.insert("name", "a").unwrap();
+    let text = meta.insert_container("content", LoroText::new()).unwrap();
+    text.insert(0, "Hello world").unwrap();
+    text.mark(0..5, "bold", true).unwrap();
+    let list = meta.insert_container("tags", LoroList::new()).unwrap();
+    list.push("x").unwrap();
+    tree.get_meta(c).unwrap().insert("name", "c").unwrap();
+
+    let subtree = tree.export_subtree(root).unwrap();
+    assert_eq!(subtree.id, root);
+    assert_eq!(subtree.children.len(), 2);
+    assert_eq!(subtree.children[0].id, a);
+    assert_eq!(subtree.children[1].id, b);
+    assert_eq!(subtree.children[0].children[0].id, c);
+    // The exported subtree is not affected by the later changes
+    text.insert(0, "!").unwrap();
+    tree.delete(b).unwrap();
+    assert!(tree.export_subtree(b).is_err());
+
+    let other = LoroDoc::new();
+    other.set_peer_id(2).unwrap();
+    let other_tree = other.get_tree("tree");
+    assert!(matches!(
+        other_tree.import_subtree(None, 0, &subtree),
+        Err(LoroError::TreeError(
+            loro::LoroTreeError::FractionalIndexNotEnabled
+        ))
+    ));
+    other_tree.enable_fractional_index(0);
+    let existing = other_tree.create(None).unwrap();
+    let mapping = other_tree.import_subtree(None, 0, &subtree).unwrap();
+    assert_eq!(mapping.len(), 4);
+    assert_eq!(other_tree.roots(), vec![mapping[&root], existing]);
+    assert_eq!(
+        other_tree.children(mapping[&root]).unwrap(),
+        vec![mapping[&a], mapping[&b]]
+    );
+    assert_eq!(other_tree.children(mapping[&a]).unwrap(), vec![mapping[&c]]);
+    let new_meta = other_tree.get_meta(mapping[&a]).unwrap();
+    assert_eq!(
+        new_meta.get_deep_value().to_json_value(),
+        json!({"name": "a", "content": "Hello world", "tags": ["x"]})
+    );
+    let new_text = new_meta
+        .get("content")
+        .unwrap()
+        .into_container()
+        .unwrap()
+        .into_text()
+        .unwrap();
+    assert_eq!(
+        new_text.to_delta().to_json_value(),
+        json!([
+            {"insert": "Hello", "attributes": {"bold": true}},
+            {"insert": " world"},
+        ])
+    );
+
+    // The same subtree can be imported again with new ids
+    let mapping_2 = other_tree.import_subtree(mapping[&b], 0, &subtree).unwrap();
+    assert_ne!(mapping_2[&root], mapping[&root]);
+    assert_eq!(
+        other_tree.children(mapping[&b]).unwrap(),
+        vec![mapping_2[&root]]
+    );
+    assert_eq!(
+        other_tree
+            .get_meta(mapping_2[&c])
+            .unwrap()
+            .get_deep_value()
+            .to_json_value(),
+        json!({"name": "c"})
+    );
+
+    // A failed import leaves the tree untouched
+    let nodes_num = other_tree.get_nodes(false).len();
+    assert!(matches!(
+        other_tree.import_subtree(None, 10, &subtree),
+        Err(LoroError::TreeError(
+            loro::LoroTreeError::IndexOutOfBound { .. }
+        ))
+    ));
+    assert!(other_tree.import_subtree(a, 0, &subtree).is_err());
+    assert_eq!(other_tree.get_nodes(false).len(), nodes_num);
+
+    // The subtree can be encoded and decoded with its ids and child containers
+    let decoded = loro::TreeSubtree::decode(&subtree.encode().unwrap()).unwrap();
+    assert_eq!(decoded.id, root);
+    assert_eq!(decoded.children[0].id, a);
+    assert_eq!(decoded.children[1].id, b);
+    assert_eq!(decoded.children[0].children[0].id, c);
+    let decoded_text = decoded.children[0]
+        .meta
+        .get("content")
+        .unwrap()
+        .into_container()
+        .unwrap()
+        .into_text()
+        .unwrap();
+    assert_eq!(
+        decoded_text.to_delta().to_json_value(),
+        json!([
+            {"insert": "Hello", "attributes": {"bold": true}},
+            {"insert": " world"},
+        ])
+    );
+    assert!(loro::TreeSubtree::decode(&other.export(loro::ExportMode::Snapshot).unwrap()).is_err());
+}
+
+#[test]
+fn clone_container() {
+    use loro::{ContainerTrait, Index, LoroMovableList, LoroTree};
+
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let map = doc.get_map("map");
+    map.insert("title", "x").unwrap();
+    let text = map.insert_container("text", LoroText::new()).unwrap();
+    text.insert(0, "Hello world").unwrap();
+    text.mark(0..5, "bold", true).unwrap();
+    let list = map.insert_container("list", LoroList::new()).unwrap();
+    let child = list.insert_container(0, LoroMap::new()).unwrap();
+    child.insert("k", 1).unwrap();
+    let mlist = map
+        .insert_container("mlist", LoroMovableList::new())
+        .unwrap();
+    mlist.push(1).unwrap();
+    mlist.push(2).unwrap();
+    let tree = map.insert_container("tree", LoroTree::new()).unwrap();
+    let root = tree.create(None).unwrap();
+    let node = tree.create(root).unwrap();
+    tree.get_meta(node).unwrap().insert("name", "node").unwrap();
+
+    // Across docs
+    let other = LoroDoc::new();
+    other.set_peer_id(2).unwrap();
+    let other_list = other.get_list("list");
+    let copy = doc
+        .clone_container(&map.id(), &other_list.to_container(), Index::Seq(0))
+        .unwrap()
+        .into_map()
+        .unwrap();
+    assert_ne!(copy.id(), map.id());
+    let expected = json!({
+        "title": "x",
+        "text": "Hello world",
+        "list": [{"k": 1}],
+        "mlist": [1, 2],
+    });
+    let mut value = copy.get_deep_value().to_json_value();
+    let copied_tree = value.as_object_mut().unwrap().remove("tree").unwrap();
+    assert_eq!(value, expected);
+    assert_eq!(
+        copied_tree[0]["children"][0]["meta"],
+        json!({"name": "node"})
+    );
+    let copied_tree = copy
+        .get("tree")
+        .unwrap()
+        .into_container()
+        .unwrap()
+        .into_tree()
+        .unwrap();
+    assert_eq!(copied_tree.roots().len(), 1);
+    assert!(!copied_tree.roots().contains(&root));
+    let copied_text = copy
+        .get("text")
+        .unwrap()
+        .into_container()
+        .unwrap()
+        .into_text()
+        .unwrap();
+    assert_eq!(
+        copied_text.to_delta().to_json_value(),
+        json!([
+            {"insert": "Hello", "attributes": {"bold": true}},
+            {"insert": " world"},
+        ])
+    );
+
+    // The copy is synced as normal ops
+    let third = LoroDoc::new();
+    third
+        .import(&other.export(loro::ExportMode::Snapshot).unwrap())
+        .unwrap();
+    assert_eq!(
+        third.get_deep_value().to_json_value(),
+        other.get_deep_value().to_json_value()
+    );
+
+    // Within the same doc
+    let dest = doc.get_map("dest");
+    let copy = doc
+        .clone_container(&text.id(), &dest.to_container(), Index::Key("text".into()))
+        .unwrap()
+        .into_text()
+        .unwrap();
+    copy.insert(0, "!").unwrap();
+    assert_eq!(copy.to_string(), "!Hello world");
+    assert_eq!(text.to_string(), "Hello world");
+
+    // Into the meta of a tree node
+    let other_tree = other.get_tree("tree");
+    let other_node = other_tree.create(None).unwrap();
+    doc.clone_container(
+        &child.id(),
+        &other_tree.to_container(),
+        Index::Node(other_node),
+    )
+    .unwrap();
+    assert_eq!(
+        other_tree
+            .get_meta(other_node)
+            .unwrap()
+            .get_deep_value()
+            .to_json_value(),
+        json!({"k": 1})
+    );
+
+    // Invalid destinations
+    assert!(matches!(
+        doc.clone_container(
+            &text.id(),
+            &other_tree.to_container(),
+            Index::Node(other_node)
+        ),
+        Err(LoroError::ArgErr(_))
+    ));
+    assert!(matches!(
+        doc.clone_container(&text.id(), &dest.to_container(), Index::Seq(0)),
+        Err(LoroError::ArgErr(_))
+    ));
+}
+
+#[test]
+fn tree_node_queries() {
+    use loro::{LoroTree, TreeParentId};
+
+    fn check(tree: &LoroTree) {
+        // r0
+        // ├── a
+        // │   ├── c
+        // │   └── d
+        // └── b
+        // r1
+        let r0 = tree.create(None).unwrap();
+        let r1 = tree.create(None).unwrap();
+        let a = tree.create(r0).unwrap();
+        let b = tree.create(r0).unwrap();
+        let c = tree.create(a).unwrap();
+        let d = tree.create(a).unwrap();
+
+        assert_eq!(tree.ancestors(r0), Some(vec![]));
+        assert_eq!(tree.ancestors(d), Some(vec![a, r0]));
+        assert_eq!(tree.depth(r1), Some(0));
+        assert_eq!(tree.depth(c), Some(2));
+
+        assert_eq!(
+            tree.lowest_common_ancestor(c, d),
+            Some(TreeParentId::Node(a))
+        );
+        assert_eq!(
+            tree.lowest_common_ancestor(c, b),
+            Some(TreeParentId::Node(r0))
+        );
+        assert_eq!(
+            tree.lowest_common_ancestor(a, d),
+            Some(TreeParentId::Node(a))
+        );
+        assert_eq!(tree.lowest_common_ancestor(c, r1), Some(TreeParentId::Root));
+
+        assert_eq!(tree.preorder_nodes_under(None), vec![r0, a, c, d, b, r1]);
+        assert_eq!(tree.postorder_nodes_under(None), vec![c, d, a, b, r0, r1]);
+        assert_eq!(tree.preorder_nodes_under(a), vec![c, d]);
+        assert_eq!(tree.postorder_nodes_under(b), vec![]);
+
+        assert_eq!(tree.get_node_by_index_path(&[0, 0, 1]), Some(d));
+        assert_eq!(tree.get_node_by_index_path(&[1]), Some(r1));
+        assert_eq!(tree.get_node_by_index_path(&[0, 2]), None);
+        assert_eq!(tree.get_node_by_index_path(&[]), None);
+        assert_eq!(tree.get_index_path(d), Some(vec![0, 0, 1]));
+        assert_eq!(tree.get_index_path(b), Some(vec![0, 1]));
+
+        tree.mov(a, r1).unwrap();
+        assert_eq!(tree.ancestors(c), Some(vec![a, r1]));
+        assert_eq!(tree.get_index_path(c), Some(vec![1, 0, 0]));
+        assert_eq!(tree.lowest_common_ancestor(c, b), Some(TreeParentId::Root));
+
+        tree.delete(a).unwrap();
+        assert_eq!(tree.ancestors(c), None);
+        assert_eq!(tree.depth(c), None);
+        assert_eq!(tree.get_index_path(c), None);
+        assert_eq!(tree.lowest_common_ancestor(c, b), None);
+        assert_eq!(tree.preorder_nodes_under(None), vec![r0, b, r1]);
+    }
+
+    let doc = LoroDoc::new();
+    check(&doc.get_tree("tree"));
+    check(&LoroTree::new());
+}